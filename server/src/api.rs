@@ -1,38 +1,70 @@
 //! HTTP API: upload and read endpoints.
 
 use axum::{
-    body::Bytes,
-    extract::{Path, Query, State},
-    http::{header, HeaderMap, StatusCode},
-    response::IntoResponse,
+    body::{Body, Bytes, HttpBody},
+    extract::{ConnectInfo, MatchedPath, Path, Query, Request, State},
+    http::{header, HeaderMap, HeaderValue, StatusCode},
+    middleware::{self, Next},
+    response::{IntoResponse, Response},
     routing::{get, post},
     Json, Router,
 };
+use futures::StreamExt;
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Arc;
 
-use crate::auth::verify_upload_signature;
+use crate::auth::{decode_public_key, verify_upload_signature};
+use crate::bandwidth::SharedBandwidth;
 use crate::contract::MassaClient;
-use crate::sc_client::get_is_allowed_uploader;
-use crate::storage::{Storage, MIN_REPLICATION_MAX, MIN_REPLICATION_MIN};
+use crate::drain::SharedDrain;
+use crate::envelope::{looks_like_envelope, EnvelopeMeta};
+use crate::erasure::{self, ErasureMeta, ErasureScheme};
+use crate::storage::{BlobMeta, Storage, MIN_REPLICATION_MAX, MIN_REPLICATION_MIN};
 
 /// Auth config for upload: when set, POST /upload requires Massa signature + storage admin.
 #[derive(Clone)]
 pub struct UploadAuthConfig {
-    pub storage_registry_address: String,
-    pub massa_json_rpc: String,
+    /// Registry client used for `getIsAllowedUploader`.
+    pub registry: Arc<MassaClient>,
 }
+use crate::operations::SharedOperations;
 use crate::p2p::SharedP2pState;
+use crate::provider_metadata::SharedMetadataReconciler;
+use crate::read_through::{Retrieved, SharedReadThrough, Streamed, CONTENT_HASH_HEADER, NO_READ_THROUGH_HEADER};
+use crate::replication::ReplicationHandle;
+use crate::rewards::SharedRewards;
+use crate::scrub::SharedScrubStatus;
+use crate::usage_outbox::SharedUsageOutbox;
 
 #[derive(Clone)]
 pub struct AppState {
     pub storage: Storage,
     /// When present, uploads require X-Massa-* headers and getIsAllowedUploader(addr).
     pub upload_auth: Option<UploadAuthConfig>,
-    /// Discovered P2P listen addresses (filtered to exclude localhost).
+    /// Confirmed external P2P addresses (AutoNAT, relay circuits).
     pub p2p_listen_addrs: Arc<std::sync::RwLock<Vec<String>>>,
     pub p2p_state: Option<SharedP2pState>,
-    /// Massa client for contract writes (recordFileUpload). Present when gRPC is configured.
+    /// Massa client, for endpoint health.
     pub massa_client: Option<Arc<MassaClient>>,
+    /// Tracker of contract writes sent by this node.
+    pub operations: Option<SharedOperations>,
+    /// Uploader usage waiting to be recorded on the contract. Present when gRPC is configured.
+    pub usage_outbox: Option<SharedUsageOutbox>,
+    /// Reward claims and balance history. Present when gRPC is configured.
+    pub rewards: Option<SharedRewards>,
+    /// Hand-off and unregistration progress. Present when the node drains; uploads are refused.
+    pub drain: Option<SharedDrain>,
+    /// Provider metadata reconciliation. Present when gRPC is configured and the node is not draining.
+    pub provider_metadata: Option<SharedMetadataReconciler>,
+    /// Queue for replicating uploaded blobs to other providers. Present when P2P is enabled.
+    pub replication: Option<ReplicationHandle>,
+    /// Integrity scrubber progress. Present when the scrubber is enabled.
+    pub scrub: Option<SharedScrubStatus>,
+    /// Bandwidth totals and rate limits (HTTP and P2P).
+    pub bandwidth: SharedBandwidth,
+    /// Fetches blobs missing locally from other providers. Present when READ_THROUGH is set.
+    pub read_through: Option<SharedReadThrough>,
 }
 
 /// Query for list: optional namespace filter.
@@ -48,20 +80,36 @@ pub struct UploadQuery {
     pub id: Option<String>,
     /// Minimum number of replicas the uploader requires (1–32). Default 1 when omitted.
     pub min_replication: Option<u8>,
+    /// When true, the body must be a valid encryption envelope (see `envelope`).
+    /// Envelopes are also detected automatically from their magic bytes.
+    pub encrypted: Option<bool>,
+    /// Erasure coding scheme `k+m` (e.g. `4+2`) instead of full replication.
+    /// Cannot be combined with `min_replication` > 1.
+    pub erasure: Option<String>,
 }
 
 /// POST /upload
 /// Body: raw binary data.
 /// When upload auth is enabled: requires X-Massa-Address, X-Massa-Signature, X-Massa-Public-Key;
 /// verifies signature (Blake3(body) + Ed25519) and getIsStorageAdmin(address) on the storage registry SC.
-/// Query: ?namespace=...&id=...&min_replication=...  (namespace defaults to "default", id optional, min_replication 1–32 default 1)
+/// Query: ?namespace=...&id=...&min_replication=...&encrypted=...&erasure=k+m  (namespace defaults to "default", id optional, min_replication 1–32 default 1)
 pub async fn upload(
     State(state): State<Arc<AppState>>,
     Query(query): Query<UploadQuery>,
     headers: HeaderMap,
     body: Bytes,
 ) -> impl IntoResponse {
+    if state.drain.is_some() {
+        return (
+            StatusCode::SERVICE_UNAVAILABLE,
+            Json(serde_json::json!({ "error": "node is draining and no longer accepts uploads" })),
+        )
+            .into_response();
+    }
+
     let mut uploader_address: Option<String> = None;
+    // Raw Ed25519 key of the authenticated uploader, the only valid envelope recipient
+    let mut uploader_key: Option<[u8; 32]> = None;
 
     // Optional: verify Massa signature and storage admin
     if let Some(ref auth) = state.upload_auth {
@@ -105,13 +153,7 @@ pub async fn upload(
                 .into_response();
         }
 
-        match get_is_allowed_uploader(
-            &auth.massa_json_rpc,
-            &auth.storage_registry_address,
-            &massa_address,
-        )
-        .await
-        {
+        match auth.registry.is_allowed_uploader(&massa_address).await {
             Ok(true) => {}
             Ok(false) => {
                 return (
@@ -132,6 +174,7 @@ pub async fn upload(
             }
         }
         uploader_address = Some(massa_address);
+        uploader_key = decode_public_key(&public_key).ok();
     }
 
     let namespace = query
@@ -160,28 +203,110 @@ pub async fn upload(
         None => MIN_REPLICATION_MIN,
     };
 
-    match state
-        .storage
-        .put(&namespace, id_hint, &body, min_replication, uploader_address.clone())
-    {
+    // A body starting with the envelope magic must be a valid envelope: storing a malformed
+    // one as plaintext would hide the mistake until the uploader tries to open it.
+    let encrypted_required = query.encrypted.unwrap_or(false);
+    let envelope = if encrypted_required || looks_like_envelope(&body) {
+        match EnvelopeMeta::from_envelope(&body) {
+            Ok(meta) => Some(meta),
+            Err(e) => {
+                return (
+                    StatusCode::BAD_REQUEST,
+                    Json(serde_json::json!({ "error": format!("invalid encryption envelope: {}", e) })),
+                )
+                    .into_response()
+            }
+        }
+    } else {
+        None
+    };
+    // With upload auth, an envelope must be sealed for the uploader's key; otherwise nobody
+    // holding that key could open it. Without upload auth there is no key to compare with.
+    if let (Some(meta), Some(key)) = (&envelope, &uploader_key) {
+        if meta.recipient != bs58::encode(key).into_string() {
+            return (
+                StatusCode::BAD_REQUEST,
+                Json(serde_json::json!({
+                    "error": "encryption envelope recipient is not the uploader's public key"
+                })),
+            )
+                .into_response();
+        }
+    }
+    let encrypted = envelope.is_some();
+
+    let erasure_meta = match query.erasure.as_deref() {
+        Some(_) if min_replication > MIN_REPLICATION_MIN => {
+            return (
+                StatusCode::BAD_REQUEST,
+                Json(serde_json::json!({
+                    "error": "erasure and min_replication > 1 are mutually exclusive"
+                })),
+            )
+                .into_response()
+        }
+        Some(s) => {
+            let encoded = ErasureScheme::parse(s)
+                .and_then(|scheme| erasure::encode(&body, scheme).map(|shards| (scheme, shards)));
+            match encoded {
+                Ok((scheme, shards)) => Some(ErasureMeta::new(scheme, body.len(), &shards)),
+                Err(e) => {
+                    return (
+                        StatusCode::BAD_REQUEST,
+                        Json(serde_json::json!({ "error": e.to_string() })),
+                    )
+                        .into_response()
+                }
+            }
+        }
+        None => None,
+    };
+    let erasure_scheme = erasure_meta.as_ref().map(|e| e.scheme().to_string());
+
+    let meta = BlobMeta {
+        min_replication,
+        uploader_address: uploader_address.clone(),
+        envelope,
+        erasure: erasure_meta,
+        ..BlobMeta::default()
+    };
+
+    // Content previously stored under this id, withdrawn from the DHT if it is replaced
+    let previous_hash = id_hint
+        .and_then(|id| state.storage.meta(&namespace, id).ok())
+        .and_then(|meta| meta.content_hash);
+
+    match state.storage.put(&namespace, id_hint, &body, meta) {
         Ok(id) => {
-            tracing::info!(namespace, id, size = body.len(), min_replication, "upload stored");
+            tracing::info!(namespace, id, size = body.len(), min_replication, encrypted, erasure = ?erasure_scheme, "upload stored");
+
+            if let Some(ref replication) = state.replication {
+                replication.enqueue(&namespace, &id);
+            }
 
-            // Update total storage usage on the contract when we have an uploader and gRPC client
-            if let (Some(ref uploader), Some(ref client)) =
-                (uploader_address.as_ref(), state.massa_client.as_ref())
+            // Announce the blob in the DHT so other nodes can find it by content hash
+            if let Some(ref p2p) = state.p2p_state {
+                let hash = state.storage.meta(&namespace, &id).ok().and_then(|meta| meta.content_hash);
+                if let Some(ref hash) = hash {
+                    crate::p2p::announce_blob(p2p, hash).await;
+                }
+                if let Some(previous) = previous_hash.filter(|previous| Some(previous) != hash.as_ref()) {
+                    crate::p2p::withdraw_blob(p2p, &state.storage, &previous).await;
+                }
+            }
+
+            // Queue the uploader's usage; the outbox records it on the contract in batches
+            if let (Some(ref uploader), Some(ref outbox)) =
+                (uploader_address.as_ref(), state.usage_outbox.as_ref())
             {
                 let size = body.len() as u64;
                 if size > 0 {
-                    if let Err(e) = client
-                        .record_file_upload(uploader, size)
-                        .await
-                    {
+                    if let Err(e) = outbox.record(uploader, size) {
                         tracing::warn!(
                             error = %e,
                             uploader = %uploader,
                             size = size,
-                            "failed to record file upload on contract (file was stored)"
+                            "failed to queue file upload usage (file was stored)"
                         );
                     }
                 }
@@ -192,7 +317,9 @@ pub async fn upload(
                 Json(serde_json::json!({
                     "id": id,
                     "namespace": namespace,
-                    "min_replication": min_replication
+                    "min_replication": min_replication,
+                    "encrypted": encrypted,
+                    "erasure": erasure_scheme
                 })),
             )
                 .into_response()
@@ -212,7 +339,7 @@ pub async fn upload(
 
 /// GET /data
 /// Query: ?namespace=...  (optional; if omitted, list all namespaces)
-/// Returns JSON array of { id, namespace, size, created_at }.
+/// Returns JSON array of { id, namespace, size, created_at, min_replication, envelope? }.
 pub async fn list(
     State(state): State<Arc<AppState>>,
     Query(query): Query<ListQuery>,
@@ -241,15 +368,52 @@ fn binary_response(status: StatusCode, data: Vec<u8>) -> axum::response::Respons
     res
 }
 
-/// GET /data/:namespace/:id  — get by namespace and id (path)
-pub async fn get_by_namespace_id(
-    State(state): State<Arc<AppState>>,
-    Path((namespace, id)): Path<(String, String)>,
-) -> impl IntoResponse {
-    match state.storage.get(&namespace, &id) {
-        Ok(data) => binary_response(StatusCode::OK, data),
+/// Blob data with its recorded Blake3 hash in `X-Content-Hash` (when known).
+fn blob_response(data: Vec<u8>, content_hash: Option<String>) -> Response {
+    let mut res = binary_response(StatusCode::OK, data);
+    if let Some(value) = content_hash.and_then(|h| header::HeaderValue::from_str(&h).ok()) {
+        res.headers_mut().insert(CONTENT_HASH_HEADER, value);
+    }
+    res
+}
+
+/// Blob streamed from another provider. `Content-Length` is set when the provider announced
+/// it, so a copy cut off by a failed check shows as truncated.
+fn streamed_blob_response(blob: Streamed) -> Response {
+    let mut res = Response::new(Body::from_stream(blob.body));
+    let headers = res.headers_mut();
+    headers.insert(header::CONTENT_TYPE, HeaderValue::from_static("application/octet-stream"));
+    if let Ok(value) = HeaderValue::from_str(&blob.content_hash) {
+        headers.insert(CONTENT_HASH_HEADER, value);
+    }
+    if let Some(size) = blob.size {
+        headers.insert(header::CONTENT_LENGTH, HeaderValue::from(size));
+    }
+    res
+}
+
+/// Serve a blob from local storage; on a miss, from the read-through cache or other
+/// providers when read-through is enabled (unless the request is itself a read-through fetch).
+async fn get_blob(state: &AppState, headers: &HeaderMap, namespace: &str, id: &str) -> Response {
+    match state.storage.get(namespace, id) {
+        Ok(data) => {
+            let content_hash = state.storage.meta(namespace, id).ok().and_then(|m| m.content_hash);
+            blob_response(data, content_hash)
+        }
         Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
-            binary_response(StatusCode::NOT_FOUND, Vec::new())
+            let Some(read_through) = &state.read_through else {
+                return binary_response(StatusCode::NOT_FOUND, Vec::new());
+            };
+            let fetched = match read_through.cached(namespace, id).await {
+                Some(cached) => Some(Retrieved::Whole(cached)),
+                None if headers.contains_key(NO_READ_THROUGH_HEADER) => None,
+                None => read_through.fetch(namespace, id).await,
+            };
+            match fetched {
+                Some(Retrieved::Whole(blob)) => blob_response(blob.data, Some(blob.content_hash)),
+                Some(Retrieved::Streamed(blob)) => streamed_blob_response(blob),
+                None => binary_response(StatusCode::NOT_FOUND, Vec::new()),
+            }
         }
         Err(e) => {
             tracing::warn!(error = %e, "get failed");
@@ -258,22 +422,23 @@ pub async fn get_by_namespace_id(
     }
 }
 
+/// GET /data/:namespace/:id  — get by namespace and id (path)
+pub async fn get_by_namespace_id(
+    State(state): State<Arc<AppState>>,
+    Path((namespace, id)): Path<(String, String)>,
+    headers: HeaderMap,
+) -> impl IntoResponse {
+    get_blob(&state, &headers, &namespace, &id).await
+}
+
 /// GET /data/:id
 /// Single path segment: treat as id, use default namespace "default".
 pub async fn get_by_id(
     State(state): State<Arc<AppState>>,
     Path(id): Path<String>,
+    headers: HeaderMap,
 ) -> impl IntoResponse {
-    match state.storage.get("default", &id) {
-        Ok(data) => binary_response(StatusCode::OK, data),
-        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
-            binary_response(StatusCode::NOT_FOUND, Vec::new())
-        }
-        Err(e) => {
-            tracing::warn!(error = %e, "get failed");
-            binary_response(StatusCode::INTERNAL_SERVER_ERROR, Vec::new())
-        }
-    }
+    get_blob(&state, &headers, "default", &id).await
 }
 
 /// Health check.
@@ -286,9 +451,14 @@ pub async fn health() -> &'static str {
 pub struct PeersResponse {
     pub local_peer_id: String,
     pub listen_addrs: Vec<String>,
-    /// Full multiaddrs with peer ID (for contract registration)
+    /// Confirmed external addresses (AutoNAT, relay circuits)
+    pub external_addrs: Vec<String>,
+    /// Full external multiaddrs with peer ID (registered in the contract)
     pub multiaddrs: Vec<String>,
+    pub reachability: crate::p2p::Reachability,
     pub connected_peers: Vec<crate::p2p::PeerInfo>,
+    /// Connection limits, peers being reconnected and banned peers
+    pub connections: crate::p2p::ConnectionStatus,
 }
 
 /// GET /peers — list connected P2P peers
@@ -300,12 +470,15 @@ pub async fn peers(State(state): State<Arc<AppState>>) -> impl IntoResponse {
             let response = PeersResponse {
                 local_peer_id: peer_id.clone(),
                 listen_addrs: s.listen_addrs.iter().map(|a| a.to_string()).collect(),
+                external_addrs: s.external_addrs.iter().map(|a| a.to_string()).collect(),
                 multiaddrs: s
-                    .listen_addrs
+                    .external_addrs
                     .iter()
                     .map(|a| format!("{}/p2p/{}", a, peer_id))
                     .collect(),
-                connected_peers: s.connected_peers.values().cloned().collect(),
+                reachability: s.reachability,
+                connected_peers: s.peer_infos(),
+                connections: s.connections.status(),
             };
             (StatusCode::OK, Json(response)).into_response()
         }
@@ -317,6 +490,88 @@ pub async fn peers(State(state): State<Arc<AppState>>) -> impl IntoResponse {
     }
 }
 
+/// One provider of a blob, as found in the DHT.
+#[derive(Debug, serde::Serialize)]
+pub struct ProviderEntry {
+    pub peer_id: String,
+    /// Known addresses (empty when the provider is not connected to this node).
+    pub addresses: Vec<String>,
+    /// True when the provider is this node.
+    pub local: bool,
+    /// Massa address of the provider, when it is connected and authenticated.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub massa_address: Option<String>,
+}
+
+/// Providers of a blob by content hash
+#[derive(Debug, serde::Serialize)]
+pub struct ProvidersResponse {
+    pub content_hash: String,
+    pub providers: Vec<ProviderEntry>,
+}
+
+/// GET /providers/:content_hash — peers that announced the blob (Blake3 hash, hex) in the DHT.
+pub async fn providers(
+    State(state): State<Arc<AppState>>,
+    Path(content_hash): Path<String>,
+) -> impl IntoResponse {
+    let Some(p2p) = &state.p2p_state else {
+        return (
+            StatusCode::SERVICE_UNAVAILABLE,
+            Json(serde_json::json!({ "error": "P2P not enabled" })),
+        )
+            .into_response();
+    };
+    if let Err(e) = crate::p2p::provider_key(&content_hash) {
+        return (
+            StatusCode::BAD_REQUEST,
+            Json(serde_json::json!({ "error": e })),
+        )
+            .into_response();
+    }
+    match crate::p2p::find_providers(p2p, &content_hash).await {
+        Ok(peers) => {
+            let s = p2p.read().await;
+            let providers = peers
+                .into_iter()
+                .map(|peer| {
+                    let local = peer == s.local_peer_id;
+                    let info = s.connected_peers.get(&peer);
+                    let addresses = if local {
+                        s.listen_addrs.iter().map(|a| a.to_string()).collect()
+                    } else {
+                        info.map(|info| info.addresses.clone()).unwrap_or_default()
+                    };
+                    ProviderEntry {
+                        peer_id: peer.to_string(),
+                        addresses,
+                        local,
+                        massa_address: info
+                            .filter(|info| info.authenticated)
+                            .and_then(|info| info.massa_address.clone()),
+                    }
+                })
+                .collect();
+            (
+                StatusCode::OK,
+                Json(ProvidersResponse {
+                    content_hash,
+                    providers,
+                }),
+            )
+                .into_response()
+        }
+        Err(e) => {
+            tracing::warn!(error = %e, "provider lookup failed");
+            (
+                StatusCode::BAD_GATEWAY,
+                Json(serde_json::json!({ "error": e })),
+            )
+                .into_response()
+        }
+    }
+}
+
 /// Storage limit and usage (for external clients).
 #[derive(Debug, serde::Serialize)]
 pub struct StorageConfigResponse {
@@ -326,7 +581,7 @@ pub struct StorageConfigResponse {
     pub storage_limit_bytes: u64,
     /// Current total size of stored data in bytes.
     pub storage_used_bytes: u64,
-    /// P2P listen address (multiaddr) for provider metadata.
+    /// Confirmed external P2P addresses (multiaddrs, comma-separated) for provider metadata.
     #[serde(skip_serializing_if = "Option::is_none")]
     pub p2p_listen_addr: Option<String>,
 }
@@ -339,7 +594,7 @@ pub async fn storage_config(
     let storage_limit_gb = limit_bytes / (1024 * 1024 * 1024);
     match state.storage.total_size() {
         Ok(used) => {
-            // Get discovered P2P addresses (already filtered to exclude localhost)
+            // Confirmed external P2P addresses (see p2p::set_external_addr)
             let p2p_addrs = state.p2p_listen_addrs.read().unwrap();
             let p2p_addr = if p2p_addrs.is_empty() {
                 None
@@ -370,12 +625,236 @@ pub async fn storage_config(
     }
 }
 
+/// GET /admin/scrub — integrity scrubber progress and recent findings.
+pub async fn scrub_status(State(state): State<Arc<AppState>>) -> impl IntoResponse {
+    match &state.scrub {
+        Some(scrub) => (StatusCode::OK, Json(scrub.read().await.clone())).into_response(),
+        None => (
+            StatusCode::SERVICE_UNAVAILABLE,
+            Json(serde_json::json!({ "error": "scrubber not enabled" })),
+        )
+            .into_response(),
+    }
+}
+
+/// GET /admin/bandwidth — bytes consumed per P2P peer, protocol, HTTP route and client.
+pub async fn bandwidth_report(State(state): State<Arc<AppState>>) -> impl IntoResponse {
+    (StatusCode::OK, Json(state.bandwidth.report()))
+}
+
+/// GET /admin/rpc — health of the configured Massa JSON-RPC and gRPC endpoints.
+pub async fn rpc_health(State(state): State<Arc<AppState>>) -> impl IntoResponse {
+    match &state.massa_client {
+        Some(client) => (StatusCode::OK, Json(client.rpc_health())).into_response(),
+        None => (
+            StatusCode::SERVICE_UNAVAILABLE,
+            Json(serde_json::json!({ "error": "Massa client not configured" })),
+        )
+            .into_response(),
+    }
+}
+
+/// GET /admin/operations — contract writes sent by this node and their status, most recent first.
+pub async fn operation_list(State(state): State<Arc<AppState>>) -> impl IntoResponse {
+    match &state.operations {
+        Some(operations) => (StatusCode::OK, Json(operations.list())).into_response(),
+        None => (
+            StatusCode::SERVICE_UNAVAILABLE,
+            Json(serde_json::json!({ "error": "Massa client not configured" })),
+        )
+            .into_response(),
+    }
+}
+
+/// GET /admin/usage-outbox — uploader usage not yet recorded on the contract, the batch in
+/// flight and the drift found by the last reconciliation.
+pub async fn usage_outbox_status(State(state): State<Arc<AppState>>) -> impl IntoResponse {
+    match &state.usage_outbox {
+        Some(outbox) => (StatusCode::OK, Json(outbox.status())).into_response(),
+        None => (
+            StatusCode::SERVICE_UNAVAILABLE,
+            Json(serde_json::json!({ "error": "Massa gRPC client not configured" })),
+        )
+            .into_response(),
+    }
+}
+
+/// GET /admin/rewards — balance, pending rewards, claim history and balance history.
+pub async fn rewards_report(State(state): State<Arc<AppState>>) -> impl IntoResponse {
+    match &state.rewards {
+        Some(rewards) => (StatusCode::OK, Json(rewards.report())).into_response(),
+        None => (
+            StatusCode::SERVICE_UNAVAILABLE,
+            Json(serde_json::json!({ "error": "Massa gRPC client not configured" })),
+        )
+            .into_response(),
+    }
+}
+
+/// GET /admin/metadata — this node's registry entry compared with its endpoint and P2P addresses.
+pub async fn provider_metadata_status(State(state): State<Arc<AppState>>) -> impl IntoResponse {
+    match &state.provider_metadata {
+        Some(reconciler) => (StatusCode::OK, Json(reconciler.status())).into_response(),
+        None => (
+            StatusCode::SERVICE_UNAVAILABLE,
+            Json(serde_json::json!({ "error": "provider metadata is not reconciled (no gRPC client, or draining)" })),
+        )
+            .into_response(),
+    }
+}
+
+/// GET /admin/drain — hand-off and unregistration progress while the node drains.
+pub async fn drain_status(State(state): State<Arc<AppState>>) -> impl IntoResponse {
+    match &state.drain {
+        Some(drain) => (StatusCode::OK, Json(drain.status())).into_response(),
+        None => (
+            StatusCode::NOT_FOUND,
+            Json(serde_json::json!({ "error": "node is not draining" })),
+        )
+            .into_response(),
+    }
+}
+
+/// Guard of the `/admin/*` routes. With `ADMIN_TOKEN`, requests must send
+/// `Authorization: Bearer <token>`; without it, only loopback clients are answered.
+async fn require_admin(State(token): State<Option<Arc<str>>>, request: Request, next: Next) -> Response {
+    let allowed = match &token {
+        Some(token) => request
+            .headers()
+            .get(header::AUTHORIZATION)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.strip_prefix("Bearer "))
+            .is_some_and(|given| tokens_match(given.trim(), token)),
+        None => request
+            .extensions()
+            .get::<ConnectInfo<SocketAddr>>()
+            .is_some_and(|info| info.0.ip().is_loopback()),
+    };
+    if allowed {
+        return next.run(request).await;
+    }
+    (
+        StatusCode::UNAUTHORIZED,
+        Json(serde_json::json!({ "error": "admin routes need ADMIN_TOKEN, or a local client when it is unset" })),
+    )
+        .into_response()
+}
+
+/// Token comparison in time independent of where the tokens differ.
+fn tokens_match(given: &str, expected: &str) -> bool {
+    given.len() == expected.len()
+        && given
+            .bytes()
+            .zip(expected.bytes())
+            .fold(0u8, |diff, (a, b)| diff | (a ^ b))
+            == 0
+}
+
+/// Largest piece of an HTTP body passed to the rate limiters at once.
+const METERED_CHUNK_BYTES: usize = 64 * 1024;
+
+/// Body bytes of one HTTP request, recorded once both bodies are dropped (fully transferred
+/// or abandoned with the connection).
+struct HttpTransfer {
+    bandwidth: SharedBandwidth,
+    route: String,
+    client: String,
+    bytes_in: AtomicU64,
+    bytes_out: AtomicU64,
+}
+
+impl Drop for HttpTransfer {
+    fn drop(&mut self) {
+        let (bytes_in, bytes_out) = (*self.bytes_in.get_mut(), *self.bytes_out.get_mut());
+        self.bandwidth.record_http(&self.route, &self.client, bytes_in, bytes_out);
+    }
+}
+
+/// Wrap `body` so each chunk is counted and held to the global limits as it is read
+/// (request body) or written (response body, `upload`).
+fn metered_body(body: Body, transfer: Arc<HttpTransfer>, upload: bool) -> Body {
+    let pieces = body.into_data_stream().flat_map(|chunk| {
+        let pieces: Vec<Result<Bytes, axum::Error>> = match chunk {
+            Ok(bytes) => (0..bytes.len())
+                .step_by(METERED_CHUNK_BYTES)
+                .map(|start| Ok(bytes.slice(start..(start + METERED_CHUNK_BYTES).min(bytes.len()))))
+                .collect(),
+            Err(e) => vec![Err(e)],
+        };
+        futures::stream::iter(pieces)
+    });
+    Body::from_stream(pieces.then(move |piece| {
+        let transfer = transfer.clone();
+        async move {
+            if let Ok(bytes) = &piece {
+                let len = bytes.len() as u64;
+                if upload {
+                    transfer.bytes_out.fetch_add(len, Ordering::Relaxed);
+                    transfer.bandwidth.throttle(len, 0).await;
+                } else {
+                    transfer.bytes_in.fetch_add(len, Ordering::Relaxed);
+                    transfer.bandwidth.throttle(0, len).await;
+                }
+            }
+            piece
+        }
+    }))
+}
+
+/// Meter every request per route and client, and hold its bodies to the global rate limits
+/// chunk by chunk as they are read and written.
+async fn meter_bandwidth(
+    State(bandwidth): State<SharedBandwidth>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let route = request
+        .extensions()
+        .get::<MatchedPath>()
+        .map_or("unmatched", |path| path.as_str())
+        .to_string();
+    let client = request
+        .extensions()
+        .get::<ConnectInfo<SocketAddr>>()
+        .map_or_else(|| "unknown".to_string(), |info| info.0.ip().to_string());
+    let transfer = Arc::new(HttpTransfer {
+        bandwidth,
+        route,
+        client,
+        bytes_in: AtomicU64::new(0),
+        bytes_out: AtomicU64::new(0),
+    });
+    let request = request.map(|body| metered_body(body, transfer.clone(), false));
+    let (mut parts, body) = next.run(request).await.into_parts();
+    match body.size_hint().exact() {
+        Some(0) => Response::from_parts(parts, body),
+        len => {
+            // The metered body is streamed: keep the length when it is known
+            if let Some(len) = len {
+                parts.headers.entry(header::CONTENT_LENGTH).or_insert(HeaderValue::from(len));
+            }
+            Response::from_parts(parts, metered_body(body, transfer, true))
+        }
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
 pub fn router(
     storage: Storage,
     upload_auth: Option<UploadAuthConfig>,
     p2p_listen_addrs: Arc<std::sync::RwLock<Vec<String>>>,
     p2p_state: Option<SharedP2pState>,
     massa_client: Option<Arc<MassaClient>>,
+    operations: Option<SharedOperations>,
+    usage_outbox: Option<SharedUsageOutbox>,
+    rewards: Option<SharedRewards>,
+    drain: Option<SharedDrain>,
+    provider_metadata: Option<SharedMetadataReconciler>,
+    replication: Option<ReplicationHandle>,
+    scrub: Option<SharedScrubStatus>,
+    bandwidth: SharedBandwidth,
+    read_through: Option<SharedReadThrough>,
+    admin_token: Option<String>,
 ) -> Router {
     let state = Arc::new(AppState {
         storage,
@@ -383,14 +862,36 @@ pub fn router(
         p2p_listen_addrs,
         p2p_state,
         massa_client,
+        operations,
+        usage_outbox,
+        rewards,
+        drain,
+        provider_metadata,
+        replication,
+        scrub,
+        bandwidth: bandwidth.clone(),
+        read_through,
     });
+    let admin = Router::new()
+        .route("/admin/scrub", get(scrub_status))
+        .route("/admin/bandwidth", get(bandwidth_report))
+        .route("/admin/rpc", get(rpc_health))
+        .route("/admin/operations", get(operation_list))
+        .route("/admin/usage-outbox", get(usage_outbox_status))
+        .route("/admin/rewards", get(rewards_report))
+        .route("/admin/drain", get(drain_status))
+        .route("/admin/metadata", get(provider_metadata_status))
+        .route_layer(middleware::from_fn_with_state(admin_token.map(Arc::<str>::from), require_admin));
     Router::new()
         .route("/health", get(health))
         .route("/config", get(storage_config))
         .route("/peers", get(peers))
+        .route("/providers/{content_hash}", get(providers))
+        .merge(admin)
         .route("/upload", post(upload))
         .route("/data", get(list))
         .route("/data/{id}", get(get_by_id))
         .route("/data/{namespace}/{id}", get(get_by_namespace_id))
+        .route_layer(middleware::from_fn_with_state(bandwidth, meter_bandwidth))
         .with_state(state)
 }
@@ -76,6 +76,13 @@ impl Args {
         self
     }
 
+    /// Append a `bool` (one byte).
+    #[allow(dead_code)]
+    pub fn add_bool(&mut self, value: bool) -> &mut Self {
+        self.data.push(value as u8);
+        self
+    }
+
     /// Append a UTF-8 string (length-prefixed).
     pub fn add_string(&mut self, value: &str) -> &mut Self {
         let bytes = value.as_bytes();
@@ -105,6 +112,15 @@ impl Args {
         self
     }
 
+    /// Append an array of `u64` (fixed-size elements, total byte length prefix).
+    pub fn add_u64_array(&mut self, values: &[u64]) -> &mut Self {
+        self.add_u32((values.len() * 8) as u32);
+        for value in values {
+            self.data.extend_from_slice(&value.to_le_bytes());
+        }
+        self
+    }
+
     // ─────────────────────────────────────────────────────────────────────────
     // Deserialization (next_*)
     // ─────────────────────────────────────────────────────────────────────────
@@ -122,7 +138,6 @@ impl Args {
     }
 
     /// Read the next `u64` value.
-    #[allow(dead_code)]
     pub fn next_u64(&mut self) -> Result<u64, ArgsError> {
         if self.offset + 8 > self.data.len() {
             return Err(ArgsError::OutOfRange("u64"));
@@ -134,6 +149,13 @@ impl Args {
         Ok(u64::from_le_bytes(bytes))
     }
 
+    /// Read the next `bool` (one byte).
+    pub fn next_bool(&mut self) -> Result<bool, ArgsError> {
+        let byte = *self.data.get(self.offset).ok_or(ArgsError::OutOfRange("bool"))?;
+        self.offset += 1;
+        Ok(byte != 0)
+    }
+
     /// Read the next length-prefixed byte array.
     pub fn next_bytes(&mut self) -> Result<Vec<u8>, ArgsError> {
         let len = self.next_u32()? as usize;
@@ -164,6 +186,21 @@ impl Args {
         }
         Ok(values)
     }
+
+    /// Read a length-prefixed `u64` array.
+    #[allow(dead_code)]
+    pub fn next_u64_array(&mut self) -> Result<Vec<u64>, ArgsError> {
+        let total = self.next_u32()? as usize;
+        if !total.is_multiple_of(8) || self.offset + total > self.data.len() {
+            return Err(ArgsError::OutOfRange("u64 array"));
+        }
+        let end = self.offset + total;
+        let mut values = Vec::with_capacity(total / 8);
+        while self.offset < end {
+            values.push(self.next_u64()?);
+        }
+        Ok(values)
+    }
 }
 
 #[cfg(test)]
@@ -202,4 +239,26 @@ mod tests {
         assert_eq!(decoded.next_string().unwrap(), "test");
         assert_eq!(decoded.next_u32().unwrap(), 100);
     }
+
+    #[test]
+    fn test_bool() {
+        let mut args = Args::new();
+        args.add_bool(true).add_bool(false);
+        let mut decoded = Args::from_bytes(args.into_bytes());
+        assert!(decoded.next_bool().unwrap());
+        assert!(!decoded.next_bool().unwrap());
+        assert!(decoded.next_bool().is_err());
+    }
+
+    #[test]
+    fn test_u64_array() {
+        let mut args = Args::new();
+        args.add_u64_array(&[1, u64::MAX]);
+        let bytes = args.into_bytes();
+        assert_eq!(&bytes[..4], &16u32.to_le_bytes());
+
+        let mut decoded = Args::from_bytes(bytes);
+        assert_eq!(decoded.next_u64_array().unwrap(), vec![1, u64::MAX]);
+        assert!(Args::from_bytes(vec![3, 0, 0, 0, 1, 2, 3]).next_u64_array().is_err());
+    }
 }
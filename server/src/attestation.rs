@@ -0,0 +1,146 @@
+//! Peer attestation: binds a libp2p `PeerId` to a Massa address.
+//!
+//! Each node signs its own `PeerId` with its Massa key and presents the result to every
+//! peer it connects to (see the `/massa-storage/attest/1.0.0` protocol in `p2p`). The
+//! remote `PeerId` is already authenticated by the Noise handshake, so a valid signature
+//! proves that the holder of the Massa key vouches for that libp2p identity. Whether the
+//! address is a registered provider advertising this `PeerId` is checked separately
+//! against the storage registry contract.
+
+use std::fmt;
+use std::str::FromStr;
+
+use libp2p::PeerId;
+use massa_hash::Hash;
+use massa_models::address::Address;
+use massa_signature::{KeyPair, PublicKey, Signature};
+
+/// Domain separation prefix of the signed message.
+const ATTESTATION_DOMAIN: &str = "massa-storage/peer-attestation/v1:";
+
+/// Errors while creating or verifying an attestation.
+#[derive(Debug, PartialEq, Eq)]
+pub enum AttestationError {
+    InvalidPublicKey,
+    InvalidSignature,
+    /// The attestation was made for another `PeerId` than the connected one.
+    PeerIdMismatch,
+    /// The public key does not derive to the claimed Massa address.
+    AddressMismatch,
+    VerificationFailed,
+    Signing(String),
+}
+
+impl fmt::Display for AttestationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::InvalidPublicKey => write!(f, "invalid public key"),
+            Self::InvalidSignature => write!(f, "invalid signature"),
+            Self::PeerIdMismatch => write!(f, "attestation is for another peer id"),
+            Self::AddressMismatch => write!(f, "public key does not match the Massa address"),
+            Self::VerificationFailed => write!(f, "signature verification failed"),
+            Self::Signing(msg) => write!(f, "signing failed: {}", msg),
+        }
+    }
+}
+
+impl std::error::Error for AttestationError {}
+
+/// A node's statement "this `PeerId` belongs to my Massa address", signed with its Massa key.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct PeerAttestation {
+    pub massa_address: String,
+    /// Massa public key (`P...`).
+    pub public_key: String,
+    pub peer_id: String,
+    /// Massa signature over Blake3(`ATTESTATION_DOMAIN` || peer id).
+    pub signature: String,
+}
+
+fn message_hash(peer_id: &PeerId) -> Hash {
+    Hash::compute_from(format!("{}{}", ATTESTATION_DOMAIN, peer_id).as_bytes())
+}
+
+impl PeerAttestation {
+    /// Sign `peer_id` with the Massa key.
+    pub fn sign(keypair: &KeyPair, peer_id: &PeerId) -> Result<Self, AttestationError> {
+        let public_key = keypair.get_public_key();
+        let signature = keypair
+            .sign(&message_hash(peer_id))
+            .map_err(|e| AttestationError::Signing(e.to_string()))?;
+        Ok(Self {
+            massa_address: Address::from_public_key(&public_key).to_string(),
+            public_key: public_key.to_string(),
+            peer_id: peer_id.to_string(),
+            signature: signature.to_string(),
+        })
+    }
+
+    /// Check that the attestation was made for `remote` and signed by the key of `massa_address`.
+    pub fn verify(&self, remote: &PeerId) -> Result<(), AttestationError> {
+        if self.peer_id != remote.to_string() {
+            return Err(AttestationError::PeerIdMismatch);
+        }
+        let public_key =
+            PublicKey::from_str(&self.public_key).map_err(|_| AttestationError::InvalidPublicKey)?;
+        if Address::from_public_key(&public_key).to_string() != self.massa_address {
+            return Err(AttestationError::AddressMismatch);
+        }
+        let signature =
+            Signature::from_str(&self.signature).map_err(|_| AttestationError::InvalidSignature)?;
+        public_key
+            .verify_signature(&message_hash(remote), &signature)
+            .map_err(|_| AttestationError::VerificationFailed)
+    }
+}
+
+/// True when one of the provider's registered multiaddrs ends with `/p2p/<peer_id>`.
+pub fn advertises_peer_id(p2p_addrs: &[String], peer_id: &PeerId) -> bool {
+    let suffix = format!("/p2p/{}", peer_id);
+    p2p_addrs.iter().any(|addr| addr.ends_with(&suffix))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sign_and_verify() {
+        let keypair = KeyPair::generate(0).unwrap();
+        let peer_id = PeerId::random();
+        let attestation = PeerAttestation::sign(&keypair, &peer_id).unwrap();
+        assert_eq!(attestation.verify(&peer_id), Ok(()));
+    }
+
+    #[test]
+    fn rejects_other_peer_and_forged_address() {
+        let keypair = KeyPair::generate(0).unwrap();
+        let peer_id = PeerId::random();
+        let attestation = PeerAttestation::sign(&keypair, &peer_id).unwrap();
+        assert_eq!(
+            attestation.verify(&PeerId::random()),
+            Err(AttestationError::PeerIdMismatch)
+        );
+
+        let other = KeyPair::generate(0).unwrap();
+        let forged = PeerAttestation {
+            massa_address: Address::from_public_key(&other.get_public_key()).to_string(),
+            ..attestation.clone()
+        };
+        assert_eq!(forged.verify(&peer_id), Err(AttestationError::AddressMismatch));
+
+        let resigned = PeerAttestation {
+            signature: other.sign(&message_hash(&peer_id)).unwrap().to_string(),
+            ..attestation
+        };
+        assert_eq!(resigned.verify(&peer_id), Err(AttestationError::VerificationFailed));
+    }
+
+    #[test]
+    fn registered_addrs_must_name_the_peer() {
+        let peer_id = PeerId::random();
+        let addrs = vec![format!("/ip4/1.2.3.4/tcp/4001/p2p/{}", peer_id)];
+        assert!(advertises_peer_id(&addrs, &peer_id));
+        assert!(!advertises_peer_id(&addrs, &PeerId::random()));
+    }
+}
@@ -75,6 +75,18 @@ fn base58_decode_versioned(encoded: &str, expected_tail_len: usize) -> Result<Ve
     Ok(raw)
 }
 
+/// Raw Ed25519 key of an `X-Massa-Public-Key` header value (`P` prefix optional).
+pub fn decode_public_key(public_key_b58: &str) -> Result<[u8; 32], AuthError> {
+    // Public key strings from massa-web3 have a leading "P" prefix (e.g. "P12...").
+    // Strip it before base58-decoding the versioned key bytes.
+    let pk_str = public_key_b58.strip_prefix('P').unwrap_or(public_key_b58);
+    base58_decode_versioned(pk_str, 32)
+        .map_err(|_| AuthError::InvalidPublicKey)?
+        .as_slice()
+        .try_into()
+        .map_err(|_| AuthError::InvalidPublicKey)
+}
+
 /// Verify upload auth: body was signed by the given public key (mode wallet uniquement).
 /// Le client envoie hex(Blake3(body)) au wallet ; le wallet signe Blake3(utf8(hex(Blake3(body)))).
 /// Headers requis : X-Massa-Address, X-Massa-Signature, X-Massa-Public-Key.
@@ -86,18 +98,11 @@ pub fn verify_upload_signature(
 ) -> Result<(), AuthError> {
     let _ = massa_address; // used for SC check; consistency with pubkey could be added later
 
-    // Public key strings from massa-web3 have a leading "P" prefix (e.g. "P12...").
-    // Strip it before base58-decoding the versioned key bytes.
-    let pk_str = public_key_b58.strip_prefix('P').unwrap_or(public_key_b58);
-
-    let pubkey_bytes =
-        base58_decode_versioned(pk_str, 32).map_err(|_| AuthError::InvalidPublicKey)?;
+    let pubkey_bytes = decode_public_key(public_key_b58)?;
     let sig_bytes = base58_decode_versioned(signature_b58, 64).map_err(|_| AuthError::InvalidSignature)?;
 
-    let verifying_key = VerifyingKey::from_bytes(
-        pubkey_bytes.as_slice().try_into().map_err(|_| AuthError::InvalidPublicKey)?,
-    )
-    .map_err(|_| AuthError::InvalidPublicKey)?;
+    let verifying_key =
+        VerifyingKey::from_bytes(&pubkey_bytes).map_err(|_| AuthError::InvalidPublicKey)?;
     let signature = Signature::from_bytes(sig_bytes.as_slice().try_into().map_err(|_| AuthError::InvalidSignature)?);
 
     // Mode wallet : client signe hex(Blake3(body)) → message signé = Blake3(utf8(hex(Blake3(body)))).
@@ -188,4 +193,13 @@ mod tests {
             other => panic!("expected VerificationFailed, got {:?}", other),
         }
     }
+
+    #[test]
+    fn decode_public_key_accepts_p_prefix() {
+        let key = SigningKey::from_bytes(&[5u8; 32]).verifying_key().to_bytes();
+        let encoded = encode_versioned_base58(&key);
+        assert_eq!(decode_public_key(&encoded).unwrap(), key);
+        assert_eq!(decode_public_key(&format!("P{}", encoded)).unwrap(), key);
+        assert!(decode_public_key("P1").is_err());
+    }
 }
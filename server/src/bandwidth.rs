@@ -0,0 +1,305 @@
+//! Bandwidth accounting and rate limiting for P2P and HTTP transfers.
+//!
+//! - Totals per P2P peer, per P2P protocol (blob request kind), per HTTP route and per
+//!   HTTP client IP, reported by `GET /admin/bandwidth`
+//! - Token-bucket rate limits: global upload/download limits shared by HTTP and P2P, and
+//!   per-peer upload/download limits for P2P
+//!
+//! Upload is egress (bytes sent by this node), download is ingress. Transfers are counted
+//! by payload size (blob and shard data; HTTP bodies as they are read and written). A
+//! transfer over the limit is delayed rather than refused, which keeps the average rate at
+//! the limit.
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::state::now_secs;
+
+/// Maximum distinct peers / HTTP clients tracked; further ones are summed under `OTHER_KEY`.
+const MAX_TRACKED_KEYS: usize = 1024;
+const OTHER_KEY: &str = "other";
+
+/// Token bucket holding up to one second of traffic. A transfer takes the bucket into debt
+/// of at most one bucket, so the next transfers wait until the debt is paid back; larger
+/// transfers pass one bucket at a time.
+pub struct RateLimiter {
+    bytes_per_sec: u64,
+    bucket: Mutex<(f64, Instant)>,
+}
+
+impl RateLimiter {
+    pub fn new(bytes_per_sec: u64) -> Self {
+        Self {
+            bytes_per_sec,
+            bucket: Mutex::new((bytes_per_sec as f64, Instant::now())),
+        }
+    }
+
+    /// Take `bytes` (at most one bucket) from the bucket and return how long the caller must
+    /// wait. When that would put the bucket more than one bucket in debt, nothing is taken
+    /// and `Err` holds the time after which to retry.
+    fn reserve(&self, bytes: u64, now: Instant) -> Result<Duration, Duration> {
+        let rate = self.bytes_per_sec as f64;
+        let mut bucket = self.bucket.lock().unwrap();
+        let (tokens, last) = *bucket;
+        let refilled = (tokens + now.saturating_duration_since(last).as_secs_f64() * rate).min(rate);
+        let remaining = refilled - bytes as f64;
+        if remaining < -rate {
+            *bucket = (refilled, now);
+            return Err(Duration::from_secs_f64((-rate - remaining) / rate));
+        }
+        *bucket = (remaining, now);
+        if remaining >= 0.0 {
+            Ok(Duration::ZERO)
+        } else {
+            Ok(Duration::from_secs_f64(-remaining / rate))
+        }
+    }
+
+    /// Wait until `bytes` may pass, one bucket at a time.
+    pub async fn acquire(&self, bytes: u64) {
+        let mut left = bytes;
+        while left > 0 {
+            let chunk = left.min(self.bytes_per_sec.max(1));
+            match self.reserve(chunk, Instant::now()) {
+                Ok(wait) => {
+                    left -= chunk;
+                    if !wait.is_zero() {
+                        tokio::time::sleep(wait).await;
+                    }
+                }
+                Err(retry) => tokio::time::sleep(retry).await,
+            }
+        }
+    }
+}
+
+/// Rate limits in bytes per second (`None` = unlimited).
+#[derive(Debug, Clone, Copy, Default, serde::Serialize)]
+pub struct BandwidthLimits {
+    pub upload: Option<u64>,
+    pub download: Option<u64>,
+    pub peer_upload: Option<u64>,
+    pub peer_download: Option<u64>,
+}
+
+/// Bytes received and sent.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, serde::Serialize)]
+pub struct Traffic {
+    pub bytes_in: u64,
+    pub bytes_out: u64,
+    /// Transfers (P2P requests or HTTP requests) counted.
+    pub transfers: u64,
+}
+
+impl Traffic {
+    fn add(&mut self, bytes_in: u64, bytes_out: u64) {
+        self.bytes_in += bytes_in;
+        self.bytes_out += bytes_out;
+        self.transfers += 1;
+    }
+}
+
+/// Traffic of one peer, protocol, route or client.
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct TrafficEntry {
+    pub key: String,
+    #[serde(flatten)]
+    pub traffic: Traffic,
+}
+
+/// Response of `GET /admin/bandwidth`.
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct BandwidthReport {
+    /// Start of the accounting period (Unix seconds); totals are kept in memory.
+    pub since: u64,
+    pub limits: BandwidthLimits,
+    pub p2p: Traffic,
+    pub http: Traffic,
+    /// Largest consumers first.
+    pub peers: Vec<TrafficEntry>,
+    pub protocols: Vec<TrafficEntry>,
+    pub routes: Vec<TrafficEntry>,
+    pub clients: Vec<TrafficEntry>,
+}
+
+#[derive(Default)]
+struct Totals {
+    p2p: Traffic,
+    http: Traffic,
+    peers: HashMap<String, Traffic>,
+    protocols: HashMap<String, Traffic>,
+    routes: HashMap<String, Traffic>,
+    clients: HashMap<String, Traffic>,
+}
+
+/// Add to `key`'s entry, or to `OTHER_KEY` once `MAX_TRACKED_KEYS` are tracked.
+fn add_capped(map: &mut HashMap<String, Traffic>, key: &str, bytes_in: u64, bytes_out: u64) {
+    let key = if map.contains_key(key) || map.len() < MAX_TRACKED_KEYS {
+        key
+    } else {
+        OTHER_KEY
+    };
+    map.entry(key.to_string()).or_default().add(bytes_in, bytes_out);
+}
+
+fn sorted_entries(map: &HashMap<String, Traffic>) -> Vec<TrafficEntry> {
+    let mut entries: Vec<TrafficEntry> = map
+        .iter()
+        .map(|(key, traffic)| TrafficEntry {
+            key: key.clone(),
+            traffic: *traffic,
+        })
+        .collect();
+    entries.sort_by_key(|e| std::cmp::Reverse(e.traffic.bytes_in + e.traffic.bytes_out));
+    entries
+}
+
+/// Upload and download limiters of one peer.
+type PeerLimiters = (Option<Arc<RateLimiter>>, Option<Arc<RateLimiter>>);
+
+/// Shared meter and limiters of the node.
+pub struct Bandwidth {
+    limits: BandwidthLimits,
+    upload: Option<RateLimiter>,
+    download: Option<RateLimiter>,
+    /// Per-peer limiters, created on first use.
+    peer_limiters: Mutex<HashMap<String, PeerLimiters>>,
+    totals: Mutex<Totals>,
+    since: u64,
+}
+
+pub type SharedBandwidth = Arc<Bandwidth>;
+
+impl Bandwidth {
+    pub fn new(limits: BandwidthLimits) -> Self {
+        Self {
+            limits,
+            upload: limits.upload.map(RateLimiter::new),
+            download: limits.download.map(RateLimiter::new),
+            peer_limiters: Mutex::new(HashMap::new()),
+            totals: Mutex::new(Totals::default()),
+            since: now_secs(),
+        }
+    }
+
+    /// Wait for the global limits (HTTP transfers).
+    pub async fn throttle(&self, upload: u64, download: u64) {
+        if let (Some(limiter), true) = (&self.upload, upload > 0) {
+            limiter.acquire(upload).await;
+        }
+        if let (Some(limiter), true) = (&self.download, download > 0) {
+            limiter.acquire(download).await;
+        }
+    }
+
+    /// Wait for the global and per-peer limits (P2P transfers with `peer`).
+    pub async fn throttle_peer(&self, peer: &str, upload: u64, download: u64) {
+        let (peer_upload, peer_download) = {
+            let mut limiters = self.peer_limiters.lock().unwrap();
+            if limiters.len() >= MAX_TRACKED_KEYS && !limiters.contains_key(peer) {
+                // Buckets are recreated full; only idle peers lose their state
+                limiters.clear();
+            }
+            limiters
+                .entry(peer.to_string())
+                .or_insert_with(|| {
+                    (
+                        self.limits.peer_upload.map(|rate| Arc::new(RateLimiter::new(rate))),
+                        self.limits.peer_download.map(|rate| Arc::new(RateLimiter::new(rate))),
+                    )
+                })
+                .clone()
+        };
+        if let (Some(limiter), true) = (peer_upload, upload > 0) {
+            limiter.acquire(upload).await;
+        }
+        if let (Some(limiter), true) = (peer_download, download > 0) {
+            limiter.acquire(download).await;
+        }
+        self.throttle(upload, download).await;
+    }
+
+    /// Count a P2P transfer with `peer` over `protocol`.
+    pub fn record_peer(&self, peer: &str, protocol: &str, bytes_in: u64, bytes_out: u64) {
+        let mut totals = self.totals.lock().unwrap();
+        totals.p2p.add(bytes_in, bytes_out);
+        add_capped(&mut totals.peers, peer, bytes_in, bytes_out);
+        add_capped(&mut totals.protocols, protocol, bytes_in, bytes_out);
+    }
+
+    /// Count an HTTP request on `route` from `client`.
+    pub fn record_http(&self, route: &str, client: &str, bytes_in: u64, bytes_out: u64) {
+        let mut totals = self.totals.lock().unwrap();
+        totals.http.add(bytes_in, bytes_out);
+        add_capped(&mut totals.routes, route, bytes_in, bytes_out);
+        add_capped(&mut totals.clients, client, bytes_in, bytes_out);
+    }
+
+    pub fn report(&self) -> BandwidthReport {
+        let totals = self.totals.lock().unwrap();
+        BandwidthReport {
+            since: self.since,
+            limits: self.limits,
+            p2p: totals.p2p,
+            http: totals.http,
+            peers: sorted_entries(&totals.peers),
+            protocols: sorted_entries(&totals.protocols),
+            routes: sorted_entries(&totals.routes),
+            clients: sorted_entries(&totals.clients),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn limiter_delays_transfers_over_the_rate() {
+        let limiter = RateLimiter::new(1000);
+        let now = Instant::now();
+        // The bucket starts full (one second of traffic)
+        assert_eq!(limiter.reserve(1000, now), Ok(Duration::ZERO));
+        // Empty: 500 bytes take half a second
+        assert_eq!(limiter.reserve(500, now), Ok(Duration::from_millis(500)));
+        // Debt of 500 bytes, paid back after 0.5 s; 2 s later the bucket is full again
+        assert_eq!(limiter.reserve(1000, now + Duration::from_secs(2)), Ok(Duration::ZERO));
+    }
+
+    #[test]
+    fn limiter_debt_is_capped_at_one_bucket() {
+        let limiter = RateLimiter::new(1000);
+        let now = Instant::now();
+        assert_eq!(limiter.reserve(1000, now), Ok(Duration::ZERO));
+        assert_eq!(limiter.reserve(1000, now), Ok(Duration::from_secs(1)));
+        // One bucket in debt: nothing more is taken until part of it is paid back
+        assert_eq!(limiter.reserve(500, now), Err(Duration::from_millis(500)));
+        assert_eq!(
+            limiter.reserve(500, now + Duration::from_millis(500)),
+            Ok(Duration::from_secs(1))
+        );
+    }
+
+
+    #[test]
+    fn totals_per_key_are_sorted_and_capped() {
+        let bandwidth = Bandwidth::new(BandwidthLimits::default());
+        bandwidth.record_peer("a", "blob/get_shard", 10, 0);
+        bandwidth.record_peer("b", "blob/store_blob", 0, 100);
+        bandwidth.record_peer("a", "blob/get_shard", 5, 0);
+        let report = bandwidth.report();
+        assert_eq!(report.p2p, Traffic { bytes_in: 15, bytes_out: 100, transfers: 3 });
+        assert_eq!(report.peers[0].key, "b");
+        assert_eq!(report.peers[1].traffic.bytes_in, 15);
+
+        for i in 0..MAX_TRACKED_KEYS + 5 {
+            bandwidth.record_http("/data/{id}", &format!("10.0.0.{}", i), 0, 1);
+        }
+        let report = bandwidth.report();
+        assert_eq!(report.clients.len(), MAX_TRACKED_KEYS + 1);
+        assert_eq!(report.clients.iter().find(|e| e.key == OTHER_KEY).unwrap().traffic.bytes_out, 5);
+        assert_eq!(report.routes[0].traffic.transfers, MAX_TRACKED_KEYS as u64 + 5);
+    }
+}
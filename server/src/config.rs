@@ -1,6 +1,13 @@
 //! Server configuration (storage path, bind address, P2P, Massa address).
 
 use std::path::PathBuf;
+use std::time::Duration;
+
+use crate::bandwidth::BandwidthLimits;
+use crate::fees::{parse_mas, FeeStrategy, GasStrategy, WriteCosts};
+use crate::massa_grpc::ChainId;
+use crate::provider_metadata::MetadataConfig;
+use crate::rewards::RewardsConfig;
 
 /// Storage server configuration.
 #[derive(Clone, Debug)]
@@ -11,22 +18,58 @@ pub struct Config {
     pub bind_address: String,
     /// Storage size limit in GB (mandatory). Uploads are rejected when total usage would exceed this.
     pub storage_limit_gb: u64,
-    /// libp2p listen address (multiaddr), e.g. `/ip4/0.0.0.0/tcp/0`.
-    pub p2p_listen_addr: String,
+    /// libp2p listen addresses (multiaddrs), e.g. `/ip4/0.0.0.0/tcp/4001`.
+    pub p2p_listen_addrs: Vec<String>,
+    /// Operator-declared public P2P addresses (multiaddrs without `/p2p`); advertised via
+    /// identify and registered in the contract without waiting for AutoNAT.
+    pub p2p_external_addrs: Vec<String>,
+    /// TCP transport enabled.
+    pub p2p_tcp: bool,
+    /// QUIC transport enabled.
+    pub p2p_quic: bool,
+    /// mDNS discovery of nodes on the local network (development clusters).
+    pub p2p_mdns: bool,
+    /// Maximum inbound / outbound P2P connections.
+    pub p2p_max_inbound: u32,
+    pub p2p_max_outbound: u32,
     /// Massa address (derived from PRIVATE_KEY).
     pub massa_address: String,
     /// Storage registry smart contract address (required). Used for upload auth, provider list, and contract writes.
     pub storage_registry_address: String,
-    /// Massa JSON-RPC URL (e.g. https://buildnet.massa.net/api/v2). Required for upload auth.
-    pub massa_json_rpc: String,
+    /// Massa JSON-RPC URLs (e.g. https://buildnet.massa.net/api/v2), tried in order with
+    /// failover. Required for upload auth.
+    pub massa_json_rpc: Vec<String>,
     /// Bootstrap peers to connect to on startup (comma-separated multiaddrs).
     pub bootstrap_peers: Vec<String>,
-    /// Massa gRPC URL for write operations (e.g. `grpc://buildnet.massa.net:33037`).
-    pub massa_grpc_url: Option<String>,
+    /// Massa gRPC URLs for write operations (e.g. `grpc://buildnet.massa.net:33037`), tried
+    /// in order with failover. Empty disables contract writes.
+    pub massa_grpc_urls: Vec<String>,
+    /// Timeout of one JSON-RPC or gRPC request.
+    pub massa_rpc_timeout: Duration,
+    /// Network that operations are signed for.
+    pub massa_chain_id: ChainId,
+    /// Fee and gas of contract writes.
+    pub write_costs: WriteCosts,
+    /// Reward claiming and the balance floor of fee-paying writes.
+    pub rewards: RewardsConfig,
+    /// Provider metadata reconciliation interval and write rate limit.
+    pub provider_metadata: MetadataConfig,
     /// Private key for signing transactions (required).
     pub private_key: String,
     /// Public HTTP endpoint for this provider (registered in contract). Defaults to http://BIND_ADDRESS when unset.
     pub public_endpoint: String,
+    /// Integrity scrubber read rate in bytes per second (0 disables the scrubber).
+    pub scrub_rate_bytes_per_sec: u64,
+    /// Pause between two scrub passes, in seconds.
+    pub scrub_interval_secs: u64,
+    /// Global and per-peer upload/download rate limits.
+    pub bandwidth_limits: BandwidthLimits,
+    /// Fetch blobs missing locally from connected providers on `GET /data/...`.
+    pub read_through: bool,
+    /// Quota of the read-through cache in bytes (0 = fetched blobs are not cached).
+    pub read_through_cache_bytes: u64,
+    /// Bearer token of `/admin/*`; without one, those routes only answer loopback clients.
+    pub admin_token: Option<String>,
 }
 
 impl Config {
@@ -36,46 +79,284 @@ impl Config {
     /// - `STORAGE_LIMIT_GB` (required): max total storage in GB; uploads rejected when exceeded
     /// - `PRIVATE_KEY` (required): Massa private key (S12...); address is derived from it
     /// - `STORAGE_REGISTRY_ADDRESS` (required): storage registry contract address; server will not start if missing
-    /// - `MASSA_JSON_RPC` (required): Massa JSON-RPC URL for read-only SC calls
-    pub fn from_env() -> Self {
+    /// - `MASSA_JSON_RPC` (required): comma-separated Massa JSON-RPC URLs for read-only SC calls
+    /// - `MASSA_GRPC_URL` (optional): comma-separated Massa gRPC URLs for contract writes
+    /// - `MASSA_RPC_TIMEOUT_SECS` (optional): timeout of one node request (default: 15)
+    /// - `MASSA_NETWORK` (optional): `mainnet`, `buildnet` or `sandbox` (default: buildnet)
+    /// - `MASSA_CHAIN_ID` (optional): numeric chain id of another network; overrides `MASSA_NETWORK`
+    /// - `MASSA_FEE` (optional): fee of every write in MAS, or `estimate` (default: 0.01)
+    /// - `MASSA_FEE_OVERRIDES` (optional): comma-separated `function=fee` pairs (fixed fee only)
+    /// - `MASSA_FEE_MIN` / `MASSA_FEE_MAX` (optional): bounds of an estimated fee (default: 0.01 / 1)
+    /// - `MASSA_MAX_GAS` (optional): `max_gas` of every write, or `estimate` to dry-run each
+    ///   call first (default: 10000000)
+    /// - `REWARDS_CLAIM_THRESHOLD` (optional): pending rewards in MAS that trigger a claim (default: 1)
+    /// - `REWARDS_INTERVAL_SECS` (optional): pause between two rewards checks (default: 3600)
+    /// - `MASSA_MIN_BALANCE` (optional): balance in MAS below which fee-paying writes are
+    ///   paused (default: 0.1)
+    /// - `METADATA_RECONCILE_SECS` (optional): pause between two comparisons of the provider
+    ///   metadata with the contract (default: 600)
+    /// - `METADATA_UPDATE_MIN_SECS` (optional): minimum delay between two registry writes of
+    ///   the reconciler (default: 1800)
+    /// - `SCRUB_RATE_MB_PER_SEC` (optional): scrubber read rate in MB/s (default: 10; 0 disables)
+    /// - `SCRUB_INTERVAL_SECS` (optional): pause between scrub passes (default: 21600)
+    /// - `P2P_PORT` (optional): TCP and QUIC (UDP) listen port (default: 0, random)
+    /// - `P2P_TCP` / `P2P_QUIC` (optional): enable the transport (default: true; not both false)
+    /// - `P2P_IPV6` (optional): also listen on IPv6 (default: false)
+    /// - `P2P_LISTEN_ADDRS` (optional): comma-separated multiaddrs; overrides `P2P_PORT` and `P2P_IPV6`
+    /// - `P2P_EXTERNAL_ADDRS` (optional): comma-separated public multiaddrs to advertise
+    /// - `P2P_MDNS` (optional): discover local nodes via mDNS, trusted without registry check (default: false)
+    /// - `P2P_MAX_INBOUND` / `P2P_MAX_OUTBOUND` (optional): connection limits (default: 64 / 32)
+    /// - `BANDWIDTH_UPLOAD_KB_PER_SEC` / `BANDWIDTH_DOWNLOAD_KB_PER_SEC` (optional): global rate
+    ///   limits for HTTP and P2P (default: unlimited)
+    /// - `BANDWIDTH_PEER_UPLOAD_KB_PER_SEC` / `BANDWIDTH_PEER_DOWNLOAD_KB_PER_SEC` (optional):
+    ///   rate limits per P2P peer (default: unlimited)
+    /// - `READ_THROUGH` (optional): serve blobs held only by other providers (default: false)
+    /// - `READ_THROUGH_CACHE_MB` (optional): cache quota for fetched blobs, separate from
+    ///   `STORAGE_LIMIT_GB` (default: 0, no cache)
+    /// - `ADMIN_TOKEN` (optional): bearer token required by `/admin/*` (default: none, those
+    ///   routes are served to loopback clients only)
+    ///
+    /// Fails with `VAR is required ...` when a required variable is missing, and with
+    /// `invalid VAR: ...` when a variable is set to an unusable value.
+    pub fn from_env() -> Result<Self, String> {
         let storage_path = std::env::var("STORAGE_PATH")
             .map(PathBuf::from)
             .unwrap_or_else(|_| PathBuf::from("./data"));
         let bind_address = std::env::var("BIND_ADDRESS")
             .unwrap_or_else(|_| "127.0.0.1:4343".to_string());
-        let storage_limit_gb = std::env::var("STORAGE_LIMIT_GB")
-            .expect("STORAGE_LIMIT_GB is required")
-            .parse::<u64>()
-            .expect("STORAGE_LIMIT_GB must be a positive integer");
-        // P2P listen addr is not configurable via env; value is shown in logs when P2P starts.
-        let p2p_listen_addr = "/ip4/0.0.0.0/tcp/0".to_string();
-        let private_key = std::env::var("PRIVATE_KEY")
-            .expect("PRIVATE_KEY is required (Massa private key, e.g. S12...)");
+        let storage_limit_gb = env_parse("STORAGE_LIMIT_GB", str::parse::<u64>)?
+            .ok_or("STORAGE_LIMIT_GB is required (max total storage in GB)")?;
+        let p2p_tcp = env_parse("P2P_TCP", parse_flag)?.unwrap_or(true);
+        let p2p_quic = env_parse("P2P_QUIC", parse_flag)?.unwrap_or(true);
+        if !p2p_tcp && !p2p_quic {
+            return Err("invalid P2P_TCP / P2P_QUIC: both transports cannot be disabled".to_string());
+        }
+        let p2p_listen_addrs = match env_list("P2P_LISTEN_ADDRS") {
+            addrs if !addrs.is_empty() => addrs,
+            _ => {
+                let port = env_parse("P2P_PORT", str::parse::<u16>)?.unwrap_or(0);
+                let ipv6 = env_parse("P2P_IPV6", parse_flag)?.unwrap_or(false);
+                listen_addrs_for(port, p2p_tcp, p2p_quic, ipv6)
+            }
+        };
+        let p2p_external_addrs = env_list("P2P_EXTERNAL_ADDRS");
+        let p2p_mdns = env_parse("P2P_MDNS", parse_flag)?.unwrap_or(false);
+        let p2p_max_inbound = env_parse("P2P_MAX_INBOUND", str::parse::<u32>)?.unwrap_or(64);
+        let p2p_max_outbound = env_parse("P2P_MAX_OUTBOUND", str::parse::<u32>)?.unwrap_or(32);
+        let private_key = env_required("PRIVATE_KEY", "Massa private key, e.g. S12...")?;
         let massa_address = crate::massa_grpc::address_from_private_key(&private_key)
-            .expect("PRIVATE_KEY is invalid (could not derive address)");
-        let storage_registry_address = std::env::var("STORAGE_REGISTRY_ADDRESS")
-            .expect("STORAGE_REGISTRY_ADDRESS is required (storage registry contract address); server will not start without it");
-        let massa_json_rpc = std::env::var("MASSA_JSON_RPC")
-            .expect("MASSA_JSON_RPC is required for upload authentication");
-        let bootstrap_peers = std::env::var("BOOTSTRAP_PEERS")
-            .map(|s| s.split(',').map(|p| p.trim().to_string()).filter(|p| !p.is_empty()).collect())
-            .unwrap_or_default();
-        let massa_grpc_url = std::env::var("MASSA_GRPC_URL").ok();
+            .map_err(|e| format!("invalid PRIVATE_KEY: {}", e))?;
+        let storage_registry_address =
+            env_required("STORAGE_REGISTRY_ADDRESS", "storage registry contract address")?;
+        let massa_json_rpc = env_list("MASSA_JSON_RPC");
+        if massa_json_rpc.is_empty() {
+            return Err("MASSA_JSON_RPC is required (Massa JSON-RPC URLs for upload authentication)".to_string());
+        }
+        let bootstrap_peers = env_list("BOOTSTRAP_PEERS");
+        let massa_grpc_urls = env_list("MASSA_GRPC_URL");
+        let massa_rpc_timeout =
+            env_parse("MASSA_RPC_TIMEOUT_SECS", parse_secs)?.unwrap_or(crate::rpc::DEFAULT_TIMEOUT);
+        let massa_chain_id = match env_parse("MASSA_CHAIN_ID", str::parse::<u64>)? {
+            Some(id) => ChainId::Custom(id),
+            None => env_parse("MASSA_NETWORK", |n| {
+                ChainId::parse(n).ok_or("expected mainnet, buildnet or sandbox")
+            })?
+            .unwrap_or(ChainId::Buildnet),
+        };
+        let fee = FeeStrategy::parse(
+            &env_or("MASSA_FEE", crate::fees::DEFAULT_FEE),
+            &env_list("MASSA_FEE_OVERRIDES"),
+            &env_or("MASSA_FEE_MIN", crate::fees::DEFAULT_FEE_MIN),
+            &env_or("MASSA_FEE_MAX", crate::fees::DEFAULT_FEE_MAX),
+        )
+        .map_err(|e| format!("invalid MASSA_FEE: {}", e))?;
+        let gas = env_parse("MASSA_MAX_GAS", GasStrategy::parse)?
+            .unwrap_or(GasStrategy::Fixed(crate::fees::DEFAULT_MAX_GAS));
+        let rewards = RewardsConfig {
+            claim_threshold: env_parse("REWARDS_CLAIM_THRESHOLD", parse_mas)?
+                .unwrap_or_else(|| parse_mas("1").expect("valid default")),
+            min_balance: env_parse("MASSA_MIN_BALANCE", parse_mas)?
+                .unwrap_or_else(|| parse_mas("0.1").expect("valid default")),
+            interval: env_parse("REWARDS_INTERVAL_SECS", parse_secs)?.unwrap_or(Duration::from_secs(3600)),
+        };
+        let provider_metadata = MetadataConfig {
+            interval: env_parse("METADATA_RECONCILE_SECS", parse_secs)?.unwrap_or(Duration::from_secs(600)),
+            min_update_interval: env_parse("METADATA_UPDATE_MIN_SECS", parse_secs)?
+                .unwrap_or(Duration::from_secs(1800)),
+        };
         let public_endpoint = std::env::var("PUBLIC_ENDPOINT")
             .unwrap_or_else(|_| format!("http://{}", bind_address));
+        let scrub_rate_bytes_per_sec = env_parse("SCRUB_RATE_MB_PER_SEC", str::parse::<u64>)?
+            .unwrap_or(10)
+            .saturating_mul(1024 * 1024);
+        let scrub_interval_secs = env_parse("SCRUB_INTERVAL_SECS", str::parse::<u64>)?.unwrap_or(21600);
+        let bandwidth_limits = BandwidthLimits {
+            upload: env_rate("BANDWIDTH_UPLOAD_KB_PER_SEC")?,
+            download: env_rate("BANDWIDTH_DOWNLOAD_KB_PER_SEC")?,
+            peer_upload: env_rate("BANDWIDTH_PEER_UPLOAD_KB_PER_SEC")?,
+            peer_download: env_rate("BANDWIDTH_PEER_DOWNLOAD_KB_PER_SEC")?,
+        };
+        let read_through = env_parse("READ_THROUGH", parse_flag)?.unwrap_or(false);
+        let read_through_cache_bytes = env_parse("READ_THROUGH_CACHE_MB", str::parse::<u64>)?
+            .unwrap_or(0)
+            .saturating_mul(1024 * 1024);
+        let admin_token = std::env::var("ADMIN_TOKEN")
+            .ok()
+            .map(|token| token.trim().to_string())
+            .filter(|token| !token.is_empty());
 
-        Self {
+        Ok(Self {
             storage_path,
             bind_address,
             storage_limit_gb,
-            p2p_listen_addr,
+            p2p_listen_addrs,
+            p2p_external_addrs,
+            p2p_tcp,
+            p2p_quic,
+            p2p_mdns,
+            p2p_max_inbound,
+            p2p_max_outbound,
             massa_address,
             storage_registry_address,
             massa_json_rpc,
             bootstrap_peers,
-            massa_grpc_url,
+            massa_grpc_urls,
+            massa_rpc_timeout,
+            massa_chain_id,
+            write_costs: WriteCosts { fee, gas },
+            rewards,
+            provider_metadata,
             private_key,
             public_endpoint,
+            scrub_rate_bytes_per_sec,
+            scrub_interval_secs,
+            bandwidth_limits,
+            read_through,
+            read_through_cache_bytes,
+            admin_token,
+        })
+    }
+}
+
+/// Comma-separated list from the environment (empty when unset).
+fn env_list(name: &str) -> Vec<String> {
+    std::env::var(name)
+        .map(|s| s.split(',').map(|p| p.trim().to_string()).filter(|p| !p.is_empty()).collect())
+        .unwrap_or_default()
+}
+
+/// Required value from the environment; a missing or empty one fails with
+/// `NAME is required (<what>)`.
+fn env_required(name: &str, what: &str) -> Result<String, String> {
+    std::env::var(name)
+        .ok()
+        .filter(|value| !value.trim().is_empty())
+        .ok_or_else(|| format!("{} is required ({})", name, what))
+}
+
+/// Value from the environment, `default` when unset.
+fn env_or(name: &str, default: &str) -> String {
+    std::env::var(name).unwrap_or_else(|_| default.to_string())
+}
+
+/// `name` from the environment parsed with `parse`, `None` when unset. Errors read
+/// `invalid NAME: <reason>`.
+fn env_parse<T, E: std::fmt::Display>(
+    name: &str,
+    parse: impl FnOnce(&str) -> Result<T, E>,
+) -> Result<Option<T>, String> {
+    match std::env::var(name) {
+        Ok(value) => parse(value.trim())
+            .map(Some)
+            .map_err(|e| format!("invalid {}: {}", name, e)),
+        Err(std::env::VarError::NotPresent) => Ok(None),
+        Err(e) => Err(format!("invalid {}: {}", name, e)),
+    }
+}
+
+/// Boolean flag (`true`/`false`, `1`/`0`, `yes`/`no`).
+fn parse_flag(value: &str) -> Result<bool, String> {
+    match value.to_ascii_lowercase().as_str() {
+        "true" | "1" | "yes" => Ok(true),
+        "false" | "0" | "no" => Ok(false),
+        _ => Err(format!("expected true or false, got {:?}", value)),
+    }
+}
+
+/// Positive number of seconds.
+fn parse_secs(value: &str) -> Result<Duration, String> {
+    match value.parse::<u64>() {
+        Ok(secs) if secs > 0 => Ok(Duration::from_secs(secs)),
+        _ => Err(format!("expected a positive number of seconds, got {:?}", value)),
+    }
+}
+
+/// Rate limit in KB/s from the environment, as bytes per second (`None` when unset or 0).
+fn env_rate(name: &str) -> Result<Option<u64>, String> {
+    Ok(env_parse(name, str::parse::<u64>)?
+        .filter(|kb| *kb > 0)
+        .map(|kb| kb.saturating_mul(1024)))
+}
+
+/// Listen multiaddrs for the enabled transports on `port` (all interfaces).
+fn listen_addrs_for(port: u16, tcp: bool, quic: bool, ipv6: bool) -> Vec<String> {
+    let mut ips = vec!["/ip4/0.0.0.0"];
+    if ipv6 {
+        ips.push("/ip6/::");
+    }
+    let mut addrs = Vec::new();
+    for ip in ips {
+        if tcp {
+            addrs.push(format!("{}/tcp/{}", ip, port));
+        }
+        if quic {
+            addrs.push(format!("{}/udp/{}/quic-v1", ip, port));
         }
     }
+    addrs
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn listen_addrs_follow_enabled_transports() {
+        assert_eq!(
+            listen_addrs_for(4001, true, true, false),
+            vec!["/ip4/0.0.0.0/tcp/4001", "/ip4/0.0.0.0/udp/4001/quic-v1"]
+        );
+        assert_eq!(
+            listen_addrs_for(4001, false, true, true),
+            vec!["/ip4/0.0.0.0/udp/4001/quic-v1", "/ip6/::/udp/4001/quic-v1"]
+        );
+    }
+
+    #[test]
+    fn env_parse_reports_the_variable() {
+        assert_eq!(env_parse("MASSA_STORAGE_TEST_UNSET", str::parse::<u32>), Ok(None));
+        std::env::set_var("MASSA_STORAGE_TEST_PORT", " 4001 ");
+        assert_eq!(env_parse("MASSA_STORAGE_TEST_PORT", str::parse::<u16>), Ok(Some(4001)));
+        std::env::set_var("MASSA_STORAGE_TEST_PORT", "70000");
+        let err = env_parse("MASSA_STORAGE_TEST_PORT", str::parse::<u16>).unwrap_err();
+        assert!(err.starts_with("invalid MASSA_STORAGE_TEST_PORT: "), "{}", err);
+    }
+
+    #[test]
+    fn env_required_names_the_variable() {
+        let err = env_required("MASSA_STORAGE_TEST_REQUIRED", "a test value").unwrap_err();
+        assert_eq!(err, "MASSA_STORAGE_TEST_REQUIRED is required (a test value)");
+        std::env::set_var("MASSA_STORAGE_TEST_REQUIRED", "value");
+        assert_eq!(env_required("MASSA_STORAGE_TEST_REQUIRED", "a test value"), Ok("value".to_string()));
+    }
+
+    #[test]
+    fn parses_flags_and_seconds() {
+        assert_eq!(parse_flag("Yes"), Ok(true));
+        assert_eq!(parse_flag("0"), Ok(false));
+        assert!(parse_flag("maybe").is_err());
+        assert_eq!(parse_secs("60"), Ok(Duration::from_secs(60)));
+        assert!(parse_secs("0").is_err());
+        assert!(parse_secs("-1").is_err());
+    }
 }
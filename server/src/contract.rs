@@ -1,17 +1,31 @@
 //! Massa smart contract interaction.
 //!
 //! - Read-only queries via JSON-RPC
-//! - Write operations (updateProviderMetadata) via gRPC
+//! - Endpoint failover, timeouts and retries in [`crate::rpc`]
+//! - Finalized contract events via JSON-RPC (`get_filtered_sc_output_event`)
+//! - Write operations via gRPC, with the fee and gas policy of [`crate::fees`]; their
+//!   outcome is followed by [`crate::operations`]
+//!
+//! Entrypoint encodings and struct layouts live in [`crate::registry`].
 
-use anyhow::{anyhow, Result};
+use anyhow::{anyhow, Context, Result};
 use serde::{Deserialize, Serialize};
-use std::sync::Arc;
-use tokio::sync::Mutex;
-
-use crate::args::Args;
-use crate::massa_grpc::{ChainId, GrpcClient};
+use std::collections::HashMap;
+use std::str::FromStr;
+use std::time::{Duration, Instant};
+
+use crate::fees::{self, GasStrategy, WriteCosts};
+use crate::massa_grpc::{self, ChainId, EventStream, ScCall, ScOutputEvent};
+use crate::registry::{self, ProviderMetadata, RegistryCall, RegistryView, Serializable};
+use crate::registry::{Challenge, GlobalStorageUsage, PeriodStats, StorageConfig, StorageNode};
+use crate::rpc::{EndpointHealth, Grpc, JsonRpc};
 use massa_models::amount::Amount;
 
+/// Gas allowed to read-only calls (views and dry runs).
+const READ_ONLY_MAX_GAS: u64 = 1_000_000_000;
+/// How long an estimated fee is reused before sampling recent blocks again.
+const FEE_ESTIMATE_TTL: Duration = Duration::from_secs(60);
+
 /// Provider info from the contract
 #[derive(Debug, Clone)]
 pub struct ProviderInfo {
@@ -23,31 +37,54 @@ pub struct ProviderInfo {
 /// Massa client for contract interactions
 /// - JSON-RPC for read-only queries
 /// - gRPC for write operations (requires private key)
+///
+/// Both go through [`crate::rpc`], which fails over between the configured endpoints.
 pub struct MassaClient {
-    http: reqwest::Client,
-    rpc_url: String,
+    rpc: JsonRpc,
     contract_address: String,
     /// gRPC client for write operations (optional, requires private key)
-    grpc_client: Option<Arc<Mutex<GrpcClient>>>,
+    grpc: Option<Grpc>,
+    /// Address that signs writes; dry runs execute as this caller.
+    sender_address: Option<String>,
+    write_costs: WriteCosts,
+    /// Last estimated fee and when it was estimated.
+    fee_estimate: std::sync::Mutex<Option<(Amount, Instant)>>,
+    /// Why fee-paying writes are refused (low balance), when they are.
+    writes_paused: std::sync::Mutex<Option<String>>,
 }
 
-#[derive(Serialize)]
-struct JsonRpcRequest<'a> {
-    jsonrpc: &'static str,
-    id: u64,
-    method: &'a str,
-    params: serde_json::Value,
+/// Write sent by [`MassaClient::call`], with what is needed to send it again.
+#[derive(Debug, Clone)]
+pub struct SubmittedCall {
+    pub call: RegistryCall,
+    pub coins: Amount,
+    pub operation_id: String,
+    /// Last period in which the operation can be included.
+    pub expire_period: u64,
 }
 
-#[derive(Deserialize)]
-struct JsonRpcResponse {
-    result: Option<serde_json::Value>,
-    error: Option<serde_json::Value>,
+/// Inclusion and execution state of an operation (`get_operations`).
+#[derive(Debug, Clone, Default)]
+pub struct OperationInfo {
+    pub in_pool: bool,
+    pub in_block: bool,
+    pub is_final: bool,
+    /// Execution result once executed: `Some(false)` when the call failed.
+    pub executed: Option<bool>,
+}
+
+/// Health of the configured node endpoints (`GET /admin/rpc`).
+#[derive(Debug, Serialize)]
+pub struct RpcHealth {
+    pub json_rpc: Vec<EndpointHealth>,
+    pub grpc: Vec<EndpointHealth>,
 }
 
 #[derive(Deserialize)]
 struct ReadOnlyResult {
     result: Option<ReadOnlyResultInner>,
+    #[serde(default)]
+    gas_cost: u64,
 }
 
 #[derive(Deserialize)]
@@ -58,127 +95,451 @@ struct ReadOnlyResultInner {
     error: Option<String>,
 }
 
+#[derive(Deserialize)]
+struct EventSlot {
+    period: u64,
+    thread: u32,
+}
+
+#[derive(Deserialize)]
+struct EventContext {
+    slot: EventSlot,
+    index_in_slot: u64,
+    #[serde(default)]
+    call_stack: Vec<String>,
+    origin_operation_id: Option<String>,
+    #[serde(default)]
+    is_error: bool,
+}
+
+#[derive(Deserialize)]
+struct RpcOperationInfo {
+    id: String,
+    #[serde(default)]
+    in_pool: bool,
+    #[serde(default)]
+    in_blocks: Vec<String>,
+    #[serde(default)]
+    is_operation_final: Option<bool>,
+    #[serde(default)]
+    op_exec_status: Option<bool>,
+}
+
+#[derive(Deserialize)]
+struct NodeStatus {
+    last_slot: Option<EventSlot>,
+    #[serde(default)]
+    chain_id: Option<u64>,
+}
+
+#[derive(Deserialize)]
+struct RpcBlock {
+    #[serde(default)]
+    operations: Vec<String>,
+}
+
+/// Operation of a `get_operations` answer, down to its fee.
+#[derive(Deserialize)]
+struct RpcOperationFee {
+    operation: RpcSignedOperation,
+}
+
+#[derive(Deserialize)]
+struct RpcSignedOperation {
+    content: RpcOperationContent,
+}
+
+#[derive(Deserialize)]
+struct RpcOperationContent {
+    fee: String,
+}
+
+#[derive(Deserialize)]
+struct OutputEvent {
+    context: EventContext,
+    data: String,
+}
+
 impl MassaClient {
-    pub fn new(rpc_url: String, contract_address: String) -> Self {
+    pub fn new(rpc_urls: Vec<String>, contract_address: String, timeout: Duration) -> Self {
         Self {
-            http: reqwest::Client::new(),
-            rpc_url,
+            rpc: JsonRpc::new(rpc_urls, timeout),
             contract_address,
-            grpc_client: None,
+            grpc: None,
+            sender_address: None,
+            write_costs: WriteCosts::default(),
+            fee_estimate: std::sync::Mutex::new(None),
+            writes_paused: std::sync::Mutex::new(None),
         }
     }
 
-    /// Create a client with gRPC support for write operations
-    pub async fn with_grpc(
-        rpc_url: String,
-        grpc_url: String,
+    /// Create a client with gRPC support for write operations, signed for `chain_id` with
+    /// the fee and gas of `write_costs`. gRPC endpoints are connected on first use; this
+    /// only fails on an invalid private key.
+    pub fn with_grpc(
+        rpc_urls: Vec<String>,
+        grpc_urls: Vec<String>,
         contract_address: String,
         private_key: &str,
+        chain_id: ChainId,
+        write_costs: WriteCosts,
+        timeout: Duration,
     ) -> Result<Self> {
-        let grpc_client = GrpcClient::new(&grpc_url, private_key, ChainId::Buildnet).await?;
+        let grpc = Grpc::new(grpc_urls, private_key, chain_id, timeout)?;
 
         Ok(Self {
-            http: reqwest::Client::new(),
-            rpc_url,
+            rpc: JsonRpc::new(rpc_urls, timeout),
             contract_address,
-            grpc_client: Some(Arc::new(Mutex::new(grpc_client))),
+            grpc: Some(grpc),
+            sender_address: Some(massa_grpc::address_from_private_key(private_key)?),
+            write_costs,
+            fee_estimate: std::sync::Mutex::new(None),
+            writes_paused: std::sync::Mutex::new(None),
         })
     }
 
-    /// Call a read-only function on the contract
-    async fn read_only_call(&self, function: &str, args: &[u8]) -> Result<Vec<u8>> {
+    /// True when gRPC endpoints are configured (writes and the event stream).
+    pub fn has_grpc(&self) -> bool {
+        self.grpc.is_some()
+    }
+
+    /// Candidate balance of the signing address (gRPC).
+    pub async fn get_balance(&self) -> Result<Amount> {
+        match (&self.grpc, &self.sender_address) {
+            (Some(grpc), Some(address)) => grpc.get_balance(address).await,
+            _ => Err(anyhow!("gRPC client not configured (cannot read balance)")),
+        }
+    }
+
+    /// Refuse fee-paying writes for `reason`, or accept them again with `None`.
+    /// `claimRewards` is always sent, since it is how a low balance recovers.
+    pub fn pause_writes(&self, reason: Option<String>) {
+        *self.writes_paused.lock().unwrap() = reason;
+    }
+
+    pub fn writes_paused(&self) -> Option<String> {
+        self.writes_paused.lock().unwrap().clone()
+    }
+
+    /// Health of every configured JSON-RPC and gRPC endpoint.
+    pub fn rpc_health(&self) -> RpcHealth {
+        RpcHealth {
+            json_rpc: self.rpc.health(),
+            grpc: self.grpc.as_ref().map(Grpc::health).unwrap_or_default(),
+        }
+    }
+
+    /// Run `execute_read_only_call` and return the per-call result.
+    async fn execute_read_only(&self, function: &str, args: &[u8]) -> Result<Option<ReadOnlyResultInner>> {
         let params = serde_json::json!([[{
             "target_address": self.contract_address,
             "target_function": function,
             "parameter": args.iter().map(|b| *b as i32).collect::<Vec<_>>(),
-            "max_gas": 1_000_000_000u64,
+            "max_gas": READ_ONLY_MAX_GAS,
         }]]);
 
-        let req = JsonRpcRequest {
-            jsonrpc: "2.0",
-            id: 1,
-            method: "execute_read_only_call",
-            params,
-        };
+        let result = self.rpc.request("execute_read_only_call", params).await?;
+        let parsed: Vec<ReadOnlyResult> = serde_json::from_value(result)?;
+        Ok(parsed.into_iter().next().and_then(|r| r.result))
+    }
 
-        let resp: JsonRpcResponse = self
-            .http
-            .post(&self.rpc_url)
-            .json(&req)
-            .send()
+    /// Call a read-only function on the contract
+    async fn read_only_call(&self, function: &str, args: &[u8]) -> Result<Vec<u8>> {
+        match self.execute_read_only(function, args).await? {
+            Some(ReadOnlyResultInner { ok: Some(data), .. }) => Ok(data),
+            Some(ReadOnlyResultInner { error: Some(e), .. }) => {
+                Err(anyhow!("{} execution failed: {}", function, e))
+            }
+            _ => Err(anyhow!("No result data")),
+        }
+    }
+
+    /// Call a read-only function; returns None when the contract execution fails (e.g. "Node not found").
+    async fn read_only_call_optional(&self, function: &str, args: &[u8]) -> Result<Option<Vec<u8>>> {
+        Ok(self
+            .execute_read_only(function, args)
             .await?
-            .json()
+            .and_then(|r| if r.error.is_some() { None } else { r.ok }))
+    }
+
+    /// Subscribe to finalized events of the contract over gRPC.
+    pub async fn subscribe_final_events(&self) -> Result<EventStream> {
+        let grpc = self
+            .grpc
+            .as_ref()
+            .ok_or_else(|| anyhow!("gRPC client not configured (cannot stream events)"))?;
+        grpc.subscribe_final_events(&self.contract_address).await
+    }
+
+    /// Final events emitted by the contract, from slot `start` (inclusive) when given.
+    pub async fn get_final_events(&self, start: Option<(u64, u32)>) -> Result<Vec<ScOutputEvent>> {
+        self.filtered_events(serde_json::json!({
+            "start": start.map(|(period, thread)| serde_json::json!({ "period": period, "thread": thread })),
+            "end": null,
+            "emitter_address": self.contract_address,
+            "original_caller_address": null,
+            "original_operation_id": null,
+            "is_final": true,
+            "is_error": null,
+        }))
+        .await
+    }
+
+    /// Events emitted while executing operation `operation_id`, including execution errors.
+    pub async fn get_operation_events(&self, operation_id: &str) -> Result<Vec<ScOutputEvent>> {
+        self.filtered_events(serde_json::json!({
+            "start": null,
+            "end": null,
+            "emitter_address": null,
+            "original_caller_address": null,
+            "original_operation_id": operation_id,
+            "is_final": null,
+            "is_error": null,
+        }))
+        .await
+    }
+
+    async fn filtered_events(&self, filter: serde_json::Value) -> Result<Vec<ScOutputEvent>> {
+        let result = self
+            .rpc
+            .request("get_filtered_sc_output_event", serde_json::json!([filter]))
             .await?;
+        let events: Vec<OutputEvent> = serde_json::from_value(result)?;
+        Ok(events
+            .into_iter()
+            .map(|event| ScOutputEvent {
+                period: event.context.slot.period,
+                thread: event.context.slot.thread,
+                index_in_slot: event.context.index_in_slot,
+                emitter: event.context.call_stack.last().cloned(),
+                operation_id: event.context.origin_operation_id,
+                is_error: event.context.is_error,
+                data: event.data,
+            })
+            .collect())
+    }
 
-        if let Some(err) = resp.error {
-            return Err(anyhow!("RPC error: {:?}", err));
-        }
+    /// Inclusion and execution state of `ids`; operations the node does not know are absent.
+    pub async fn get_operations(&self, ids: &[String]) -> Result<HashMap<String, OperationInfo>> {
+        let result = self.rpc.request("get_operations", serde_json::json!([ids])).await?;
+        let infos: Vec<RpcOperationInfo> = serde_json::from_value(result)?;
+        Ok(infos
+            .into_iter()
+            .map(|info| {
+                (
+                    info.id,
+                    OperationInfo {
+                        in_pool: info.in_pool,
+                        in_block: !info.in_blocks.is_empty(),
+                        is_final: info.is_operation_final.unwrap_or(false),
+                        executed: info.op_exec_status,
+                    },
+                )
+            })
+            .collect())
+    }
 
-        let result = resp.result.ok_or_else(|| anyhow!("No result"))?;
-        let parsed: Vec<ReadOnlyResult> = serde_json::from_value(result)?;
+    /// Chain id the node reports; `None` for nodes that do not report it.
+    pub async fn get_chain_id(&self) -> Result<Option<u64>> {
+        let result = self.rpc.request("get_status", serde_json::json!([])).await?;
+        let status: NodeStatus = serde_json::from_value(result)?;
+        Ok(status.chain_id)
+    }
 
-        parsed
-            .first()
-            .and_then(|r| r.result.as_ref())
-            .and_then(|r| r.ok.clone())
-            .ok_or_else(|| anyhow!("No result data"))
+    /// Period of the node's latest slot.
+    pub async fn get_current_period(&self) -> Result<u64> {
+        let result = self.rpc.request("get_status", serde_json::json!([])).await?;
+        let status: NodeStatus = serde_json::from_value(result)?;
+        status
+            .last_slot
+            .map(|slot| slot.period)
+            .ok_or_else(|| anyhow!("node reported no slot"))
     }
 
-    /// Call a read-only function; returns None when the contract execution fails (e.g. "Node not found").
-    async fn read_only_call_optional(&self, function: &str, args: &[u8]) -> Result<Option<Vec<u8>>> {
-        let params = serde_json::json!([[{
-            "target_address": self.contract_address,
-            "target_function": function,
-            "parameter": args.iter().map(|b| *b as i32).collect::<Vec<_>>(),
-            "max_gas": 1_000_000_000u64,
-        }]]);
+    /// Run a registry view; `None` when the contract execution fails (e.g. "Node not found").
+    pub async fn view(&self, view: &RegistryView) -> Result<Option<Vec<u8>>> {
+        self.read_only_call_optional(view.function(), &view.args()).await
+    }
 
-        let req = JsonRpcRequest {
-            jsonrpc: "2.0",
-            id: 1,
-            method: "execute_read_only_call",
-            params,
-        };
+    /// Run a registry view that is not expected to fail.
+    async fn view_required(&self, view: &RegistryView) -> Result<Vec<u8>> {
+        self.read_only_call(view.function(), &view.args()).await
+    }
 
-        let resp: JsonRpcResponse = self
-            .http
-            .post(&self.rpc_url)
-            .json(&req)
-            .send()
+    /// Send a registry call through gRPC, forwarding `coins` to payable entrypoints.
+    pub async fn call(&self, call: &RegistryCall, coins: Amount) -> Result<SubmittedCall> {
+        self.call_many(&[(call.clone(), coins)])
             .await?
-            .json()
-            .await?;
+            .pop()
+            .ok_or_else(|| anyhow!("No operation for {}", call.function()))
+    }
 
-        if let Some(err) = resp.error {
-            return Err(anyhow!("RPC error: {:?}", err));
+    /// Send several registry calls in one gRPC submission. Each becomes its own operation,
+    /// returned in the same order. Calls from concurrent tasks are pipelined, not queued.
+    ///
+    /// With estimated gas, each call is dry-run first and a call that fails its dry run is
+    /// not sent. Calls are dry-run against the current state, so a call that depends on an
+    /// earlier call of the same submission needs a fixed `MASSA_MAX_GAS`.
+    pub async fn call_many(&self, calls: &[(RegistryCall, Amount)]) -> Result<Vec<SubmittedCall>> {
+        let functions = || calls.iter().map(|(c, _)| c.function()).collect::<Vec<_>>().join(", ");
+        let grpc = self.grpc.as_ref().ok_or_else(|| {
+            anyhow!("gRPC client not configured (cannot call {})", functions())
+        })?;
+        if let Some(reason) = self.writes_paused() {
+            if !calls.iter().all(|(call, _)| *call == RegistryCall::ClaimRewards) {
+                return Err(anyhow!("Contract writes paused ({}): cannot call {}", reason, functions()));
+            }
         }
 
-        let result = resp.result.ok_or_else(|| anyhow!("No result"))?;
-        let parsed: Vec<ReadOnlyResult> = serde_json::from_value(result)?;
+        let mut sc_calls = Vec::with_capacity(calls.len());
+        for (call, coins) in calls {
+            let fee = self.fee_for(call.function()).await;
+            let max_gas = match self.write_costs.gas {
+                GasStrategy::Fixed(gas) => gas,
+                GasStrategy::Estimated => fees::gas_with_margin(self.dry_run(call, *coins, fee).await?),
+            };
+            sc_calls.push(ScCall {
+                contract_address: self.contract_address.clone(),
+                function_name: call.function().to_string(),
+                args: call.args(),
+                fee,
+                max_gas,
+                coins: *coins,
+            });
+        }
+        let sent = grpc
+            .send_calls(&sc_calls)
+            .await
+            .with_context(|| format!("Failed to call {}", functions()))?;
+        Ok(calls
+            .iter()
+            .zip(sent)
+            .map(|((call, coins), sent)| SubmittedCall {
+                call: call.clone(),
+                coins: *coins,
+                operation_id: sent.id,
+                expire_period: sent.expire_period,
+            })
+            .collect())
+    }
 
-        let inner = parsed.first().and_then(|r| r.result.as_ref());
-        Ok(inner.and_then(|r| {
-            if r.error.is_some() {
-                None
-            } else {
-                r.ok.clone()
+    /// Fee of a write to `function`. An estimate is reused for a minute; when recent blocks
+    /// cannot be read, the lower bound is used.
+    async fn fee_for(&self, function: &str) -> Amount {
+        if !self.write_costs.fee.is_estimated() {
+            return self.write_costs.fee.fee(function, Vec::new());
+        }
+        if let Some((fee, at)) = *self.fee_estimate.lock().unwrap() {
+            if at.elapsed() < FEE_ESTIMATE_TTL {
+                return fee;
             }
-        }))
+        }
+        let recent = self.recent_fees().await.unwrap_or_else(|e| {
+            tracing::warn!(error = %e, "failed to read recent fees; using the minimum fee");
+            Vec::new()
+        });
+        let samples = recent.len();
+        let fee = self.write_costs.fee.fee(function, recent);
+        tracing::debug!(%fee, samples, "estimated operation fee");
+        *self.fee_estimate.lock().unwrap() = Some((fee, Instant::now()));
+        fee
+    }
+
+    /// Fees (nanoMAS) of the operations in the last blockclique blocks.
+    async fn recent_fees(&self) -> Result<Vec<u64>> {
+        let result = self.rpc.request("get_status", serde_json::json!([])).await?;
+        let status: NodeStatus = serde_json::from_value(result)?;
+        let last = status.last_slot.ok_or_else(|| anyhow!("node reported no slot"))?;
+
+        let mut ids = Vec::new();
+        for (period, thread) in fees::recent_slots(last.period, last.thread, fees::FEE_SAMPLE_SLOTS) {
+            let slot = serde_json::json!([{ "period": period, "thread": thread }]);
+            let result = self.rpc.request("get_blockclique_block_by_slot", slot).await?;
+            // Null for a slot without block
+            if let Some(block) = serde_json::from_value::<Option<RpcBlock>>(result)? {
+                ids.extend(block.operations);
+            }
+        }
+        if ids.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let result = self.rpc.request("get_operations", serde_json::json!([ids])).await?;
+        let operations: Vec<RpcOperationFee> = serde_json::from_value(result)?;
+        Ok(operations
+            .iter()
+            .filter_map(|op| Amount::from_str(&op.operation.content.fee).ok())
+            .map(|fee| fee.to_raw())
+            .collect())
+    }
+
+    /// Execute `call` read-only as the signing address and return the gas it used. Fails
+    /// when the contract rejects the call.
+    async fn dry_run(&self, call: &RegistryCall, coins: Amount, fee: Amount) -> Result<u64> {
+        let params = serde_json::json!([[{
+            "target_address": self.contract_address,
+            "target_function": call.function(),
+            "parameter": call.args().iter().map(|b| *b as i32).collect::<Vec<_>>(),
+            "max_gas": READ_ONLY_MAX_GAS,
+            "caller_address": self.sender_address,
+            "coins": coins.to_string(),
+            "fee": fee.to_string(),
+        }]]);
+
+        let result = self.rpc.request("execute_read_only_call", params).await?;
+        let parsed: Vec<ReadOnlyResult> = serde_json::from_value(result)?;
+        match parsed.into_iter().next() {
+            Some(ReadOnlyResult { result: Some(ReadOnlyResultInner { error: Some(e), .. }), .. }) => {
+                Err(anyhow!("dry run of {} failed: {}", call.function(), e))
+            }
+            Some(result) => Ok(result.gas_cost),
+            None => Err(anyhow!("No result data")),
+        }
     }
 
     /// Returns true if the address is already registered as a storage node.
     pub async fn is_node_registered(&self, address: &str) -> Result<bool> {
-        let mut request = Args::new();
-        request.add_string(address);
-        match self
-            .read_only_call_optional("getNodeInfo", &request.into_bytes())
-            .await?
-        {
-            Some(data) => Ok(!data.is_empty()),
-            None => Ok(false),
+        let view = RegistryView::NodeInfo { address: address.to_string() };
+        Ok(matches!(self.view(&view).await?, Some(data) if !data.is_empty()))
+    }
+
+    /// Storage node record of `address`, or `None` when it is not registered.
+    pub async fn get_node_info(&self, address: &str) -> Result<Option<StorageNode>> {
+        let view = RegistryView::NodeInfo { address: address.to_string() };
+        match self.view(&view).await? {
+            Some(data) if !data.is_empty() => Ok(Some(StorageNode::from_bytes(data)?)),
+            _ => Ok(None),
         }
     }
 
+    /// Current contract configuration.
+    pub async fn get_config(&self) -> Result<StorageConfig> {
+        let data = self.view_required(&RegistryView::Config).await?;
+        Ok(StorageConfig::from_bytes(data)?)
+    }
+
+    /// Unclaimed rewards of `address` in nanoMAS (0 when unregistered).
+    pub async fn calculate_pending_rewards(&self, address: &str) -> Result<u64> {
+        let view = RegistryView::PendingRewards { address: address.to_string() };
+        Ok(registry::decode_u64(self.view_required(&view).await?)?)
+    }
+
+    /// Returns true if the address is allowed to upload (storage admin or has booked storage).
+    pub async fn is_allowed_uploader(&self, address: &str) -> Result<bool> {
+        let view = RegistryView::IsAllowedUploader { address: address.to_string() };
+        let data = self
+            .view(&view)
+            .await?
+            .ok_or_else(|| anyhow!("getIsAllowedUploader execution failed"))?;
+        Ok(registry::decode_flag(data)?)
+    }
+
+    /// Bytes recorded for uploader `address` (`recordFileUpload`, `recordFileUploads`).
+    pub async fn get_uploader_usage(&self, address: &str) -> Result<u64> {
+        let view = RegistryView::UploaderUsage { address: address.to_string() };
+        Ok(registry::decode_u64(self.view_required(&view).await?)?)
+    }
+
     /// Register this address as a storage node (allocated GB, endpoint, P2P addrs).
     /// Call only when gRPC is configured and the node is not yet registered.
     pub async fn register_storage_node(
@@ -186,69 +547,74 @@ impl MassaClient {
         allocated_gb: u64,
         endpoint: &str,
         p2p_addrs: &[String],
-    ) -> Result<String> {
-        let grpc = self
-            .grpc_client
-            .as_ref()
-            .ok_or_else(|| anyhow!("gRPC client not configured (cannot register storage node)"))?;
-
-        let mut args = Args::new();
-        args.add_u64(allocated_gb);
-        args.add_string(endpoint);
-        args.add_string_array(p2p_addrs);
-
-        let mut client = grpc.lock().await;
-        let op_id = client
-            .call_sc(
-                &self.contract_address,
-                "registerStorageNode",
-                args.into_bytes(),
-                "0.01",
-                10_000_000,
-                Amount::from_raw(0),
-            )
-            .await
-            .map_err(|e| anyhow!("Failed to call registerStorageNode: {}", e))?;
+    ) -> Result<SubmittedCall> {
+        let call = RegistryCall::RegisterStorageNode {
+            allocated_gb,
+            endpoint: endpoint.to_string(),
+            p2p_addrs: p2p_addrs.to_vec(),
+        };
+        let sent = self.call(&call, Amount::from_raw(0)).await?;
 
         tracing::info!(
-            operation_id = %op_id,
+            operation_id = %sent.operation_id,
             allocated_gb,
             endpoint = %endpoint,
             p2p_addrs_count = p2p_addrs.len(),
             "storage node registration sent"
         );
 
-        Ok(op_id)
+        Ok(sent)
+    }
+
+    /// Change this node's allocation (within the configured min/max).
+    pub async fn update_storage_allocation(&self, allocated_gb: u64) -> Result<SubmittedCall> {
+        let call = RegistryCall::UpdateStorageAllocation { allocated_gb };
+        let sent = self.call(&call, Amount::from_raw(0)).await?;
+        tracing::info!(operation_id = %sent.operation_id, allocated_gb, "storage allocation update sent");
+        Ok(sent)
+    }
+
+    /// Deactivate this node in the registry.
+    pub async fn unregister_storage_node(&self) -> Result<SubmittedCall> {
+        let sent = self.call(&RegistryCall::UnregisterStorageNode, Amount::from_raw(0)).await?;
+        tracing::info!(operation_id = %sent.operation_id, "storage node unregistration sent");
+        Ok(sent)
+    }
+
+    /// Transfer this node's pending rewards to its address.
+    pub async fn claim_rewards(&self) -> Result<SubmittedCall> {
+        let sent = self.call(&RegistryCall::ClaimRewards, Amount::from_raw(0)).await?;
+        tracing::info!(operation_id = %sent.operation_id, "reward claim sent");
+        Ok(sent)
     }
 
     /// Get all registered provider addresses
     pub async fn get_registered_addresses(&self) -> Result<Vec<String>> {
-        let data = self.read_only_call("getRegisteredAddressesView", &[]).await?;
-        if data.is_empty() {
-            return Ok(Vec::new());
-        }
-        let mut args = Args::from_bytes(data);
-        Ok(args.next_string_array()?)
+        let data = self.view_required(&RegistryView::RegisteredAddresses).await?;
+        Ok(registry::decode_string_array(data)?)
     }
 
     /// Get provider metadata (endpoint + p2p addrs)
     pub async fn get_provider_metadata(&self, address: &str) -> Result<ProviderInfo> {
-        let mut request = Args::new();
-        request.add_string(address);
-
-        let data = self.read_only_call("getProviderMetadataView", &request.into_bytes()).await?;
-
-        let mut response = Args::from_bytes(data);
-        let endpoint = response.next_string()?;
-        let p2p_addrs = response.next_string_array()?;
+        let view = RegistryView::ProviderMetadata { address: address.to_string() };
+        let metadata = ProviderMetadata::from_bytes(self.view_required(&view).await?)?;
 
         Ok(ProviderInfo {
             address: address.to_string(),
-            endpoint,
-            p2p_addrs,
+            endpoint: metadata.endpoint,
+            p2p_addrs: metadata.p2p_addrs,
         })
     }
 
+    /// Provider metadata of `address`, or `None` when it never set any.
+    pub async fn find_provider_metadata(&self, address: &str) -> Result<Option<ProviderMetadata>> {
+        let view = RegistryView::ProviderMetadata { address: address.to_string() };
+        match self.view(&view).await? {
+            Some(data) if !data.is_empty() => Ok(Some(ProviderMetadata::from_bytes(data)?)),
+            _ => Ok(None),
+        }
+    }
+
     /// Get all providers with their metadata
     pub async fn get_all_providers(&self) -> Result<Vec<ProviderInfo>> {
         let addresses = self.get_registered_addresses().await?;
@@ -281,80 +647,106 @@ impl MassaClient {
         &self,
         endpoint: &str,
         p2p_addrs: &[String],
-    ) -> Result<String> {
-        let grpc = self
-            .grpc_client
-            .as_ref()
-            .ok_or_else(|| anyhow!("gRPC client not configured (missing private key)"))?;
-
-        // Build args: endpoint (string) + p2p_addrs (string array)
-        let mut args = Args::new();
-        args.add_string(endpoint);
-        args.add_string_array(p2p_addrs);
-
-        let mut client = grpc.lock().await;
-        let op_id = client
-            .call_sc(
-                &self.contract_address,
-                "updateProviderMetadata",
-                args.into_bytes(),
-                "0.01",      // fee
-                10_000_000,  // max_gas
-                Amount::from_raw(0), // coins
-            )
-            .await
-            .map_err(|e| anyhow!("Failed to call updateProviderMetadata: {}", e))?;
+    ) -> Result<SubmittedCall> {
+        let call = RegistryCall::UpdateProviderMetadata {
+            endpoint: endpoint.to_string(),
+            p2p_addrs: p2p_addrs.to_vec(),
+        };
+        let sent = self.call(&call, Amount::from_raw(0)).await?;
 
         tracing::info!(
-            operation_id = %op_id,
+            operation_id = %sent.operation_id,
             endpoint = %endpoint,
             p2p_addrs_count = p2p_addrs.len(),
             "provider metadata update sent"
         );
 
-        Ok(op_id)
+        Ok(sent)
     }
 
     /// Record a file upload in the storage registry (updates total storage usage per uploader).
     /// Callable only when the server is a storage admin on the contract. Requires gRPC client.
+    /// Uploads are recorded in batches by the usage outbox instead.
+    #[allow(dead_code)]
     pub async fn record_file_upload(
         &self,
         uploader_address: &str,
         file_size_bytes: u64,
-    ) -> Result<String> {
-        let grpc = self
-            .grpc_client
-            .as_ref()
-            .ok_or_else(|| anyhow!("gRPC client not configured (cannot record file upload)"))?;
-
+    ) -> Result<Option<SubmittedCall>> {
         if file_size_bytes == 0 {
-            return Ok(String::new());
+            return Ok(None);
         }
 
-        let mut args = Args::new();
-        args.add_string(uploader_address);
-        args.add_u64(file_size_bytes);
-
-        let mut client = grpc.lock().await;
-        let op_id = client
-            .call_sc(
-                &self.contract_address,
-                "recordFileUpload",
-                args.into_bytes(),
-                "0.01",
-                10_000_000,
-                Amount::from_raw(0),
-            )
-            .await
-            .map_err(|e| anyhow!("Failed to call recordFileUpload: {}", e))?;
+        let call = RegistryCall::RecordFileUpload {
+            uploader_address: uploader_address.to_string(),
+            file_size_bytes,
+        };
+        let sent = self.call(&call, Amount::from_raw(0)).await?;
 
         tracing::info!(
-            operation_id = %op_id,
+            operation_id = %sent.operation_id,
             uploader = %uploader_address,
             size_bytes = file_size_bytes,
             "file upload recorded on contract"
         );
 
-        Ok(op_id)
+        Ok(Some(sent))
+    }
+}
+
+/// Registry views the server does not call yet. They are kept so the binding covers every
+/// read-only entrypoint of `storage-registry.ts` (see [`RegistryView`]).
+#[allow(dead_code)]
+impl MassaClient {
+    /// Challenge `challenge_id`, or `None` when the contract does not know it.
+    pub async fn get_challenge_info(&self, challenge_id: &str) -> Result<Option<Challenge>> {
+        let view = RegistryView::ChallengeInfo { challenge_id: challenge_id.to_string() };
+        match self.view(&view).await? {
+            Some(data) if !data.is_empty() => Ok(Some(Challenge::from_bytes(data)?)),
+            _ => Ok(None),
+        }
+    }
+
+    /// Reward statistics of `period` (zeroed when nothing happened in it).
+    pub async fn get_period_stats(&self, period: u64) -> Result<PeriodStats> {
+        let data = self.view_required(&RegistryView::PeriodStats { period }).await?;
+        Ok(PeriodStats::from_bytes(data)?)
+    }
+
+    /// Number of registered storage nodes.
+    pub async fn get_total_nodes_count(&self) -> Result<u64> {
+        let data = self.view_required(&RegistryView::TotalNodesCount).await?;
+        Ok(registry::decode_u64(data)?)
+    }
+
+    /// Address of the node at `index`, or `None` past the end.
+    pub async fn get_node_address_at(&self, index: u64) -> Result<Option<String>> {
+        let data = self.view_required(&RegistryView::NodeAddressAt { index }).await?;
+        let address = registry::decode_string(data)?;
+        Ok((!address.is_empty()).then_some(address))
+    }
+
+    /// Allocated, booked and available GB across the network.
+    pub async fn get_global_storage_usage(&self) -> Result<GlobalStorageUsage> {
+        let data = self.view_required(&RegistryView::GlobalStorageUsage).await?;
+        Ok(GlobalStorageUsage::from_bytes(data)?)
+    }
+
+    /// Returns true if `address` is a storage admin.
+    pub async fn is_storage_admin(&self, address: &str) -> Result<bool> {
+        let view = RegistryView::IsStorageAdmin { address: address.to_string() };
+        Ok(registry::decode_flag(self.view_required(&view).await?)?)
+    }
+
+    /// GB booked by uploader `address`.
+    pub async fn get_booked_uploader_gb(&self, address: &str) -> Result<u64> {
+        let view = RegistryView::BookedUploaderGb { address: address.to_string() };
+        Ok(registry::decode_u64(self.view_required(&view).await?)?)
+    }
+
+    /// Price of one booked GB in nanoMAS (`registerAsUploader`).
+    pub async fn get_uploader_price_per_gb(&self) -> Result<u64> {
+        let data = self.view_required(&RegistryView::UploaderPricePerGb).await?;
+        Ok(registry::decode_u64(data)?)
     }
 }
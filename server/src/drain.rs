@@ -0,0 +1,174 @@
+//! Graceful exit from the network: `massa-storage-server drain`.
+//!
+//! The server starts as usual but refuses uploads and does not register or update its
+//! provider metadata. Meanwhile the drain task:
+//!
+//! 1. Hands every local blob off to other providers over P2P (see
+//!    `ReplicationHandle::hand_off_all`), retrying every `HAND_OFF_RETRY` until none is left.
+//! 2. Sends `unregisterStorageNode` and follows it until it is final, retrying every
+//!    `UNREGISTER_RETRY` if it fails.
+//!
+//! Reads are still served afterwards. Progress is served by `GET /admin/drain`; the node can
+//! be stopped once the phase is `unregistered`.
+
+use std::collections::HashSet;
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::{bail, Result};
+use serde::Serialize;
+
+use crate::contract::MassaClient;
+use crate::operations::{OperationStatus, SharedOperations};
+use crate::replication::ReplicationHandle;
+use crate::state::StateCell;
+
+/// Delay between hand-off passes while some blobs have no provider to take them.
+const HAND_OFF_RETRY: Duration = Duration::from_secs(60);
+/// Delay before sending `unregisterStorageNode` again after it failed.
+const UNREGISTER_RETRY: Duration = Duration::from_secs(600);
+/// Times an expired `unregisterStorageNode` is re-sent before the attempt fails.
+const UNREGISTER_RESUBMITS: u32 = 2;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DrainPhase {
+    /// Blobs are being handed off to other providers.
+    HandingOff,
+    /// Every blob was handed off; `unregisterStorageNode` is pending.
+    Unregistering,
+    /// The node is no longer active in the registry.
+    Unregistered,
+}
+
+/// Drain progress, as returned by `GET /admin/drain`.
+#[derive(Debug, Clone, Serialize)]
+pub struct DrainStatus {
+    pub phase: DrainPhase,
+    /// Blobs that no longer depend on this node, as of the last pass.
+    pub handed_off: usize,
+    /// Blobs still waiting for a provider, as of the last pass.
+    pub pending: usize,
+    /// Last `unregisterStorageNode` operation sent.
+    pub operation_id: Option<String>,
+    pub last_error: Option<String>,
+}
+
+/// Hands off this node's blobs, then unregisters it.
+pub struct Drain {
+    registry: Arc<MassaClient>,
+    operations: SharedOperations,
+    replication: ReplicationHandle,
+    address: String,
+    status: StateCell<DrainStatus>,
+}
+
+pub type SharedDrain = Arc<Drain>;
+
+/// Start draining the node registered as `address`.
+pub fn spawn(
+    registry: Arc<MassaClient>,
+    operations: SharedOperations,
+    replication: ReplicationHandle,
+    address: String,
+) -> SharedDrain {
+    let drain = Arc::new(Drain {
+        registry,
+        operations,
+        replication,
+        address,
+        status: StateCell::new(
+            DrainStatus {
+                phase: DrainPhase::HandingOff,
+                handed_off: 0,
+                pending: 0,
+                operation_id: None,
+                last_error: None,
+            },
+            "drain status",
+        ),
+    });
+    let worker = drain.clone();
+    tokio::spawn(async move { worker.run().await });
+    drain
+}
+
+impl Drain {
+    pub fn status(&self) -> DrainStatus {
+        self.status.snapshot()
+    }
+
+    async fn run(&self) {
+        tracing::warn!("draining: uploads are refused; handing blobs off to other providers");
+        self.hand_off().await;
+
+        self.status.update(|status| status.phase = DrainPhase::Unregistering);
+        loop {
+            match self.unregister().await {
+                Ok(()) => break,
+                Err(e) => {
+                    tracing::error!(error = %e, "unregistration failed; retrying later");
+                    self.status.update(|status| status.last_error = Some(format!("{:#}", e)));
+                }
+            }
+            tokio::time::sleep(UNREGISTER_RETRY).await;
+        }
+        self.status.update(|status| {
+            status.phase = DrainPhase::Unregistered;
+            status.last_error = None;
+        });
+        tracing::warn!("drain complete: node unregistered; it can be stopped");
+    }
+
+    /// Repeat hand-off passes until every local blob is handed off.
+    async fn hand_off(&self) {
+        let mut done = HashSet::new();
+        loop {
+            match self.replication.hand_off_all(&mut done).await {
+                Ok(pass) => {
+                    self.status.update(|status| {
+                        status.handed_off = pass.handed_off;
+                        status.pending = pass.pending;
+                        status.last_error = None;
+                    });
+                    if pass.pending == 0 {
+                        tracing::info!(handed_off = pass.handed_off, "all blobs handed off");
+                        return;
+                    }
+                    tracing::info!(
+                        handed_off = pass.handed_off,
+                        pending = pass.pending,
+                        "blobs still waiting for a provider; retrying"
+                    );
+                }
+                Err(e) => {
+                    tracing::warn!(error = %e, "hand-off pass failed");
+                    self.status.update(|status| status.last_error = Some(e.to_string()));
+                }
+            }
+            tokio::time::sleep(HAND_OFF_RETRY).await;
+        }
+    }
+
+    /// Send `unregisterStorageNode` unless the node is already inactive, and wait until it
+    /// executed.
+    async fn unregister(&self) -> Result<()> {
+        match self.registry.get_node_info(&self.address).await? {
+            Some(node) if node.active => {}
+            _ => {
+                tracing::info!("node is not registered or already inactive; nothing to unregister");
+                return Ok(());
+            }
+        }
+        let sent = self.registry.unregister_storage_node().await?;
+        let operation_id = sent.operation_id.clone();
+        self.status.update(|status| status.operation_id = Some(operation_id.clone()));
+        match self.operations.follow(sent, UNREGISTER_RESUBMITS).await? {
+            OperationStatus::Executed => {
+                tracing::info!(%operation_id, "storage node unregistered");
+                Ok(())
+            }
+            status => bail!("unregisterStorageNode {} did not execute: {:?}", operation_id, status),
+        }
+    }
+}
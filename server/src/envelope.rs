@@ -0,0 +1,311 @@
+//! Client-side encryption envelope for blobs stored on untrusted providers.
+//!
+//! A blob is encrypted with a random per-blob content key (XChaCha20-Poly1305); the content
+//! key is wrapped for the uploader's Massa key (Ed25519, converted to X25519) using an
+//! ephemeral X25519 exchange. Providers only ever see the envelope: they can chunk, hash,
+//! challenge and replicate it like any other blob, but cannot read the content.
+//!
+//! # Format (version 1)
+//! All offsets in bytes; the header is fixed-size.
+//! - `0..4`     magic `MSE1`
+//! - `4`        version (`1`)
+//! - `5`        algorithm (`1` = X25519 key wrap + XChaCha20-Poly1305)
+//! - `6..38`    recipient Ed25519 public key (the uploader)
+//! - `38..70`   ephemeral X25519 public key
+//! - `70..94`   key-wrap nonce
+//! - `94..142`  wrapped content key (32-byte key + 16-byte tag)
+//! - `142..166` content nonce
+//! - `166..`    ciphertext (plaintext length + 16-byte tag)
+//!
+//! The key-wrap AEAD authenticates bytes `0..70` and the content AEAD authenticates the
+//! whole header (`0..166`), so no header field can be altered without detection.
+
+use std::fmt;
+
+use chacha20poly1305::{
+    aead::{Aead, AeadCore, KeyInit, OsRng, Payload},
+    XChaCha20Poly1305, XNonce,
+};
+use ed25519_dalek::{SigningKey, VerifyingKey};
+use x25519_dalek::{PublicKey as X25519PublicKey, StaticSecret};
+
+/// Magic bytes at the start of every envelope.
+pub const ENVELOPE_MAGIC: &[u8; 4] = b"MSE1";
+/// Current envelope format version.
+pub const ENVELOPE_VERSION: u8 = 1;
+/// Algorithm id: X25519 key wrap + XChaCha20-Poly1305 content encryption.
+pub const ALG_X25519_XCHACHA20POLY1305: u8 = 1;
+
+const KEY_LEN: usize = 32;
+const NONCE_LEN: usize = 24;
+const TAG_LEN: usize = 16;
+const WRAP_AAD_LEN: usize = 4 + 1 + 1 + KEY_LEN + KEY_LEN;
+/// Size of the fixed envelope header in bytes.
+pub const HEADER_LEN: usize = WRAP_AAD_LEN + NONCE_LEN + KEY_LEN + TAG_LEN + NONCE_LEN;
+/// Total bytes added to the plaintext by sealing (header + content tag).
+pub const ENVELOPE_OVERHEAD: usize = HEADER_LEN + TAG_LEN;
+
+/// Domain separation context for deriving the key-wrapping key.
+const KEY_WRAP_CONTEXT: &str = "massa-storage envelope v1 key wrap";
+
+/// Errors while building, parsing or opening an envelope.
+#[derive(Debug, PartialEq, Eq)]
+pub enum EnvelopeError {
+    /// Data is shorter than the header or does not start with the magic bytes.
+    NotAnEnvelope,
+    /// Envelope version is not supported by this server.
+    UnsupportedVersion(u8),
+    /// Algorithm id is not supported by this server.
+    UnsupportedAlgorithm(u8),
+    /// Recipient public key in the header is not a valid Ed25519 point.
+    InvalidRecipientKey,
+    /// The secret key does not belong to the recipient recorded in the header.
+    WrongRecipient,
+    /// Key unwrap or content decryption failed (tampered data or wrong key).
+    DecryptionFailed,
+    /// Encryption failed.
+    EncryptionFailed,
+}
+
+impl fmt::Display for EnvelopeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::NotAnEnvelope => write!(f, "data is not an encryption envelope"),
+            Self::UnsupportedVersion(v) => write!(f, "unsupported envelope version {}", v),
+            Self::UnsupportedAlgorithm(a) => write!(f, "unsupported envelope algorithm {}", a),
+            Self::InvalidRecipientKey => write!(f, "invalid recipient public key"),
+            Self::WrongRecipient => write!(f, "secret key does not match envelope recipient"),
+            Self::DecryptionFailed => write!(f, "envelope decryption failed"),
+            Self::EncryptionFailed => write!(f, "envelope encryption failed"),
+        }
+    }
+}
+
+impl std::error::Error for EnvelopeError {}
+
+/// Parsed envelope header (everything a provider can learn without the key).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EnvelopeHeader {
+    pub version: u8,
+    pub algorithm: u8,
+    /// Recipient Ed25519 public key (raw bytes).
+    pub recipient: [u8; KEY_LEN],
+    pub ephemeral_public: [u8; KEY_LEN],
+    pub wrap_nonce: [u8; NONCE_LEN],
+    pub wrapped_key: [u8; KEY_LEN + TAG_LEN],
+    pub content_nonce: [u8; NONCE_LEN],
+}
+
+/// Envelope information recorded in `BlobMeta` for encrypted blobs.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct EnvelopeMeta {
+    pub version: u8,
+    pub algorithm: u8,
+    /// Recipient public key, base58 (raw 32 bytes, no version prefix).
+    pub recipient: String,
+    /// Size of the plaintext in bytes (envelope size minus overhead).
+    pub plaintext_size: u64,
+}
+
+impl EnvelopeMeta {
+    /// Parse the header of `data` and describe it for blob metadata.
+    pub fn from_envelope(data: &[u8]) -> Result<Self, EnvelopeError> {
+        let header = parse_header(data)?;
+        Ok(Self {
+            version: header.version,
+            algorithm: header.algorithm,
+            recipient: bs58::encode(header.recipient).into_string(),
+            plaintext_size: (data.len() - ENVELOPE_OVERHEAD) as u64,
+        })
+    }
+}
+
+/// Returns true if `data` starts with the envelope magic bytes.
+pub fn looks_like_envelope(data: &[u8]) -> bool {
+    data.starts_with(ENVELOPE_MAGIC)
+}
+
+/// Parse and validate the fixed-size header. Does not decrypt anything.
+pub fn parse_header(data: &[u8]) -> Result<EnvelopeHeader, EnvelopeError> {
+    if data.len() < ENVELOPE_OVERHEAD || !looks_like_envelope(data) {
+        return Err(EnvelopeError::NotAnEnvelope);
+    }
+    let version = data[4];
+    if version != ENVELOPE_VERSION {
+        return Err(EnvelopeError::UnsupportedVersion(version));
+    }
+    let algorithm = data[5];
+    if algorithm != ALG_X25519_XCHACHA20POLY1305 {
+        return Err(EnvelopeError::UnsupportedAlgorithm(algorithm));
+    }
+    let mut offset = 6;
+    let mut take = |len: usize| {
+        let slice = &data[offset..offset + len];
+        offset += len;
+        slice
+    };
+    let recipient: [u8; KEY_LEN] = take(KEY_LEN).try_into().unwrap();
+    let ephemeral_public: [u8; KEY_LEN] = take(KEY_LEN).try_into().unwrap();
+    let wrap_nonce: [u8; NONCE_LEN] = take(NONCE_LEN).try_into().unwrap();
+    let wrapped_key: [u8; KEY_LEN + TAG_LEN] = take(KEY_LEN + TAG_LEN).try_into().unwrap();
+    let content_nonce: [u8; NONCE_LEN] = take(NONCE_LEN).try_into().unwrap();
+    VerifyingKey::from_bytes(&recipient).map_err(|_| EnvelopeError::InvalidRecipientKey)?;
+    Ok(EnvelopeHeader {
+        version,
+        algorithm,
+        recipient,
+        ephemeral_public,
+        wrap_nonce,
+        wrapped_key,
+        content_nonce,
+    })
+}
+
+/// Derive the key-wrapping key from the X25519 shared secret and both public keys.
+fn derive_wrap_key(
+    shared: &[u8; KEY_LEN],
+    ephemeral: &[u8; KEY_LEN],
+    recipient: &[u8; KEY_LEN],
+) -> [u8; KEY_LEN] {
+    let mut material = Vec::with_capacity(3 * KEY_LEN);
+    material.extend_from_slice(shared);
+    material.extend_from_slice(ephemeral);
+    material.extend_from_slice(recipient);
+    blake3::derive_key(KEY_WRAP_CONTEXT, &material)
+}
+
+/// Encrypt `plaintext` into an envelope readable only by the holder of `recipient`'s secret key.
+#[allow(dead_code)]
+pub fn seal(plaintext: &[u8], recipient: &VerifyingKey) -> Result<Vec<u8>, EnvelopeError> {
+    let recipient_bytes = recipient.to_bytes();
+    let recipient_x25519 = X25519PublicKey::from(recipient.to_montgomery().to_bytes());
+
+    let ephemeral = StaticSecret::random_from_rng(OsRng);
+    let ephemeral_public = X25519PublicKey::from(&ephemeral);
+    let shared = ephemeral.diffie_hellman(&recipient_x25519);
+    let wrap_key = derive_wrap_key(shared.as_bytes(), ephemeral_public.as_bytes(), &recipient_bytes);
+
+    let mut out = Vec::with_capacity(plaintext.len() + ENVELOPE_OVERHEAD);
+    out.extend_from_slice(ENVELOPE_MAGIC);
+    out.push(ENVELOPE_VERSION);
+    out.push(ALG_X25519_XCHACHA20POLY1305);
+    out.extend_from_slice(&recipient_bytes);
+    out.extend_from_slice(ephemeral_public.as_bytes());
+
+    let content_key = XChaCha20Poly1305::generate_key(&mut OsRng);
+    let wrap_nonce = XChaCha20Poly1305::generate_nonce(&mut OsRng);
+    let wrapped_key = XChaCha20Poly1305::new(&wrap_key.into())
+        .encrypt(
+            &wrap_nonce,
+            Payload {
+                msg: content_key.as_slice(),
+                aad: &out[..WRAP_AAD_LEN],
+            },
+        )
+        .map_err(|_| EnvelopeError::EncryptionFailed)?;
+    out.extend_from_slice(&wrap_nonce);
+    out.extend_from_slice(&wrapped_key);
+
+    let content_nonce = XChaCha20Poly1305::generate_nonce(&mut OsRng);
+    out.extend_from_slice(&content_nonce);
+    let ciphertext = XChaCha20Poly1305::new(&content_key)
+        .encrypt(
+            &content_nonce,
+            Payload {
+                msg: plaintext,
+                aad: &out[..HEADER_LEN],
+            },
+        )
+        .map_err(|_| EnvelopeError::EncryptionFailed)?;
+    out.extend_from_slice(&ciphertext);
+    Ok(out)
+}
+
+/// Decrypt an envelope with the recipient's Ed25519 secret key.
+#[allow(dead_code)]
+pub fn open(envelope: &[u8], secret: &SigningKey) -> Result<Vec<u8>, EnvelopeError> {
+    let header = parse_header(envelope)?;
+    if secret.verifying_key().to_bytes() != header.recipient {
+        return Err(EnvelopeError::WrongRecipient);
+    }
+
+    let static_secret = StaticSecret::from(secret.to_scalar_bytes());
+    let shared = static_secret.diffie_hellman(&X25519PublicKey::from(header.ephemeral_public));
+    let wrap_key = derive_wrap_key(shared.as_bytes(), &header.ephemeral_public, &header.recipient);
+
+    let content_key = XChaCha20Poly1305::new(&wrap_key.into())
+        .decrypt(
+            XNonce::from_slice(&header.wrap_nonce),
+            Payload {
+                msg: &header.wrapped_key,
+                aad: &envelope[..WRAP_AAD_LEN],
+            },
+        )
+        .map_err(|_| EnvelopeError::DecryptionFailed)?;
+    let content_key: [u8; KEY_LEN] = content_key
+        .as_slice()
+        .try_into()
+        .map_err(|_| EnvelopeError::DecryptionFailed)?;
+
+    XChaCha20Poly1305::new(&content_key.into())
+        .decrypt(
+            XNonce::from_slice(&header.content_nonce),
+            Payload {
+                msg: &envelope[HEADER_LEN..],
+                aad: &envelope[..HEADER_LEN],
+            },
+        )
+        .map_err(|_| EnvelopeError::DecryptionFailed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn seal_open_roundtrip() {
+        let secret = SigningKey::from_bytes(&[9u8; 32]);
+        let envelope = seal(b"hello provider", &secret.verifying_key()).unwrap();
+
+        assert_eq!(envelope.len(), b"hello provider".len() + ENVELOPE_OVERHEAD);
+        assert_eq!(open(&envelope, &secret).unwrap(), b"hello provider");
+    }
+
+    #[test]
+    fn meta_describes_envelope() {
+        let secret = SigningKey::from_bytes(&[1u8; 32]);
+        let envelope = seal(&[0u8; 100], &secret.verifying_key()).unwrap();
+
+        let meta = EnvelopeMeta::from_envelope(&envelope).unwrap();
+        assert_eq!(meta.version, ENVELOPE_VERSION);
+        assert_eq!(meta.plaintext_size, 100);
+        assert_eq!(
+            meta.recipient,
+            bs58::encode(secret.verifying_key().to_bytes()).into_string()
+        );
+    }
+
+    #[test]
+    fn open_rejects_tampered_ciphertext() {
+        let secret = SigningKey::from_bytes(&[2u8; 32]);
+        let mut envelope = seal(b"payload", &secret.verifying_key()).unwrap();
+        let last = envelope.len() - 1;
+        envelope[last] ^= 0x01;
+
+        assert_eq!(open(&envelope, &secret), Err(EnvelopeError::DecryptionFailed));
+    }
+
+    #[test]
+    fn open_rejects_wrong_recipient() {
+        let secret = SigningKey::from_bytes(&[3u8; 32]);
+        let other = SigningKey::from_bytes(&[4u8; 32]);
+        let envelope = seal(b"payload", &secret.verifying_key()).unwrap();
+
+        assert_eq!(open(&envelope, &other), Err(EnvelopeError::WrongRecipient));
+    }
+
+    #[test]
+    fn parse_header_rejects_plain_data() {
+        assert_eq!(parse_header(b"plain text"), Err(EnvelopeError::NotAnEnvelope));
+    }
+}
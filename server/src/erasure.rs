@@ -0,0 +1,269 @@
+//! Reed-Solomon erasure coding for blob redundancy.
+//!
+//! A blob is split into `k` data shards (zero-padded to equal size) plus `m` parity shards.
+//! Any `k` of the `k + m` shards are enough to rebuild the original bytes, so storing one
+//! shard per provider costs `(k + m) / k` times the blob size instead of one full copy per
+//! replica.
+
+use std::fmt;
+
+use reed_solomon_erasure::galois_8::ReedSolomon;
+
+/// Maximum total shards (`k + m`) per blob; matches the replication upper bound.
+pub const MAX_TOTAL_SHARDS: u8 = crate::storage::MIN_REPLICATION_MAX;
+
+/// Errors while parsing a scheme or encoding/decoding shards.
+#[derive(Debug, PartialEq, Eq)]
+pub enum ErasureError {
+    /// Scheme string is not of the form `k+m`.
+    InvalidScheme(String),
+    /// Not enough shards are available to reconstruct the blob.
+    NotEnoughShards { available: usize, required: usize },
+    /// Underlying Reed-Solomon codec error.
+    Codec(String),
+}
+
+impl fmt::Display for ErasureError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::InvalidScheme(msg) => write!(f, "invalid erasure scheme: {}", msg),
+            Self::NotEnoughShards {
+                available,
+                required,
+            } => write!(
+                f,
+                "not enough shards to reconstruct: {} available, {} required",
+                available, required
+            ),
+            Self::Codec(msg) => write!(f, "erasure codec error: {}", msg),
+        }
+    }
+}
+
+impl std::error::Error for ErasureError {}
+
+impl From<reed_solomon_erasure::Error> for ErasureError {
+    fn from(e: reed_solomon_erasure::Error) -> Self {
+        ErasureError::Codec(format!("{:?}", e))
+    }
+}
+
+/// Erasure coding scheme chosen by the uploader: `k` data shards + `m` parity shards.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ErasureScheme {
+    pub data_shards: u8,
+    pub parity_shards: u8,
+}
+
+impl ErasureScheme {
+    /// Build and validate a scheme (`k >= 1`, `m >= 1`, `k + m <= MAX_TOTAL_SHARDS`).
+    pub fn new(data_shards: u8, parity_shards: u8) -> Result<Self, ErasureError> {
+        if data_shards == 0 || parity_shards == 0 {
+            return Err(ErasureError::InvalidScheme(
+                "data and parity shard counts must be at least 1".to_string(),
+            ));
+        }
+        let total = data_shards as u16 + parity_shards as u16;
+        if total > MAX_TOTAL_SHARDS as u16 {
+            return Err(ErasureError::InvalidScheme(format!(
+                "total shards {} exceeds maximum {}",
+                total, MAX_TOTAL_SHARDS
+            )));
+        }
+        Ok(Self {
+            data_shards,
+            parity_shards,
+        })
+    }
+
+    /// Parse `k+m` (e.g. `4+2`).
+    pub fn parse(s: &str) -> Result<Self, ErasureError> {
+        let (k, m) = s
+            .split_once('+')
+            .ok_or_else(|| ErasureError::InvalidScheme(format!("expected k+m, got '{}'", s)))?;
+        let k = k
+            .trim()
+            .parse::<u8>()
+            .map_err(|_| ErasureError::InvalidScheme(format!("invalid data shard count '{}'", k)))?;
+        let m = m
+            .trim()
+            .parse::<u8>()
+            .map_err(|_| ErasureError::InvalidScheme(format!("invalid parity shard count '{}'", m)))?;
+        Self::new(k, m)
+    }
+
+    /// Total number of shards (`k + m`).
+    pub fn total_shards(&self) -> usize {
+        self.data_shards as usize + self.parity_shards as usize
+    }
+
+    /// Size of each shard for a blob of `len` bytes (at least 1 byte).
+    pub fn shard_size(&self, len: usize) -> usize {
+        len.div_ceil(self.data_shards as usize).max(1)
+    }
+
+    fn codec(&self) -> Result<ReedSolomon, ErasureError> {
+        Ok(ReedSolomon::new(
+            self.data_shards as usize,
+            self.parity_shards as usize,
+        )?)
+    }
+}
+
+impl fmt::Display for ErasureScheme {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}+{}", self.data_shards, self.parity_shards)
+    }
+}
+
+/// Erasure coding info recorded in `BlobMeta` on the node that encoded the blob.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct ErasureMeta {
+    pub data_shards: u8,
+    pub parity_shards: u8,
+    /// Size of each shard in bytes.
+    pub shard_size: u64,
+    /// Size of the original blob in bytes (shards are zero-padded).
+    pub original_size: u64,
+    /// Blake3 hash (hex) of each shard, by shard index.
+    pub shard_hashes: Vec<String>,
+    /// Peer id of the provider holding each shard, by shard index (None = not yet placed).
+    pub shard_holders: Vec<Option<String>>,
+}
+
+impl ErasureMeta {
+    /// Describe freshly encoded `shards` of a blob of `original_size` bytes; no shard placed yet.
+    pub fn new(scheme: ErasureScheme, original_size: usize, shards: &[Vec<u8>]) -> Self {
+        Self {
+            data_shards: scheme.data_shards,
+            parity_shards: scheme.parity_shards,
+            shard_size: scheme.shard_size(original_size) as u64,
+            original_size: original_size as u64,
+            shard_hashes: shards.iter().map(|s| shard_hash(s)).collect(),
+            shard_holders: vec![None; shards.len()],
+        }
+    }
+
+    pub fn scheme(&self) -> ErasureScheme {
+        ErasureScheme {
+            data_shards: self.data_shards,
+            parity_shards: self.parity_shards,
+        }
+    }
+
+    /// Indices of shards that have no holder yet.
+    pub fn unplaced_shards(&self) -> Vec<usize> {
+        self.shard_holders
+            .iter()
+            .enumerate()
+            .filter(|(_, h)| h.is_none())
+            .map(|(i, _)| i)
+            .collect()
+    }
+}
+
+/// Split `data` into `k + m` shards of equal size (data shards first, then parity).
+pub fn encode(data: &[u8], scheme: ErasureScheme) -> Result<Vec<Vec<u8>>, ErasureError> {
+    let shard_size = scheme.shard_size(data.len());
+    let mut shards: Vec<Vec<u8>> = Vec::with_capacity(scheme.total_shards());
+    for i in 0..scheme.data_shards as usize {
+        let start = (i * shard_size).min(data.len());
+        let end = ((i + 1) * shard_size).min(data.len());
+        let mut shard = data[start..end].to_vec();
+        shard.resize(shard_size, 0);
+        shards.push(shard);
+    }
+    for _ in 0..scheme.parity_shards {
+        shards.push(vec![0u8; shard_size]);
+    }
+    scheme.codec()?.encode(&mut shards)?;
+    Ok(shards)
+}
+
+/// Rebuild the original `original_size` bytes from any `k` of the `k + m` shards.
+/// `shards[i]` is `None` when shard `i` is unavailable.
+pub fn reconstruct(
+    mut shards: Vec<Option<Vec<u8>>>,
+    scheme: ErasureScheme,
+    original_size: usize,
+) -> Result<Vec<u8>, ErasureError> {
+    if shards.len() != scheme.total_shards() {
+        return Err(ErasureError::Codec(format!(
+            "expected {} shards, got {}",
+            scheme.total_shards(),
+            shards.len()
+        )));
+    }
+    let available = shards.iter().filter(|s| s.is_some()).count();
+    if available < scheme.data_shards as usize {
+        return Err(ErasureError::NotEnoughShards {
+            available,
+            required: scheme.data_shards as usize,
+        });
+    }
+    scheme.codec()?.reconstruct_data(&mut shards)?;
+    let mut data = Vec::with_capacity(original_size);
+    for shard in shards.into_iter().take(scheme.data_shards as usize) {
+        data.extend_from_slice(&shard.expect("data shards are present after reconstruct_data"));
+    }
+    data.truncate(original_size);
+    Ok(data)
+}
+
+/// Blake3 hash of a shard (hex), recorded at encode time and checked on receipt.
+pub fn shard_hash(shard: &[u8]) -> String {
+    blake3::hash(shard).to_hex().to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_scheme() {
+        assert_eq!(ErasureScheme::parse("4+2").unwrap(), ErasureScheme::new(4, 2).unwrap());
+        assert!(ErasureScheme::parse("4").is_err());
+        assert!(ErasureScheme::parse("0+2").is_err());
+        assert!(ErasureScheme::parse("30+3").is_err());
+    }
+
+    #[test]
+    fn reconstruct_from_any_k_shards() {
+        let scheme = ErasureScheme::new(4, 2).unwrap();
+        let data: Vec<u8> = (0..1001u32).map(|i| (i % 251) as u8).collect();
+        let shards = encode(&data, scheme).unwrap();
+        assert_eq!(shards.len(), 6);
+
+        // Lose two shards (one data, one parity).
+        let mut partial: Vec<Option<Vec<u8>>> = shards.into_iter().map(Some).collect();
+        partial[1] = None;
+        partial[5] = None;
+
+        assert_eq!(reconstruct(partial, scheme, data.len()).unwrap(), data);
+    }
+
+    #[test]
+    fn reconstruct_fails_below_k() {
+        let scheme = ErasureScheme::new(3, 1).unwrap();
+        let shards = encode(b"hello erasure", scheme).unwrap();
+        let mut partial: Vec<Option<Vec<u8>>> = shards.into_iter().map(Some).collect();
+        partial[0] = None;
+        partial[2] = None;
+
+        assert_eq!(
+            reconstruct(partial, scheme, 13),
+            Err(ErasureError::NotEnoughShards {
+                available: 2,
+                required: 3
+            })
+        );
+    }
+
+    #[test]
+    fn encode_empty_blob() {
+        let scheme = ErasureScheme::new(2, 1).unwrap();
+        let shards = encode(&[], scheme).unwrap();
+        let partial: Vec<Option<Vec<u8>>> = shards.into_iter().map(Some).collect();
+        assert!(reconstruct(partial, scheme, 0).unwrap().is_empty());
+    }
+}
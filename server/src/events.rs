@@ -0,0 +1,418 @@
+//! Storage registry event watcher.
+//!
+//! The contract reports state changes as string events (`NAME:field,field,...`). The watcher
+//! follows the finalized events emitted by the registry and parses them into `ContractEvent`:
+//! - With `MASSA_GRPC_URL`: the new slot execution outputs stream, filtered on final slots
+//!   and on the registry as emitter. Events finalized before the stream opened are fetched
+//!   over JSON-RPC first.
+//! - Without gRPC, or while the stream is down: polling of `get_filtered_sc_output_event`.
+//!
+//! The position of the last delivered event is persisted in `{storage}/.state/event-cursor.json`
+//! so a restart resumes where it stopped; events at or before the cursor are never delivered
+//! twice. Events are fanned out to subscribers inside the server through `EventBus`.
+
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use tokio::sync::broadcast;
+
+use crate::contract::MassaClient;
+use crate::massa_grpc::ScOutputEvent;
+use crate::storage::write_atomic;
+
+/// Pause between two JSON-RPC polls (one period is 16 s).
+const POLL_INTERVAL: Duration = Duration::from_secs(16);
+/// How long to poll before trying the gRPC stream again after it failed.
+const STREAM_RETRY: Duration = Duration::from_secs(120);
+/// Events buffered per subscriber; a slower subscriber skips the oldest ones.
+const BUS_CAPACITY: usize = 1024;
+
+/// Typed storage registry event. Amounts are in nanoMAS, sizes in GB unless noted.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ContractEvent {
+    RegistryDeployed { admin: String },
+    NodeRegistered { address: String, allocated_gb: u64, endpoint: Option<String> },
+    NodeUpdated { address: String, old_gb: u64, new_gb: u64 },
+    NodeUnregistered { address: String },
+    ChallengeIssued { challenge_id: String, node: String, chunk_id: String },
+    ChallengePassed { challenge_id: String, node: String },
+    ChallengeFailed { challenge_id: String, node: String, reason: String },
+    ChallengeExpired { challenge_id: String, node: String },
+    RewardSkipped { node: String, reason: String },
+    RewardsDistributed { period: u64, total: u64, node_count: u64 },
+    RewardsClaimed { node: String, amount: u64 },
+    ProviderMetadataUpdated { address: String, endpoint: String },
+    UploaderBooked { uploader: String, amount_gb: u64, updated: bool },
+    FileUploadRecorded { uploader: String, size_bytes: u64, usage_bytes: u64 },
+    /// A `recordFileUploads` entry left out (zero size or uploader no longer allowed).
+    FileUploadSkipped { uploader: String, size_bytes: u64 },
+    FileUploadRemoved { uploader: String, size_bytes: u64, usage_bytes: u64 },
+    ContractFunded { from: String, amount: u64 },
+    ConfigUpdated,
+    ChallengerAdded { address: String },
+    ChallengerRemoved { address: String },
+    StorageAdminAdded { address: String },
+    StorageAdminRemoved { address: String },
+    UploaderPricePerGbSet { price: u64 },
+    ContractPaused { paused: bool },
+    AdminTransferred { new_admin: String },
+    /// Unknown event name or fields that do not parse (e.g. a newer contract version).
+    Other { data: String },
+}
+
+impl ContractEvent {
+    /// Parse an event string emitted by the registry.
+    pub fn parse(data: &str) -> Self {
+        Self::parse_known(data).unwrap_or_else(|| Self::Other {
+            data: data.to_string(),
+        })
+    }
+
+    fn parse_known(data: &str) -> Option<Self> {
+        let (name, rest) = data.split_once(':').unwrap_or((data, ""));
+        let fields: Vec<&str> = rest.split(',').collect();
+        let text = |i: usize| fields.get(i).map(|f| f.to_string());
+        let number = |i: usize| fields.get(i).and_then(|f| f.parse::<u64>().ok());
+        let event = match name {
+            "STORAGE_REGISTRY_DEPLOYED" => Self::RegistryDeployed { admin: rest.to_string() },
+            "STORAGE_NODE_REGISTERED" => {
+                // `address,gb` or `address,gb,METADATA:endpoint` (the endpoint may hold commas)
+                let mut parts = rest.splitn(3, ',');
+                let address = parts.next()?.to_string();
+                let allocated_gb = parts.next()?.parse().ok()?;
+                let endpoint = match parts.next() {
+                    Some(metadata) => Some(metadata.strip_prefix("METADATA:")?.to_string()),
+                    None => None,
+                };
+                Self::NodeRegistered { address, allocated_gb, endpoint }
+            }
+            "STORAGE_NODE_UPDATED" => Self::NodeUpdated {
+                address: text(0)?,
+                old_gb: number(1)?,
+                new_gb: number(2)?,
+            },
+            "STORAGE_NODE_UNREGISTERED" => Self::NodeUnregistered { address: rest.to_string() },
+            "CHALLENGE_ISSUED" => Self::ChallengeIssued {
+                challenge_id: text(0)?,
+                node: text(1)?,
+                chunk_id: text(2)?,
+            },
+            "CHALLENGE_PASSED" => Self::ChallengePassed {
+                challenge_id: text(0)?,
+                node: text(1)?,
+            },
+            "CHALLENGE_FAILED" => Self::ChallengeFailed {
+                challenge_id: text(0)?,
+                node: text(1)?,
+                reason: text(2).unwrap_or_default(),
+            },
+            "CHALLENGE_EXPIRED" => Self::ChallengeExpired {
+                challenge_id: text(0)?,
+                node: text(1)?,
+            },
+            "REWARD_SKIPPED" => Self::RewardSkipped {
+                node: text(0)?,
+                reason: text(1).unwrap_or_default(),
+            },
+            "REWARDS_DISTRIBUTED" => Self::RewardsDistributed {
+                period: number(0)?,
+                total: number(1)?,
+                node_count: number(2)?,
+            },
+            "REWARDS_CLAIMED" => Self::RewardsClaimed {
+                node: text(0)?,
+                amount: number(1)?,
+            },
+            "PROVIDER_METADATA_UPDATED" => {
+                let (address, endpoint) = rest.split_once(',')?;
+                Self::ProviderMetadataUpdated {
+                    address: address.to_string(),
+                    endpoint: endpoint.to_string(),
+                }
+            }
+            "UPLOADER_BOOKED" => Self::UploaderBooked {
+                uploader: text(0)?,
+                amount_gb: number(1)?,
+                updated: text(2)? == "UPDATED",
+            },
+            "FILE_UPLOAD_RECORDED" => Self::FileUploadRecorded {
+                uploader: text(0)?,
+                size_bytes: number(1)?,
+                usage_bytes: number(2)?,
+            },
+            "FILE_UPLOAD_SKIPPED" => Self::FileUploadSkipped {
+                uploader: text(0)?,
+                size_bytes: number(1)?,
+            },
+            "FILE_UPLOAD_REMOVED" => Self::FileUploadRemoved {
+                uploader: text(0)?,
+                size_bytes: number(1)?,
+                usage_bytes: number(2)?,
+            },
+            "CONTRACT_FUNDED" => Self::ContractFunded {
+                from: text(0)?,
+                amount: number(1)?,
+            },
+            "CONFIG_UPDATED" => Self::ConfigUpdated,
+            "CHALLENGER_ADDED" => Self::ChallengerAdded { address: rest.to_string() },
+            "CHALLENGER_REMOVED" => Self::ChallengerRemoved { address: rest.to_string() },
+            "STORAGE_ADMIN_ADDED" => Self::StorageAdminAdded { address: rest.to_string() },
+            "STORAGE_ADMIN_REMOVED" => Self::StorageAdminRemoved { address: rest.to_string() },
+            "UPLOADER_PRICE_PER_GB_SET" => Self::UploaderPricePerGbSet { price: rest.parse().ok()? },
+            "CONTRACT_PAUSED" => Self::ContractPaused { paused: rest.parse().ok()? },
+            "ADMIN_TRANSFERRED" => Self::AdminTransferred { new_admin: rest.to_string() },
+            _ => return None,
+        };
+        Some(event)
+    }
+
+    /// Storage node the event is about, if any.
+    pub fn node(&self) -> Option<&str> {
+        match self {
+            Self::NodeRegistered { address, .. }
+            | Self::NodeUpdated { address, .. }
+            | Self::NodeUnregistered { address }
+            | Self::ProviderMetadataUpdated { address, .. } => Some(address),
+            Self::ChallengeIssued { node, .. }
+            | Self::ChallengePassed { node, .. }
+            | Self::ChallengeFailed { node, .. }
+            | Self::ChallengeExpired { node, .. }
+            | Self::RewardSkipped { node, .. }
+            | Self::RewardsClaimed { node, .. } => Some(node),
+            _ => None,
+        }
+    }
+}
+
+/// Position of an event in the chain: slot, then index within the slot.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, serde::Serialize, serde::Deserialize)]
+pub struct EventCursor {
+    pub period: u64,
+    pub thread: u32,
+    pub index_in_slot: u64,
+}
+
+impl EventCursor {
+    fn of(event: &ScOutputEvent) -> Self {
+        Self {
+            period: event.period,
+            thread: event.thread,
+            index_in_slot: event.index_in_slot,
+        }
+    }
+}
+
+/// A registry event with its position and origin.
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct WatchedEvent {
+    pub cursor: EventCursor,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub operation_id: Option<String>,
+    pub event: ContractEvent,
+}
+
+/// Registry events after `cursor`, in chain order. Events from other emitters and failed
+/// executions are skipped.
+fn events_after(
+    cursor: Option<EventCursor>,
+    contract_address: &str,
+    mut events: Vec<ScOutputEvent>,
+) -> Vec<WatchedEvent> {
+    events.sort_by_key(EventCursor::of);
+    events
+        .into_iter()
+        .filter(|e| cursor.is_none_or(|c| EventCursor::of(e) > c))
+        .filter(|e| !e.is_error && e.emitter.as_deref() == Some(contract_address))
+        .map(|e| WatchedEvent {
+            cursor: EventCursor::of(&e),
+            operation_id: e.operation_id,
+            event: ContractEvent::parse(&e.data),
+        })
+        .collect()
+}
+
+/// Fan-out of registry events to subscribers inside the server.
+#[derive(Clone)]
+pub struct EventBus {
+    tx: broadcast::Sender<Arc<WatchedEvent>>,
+}
+
+impl EventBus {
+    /// Receive the events delivered from now on.
+    pub fn subscribe(&self) -> broadcast::Receiver<Arc<WatchedEvent>> {
+        self.tx.subscribe()
+    }
+}
+
+struct EventWatcher {
+    registry: Arc<MassaClient>,
+    contract_address: String,
+    cursor_path: PathBuf,
+    cursor: Option<EventCursor>,
+    tx: broadcast::Sender<Arc<WatchedEvent>>,
+}
+
+/// Spawn the event watcher. The cursor is kept in `state_dir`.
+pub fn spawn(
+    registry: Arc<MassaClient>,
+    contract_address: String,
+    state_dir: PathBuf,
+) -> EventBus {
+    let (tx, _) = broadcast::channel(BUS_CAPACITY);
+    let cursor_path = state_dir.join("event-cursor.json");
+    let cursor = std::fs::read(&cursor_path)
+        .ok()
+        .and_then(|data| serde_json::from_slice(&data).ok());
+    tracing::info!(cursor = ?cursor, "contract event watcher starting");
+    let mut watcher = EventWatcher {
+        registry,
+        contract_address,
+        cursor_path,
+        cursor,
+        tx: tx.clone(),
+    };
+    tokio::spawn(async move { watcher.run().await });
+    EventBus { tx }
+}
+
+impl EventWatcher {
+    async fn run(&mut self) {
+        loop {
+            if self.registry.has_grpc() {
+                if let Err(e) = self.follow_stream().await {
+                    tracing::warn!(error = %e, "contract event stream failed; polling JSON-RPC");
+                }
+            }
+            let retry_at = Instant::now() + STREAM_RETRY;
+            loop {
+                if let Err(e) = self.poll().await {
+                    tracing::warn!(error = %e, "failed to poll contract events");
+                }
+                if self.registry.has_grpc() && Instant::now() >= retry_at {
+                    break;
+                }
+                tokio::time::sleep(POLL_INTERVAL).await;
+            }
+        }
+    }
+
+    /// Follow the gRPC stream until it fails. Events finalized before the subscription are
+    /// caught up over JSON-RPC once the stream is open, so none fall in between.
+    async fn follow_stream(&mut self) -> anyhow::Result<()> {
+        let mut stream = self.registry.subscribe_final_events().await?;
+        tracing::info!("following contract events over gRPC");
+        self.poll().await?;
+        while let Some(events) = stream.next_slot().await? {
+            self.deliver(events);
+        }
+        Err(anyhow::anyhow!("stream closed by the node"))
+    }
+
+    async fn poll(&mut self) -> anyhow::Result<()> {
+        let start = self.cursor.map(|c| (c.period, c.thread));
+        let events = self.registry.get_final_events(start).await?;
+        self.deliver(events);
+        Ok(())
+    }
+
+    /// Publish the events after the cursor and persist the new cursor.
+    fn deliver(&mut self, events: Vec<ScOutputEvent>) {
+        let events = events_after(self.cursor, &self.contract_address, events);
+        let Some(last) = events.last().map(|e| e.cursor) else {
+            return;
+        };
+        for event in events {
+            tracing::debug!(cursor = ?event.cursor, event = ?event.event, "contract event");
+            // No subscriber is not an error
+            let _ = self.tx.send(Arc::new(event));
+        }
+        self.cursor = Some(last);
+        if let Err(e) = self.save_cursor(last) {
+            tracing::warn!(error = %e, "failed to persist contract event cursor");
+        }
+    }
+
+    fn save_cursor(&self, cursor: EventCursor) -> std::io::Result<()> {
+        if let Some(dir) = self.cursor_path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        let data = serde_json::to_vec(&cursor).expect("cursor serialization is infallible");
+        write_atomic(&self.cursor_path, &data)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn raw(period: u64, index_in_slot: u64, emitter: &str, data: &str) -> ScOutputEvent {
+        ScOutputEvent {
+            period,
+            thread: 0,
+            index_in_slot,
+            emitter: Some(emitter.to_string()),
+            operation_id: None,
+            is_error: false,
+            data: data.to_string(),
+        }
+    }
+
+    #[test]
+    fn parses_registry_events() {
+        assert_eq!(
+            ContractEvent::parse("STORAGE_NODE_REGISTERED:AU1node,100,METADATA:http://a:4343"),
+            ContractEvent::NodeRegistered {
+                address: "AU1node".to_string(),
+                allocated_gb: 100,
+                endpoint: Some("http://a:4343".to_string()),
+            }
+        );
+        assert_eq!(
+            ContractEvent::parse("CHALLENGE_FAILED:c1,AU1node,invalid_proof"),
+            ContractEvent::ChallengeFailed {
+                challenge_id: "c1".to_string(),
+                node: "AU1node".to_string(),
+                reason: "invalid_proof".to_string(),
+            }
+        );
+        assert_eq!(
+            ContractEvent::parse("REWARDS_DISTRIBUTED:42,1000,3"),
+            ContractEvent::RewardsDistributed { period: 42, total: 1000, node_count: 3 }
+        );
+        assert_eq!(
+            ContractEvent::parse("FILE_UPLOAD_SKIPPED:AU1up,0"),
+            ContractEvent::FileUploadSkipped { uploader: "AU1up".to_string(), size_bytes: 0 }
+        );
+        assert_eq!(ContractEvent::parse("CONFIG_UPDATED"), ContractEvent::ConfigUpdated);
+        assert_eq!(
+            ContractEvent::parse("CONTRACT_PAUSED:true"),
+            ContractEvent::ContractPaused { paused: true }
+        );
+        // Malformed fields and unknown names are kept as-is
+        assert_eq!(
+            ContractEvent::parse("REWARDS_CLAIMED:AU1node,lots"),
+            ContractEvent::Other { data: "REWARDS_CLAIMED:AU1node,lots".to_string() }
+        );
+        assert!(matches!(ContractEvent::parse("NEW_EVENT:x"), ContractEvent::Other { .. }));
+    }
+
+    #[test]
+    fn only_registry_events_after_the_cursor_are_delivered() {
+        let cursor = EventCursor { period: 10, thread: 0, index_in_slot: 1 };
+        let mut failed = raw(11, 0, "AS1registry", "CONFIG_UPDATED");
+        failed.is_error = true;
+        let events = vec![
+            raw(11, 1, "AS1registry", "STORAGE_NODE_UNREGISTERED:AU1b"),
+            raw(10, 1, "AS1registry", "STORAGE_NODE_UNREGISTERED:AU1old"),
+            raw(10, 2, "AS1registry", "STORAGE_NODE_UNREGISTERED:AU1a"),
+            raw(11, 2, "AS1other", "CONFIG_UPDATED"),
+            failed,
+        ];
+        let delivered = events_after(Some(cursor), "AS1registry", events);
+        let nodes: Vec<_> = delivered.iter().filter_map(|e| e.event.node()).collect();
+        assert_eq!(nodes, vec!["AU1a", "AU1b"]);
+        assert_eq!(delivered[1].cursor, EventCursor { period: 11, thread: 0, index_in_slot: 1 });
+    }
+}
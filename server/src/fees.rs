@@ -0,0 +1,219 @@
+//! Fee and gas of contract writes.
+//!
+//! - [`FeeStrategy`]: a fixed fee, a fee per entrypoint, or the median fee of operations in
+//!   recent blocks, clamped to configured bounds
+//! - [`GasStrategy`]: a fixed `max_gas`, or the gas used by a read-only dry run of the call
+//!   plus a safety margin
+//!
+//! `MassaClient` applies both to every write; this module only holds the policy and the
+//! arithmetic.
+
+use std::collections::HashMap;
+use std::str::FromStr;
+
+use massa_models::amount::Amount;
+
+/// Fee of every write when `MASSA_FEE` is unset (MAS).
+pub const DEFAULT_FEE: &str = "0.01";
+/// Bounds of an estimated fee when `MASSA_FEE_MIN` / `MASSA_FEE_MAX` are unset (MAS).
+pub const DEFAULT_FEE_MIN: &str = "0.01";
+pub const DEFAULT_FEE_MAX: &str = "1";
+/// `max_gas` of every write when `MASSA_MAX_GAS` is unset.
+pub const DEFAULT_MAX_GAS: u64 = 10_000_000;
+/// Slots of the blockclique whose operations are sampled for a fee estimate.
+pub const FEE_SAMPLE_SLOTS: usize = 8;
+/// Gas added on top of a dry run's cost, in percent: execution may differ slightly once
+/// the operation is included.
+const GAS_MARGIN_PERCENT: u64 = 20;
+/// Upper bound of an estimated `max_gas` (Massa's gas limit per block).
+pub const MAX_GAS_CAP: u64 = u32::MAX as u64;
+/// Threads per period on Massa networks.
+const THREAD_COUNT: u32 = 32;
+
+/// How the fee of a write is chosen (`MASSA_FEE`, `MASSA_FEE_OVERRIDES`).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FeeStrategy {
+    /// The same fee for every entrypoint.
+    Fixed(Amount),
+    /// A fee per entrypoint, `default` for the others.
+    PerFunction {
+        default: Amount,
+        fees: HashMap<String, Amount>,
+    },
+    /// Median fee of operations in the last [`FEE_SAMPLE_SLOTS`] blockclique blocks,
+    /// clamped to `[min, max]`; `min` when the blocks hold no operation.
+    Estimated { min: Amount, max: Amount },
+}
+
+impl FeeStrategy {
+    /// Strategy from `MASSA_FEE` (a fee in MAS, or `estimate`), `MASSA_FEE_OVERRIDES`
+    /// (`function=fee,...`) and the bounds used when estimating.
+    pub fn parse(fee: &str, overrides: &[String], min: &str, max: &str) -> Result<Self, String> {
+        if fee.trim().eq_ignore_ascii_case("estimate") {
+            if !overrides.is_empty() {
+                return Err("MASSA_FEE_OVERRIDES cannot be combined with MASSA_FEE=estimate".into());
+            }
+            let (min, max) = (parse_mas(min)?, parse_mas(max)?);
+            if min > max {
+                return Err(format!("fee bounds are inverted ({} > {})", min, max));
+            }
+            return Ok(Self::Estimated { min, max });
+        }
+        let default = parse_mas(fee)?;
+        if overrides.is_empty() {
+            return Ok(Self::Fixed(default));
+        }
+        let mut fees = HashMap::new();
+        for entry in overrides {
+            let (function, fee) = entry
+                .split_once('=')
+                .ok_or_else(|| format!("expected function=fee, got {:?}", entry))?;
+            fees.insert(function.trim().to_string(), parse_mas(fee)?);
+        }
+        Ok(Self::PerFunction { default, fees })
+    }
+
+    /// True when fees come from recent blocks.
+    pub fn is_estimated(&self) -> bool {
+        matches!(self, Self::Estimated { .. })
+    }
+
+    /// Fee for `function`. `recent_fees` (nanoMAS) are only used when estimating.
+    pub fn fee(&self, function: &str, recent_fees: Vec<u64>) -> Amount {
+        match self {
+            Self::Fixed(fee) => *fee,
+            Self::PerFunction { default, fees } => *fees.get(function).unwrap_or(default),
+            Self::Estimated { min, max } => estimate_fee(recent_fees, *min, *max),
+        }
+    }
+}
+
+/// How the `max_gas` of a write is chosen (`MASSA_MAX_GAS`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GasStrategy {
+    Fixed(u64),
+    /// Dry-run the call with `execute_read_only_call` and add a margin.
+    Estimated,
+}
+
+impl GasStrategy {
+    /// Strategy from `MASSA_MAX_GAS`: a gas amount, or `estimate`.
+    pub fn parse(value: &str) -> Result<Self, String> {
+        let value = value.trim();
+        if value.eq_ignore_ascii_case("estimate") {
+            return Ok(Self::Estimated);
+        }
+        match value.parse::<u64>() {
+            Ok(gas) if gas > 0 && gas <= MAX_GAS_CAP => Ok(Self::Fixed(gas)),
+            _ => Err(format!("expected a gas amount (1 to {}) or estimate, got {:?}", MAX_GAS_CAP, value)),
+        }
+    }
+}
+
+/// Fee and gas policy of contract writes.
+#[derive(Debug, Clone)]
+pub struct WriteCosts {
+    pub fee: FeeStrategy,
+    pub gas: GasStrategy,
+}
+
+impl Default for WriteCosts {
+    fn default() -> Self {
+        Self {
+            fee: FeeStrategy::Fixed(parse_mas(DEFAULT_FEE).expect("valid default fee")),
+            gas: GasStrategy::Fixed(DEFAULT_MAX_GAS),
+        }
+    }
+}
+
+/// MAS amount such as `0.01`.
+pub fn parse_mas(value: &str) -> Result<Amount, String> {
+    Amount::from_str(value.trim()).map_err(|e| format!("invalid MAS amount {:?}: {}", value, e))
+}
+
+/// Median of `fees` (nanoMAS) clamped to `[min, max]`; `min` when there is no sample.
+fn estimate_fee(mut fees: Vec<u64>, min: Amount, max: Amount) -> Amount {
+    if fees.is_empty() {
+        return min;
+    }
+    fees.sort_unstable();
+    let median = fees[fees.len() / 2];
+    Amount::from_raw(median.clamp(min.to_raw(), max.to_raw()))
+}
+
+/// `max_gas` for a call whose dry run used `gas_cost`.
+pub fn gas_with_margin(gas_cost: u64) -> u64 {
+    gas_cost
+        .saturating_add(gas_cost.saturating_mul(GAS_MARGIN_PERCENT) / 100)
+        .clamp(1, MAX_GAS_CAP)
+}
+
+/// The `count` slots up to and including `(period, thread)`, most recent first.
+pub fn recent_slots(period: u64, thread: u32, count: usize) -> Vec<(u64, u32)> {
+    let mut slots = Vec::with_capacity(count);
+    let (mut period, mut thread) = (period, thread.min(THREAD_COUNT - 1));
+    for _ in 0..count {
+        slots.push((period, thread));
+        if thread == 0 {
+            if period == 0 {
+                break;
+            }
+            period -= 1;
+            thread = THREAD_COUNT - 1;
+        } else {
+            thread -= 1;
+        }
+    }
+    slots
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mas(value: &str) -> Amount {
+        Amount::from_str(value).unwrap()
+    }
+
+    #[test]
+    fn fee_strategies_parse() {
+        assert_eq!(FeeStrategy::parse("0.02", &[], "0", "1"), Ok(FeeStrategy::Fixed(mas("0.02"))));
+
+        let overrides = vec!["recordFileUploads=0.05".to_string(), " claimRewards = 0.1".to_string()];
+        let strategy = FeeStrategy::parse("0.01", &overrides, "0", "1").unwrap();
+        assert_eq!(strategy.fee("recordFileUploads", vec![]), mas("0.05"));
+        assert_eq!(strategy.fee("claimRewards", vec![]), mas("0.1"));
+        assert_eq!(strategy.fee("submitProof", vec![mas("0.5").to_raw()]), mas("0.01"));
+
+        let estimated = FeeStrategy::parse("estimate", &[], "0.01", "0.5").unwrap();
+        assert_eq!(estimated, FeeStrategy::Estimated { min: mas("0.01"), max: mas("0.5") });
+        assert!(estimated.is_estimated());
+        assert_eq!(estimated.fee("submitProof", vec![mas("0.02").to_raw()]), mas("0.02"));
+
+        assert!(FeeStrategy::parse("estimate", &[], "1", "0.5").is_err());
+        assert!(FeeStrategy::parse("0.01", &["claimRewards".to_string()], "0", "1").is_err());
+        assert!(FeeStrategy::parse("cheap", &[], "0", "1").is_err());
+    }
+
+    #[test]
+    fn estimates_are_bounded() {
+        let (min, max) = (mas("0.01"), mas("0.5"));
+        assert_eq!(estimate_fee(vec![], min, max), min);
+        let fees = vec![mas("0.2").to_raw(), mas("0.03").to_raw(), mas("0.04").to_raw()];
+        assert_eq!(estimate_fee(fees, min, max), mas("0.04"));
+        assert_eq!(estimate_fee(vec![1, 2, 3], min, max), min);
+        assert_eq!(estimate_fee(vec![mas("3").to_raw()], min, max), max);
+
+        assert_eq!(gas_with_margin(1_000_000), 1_200_000);
+        assert_eq!(gas_with_margin(u64::MAX), MAX_GAS_CAP);
+        assert_eq!(GasStrategy::parse("estimate"), Ok(GasStrategy::Estimated));
+        assert_eq!(GasStrategy::parse("10000000"), Ok(GasStrategy::Fixed(10_000_000)));
+        assert!(GasStrategy::parse("0").is_err());
+    }
+
+    #[test]
+    fn recent_slots_walk_back_across_periods() {
+        assert_eq!(recent_slots(10, 1, 3), vec![(10, 1), (10, 0), (9, 31)]);
+        assert_eq!(recent_slots(0, 1, 4), vec![(0, 1), (0, 0)]);
+    }
+}
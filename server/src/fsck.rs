@@ -0,0 +1,410 @@
+//! Offline consistency check (`fsck`) of a storage directory.
+//!
+//! Runs without HTTP, P2P or contract access, so it can validate a data directory copied
+//! between machines before the server is started on it:
+//!
+//! ```text
+//! massa-storage-server fsck [--path DIR] [--repair] [--rebuild-index] [--json]
+//! ```
+//!
+//! Checks every blob against its `.meta` (Blake3 `content_hash`), and reports orphan
+//! `.meta` files, blobs without metadata, unreadable metadata, non-sanitized names,
+//! leftover temp files and usage totals. `--repair` fixes what can be fixed safely;
+//! `--rebuild-index` regenerates missing or unreadable metadata from the data on disk.
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::storage::{
+    content_hash, is_reserved_dir, sanitize_segment, BlobMeta, Storage, TMP_SUFFIX,
+};
+
+/// Command-line options for `fsck`.
+#[derive(Debug, Default)]
+pub struct FsckOptions {
+    pub path: Option<PathBuf>,
+    pub repair: bool,
+    pub rebuild_index: bool,
+    pub json: bool,
+}
+
+impl FsckOptions {
+    /// Parse `fsck` arguments (everything after the subcommand).
+    pub fn parse(args: &[String]) -> Result<Self, String> {
+        let mut opts = Self::default();
+        let mut iter = args.iter();
+        while let Some(arg) = iter.next() {
+            match arg.as_str() {
+                "--path" => {
+                    let path = iter.next().ok_or("--path requires a directory")?;
+                    opts.path = Some(PathBuf::from(path));
+                }
+                "--repair" => opts.repair = true,
+                "--rebuild-index" => opts.rebuild_index = true,
+                "--json" => opts.json = true,
+                other => return Err(format!("unknown fsck argument: {}", other)),
+            }
+        }
+        Ok(opts)
+    }
+}
+
+/// Findings of one `fsck` run. Entries are `namespace/name` paths relative to the base.
+#[derive(Debug, Default, serde::Serialize)]
+pub struct FsckReport {
+    pub namespaces: u64,
+    pub blobs: u64,
+    pub blob_bytes: u64,
+    /// Bytes in `.meta` files, shard directories, temp files and quarantine.
+    pub other_bytes: u64,
+    pub hash_ok: u64,
+    /// Blobs whose metadata has no `content_hash` (legacy uploads).
+    pub unverified: Vec<String>,
+    pub hash_mismatch: Vec<String>,
+    /// `.meta` files without a data file.
+    pub orphan_meta: Vec<String>,
+    /// Data files without a `.meta` file.
+    pub missing_meta: Vec<String>,
+    /// `.meta` files that are not valid JSON metadata.
+    pub invalid_meta: Vec<String>,
+    /// Namespace directories or blob files whose name is not sanitized.
+    pub unsanitized: Vec<String>,
+    pub tmp_files: Vec<String>,
+    /// Actions taken by `--repair` / `--rebuild-index`.
+    pub actions: Vec<String>,
+    /// Problems left once the run is over: found by a second scan after `--repair` /
+    /// `--rebuild-index`, otherwise the problems found.
+    pub remaining_problems: usize,
+}
+
+impl FsckReport {
+    /// Number of problems found (before any repair).
+    pub fn problems(&self) -> usize {
+        self.hash_mismatch.len()
+            + self.orphan_meta.len()
+            + self.missing_meta.len()
+            + self.invalid_meta.len()
+            + self.unsanitized.len()
+            + self.tmp_files.len()
+    }
+}
+
+fn dir_size(path: &Path) -> io::Result<u64> {
+    let mut total = 0;
+    for entry in fs::read_dir(path)? {
+        let entry = entry?;
+        if entry.path().is_dir() {
+            total += dir_size(&entry.path())?;
+        } else {
+            total += entry.metadata()?.len();
+        }
+    }
+    Ok(total)
+}
+
+/// Check (and optionally repair) the storage directory at `base`.
+pub fn check(base: &Path, opts: &FsckOptions) -> io::Result<FsckReport> {
+    let mut report = FsckReport::default();
+    if !base.is_dir() {
+        return Err(io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("storage directory {} does not exist", base.display()),
+        ));
+    }
+    let storage = Storage::new(base.to_path_buf(), u64::MAX);
+
+    for ns_entry in fs::read_dir(base)? {
+        let ns_entry = ns_entry?;
+        let ns_path = ns_entry.path();
+        let ns = ns_entry.file_name().to_string_lossy().to_string();
+        if !ns_path.is_dir() {
+            report.other_bytes += ns_entry.metadata()?.len();
+            continue;
+        }
+        if is_reserved_dir(&ns) {
+            report.other_bytes += dir_size(&ns_path)?;
+            continue;
+        }
+        report.namespaces += 1;
+        if sanitize_segment(&ns) != ns {
+            report.unsanitized.push(ns.clone());
+            continue;
+        }
+        check_namespace(&storage, &ns_path, &ns, opts, &mut report)?;
+    }
+
+    if opts.repair {
+        repair(&storage, base, &mut report)?;
+    }
+    report.remaining_problems = if opts.repair || opts.rebuild_index {
+        check(base, &FsckOptions::default())?.problems()
+    } else {
+        report.problems()
+    };
+    Ok(report)
+}
+
+fn check_namespace(
+    storage: &Storage,
+    ns_path: &Path,
+    ns: &str,
+    opts: &FsckOptions,
+    report: &mut FsckReport,
+) -> io::Result<()> {
+    for entry in fs::read_dir(ns_path)? {
+        let entry = entry?;
+        let path = entry.path();
+        let name = entry.file_name().to_string_lossy().to_string();
+        let label = format!("{}/{}", ns, name);
+
+        if path.is_dir() {
+            // Shard directories (`{id}.shards`) hold data for other providers.
+            report.other_bytes += dir_size(&path)?;
+            continue;
+        }
+        let size = entry.metadata()?.len();
+        if name.ends_with(TMP_SUFFIX) {
+            report.other_bytes += size;
+            report.tmp_files.push(label);
+            continue;
+        }
+        if let Some(id) = name.strip_suffix(".meta") {
+            report.other_bytes += size;
+            if !ns_path.join(id).is_file() {
+                report.orphan_meta.push(format!("{}/{}", ns, id));
+            }
+            continue;
+        }
+        if sanitize_segment(&name) != name {
+            report.other_bytes += size;
+            report.unsanitized.push(label);
+            continue;
+        }
+
+        report.blobs += 1;
+        report.blob_bytes += size;
+        let meta_path = ns_path.join(format!("{}.meta", name));
+        let meta = match fs::read_to_string(&meta_path) {
+            Ok(contents) => match serde_json::from_str::<BlobMeta>(&contents) {
+                Ok(meta) => Some(meta),
+                Err(_) => {
+                    report.invalid_meta.push(label.clone());
+                    None
+                }
+            },
+            Err(_) => {
+                report.missing_meta.push(label.clone());
+                None
+            }
+        };
+        let data = fs::read(&path)?;
+        let actual = content_hash(&data);
+
+        match meta {
+            Some(meta) => match meta.content_hash.as_ref() {
+                Some(expected) if *expected == actual => report.hash_ok += 1,
+                Some(_) => report.hash_mismatch.push(label),
+                None => {
+                    report.unverified.push(label.clone());
+                    if opts.rebuild_index {
+                        let meta = BlobMeta {
+                            content_hash: Some(actual),
+                            ..meta
+                        };
+                        storage.update_meta(ns, &name, &meta)?;
+                        report.actions.push(format!("recorded content hash for {}", label));
+                    }
+                }
+            },
+            None if opts.rebuild_index => {
+                let meta = BlobMeta {
+                    content_hash: Some(actual),
+                    ..BlobMeta::default()
+                };
+                storage.update_meta(ns, &name, &meta)?;
+                report.actions.push(format!("rebuilt metadata for {}", label));
+            }
+            None => {}
+        }
+    }
+    Ok(())
+}
+
+/// Safe repairs: finish/discard interrupted writes, quarantine corrupt blobs, remove
+/// orphan metadata of data that cannot be recovered, and rename non-sanitized blobs.
+fn repair(storage: &Storage, base: &Path, report: &mut FsckReport) -> io::Result<()> {
+    let recovery = storage.recover()?;
+    if recovery.rolled_forward + recovery.discarded > 0 {
+        report.actions.push(format!(
+            "recovered interrupted writes: {} rolled forward, {} discarded",
+            recovery.rolled_forward, recovery.discarded
+        ));
+    }
+
+    for label in &report.hash_mismatch {
+        if let Some((ns, id)) = label.split_once('/') {
+            let target = storage.quarantine(ns, id)?;
+            report
+                .actions
+                .push(format!("quarantined {} to {}", label, target.display()));
+        }
+    }
+
+    for label in &report.orphan_meta {
+        let Some((ns, id)) = label.split_once('/') else {
+            continue;
+        };
+        // The server rebuilds erasure-coded blobs from shards and fetches blobs with a
+        // known hash back from their replicas; quarantined data is left to the operator.
+        let recoverable = match storage.meta(ns, id) {
+            Ok(meta) => meta.erasure.is_some() || (meta.content_hash.is_some() && !meta.replicas.is_empty()),
+            Err(_) => false,
+        };
+        if recoverable || storage.is_quarantined(ns, id) {
+            report.actions.push(format!("kept orphan metadata {}: data can be recovered", label));
+            continue;
+        }
+        fs::remove_file(base.join(ns).join(format!("{}.meta", id)))?;
+        report.actions.push(format!("removed orphan metadata {}", label));
+    }
+
+    for label in &report.unsanitized {
+        let Some((ns, name)) = label.split_once('/') else {
+            // Namespace directories are left to the operator (merging may be needed).
+            continue;
+        };
+        let from = base.join(ns).join(name);
+        let to = base.join(ns).join(sanitize_segment(name));
+        let meta_from = base.join(ns).join(format!("{}.meta", name));
+        let meta_to = base.join(ns).join(format!("{}.meta", sanitize_segment(name)));
+        if to.exists() || meta_to.exists() {
+            report
+                .actions
+                .push(format!("skipped rename of {}: sanitized name already exists", label));
+            continue;
+        }
+        // Metadata first: a crash in between leaves metadata with no data, which fsck reports
+        if meta_from.is_file() {
+            fs::rename(&meta_from, &meta_to)?;
+        }
+        fs::rename(&from, &to)?;
+        report
+            .actions
+            .push(format!("renamed {} to {}", label, to.display()));
+    }
+    Ok(())
+}
+
+fn print_list(title: &str, items: &[String]) {
+    if items.is_empty() {
+        return;
+    }
+    println!("{} ({}):", title, items.len());
+    for item in items {
+        println!("  {}", item);
+    }
+}
+
+/// Entry point for `massa-storage-server fsck ...`. Exits with status 1 when problems remain.
+pub fn run(args: &[String]) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let opts = FsckOptions::parse(args)?;
+    let base = opts.path.clone().unwrap_or_else(|| {
+        std::env::var("STORAGE_PATH")
+            .map(PathBuf::from)
+            .unwrap_or_else(|_| PathBuf::from("./data"))
+    });
+    let report = check(&base, &opts)?;
+
+    if opts.json {
+        println!("{}", serde_json::to_string_pretty(&report)?);
+    } else {
+        println!("fsck {}", base.display());
+        println!(
+            "namespaces: {}, blobs: {} ({} bytes), other files: {} bytes",
+            report.namespaces, report.blobs, report.blob_bytes, report.other_bytes
+        );
+        println!("hash ok: {}", report.hash_ok);
+        print_list("hash mismatch", &report.hash_mismatch);
+        print_list("no recorded hash", &report.unverified);
+        print_list("orphan metadata", &report.orphan_meta);
+        print_list("missing metadata", &report.missing_meta);
+        print_list("invalid metadata", &report.invalid_meta);
+        print_list("non-sanitized names", &report.unsanitized);
+        print_list("temp files", &report.tmp_files);
+        print_list("actions", &report.actions);
+        if opts.repair || opts.rebuild_index {
+            println!("problems remaining: {}", report.remaining_problems);
+        }
+    }
+
+    if report.remaining_problems > 0 {
+        std::process::exit(1);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_dir() -> PathBuf {
+        let base = std::env::temp_dir().join(format!("massa-fsck-test-{}", uuid::Uuid::new_v4()));
+        fs::create_dir_all(base.join("ns")).unwrap();
+        base
+    }
+
+    #[test]
+    fn detects_and_repairs_problems() {
+        let base = temp_dir();
+        let storage = Storage::new(base.clone(), u64::MAX);
+        storage.put("ns", Some("good"), b"good", BlobMeta::default()).unwrap();
+        storage.put("ns", Some("bad"), b"bad", BlobMeta::default()).unwrap();
+        fs::write(base.join("ns").join("bad"), b"bit rot").unwrap();
+        fs::write(base.join("ns").join("orphan.meta"), b"{\"min_replication\":1}").unwrap();
+        fs::write(base.join("ns").join("nometa"), b"x").unwrap();
+        fs::write(base.join("ns").join("with space"), b"y").unwrap();
+        storage.put("ns", Some("tmp"), b"z", BlobMeta::default()).unwrap();
+        fs::rename(base.join("ns").join("tmp"), base.join("ns").join("also spaced")).unwrap();
+        fs::rename(base.join("ns").join("tmp.meta"), base.join("ns").join("also spaced.meta")).unwrap();
+
+        let report = check(&base, &FsckOptions::default()).unwrap();
+        assert_eq!(report.hash_ok, 1);
+        assert_eq!(report.hash_mismatch, vec!["ns/bad"]);
+        assert_eq!(report.orphan_meta, vec!["ns/orphan"]);
+        assert_eq!(report.missing_meta, vec!["ns/nometa"]);
+        let mut unsanitized = report.unsanitized.clone();
+        unsanitized.sort();
+        assert_eq!(unsanitized, vec!["ns/also spaced", "ns/with space"]);
+        assert_eq!(report.remaining_problems, report.problems());
+
+        let opts = FsckOptions {
+            repair: true,
+            rebuild_index: true,
+            ..FsckOptions::default()
+        };
+        let repaired = check(&base, &opts).unwrap();
+        let report = check(&base, &FsckOptions::default()).unwrap();
+        // The renamed blob still lacks metadata until the next --rebuild-index run.
+        assert_eq!(report.missing_meta, vec!["ns/with_space"]);
+        assert!(report.hash_mismatch.is_empty());
+        assert!(report.orphan_meta.contains(&"ns/bad".to_string()));
+        assert_eq!(repaired.remaining_problems, report.problems());
+        // Metadata is renamed with its blob
+        assert_eq!(report.hash_ok, 3);
+        assert!(storage.meta("ns", "also_spaced").unwrap().content_hash.is_some());
+
+        // Metadata of quarantined or replicated data survives another repair
+        let replicated = BlobMeta {
+            content_hash: Some(content_hash(b"elsewhere")),
+            replicas: vec!["peer".to_string()],
+            ..BlobMeta::default()
+        };
+        storage.put("ns", Some("lost"), b"elsewhere", replicated).unwrap();
+        fs::remove_file(base.join("ns").join("lost")).unwrap();
+        check(&base, &opts).unwrap();
+        assert!(base.join("ns").join("bad.meta").is_file());
+        assert!(base.join("ns").join("lost.meta").is_file());
+        fs::remove_dir_all(&base).unwrap();
+    }
+}
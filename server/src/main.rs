@@ -1,44 +1,38 @@
 //! Massa storage server — simple upload and read API with filesystem storage.
+//! `massa-storage-server fsck` checks the storage directory offline (see `fsck`).
+//! `massa-storage-server drain` hands the node's blobs off and unregisters it (see `drain`).
 
 use std::sync::Arc;
 use tower_http::cors::{Any, CorsLayer};
 
-/// Returns true if the endpoint is local/unreachable (0.0.0.0, 127.0.0.1, localhost).
-fn is_local_endpoint(endpoint: &str) -> bool {
-    let lower = endpoint.to_lowercase();
-    lower.contains("0.0.0.0") || lower.contains("127.0.0.1") || lower.contains("localhost")
-}
-
-/// Tries to get a reachable endpoint for the local network (e.g. http://192.168.0.4:4343)
-/// when binding to 0.0.0.0. Uses a UDP "connect" to discover the local IP. Port is taken from bind_address.
-fn try_local_network_endpoint(bind_address: &str) -> Option<String> {
-    let port = bind_address
-        .rsplit(':')
-        .next()
-        .unwrap_or("4343")
-        .trim();
-    let ip = (|| {
-        let socket = std::net::UdpSocket::bind("0.0.0.0:0").ok()?;
-        socket.connect("8.8.8.8:80").ok()?;
-        let addr = socket.local_addr().ok()?;
-        let ip = addr.ip();
-        if ip.is_loopback() {
-            return None;
-        }
-        Some(ip)
-    })()?;
-    Some(format!("http://{}:{}", ip, port))
-}
-
 mod api;
+mod attestation;
 mod auth;
+mod bandwidth;
 mod args;
 mod config;
 mod contract;
+mod drain;
+mod envelope;
+mod erasure;
+mod events;
+mod fees;
+mod fsck;
+mod inventory;
 mod massa_grpc;
+mod operations;
 mod p2p;
-mod sc_client;
+mod provider_metadata;
+mod read_through;
+mod registry;
+mod replication;
+mod reputation;
+mod rewards;
+mod rpc;
+mod scrub;
+mod state;
 mod storage;
+mod usage_outbox;
 
 use api::{router, UploadAuthConfig};
 use config::Config;
@@ -56,11 +50,30 @@ async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
         )
         .init();
 
-    let config = Config::from_env();
+    // `fsck` subcommand: offline check of the storage directory (no HTTP, P2P or contract)
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if args.first().map(String::as_str) == Some("fsck") {
+        return fsck::run(&args[1..]);
+    }
+
+    // `drain` subcommand: run as usual but refuse uploads, hand blobs off and unregister
+    let draining = args.first().map(String::as_str) == Some("drain");
+
+    let config = Config::from_env()?;
     std::fs::create_dir_all(&config.storage_path)?;
     let storage_limit_bytes = config.storage_limit_gb.saturating_mul(1024 * 1024 * 1024);
     let storage = Storage::new(config.storage_path.clone(), storage_limit_bytes);
 
+    // Finish or discard writes interrupted by a previous crash before serving anything
+    let recovery = storage.recover()?;
+    if recovery.rolled_forward > 0 || recovery.discarded > 0 {
+        tracing::warn!(
+            rolled_forward = recovery.rolled_forward,
+            discarded = recovery.discarded,
+            "storage recovery completed"
+        );
+    }
+
     tracing::info!(
         storage_limit_gb = config.storage_limit_gb,
         "storage configured"
@@ -74,20 +87,26 @@ async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
         "provider identity (from PRIVATE_KEY)"
     );
 
-    // Shared state for discovered P2P addresses (filtered to exclude localhost)
+    // Shared state for confirmed external P2P addresses (reported by /config)
     let p2p_discovered_addrs = Arc::new(std::sync::RwLock::new(Vec::new()));
     // Create Massa client (with gRPC for write operations when MASSA_GRPC_URL is set)
-    let massa_client = if let Some(grpc_url) = &config.massa_grpc_url
-    {
-        tracing::info!("gRPC client enabled for contract writes");
+    let massa_client = if !config.massa_grpc_urls.is_empty() {
+        tracing::info!(
+            endpoints = ?config.massa_grpc_urls,
+            network = %config.massa_chain_id,
+            fee = ?config.write_costs.fee,
+            max_gas = ?config.write_costs.gas,
+            "gRPC client enabled for contract writes"
+        );
         match MassaClient::with_grpc(
             config.massa_json_rpc.clone(),
-            grpc_url.clone(),
+            config.massa_grpc_urls.clone(),
             config.storage_registry_address.clone(),
             &config.private_key,
-        )
-        .await
-        {
+            config.massa_chain_id,
+            config.write_costs.clone(),
+            config.massa_rpc_timeout,
+        ) {
             Ok(client) => Some(client),
             Err(e) => {
                 tracing::warn!(error = %e, "failed to create gRPC client, writes disabled");
@@ -100,14 +119,56 @@ async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
 
     // Fallback to read-only client if gRPC not configured
     let massa_client = Arc::new(massa_client.unwrap_or_else(|| {
-        MassaClient::new(config.massa_json_rpc.clone(), config.storage_registry_address.clone())
+        MassaClient::new(
+            config.massa_json_rpc.clone(),
+            config.storage_registry_address.clone(),
+            config.massa_rpc_timeout,
+        )
     }));
 
+    // Operations signed for another network are rejected by the node
+    if massa_client.has_grpc() {
+        match massa_client.get_chain_id().await {
+            Ok(Some(node_chain_id)) if node_chain_id != config.massa_chain_id.to_u64() => {
+                tracing::error!(
+                    configured = %config.massa_chain_id,
+                    node = node_chain_id,
+                    "MASSA_NETWORK / MASSA_CHAIN_ID does not match the node's chain id; contract writes will be rejected"
+                );
+            }
+            Ok(_) => {}
+            Err(e) => tracing::warn!(error = %e, "failed to read the node's chain id"),
+        }
+    }
+
+    if draining && !massa_client.has_grpc() {
+        return Err("drain sends unregisterStorageNode and needs MASSA_GRPC_URL and PRIVATE_KEY".into());
+    }
+
+    // Follow contract writes until they are final or expired
+    let operations = operations::spawn(massa_client.clone());
+
+    // Record uploader usage on the contract in batches (writes need gRPC)
+    let usage_outbox = massa_client
+        .has_grpc()
+        .then(|| usage_outbox::spawn(massa_client.clone(), operations.clone(), &storage.state_dir()));
+
+    // Claim rewards and watch the wallet balance (writes need gRPC)
+    let rewards = massa_client.has_grpc().then(|| {
+        rewards::spawn(
+            massa_client.clone(),
+            operations.clone(),
+            config.massa_address.clone(),
+            config.rewards,
+            &storage.state_dir(),
+        )
+    });
+
     // Discover peers from smart contract
     let mut peers_to_dial = config.bootstrap_peers.clone();
     tracing::info!(
         contract = %config.storage_registry_address,
-        rpc = %config.massa_json_rpc,
+        rpc = ?config.massa_json_rpc,
         "querying contract for peers"
     );
     match massa_client.get_all_providers().await {
@@ -128,38 +189,132 @@ async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
         }
     }
 
+    // Bandwidth metering and rate limits, shared by HTTP and P2P
+    let bandwidth = Arc::new(bandwidth::Bandwidth::new(config.bandwidth_limits));
+
     // Start libp2p (bootstrap peers + initial contract discovery)
     tracing::info!(
-        listen_addr = %config.p2p_listen_addr,
+        listen_addrs = ?config.p2p_listen_addrs,
+        external_addrs = ?config.p2p_external_addrs,
         "starting libp2p"
     );
     let p2p_state = p2p::spawn(
-        config.p2p_listen_addr.clone(),
-        config.massa_address.clone(),
-        peers_to_dial,
+        p2p::P2pConfig {
+            listen_addrs: config.p2p_listen_addrs.clone(),
+            external_addrs: config.p2p_external_addrs.clone(),
+            tcp: config.p2p_tcp,
+            quic: config.p2p_quic,
+            mdns: config.p2p_mdns,
+            max_inbound: config.p2p_max_inbound,
+            max_outbound: config.p2p_max_outbound,
+            private_key: config.private_key.clone(),
+            peers_to_dial,
+        },
         p2p_discovered_addrs.clone(),
+        storage.clone(),
+        massa_client.clone(),
+        bandwidth.clone(),
+    )?;
+
+    // Replicate uploaded blobs (full copies or erasure-coded shards) to connected peers
+    let replication = replication::spawn(storage.clone(), p2p_state.clone());
+
+    // Leaving the network: hand every blob off, then unregister
+    let drain = draining.then(|| {
+        drain::spawn(
+            massa_client.clone(),
+            operations.clone(),
+            replication.clone(),
+            config.massa_address.clone(),
+        )
+    });
+
+    // Background integrity scrubber (re-hashes stored blobs, repairs from peers)
+    let scrub = if config.scrub_rate_bytes_per_sec > 0 {
+        tracing::info!(
+            rate_bytes_per_sec = config.scrub_rate_bytes_per_sec,
+            interval_secs = config.scrub_interval_secs,
+            "integrity scrubber enabled"
+        );
+        Some(scrub::spawn(
+            storage.clone(),
+            p2p_state.clone(),
+            config.scrub_rate_bytes_per_sec,
+            std::time::Duration::from_secs(config.scrub_interval_secs),
+        ))
+    } else {
+        None
+    };
+
+    // Read-through: serve blobs held only by other providers (optionally cached locally)
+    let read_through = if config.read_through {
+        tracing::info!(
+            cache_bytes = config.read_through_cache_bytes,
+            "read-through retrieval enabled"
+        );
+        Some(Arc::new(read_through::ReadThrough::new(
+            &storage,
+            p2p_state.clone(),
+            massa_client.clone(),
+            bandwidth.clone(),
+            config.read_through_cache_bytes,
+        )))
+    } else {
+        None
+    };
+
+    // Follow storage registry events; challenges and rewards concerning this node are logged
+    let contract_events = events::spawn(
+        massa_client.clone(),
+        config.storage_registry_address.clone(),
+        storage.state_dir(),
     );
+    {
+        let mut events_rx = contract_events.subscribe();
+        let massa_address = config.massa_address.clone();
+        tokio::spawn(async move {
+            use events::ContractEvent;
+            use tokio::sync::broadcast::error::RecvError;
+            loop {
+                match events_rx.recv().await {
+                    Ok(watched) if watched.event.node() == Some(massa_address.as_str()) => {
+                        match &watched.event {
+                            ContractEvent::ChallengeFailed { .. }
+                            | ContractEvent::ChallengeExpired { .. }
+                            | ContractEvent::RewardSkipped { .. } => {
+                                tracing::warn!(event = ?watched.event, "registry event for this node")
+                            }
+                            _ => tracing::info!(event = ?watched.event, "registry event for this node"),
+                        }
+                    }
+                    Ok(_) => {}
+                    Err(RecvError::Lagged(skipped)) => {
+                        tracing::warn!(skipped, "contract event subscriber lagged")
+                    }
+                    Err(RecvError::Closed) => break,
+                }
+            }
+        });
+    }
 
     // Upload authentication is mandatory: server refuses to start if
     // STORAGE_REGISTRY_ADDRESS or MASSA_JSON_RPC are missing (see Config::from_env).
     tracing::info!(
         registry = %config.storage_registry_address,
-        rpc = %config.massa_json_rpc,
+        rpc = ?config.massa_json_rpc,
         "upload authentication enabled (Massa signature + getIsAllowedUploader)"
     );
     let upload_auth = Some(UploadAuthConfig {
-        storage_registry_address: config.storage_registry_address.clone(),
-        massa_json_rpc: config.massa_json_rpc.clone(),
+        registry: massa_client.clone(),
     });
     // Periodic peer discovery from smart contract
     {
         let p2p_state_discovery = p2p_state.clone();
         let massa_address = config.massa_address.clone();
-        let rpc_url = config.massa_json_rpc.clone();
+        let client = massa_client.clone();
         let storage_registry_address = config.storage_registry_address.clone();
 
         tokio::spawn(async move {
-            let client = MassaClient::new(rpc_url, storage_registry_address.clone());
             let mut known_addrs: std::collections::HashSet<String> = std::collections::HashSet::new();
 
             loop {
@@ -172,20 +327,19 @@ async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
                             if massa_address == provider.address {
                                 continue;
                             }
-                            // Dial new p2p addresses
-                            for addr in &provider.p2p_addrs {
-                                if !addr.is_empty() && !known_addrs.contains(addr) {
+                            // Hand every registered address to the connection manager, which
+                            // keeps the peers connected (already known ones are not redialed)
+                            for addr in provider.p2p_addrs.iter().filter(|a| !a.is_empty()) {
+                                if known_addrs.insert(addr.clone()) {
                                     tracing::info!(
                                         provider = %provider.address,
                                         p2p_addr = %addr,
                                         "discovered peer from contract"
                                     );
-                                    known_addrs.insert(addr.clone());
-
-                                    let p2p = p2p_state_discovery.read().await;
-                                    if let Err(e) = p2p.dial(addr).await {
-                                        tracing::warn!(error = %e, "failed to send dial command");
-                                    }
+                                }
+                                let p2p = p2p_state_discovery.read().await;
+                                if let Err(e) = p2p.dial(addr).await {
+                                    tracing::warn!(error = %e, "failed to send dial command");
                                 }
                             }
                         }
@@ -195,158 +349,27 @@ async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
                     }
                 }
 
-                // Wait before next discovery (30 seconds)
-                tokio::time::sleep(std::time::Duration::from_secs(30)).await;
+                // Registry addresses only seed the DHT; Kademlia finds further peers and
+                // provider records itself, so the contract is polled infrequently.
+                tokio::time::sleep(std::time::Duration::from_secs(300)).await;
             }
         });
     }
 
-    // Register as storage node and publish P2P/endpoint in smart contract (if gRPC enabled)
-    if config.massa_grpc_url.is_some() {
-        let p2p_state_clone = p2p_state.clone();
-        let public_endpoint = config.public_endpoint.clone();
-        // When binding to 0.0.0.0 (or other local endpoint), use local network IP for contract if discoverable
-        let endpoint_for_contract = if is_local_endpoint(&public_endpoint) {
-            match try_local_network_endpoint(&config.bind_address) {
-                Some(discovered) => {
-                    tracing::info!(
-                        bind = %config.bind_address,
-                        endpoint = %discovered,
-                        "endpoint is local; using discovered local network IP for contract"
-                    );
-                    discovered
-                }
-                None => {
-                    tracing::info!(
-                        endpoint = %public_endpoint,
-                        "endpoint is local (0.0.0.0 / 127.0.0.1 / localhost); could not discover local network IP; not storing endpoint in contract metadata"
-                    );
-                    String::new()
-                }
-            }
-        } else {
-            public_endpoint.clone()
-        };
-        let massa_client_reg = massa_client.clone();
-        let massa_address = config.massa_address.clone();
-        let storage_limit_gb = config.storage_limit_gb;
-        tokio::spawn(async move {
-            // Wait for P2P to get its addresses with exponential backoff
-            let mut backoff = std::time::Duration::from_millis(500);
-            let max_backoff = std::time::Duration::from_secs(8);
-            let deadline = tokio::time::Instant::now() + std::time::Duration::from_secs(60);
-
-            let multiaddrs = loop {
-                let p2p = p2p_state_clone.read().await;
-                let peer_id = p2p.local_peer_id.to_string();
-                let addrs: Vec<String> = p2p
-                    .listen_addrs
-                    .iter()
-                    .map(|a| format!("{}/p2p/{}", a, peer_id))
-                    .collect();
-                drop(p2p);
-
-                if !addrs.is_empty() {
-                    break addrs;
-                }
-
-                if tokio::time::Instant::now() >= deadline {
-                    tracing::warn!(
-                        "timed out waiting for P2P addresses; skipping registration"
-                    );
-                    return;
-                }
-
-                tracing::debug!(?backoff, "waiting for P2P addresses");
-                tokio::time::sleep(backoff).await;
-                backoff = std::cmp::min(backoff * 2, max_backoff);
-            };
-
-            let registered = match massa_client_reg.is_node_registered(&massa_address).await {
-                Ok(r) => r,
-                Err(e) => {
-                    tracing::error!(
-                        error = %e,
-                        "failed to check if node is registered; skipping registration"
-                    );
-                    return;
-                }
-            };
-
-            if !registered {
-                tracing::info!(
-                    allocated_gb = storage_limit_gb,
-                    endpoint = %endpoint_for_contract,
-                    "node not yet registered; calling registerStorageNode"
-                );
-                match massa_client_reg
-                    .register_storage_node(storage_limit_gb, &endpoint_for_contract, &multiaddrs)
-                    .await
-                {
-                    Ok(op_id) => {
-                        tracing::info!(
-                            operation_id = %op_id,
-                            "provider registration succeeded (registerStorageNode sent)"
-                        );
-                    }
-                    Err(e) => {
-                        tracing::error!(
-                            error = %e,
-                            "provider registration failed (registerStorageNode)"
-                        );
-                    }
-                }
-            } else {
-                // Already registered: update metadata only if it changed
-                let metadata_needs_update = match massa_client_reg
-                    .get_provider_metadata(&massa_address)
-                    .await
-                {
-                    Ok(current) => {
-                        let endpoint_diff = current.endpoint != endpoint_for_contract;
-                        let current_p2p: std::collections::HashSet<_> =
-                            current.p2p_addrs.iter().collect();
-                        let wanted_p2p: std::collections::HashSet<_> =
-                            multiaddrs.iter().collect();
-                        let p2p_diff = current_p2p != wanted_p2p;
-                        endpoint_diff || p2p_diff
-                    }
-                    Err(_) => true, // could not read current metadata, assume update needed
-                };
-
-                if !metadata_needs_update {
-                    tracing::info!(
-                        "node already registered; provider metadata unchanged, skipping update"
-                    );
-                } else {
-                    tracing::info!(
-                        addrs = ?multiaddrs,
-                        endpoint = %endpoint_for_contract,
-                        "node already registered; updating P2P addresses and endpoint"
-                    );
-                    match massa_client_reg
-                        .update_provider_metadata(&endpoint_for_contract, &multiaddrs)
-                        .await
-                    {
-                        Ok(op_id) => {
-                            tracing::info!(
-                                operation_id = %op_id,
-                                endpoint = %endpoint_for_contract,
-                                "provider metadata update succeeded"
-                            );
-                        }
-                        Err(e) => {
-                            tracing::error!(
-                                error = %e,
-                                endpoint = %endpoint_for_contract,
-                                "provider metadata update failed"
-                            );
-                        }
-                    }
-                }
-            }
-        });
-    }
+    // Register as storage node and keep its allocation, endpoint and P2P addresses in line
+    // with the contract (if gRPC enabled). A draining node leaves the registry instead.
+    let provider_metadata = (massa_client.has_grpc() && !draining).then(|| {
+        provider_metadata::spawn(
+            massa_client.clone(),
+            operations.clone(),
+            p2p_state.clone(),
+            config.massa_address.clone(),
+            config.storage_limit_gb,
+            config.public_endpoint.clone(),
+            config.bind_address.clone(),
+            config.provider_metadata,
+        )
+    });
 
     // Start HTTP server
     let app = router(
@@ -355,6 +378,16 @@ async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
         p2p_discovered_addrs,
         Some(p2p_state),
         Some(massa_client),
+        Some(operations),
+        usage_outbox,
+        rewards,
+        drain,
+        provider_metadata,
+        Some(replication),
+        scrub,
+        bandwidth,
+        read_through,
+        config.admin_token.clone(),
     )
     .layer(
         CorsLayer::new()
@@ -365,6 +398,11 @@ async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
 
     let listener = tokio::net::TcpListener::bind(&config.bind_address).await?;
     tracing::info!("HTTP server on http://{}", config.bind_address);
-    axum::serve(listener, app).await?;
+    // Client addresses are needed for per-client bandwidth accounting
+    axum::serve(
+        listener,
+        app.into_make_service_with_connect_info::<std::net::SocketAddr>(),
+    )
+    .await?;
     Ok(())
 }
@@ -1,12 +1,19 @@
 //! Massa gRPC client for smart contract interactions.
 //!
 //! Provides:
-//! - Smart contract calls (write operations)
+//! - Smart contract calls (write operations), pipelined over one `send_operations` stream
 //! - Datastore reads
 //! - Balance queries
+//! - Finalized execution events (new slot execution outputs stream)
 //! - Keypair generation and address utilities
 
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{HashMap, VecDeque};
+use std::hash::{Hash, Hasher};
 use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 
 use anyhow::{Context, Error, Result};
 use massa_models::{
@@ -16,30 +23,61 @@ use massa_models::{
     secure_share::{SecureShareContent, SecureShareSerializer},
 };
 use massa_proto_rs::massa::api::v1::{
-    execution_query_request_item, execution_query_response, execution_query_response_item,
-    public_service_client::PublicServiceClient, AddressBalanceCandidate,
+    execution_event_filter, execution_query_request_item, execution_query_response,
+    execution_query_response_item, new_slot_execution_outputs_filter,
+    public_service_client::PublicServiceClient, AddressBalanceCandidate, ExecutionEventFilter,
     ExecutionQueryRequestItem, GetDatastoreEntriesRequest, GetStatusRequest,
-    QueryStateRequest, SendOperationsRequest, get_datastore_entry_filter,
-    send_operations_response,
+    NewSlotExecutionOutputsFilter, NewSlotExecutionOutputsRequest,
+    NewSlotExecutionOutputsResponse, QueryStateRequest, SendOperationsRequest,
+    SendOperationsResponse, get_datastore_entry_filter, send_operations_response,
 };
-use massa_proto_rs::massa::model::v1::AddressKeyEntry;
+use massa_proto_rs::massa::model::v1::{AddressKeyEntry, Empty, ExecutionOutputStatus};
 use massa_serialization::Serializer;
 use massa_signature::KeyPair;
-use tokio::sync::mpsc;
+use tokio::sync::{mpsc, oneshot};
 use tokio_stream::wrappers::ReceiverStream;
 use tonic::transport::Channel;
 
 /// Chain ID for transaction signing
-#[derive(Debug, Clone, Copy)]
-#[allow(dead_code)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum ChainId {
-    Mainnet = 0,
-    Buildnet = 77658366,
+    Mainnet,
+    Buildnet,
+    Sandbox,
+    /// Any other network, by its numeric chain id.
+    Custom(u64),
 }
 
 impl ChainId {
     pub fn to_u64(self) -> u64 {
-        self as u64
+        match self {
+            ChainId::Mainnet => 77658377,
+            ChainId::Buildnet => 77658366,
+            ChainId::Sandbox => 77,
+            ChainId::Custom(id) => id,
+        }
+    }
+
+    /// `mainnet`, `buildnet`, `sandbox` or a numeric chain id.
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "mainnet" => Some(ChainId::Mainnet),
+            "buildnet" => Some(ChainId::Buildnet),
+            "sandbox" => Some(ChainId::Sandbox),
+            other => other.parse::<u64>().ok().map(ChainId::Custom),
+        }
+    }
+}
+
+impl std::fmt::Display for ChainId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            ChainId::Mainnet => "mainnet",
+            ChainId::Buildnet => "buildnet",
+            ChainId::Sandbox => "sandbox",
+            ChainId::Custom(_) => "chain",
+        };
+        write!(f, "{} {}", name, self.to_u64())
     }
 }
 
@@ -79,129 +117,345 @@ pub fn parse_address(address: &str) -> Result<Address> {
 // gRPC Client
 // ============================================================================
 
-/// gRPC client for Massa smart contract calls
+/// Periods an operation stays valid after the current one.
+const EXPIRE_PERIODS: u64 = 10;
+/// A cached period older than this is refreshed with `get_status`.
+const PERIOD_MAX_AGE: Duration = Duration::from_secs(30);
+/// Requests written to the operation stream before senders wait for the node.
+const OPERATION_STREAM_DEPTH: usize = 64;
+
+/// One smart contract call of a [`GrpcClient::send_calls`] submission.
 #[derive(Debug, Clone)]
+pub struct ScCall {
+    pub contract_address: String,
+    pub function_name: String,
+    pub args: Vec<u8>,
+    pub fee: Amount,
+    pub max_gas: u64,
+    pub coins: Amount,
+}
+
+impl ScCall {
+    /// Hash of everything in the operation except its expire period.
+    fn content_key(&self) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        self.contract_address.hash(&mut hasher);
+        self.function_name.hash(&mut hasher);
+        self.args.hash(&mut hasher);
+        self.fee.to_raw().hash(&mut hasher);
+        self.max_gas.hash(&mut hasher);
+        self.coins.to_raw().hash(&mut hasher);
+        hasher.finish()
+    }
+}
+
+/// Expire periods given to the operations of one client.
+///
+/// Massa operations have no nonce: two identical calls signed with the same expire period
+/// are the same operation, and the node keeps only one. An identical call sent while the
+/// first is still valid gets the next free expire period instead.
+#[derive(Debug, Default)]
+struct Expiries {
+    /// Content key of a call and the last expire period it was given.
+    used: HashMap<u64, u64>,
+}
+
+impl Expiries {
+    fn allocate(&mut self, key: u64, current_period: u64) -> u64 {
+        self.used.retain(|_, expire| *expire >= current_period);
+        let wanted = current_period + EXPIRE_PERIODS;
+        let expire = match self.used.get(&key) {
+            Some(&last) if last >= wanted => last + 1,
+            _ => wanted,
+        };
+        self.used.insert(key, expire);
+        expire
+    }
+}
+
+type OperationReply = oneshot::Sender<Result<Vec<String>>>;
+
+/// Open `send_operations` stream. The node answers requests in the order they were
+/// written, so each response goes to the oldest waiting sender.
+struct OperationStream {
+    requests: mpsc::Sender<SendOperationsRequest>,
+    waiting: Arc<std::sync::Mutex<VecDeque<OperationReply>>>,
+    closed: Arc<AtomicBool>,
+}
+
+impl OperationStream {
+    async fn open(client: &mut PublicServiceClient<Channel>) -> Result<Self> {
+        let (tx, rx) = mpsc::channel(OPERATION_STREAM_DEPTH);
+        let responses = client
+            .send_operations(tonic::Request::new(ReceiverStream::new(rx)))
+            .await
+            .context("Failed to send operation")?
+            .into_inner();
+        let waiting = Arc::new(std::sync::Mutex::new(VecDeque::new()));
+        let closed = Arc::new(AtomicBool::new(false));
+        tokio::spawn(read_operation_responses(responses, waiting.clone(), closed.clone()));
+        Ok(Self {
+            requests: tx,
+            waiting,
+            closed,
+        })
+    }
+}
+
+/// The operation stream ended after a request was written to it: the node may or may not
+/// have received the operations.
+#[derive(Debug)]
+pub struct StreamError(String);
+
+impl std::fmt::Display for StreamError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "operation stream lost: {}", self.0)
+    }
+}
+
+impl std::error::Error for StreamError {}
+
+/// Hand each response of the stream to its sender. When the stream ends, senders still
+/// waiting get a [`StreamError`]: their operations may or may not have reached the node.
+async fn read_operation_responses(
+    mut responses: tonic::Streaming<SendOperationsResponse>,
+    waiting: Arc<std::sync::Mutex<VecDeque<OperationReply>>>,
+    closed: Arc<AtomicBool>,
+) {
+    let end = loop {
+        let result = match responses.message().await {
+            Ok(Some(response)) => match response.result {
+                Some(send_operations_response::Result::OperationIds(ops)) => Ok(ops.operation_ids),
+                Some(send_operations_response::Result::Error(e)) => {
+                    Err(Error::msg(format!("Operation error: {:?}", e)))
+                }
+                None => Err(Error::msg("No result")),
+            },
+            Ok(None) => break Error::msg("operation stream closed by the node"),
+            Err(status) => break Error::new(status),
+        };
+        match waiting.lock().unwrap().pop_front() {
+            Some(reply) => {
+                let _ = reply.send(result);
+            }
+            None => tracing::warn!("send_operations response without a pending request"),
+        }
+    };
+    closed.store(true, Ordering::SeqCst);
+    let end = format!("{:#}", end);
+    for reply in waiting.lock().unwrap().drain(..) {
+        let _ = reply.send(Err(Error::new(StreamError(end.clone()))));
+    }
+}
+
+/// State shared by the clones of a [`GrpcClient`].
+struct Shared {
+    /// Replaced when the node closes it.
+    operations: tokio::sync::Mutex<Option<OperationStream>>,
+    /// Last period seen on the slot subscription or from `get_status`, and when.
+    period: Arc<std::sync::Mutex<Option<(u64, Instant)>>>,
+    expiries: std::sync::Mutex<Expiries>,
+    /// Keeps the request side of the slot subscription open.
+    _slots: Option<mpsc::Sender<NewSlotExecutionOutputsRequest>>,
+}
+
+/// gRPC client for Massa smart contract calls. Clones share one operation stream, so
+/// concurrent calls are pipelined instead of waiting for each other.
+#[derive(Clone)]
 pub struct GrpcClient {
     client: PublicServiceClient<Channel>,
     keypair: KeyPair,
     chain_id: ChainId,
+    shared: Arc<Shared>,
 }
 
 impl GrpcClient {
     /// Create a new gRPC client
     pub async fn new(grpc_url: &str, private_key: &str, chain_id: ChainId) -> Result<Self> {
-        let client = PublicServiceClient::connect(grpc_url.to_string())
+        let mut client = PublicServiceClient::connect(grpc_url.to_string())
             .await
             .context("Failed to connect to gRPC")?;
 
         let keypair =
             KeyPair::from_str(private_key).map_err(|e| Error::msg(format!("Invalid key: {}", e)))?;
 
+        let period = Arc::new(std::sync::Mutex::new(None));
+        let slots = match subscribe_periods(&mut client, period.clone()).await {
+            Ok(slots) => Some(slots),
+            Err(e) => {
+                tracing::warn!(url = %grpc_url, error = %format!("{:#}", e), "slot subscription failed; reading the period with get_status");
+                None
+            }
+        };
+
         Ok(Self {
             client,
             keypair,
             chain_id,
+            shared: Arc::new(Shared {
+                operations: tokio::sync::Mutex::new(None),
+                period,
+                expiries: std::sync::Mutex::new(Expiries::default()),
+                _slots: slots,
+            }),
         })
     }
 
-    /// Get current period + buffer for transaction expiry
-    pub async fn get_expire_period(&mut self) -> Result<u64> {
-        let response = self
-            .client
-            .get_status(GetStatusRequest {})
-            .await
-            .context("Failed to get status")?
-            .into_inner();
-
-        let status = response.status.context("No status in response")?;
-        let last_slot = status
-            .last_executed_speculative_slot
-            .context("No last slot")?;
+    /// Current period: cached from the slot subscription, read with `get_status` when stale.
+    pub async fn current_period(&self) -> Result<u64> {
+        if let Some((period, seen)) = *self.shared.period.lock().unwrap() {
+            if seen.elapsed() < PERIOD_MAX_AGE {
+                return Ok(period);
+            }
+        }
+        let period = self.get_status().await?.current_period;
+        *self.shared.period.lock().unwrap() = Some((period, Instant::now()));
+        Ok(period)
+    }
 
-        // Add 10 periods buffer
-        Ok(last_slot.period + 10)
+    /// Get current period + buffer for transaction expiry
+    #[allow(dead_code)]
+    pub async fn get_expire_period(&self) -> Result<u64> {
+        Ok(self.current_period().await? + EXPIRE_PERIODS)
     }
 
-    /// Call a smart contract function
+    /// Call a smart contract function. Returns the operation id and the period after
+    /// which the operation can no longer be included.
+    #[allow(dead_code)]
     pub async fn call_sc(
-        &mut self,
+        &self,
         contract_address: &str,
         function_name: &str,
         args: Vec<u8>,
         fee: &str,
         max_gas: u64,
         coins: Amount,
-    ) -> Result<String> {
-        let expire_period = self.get_expire_period().await?;
-
-        let operation = Operation {
+    ) -> Result<SentOperation> {
+        let call = ScCall {
+            contract_address: contract_address.to_string(),
+            function_name: function_name.to_string(),
+            args,
             fee: Amount::from_str(fee).context("Invalid fee")?,
-            expire_period,
-            op: OperationType::CallSC {
-                target_addr: Address::from_str(contract_address).context("Invalid address")?,
-                target_func: function_name.to_string(),
-                param: args,
-                max_gas,
-                coins,
-            },
+            max_gas,
+            coins,
         };
+        self.send_calls(std::slice::from_ref(&call))
+            .await?
+            .pop()
+            .context("No operation ID")
+    }
 
-        // Sign the operation
-        let secured: SecureShareOperation = Operation::new_verifiable(
-            operation,
-            OperationSerializer::new(),
-            &self.keypair,
-            self.chain_id.to_u64(),
-        )
-        .context("Failed to sign operation")?;
-
-        // Serialize
-        let mut serialized = Vec::new();
-        SecureShareSerializer::new()
-            .serialize(&secured, &mut serialized)
-            .context("Failed to serialize")?;
-
-        // Send via streaming RPC
-        let (tx, rx) = mpsc::channel(1);
-        let request = tonic::Request::new(ReceiverStream::new(rx));
+    /// Sign `calls` and send them in one request on the shared operation stream. Returns
+    /// one [`SentOperation`] per call, in order.
+    ///
+    /// Errors with a [`StreamError`] once the request was written: the node may have
+    /// received it. Earlier failures leave a transport error or an `Unavailable` status.
+    pub async fn send_calls(&self, calls: &[ScCall]) -> Result<Vec<SentOperation>> {
+        let signed = self.sign_calls(calls).await?;
+        self.send_signed(signed).await
+    }
 
-        let response = self
-            .client
-            .send_operations(request)
-            .await
-            .context("Failed to send operation")?;
+    /// Sign `calls` without sending them, so their ids are known before they reach a node.
+    pub async fn sign_calls(&self, calls: &[ScCall]) -> Result<SignedCalls> {
+        if calls.is_empty() {
+            return Ok(SignedCalls::default());
+        }
+        let current_period = self.current_period().await?;
+
+        let mut operations = Vec::with_capacity(calls.len());
+        let mut sent = Vec::with_capacity(calls.len());
+        for call in calls {
+            let expire_period = self
+                .shared
+                .expiries
+                .lock()
+                .unwrap()
+                .allocate(call.content_key(), current_period);
+            let operation = Operation {
+                fee: call.fee,
+                expire_period,
+                op: OperationType::CallSC {
+                    target_addr: Address::from_str(&call.contract_address).context("Invalid address")?,
+                    target_func: call.function_name.clone(),
+                    param: call.args.clone(),
+                    max_gas: call.max_gas,
+                    coins: call.coins,
+                },
+            };
+
+            // Sign the operation
+            let secured: SecureShareOperation = Operation::new_verifiable(
+                operation,
+                OperationSerializer::new(),
+                &self.keypair,
+                self.chain_id.to_u64(),
+            )
+            .context("Failed to sign operation")?;
+
+            // Serialize
+            let mut serialized = Vec::new();
+            SecureShareSerializer::new()
+                .serialize(&secured, &mut serialized)
+                .context("Failed to serialize")?;
+            operations.push(serialized);
+            sent.push(SentOperation {
+                id: secured.id.to_string(),
+                expire_period,
+            });
+        }
+        Ok(SignedCalls { operations, sent })
+    }
 
-        tx.send(SendOperationsRequest {
-            operations: vec![serialized],
-        })
-        .await
-        .context("Failed to send to channel")?;
-
-        // Get operation ID from response
-        let mut stream = response.into_inner();
-        use tokio_stream::StreamExt;
-
-        while let Some(res) = stream.next().await {
-            let result = res
-                .context("Stream error")?
-                .result
-                .context("No result")?;
-
-            match result {
-                send_operations_response::Result::OperationIds(ops) => {
-                    return ops
-                        .operation_ids
-                        .first()
-                        .cloned()
-                        .context("No operation ID");
-                }
-                send_operations_response::Result::Error(e) => {
-                    return Err(Error::msg(format!("Operation error: {:?}", e)));
-                }
-            }
+    /// Send operations signed with [`GrpcClient::sign_calls`], with the same errors as
+    /// [`GrpcClient::send_calls`].
+    pub async fn send_signed(&self, signed: SignedCalls) -> Result<Vec<SentOperation>> {
+        if signed.operations.is_empty() {
+            return Ok(Vec::new());
         }
+        let ids = self.send_operations(signed.operations).await?;
+        if ids.len() != signed.sent.len() {
+            return Err(Error::msg(format!(
+                "Expected {} operation IDs, got {}",
+                signed.sent.len(),
+                ids.len()
+            )));
+        }
+        Ok(ids
+            .into_iter()
+            .zip(signed.sent)
+            .map(|(id, sent)| SentOperation {
+                id,
+                expire_period: sent.expire_period,
+            })
+            .collect())
+    }
 
-        Err(Error::msg("No response from stream"))
+    /// Write serialized operations to the operation stream (opening it if needed) and wait
+    /// for their ids.
+    async fn send_operations(&self, operations: Vec<Vec<u8>>) -> Result<Vec<String>> {
+        let (reply, response) = oneshot::channel();
+        {
+            let mut stream = self.shared.operations.lock().await;
+            if stream.as_ref().is_none_or(|s| s.closed.load(Ordering::SeqCst)) {
+                *stream = Some(OperationStream::open(&mut self.client.clone()).await?);
+            }
+            let stream = stream.as_ref().expect("opened above");
+            // Queued before writing so the response cannot arrive first; both happen under
+            // the lock so the queue keeps the order of the requests.
+            stream.waiting.lock().unwrap().push_back(reply);
+            if stream
+                .requests
+                .send(SendOperationsRequest { operations })
+                .await
+                .is_err()
+            {
+                stream.closed.store(true, Ordering::SeqCst);
+                return Err(Error::new(tonic::Status::unavailable("operation stream closed")));
+            }
+        }
+        response
+            .await
+            .map_err(|_| Error::new(StreamError("operation stream dropped".to_string())))?
     }
 
     /// Get the address associated with this client's keypair
@@ -210,9 +464,8 @@ impl GrpcClient {
         address_from_keypair(&self.keypair)
     }
 
-    /// Get MAS balance for an address
-    #[allow(dead_code)]
-    pub async fn get_balance(&mut self, address: &str) -> Result<f64> {
+    /// Candidate MAS balance of an address
+    pub async fn get_balance(&self, address: &str) -> Result<Amount> {
         let request = tonic::Request::new(QueryStateRequest {
             queries: vec![ExecutionQueryRequestItem {
                 request_item: Some(
@@ -227,6 +480,7 @@ impl GrpcClient {
 
         let response = self
             .client
+            .clone()
             .query_state(request)
             .await
             .context("Failed to query state")?
@@ -244,9 +498,8 @@ impl GrpcClient {
             if let Some(execution_query_response_item::ResponseItem::Amount(amount)) =
                 &item.response_item
             {
-                // Convert from nanoMAS to MAS
-                let nanos = amount.mantissa as f64 * 10f64.powi(-(amount.scale as i32));
-                return Ok(nanos);
+                return Amount::from_mantissa_scale(amount.mantissa, amount.scale)
+                    .map_err(|e| Error::msg(format!("Invalid balance: {}", e)));
             }
         }
 
@@ -255,7 +508,7 @@ impl GrpcClient {
 
     /// Read a value from contract datastore
     #[allow(dead_code)]
-    pub async fn read_datastore(&mut self, address: &str, key: &[u8]) -> Result<Option<Vec<u8>>> {
+    pub async fn read_datastore(&self, address: &str, key: &[u8]) -> Result<Option<Vec<u8>>> {
         let request = GetDatastoreEntriesRequest {
             filters: vec![massa_proto_rs::massa::api::v1::GetDatastoreEntryFilter {
                 filter: Some(get_datastore_entry_filter::Filter::AddressKey(
@@ -269,6 +522,7 @@ impl GrpcClient {
 
         let response = self
             .client
+            .clone()
             .get_datastore_entries(request)
             .await
             .context("Failed to read datastore")?
@@ -287,10 +541,10 @@ impl GrpcClient {
     }
 
     /// Get network status (version, current period, etc.)
-    #[allow(dead_code)]
-    pub async fn get_status(&mut self) -> Result<NetworkStatus> {
+    pub async fn get_status(&self) -> Result<NetworkStatus> {
         let response = self
             .client
+            .clone()
             .get_status(GetStatusRequest {})
             .await
             .context("Failed to get status")?
@@ -312,6 +566,22 @@ impl GrpcClient {
     }
 }
 
+/// Operation accepted by the node.
+#[derive(Debug, Clone)]
+pub struct SentOperation {
+    pub id: String,
+    /// Last period in which the operation can be included in a block.
+    pub expire_period: u64,
+}
+
+/// Operations signed by [`GrpcClient::sign_calls`] and not sent yet.
+#[derive(Debug, Default)]
+pub struct SignedCalls {
+    operations: Vec<Vec<u8>>,
+    /// Id and expire period of each operation, in call order.
+    pub sent: Vec<SentOperation>,
+}
+
 /// Network status information
 #[derive(Debug, Clone)]
 #[allow(dead_code)]
@@ -320,3 +590,171 @@ pub struct NetworkStatus {
     pub current_period: u64,
     pub current_thread: u32,
 }
+
+// ============================================================================
+// Execution events
+// ============================================================================
+
+/// Smart contract event emitted during the execution of a slot.
+#[derive(Debug, Clone)]
+pub struct ScOutputEvent {
+    pub period: u64,
+    pub thread: u32,
+    /// Position of the event among the slot's events.
+    pub index_in_slot: u64,
+    /// Address of the contract that emitted the event (last entry of the call stack).
+    pub emitter: Option<String>,
+    pub operation_id: Option<String>,
+    pub is_error: bool,
+    pub data: String,
+}
+
+/// Follow candidate slots to keep `period` current. Slot outputs are requested without
+/// events; only their slot is read. Returns the request side, which keeps the stream open.
+async fn subscribe_periods(
+    client: &mut PublicServiceClient<Channel>,
+    period: Arc<std::sync::Mutex<Option<(u64, Instant)>>>,
+) -> Result<mpsc::Sender<NewSlotExecutionOutputsRequest>> {
+    let (tx, rx) = mpsc::channel(1);
+    tx.send(NewSlotExecutionOutputsRequest {
+        filters: vec![
+            NewSlotExecutionOutputsFilter {
+                filter: Some(new_slot_execution_outputs_filter::Filter::Status(
+                    ExecutionOutputStatus::Candidate as i32,
+                )),
+            },
+            NewSlotExecutionOutputsFilter {
+                filter: Some(new_slot_execution_outputs_filter::Filter::EventFilter(
+                    ExecutionEventFilter {
+                        filter: Some(execution_event_filter::Filter::None(Empty {})),
+                    },
+                )),
+            },
+        ],
+    })
+    .await
+    .context("Failed to send to channel")?;
+
+    let mut responses = client
+        .new_slot_execution_outputs(tonic::Request::new(ReceiverStream::new(rx)))
+        .await
+        .context("Failed to subscribe to execution outputs")?
+        .into_inner();
+
+    tokio::spawn(async move {
+        while let Ok(Some(response)) = responses.message().await {
+            if let Some(slot) = response.output.and_then(|o| o.execution_output).and_then(|o| o.slot) {
+                *period.lock().unwrap() = Some((slot.period, Instant::now()));
+            }
+        }
+    });
+    Ok(tx)
+}
+
+/// Stream of the events `emitter` emits in finalized slots.
+pub struct EventStream {
+    responses: tonic::Streaming<NewSlotExecutionOutputsResponse>,
+    /// Keeps the request side of the bidirectional stream open.
+    _requests: mpsc::Sender<NewSlotExecutionOutputsRequest>,
+}
+
+/// Subscribe to the finalized execution outputs of the node, filtered on events of `emitter`.
+pub async fn subscribe_final_events(grpc_url: &str, emitter: &str) -> Result<EventStream> {
+    let mut client = PublicServiceClient::connect(grpc_url.to_string())
+        .await
+        .context("Failed to connect to gRPC")?;
+
+    let (tx, rx) = mpsc::channel(1);
+    tx.send(NewSlotExecutionOutputsRequest {
+        filters: vec![
+            NewSlotExecutionOutputsFilter {
+                filter: Some(new_slot_execution_outputs_filter::Filter::Status(
+                    ExecutionOutputStatus::Final as i32,
+                )),
+            },
+            NewSlotExecutionOutputsFilter {
+                filter: Some(new_slot_execution_outputs_filter::Filter::EventFilter(
+                    ExecutionEventFilter {
+                        filter: Some(execution_event_filter::Filter::EmitterAddress(
+                            emitter.to_string(),
+                        )),
+                    },
+                )),
+            },
+        ],
+    })
+    .await
+    .context("Failed to send to channel")?;
+
+    let responses = client
+        .new_slot_execution_outputs(tonic::Request::new(ReceiverStream::new(rx)))
+        .await
+        .context("Failed to subscribe to execution outputs")?
+        .into_inner();
+
+    Ok(EventStream {
+        responses,
+        _requests: tx,
+    })
+}
+
+impl EventStream {
+    /// Events of the next finalized slot; `None` when the node closed the stream.
+    pub async fn next_slot(&mut self) -> Result<Option<Vec<ScOutputEvent>>> {
+        let Some(response) = self.responses.message().await.context("Stream error")? else {
+            return Ok(None);
+        };
+        let Some(output) = response.output.and_then(|o| o.execution_output) else {
+            return Ok(Some(Vec::new()));
+        };
+        let (period, thread) = output.slot.map(|s| (s.period, s.thread)).unwrap_or_default();
+
+        let events = output
+            .events
+            .into_iter()
+            .map(|event| {
+                let context = event.context.unwrap_or_default();
+                ScOutputEvent {
+                    period,
+                    thread,
+                    index_in_slot: context.index_in_slot,
+                    emitter: context.call_stack.last().cloned(),
+                    operation_id: Option::<String>::from(context.origin_operation_id)
+                        .filter(|id| !id.is_empty()),
+                    is_error: context.is_failure,
+                    data: String::from_utf8_lossy(event.data.as_ref()).into_owned(),
+                }
+            })
+            .collect();
+        Ok(Some(events))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn chain_ids_parse() {
+        assert_eq!(ChainId::parse("Mainnet").map(ChainId::to_u64), Some(77658377));
+        assert_eq!(ChainId::parse("buildnet"), Some(ChainId::Buildnet));
+        assert_eq!(ChainId::parse("77"), Some(ChainId::Custom(77)));
+        assert_eq!(ChainId::parse("devnet"), None);
+    }
+
+    #[test]
+    fn identical_calls_get_distinct_expire_periods() {
+        let mut expiries = Expiries::default();
+        assert_eq!(expiries.allocate(1, 100), 110);
+        assert_eq!(expiries.allocate(1, 100), 111);
+        assert_eq!(expiries.allocate(2, 100), 110);
+
+        // The period moved on: later expire periods are still taken
+        assert_eq!(expiries.allocate(1, 101), 112);
+        assert_eq!(expiries.allocate(2, 101), 111);
+
+        // Entries are dropped once expired
+        assert_eq!(expiries.allocate(1, 120), 130);
+        assert_eq!(expiries.used.len(), 1);
+    }
+}
@@ -0,0 +1,388 @@
+//! Lifecycle of contract write operations.
+//!
+//! Every write sent through [`MassaClient::call`] returns a [`SubmittedCall`]. The tracker
+//! follows it until it is final or expired:
+//!
+//! - `get_operations` tells whether the operation is in the pool, in a block, final, and
+//!   whether its execution succeeded.
+//! - For a failed execution, the error event of the operation gives the contract's
+//!   assertion message (e.g. "Node not found").
+//! - An operation that is not in a block once the node is past its expire period can no
+//!   longer be included, and is reported as expired. It can be re-sent with a new expiry.
+//!
+//! Callers await the outcome through an [`OperationHandle`]; terminal states are logged.
+
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+use tokio::sync::watch;
+
+use crate::contract::{MassaClient, OperationInfo, SubmittedCall};
+use crate::massa_grpc::ScOutputEvent;
+use crate::registry::RegistryCall;
+use crate::state::now_secs;
+use massa_models::amount::Amount;
+
+/// A period lasts 16 s; polling twice per period keeps latency low without load.
+const POLL_INTERVAL: Duration = Duration::from_secs(8);
+/// Finished operations kept for `GET /admin/operations`.
+const MAX_HISTORY: usize = 256;
+
+/// Where an operation is in its lifecycle.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum OperationStatus {
+    /// Sent, not yet in a block.
+    Pending,
+    /// In a block that is not final yet.
+    Included,
+    /// Final and executed successfully.
+    Executed,
+    /// Final, but the contract call failed.
+    Failed { message: String },
+    /// Not included before its expire period.
+    Expired,
+}
+
+impl OperationStatus {
+    pub fn is_terminal(&self) -> bool {
+        matches!(self, Self::Executed | Self::Failed { .. } | Self::Expired)
+    }
+}
+
+/// Tracked operation, as reported by `GET /admin/operations`.
+#[derive(Debug, Clone, Serialize)]
+pub struct TrackedOperation {
+    pub id: String,
+    pub function: &'static str,
+    pub expire_period: u64,
+    /// Unix time in seconds.
+    pub submitted_at: u64,
+    #[serde(flatten)]
+    pub status: OperationStatus,
+    /// Operation that re-sent this one after it expired.
+    pub replaced_by: Option<String>,
+}
+
+struct Entry {
+    operation: TrackedOperation,
+    call: RegistryCall,
+    coins: Amount,
+    tx: watch::Sender<OperationStatus>,
+}
+
+#[derive(Default)]
+struct Operations {
+    entries: HashMap<String, Entry>,
+    /// Ids in submission order, to drop the oldest finished operations.
+    order: VecDeque<String>,
+}
+
+/// Receives the status changes of one operation.
+pub struct OperationHandle {
+    pub id: String,
+    rx: watch::Receiver<OperationStatus>,
+}
+
+impl OperationHandle {
+    /// Wait until the operation is executed, failed or expired.
+    pub async fn outcome(&mut self) -> OperationStatus {
+        loop {
+            let status = self.rx.borrow_and_update().clone();
+            if status.is_terminal() || self.rx.changed().await.is_err() {
+                return self.rx.borrow().clone();
+            }
+        }
+    }
+}
+
+/// Follows contract write operations until they are final or expired.
+pub struct OperationTracker {
+    registry: Arc<MassaClient>,
+    operations: Mutex<Operations>,
+}
+
+pub type SharedOperations = Arc<OperationTracker>;
+
+/// Start the tracker; it polls the node while operations are pending.
+pub fn spawn(registry: Arc<MassaClient>) -> SharedOperations {
+    let tracker = Arc::new(OperationTracker {
+        registry,
+        operations: Mutex::new(Operations::default()),
+    });
+    let poller = tracker.clone();
+    tokio::spawn(async move {
+        loop {
+            tokio::time::sleep(POLL_INTERVAL).await;
+            if let Err(e) = poller.poll().await {
+                tracing::warn!(error = %e, "failed to poll operation status");
+            }
+        }
+    });
+    tracker
+}
+
+impl OperationTracker {
+    /// Follow an operation sent with [`MassaClient::call`].
+    pub fn track(&self, sent: SubmittedCall) -> OperationHandle {
+        let (tx, rx) = watch::channel(OperationStatus::Pending);
+        let id = sent.operation_id.clone();
+        let operation = TrackedOperation {
+            id: id.clone(),
+            function: sent.call.function(),
+            expire_period: sent.expire_period,
+            submitted_at: now_secs(),
+            status: OperationStatus::Pending,
+            replaced_by: None,
+        };
+        let mut operations = self.operations.lock().unwrap();
+        operations.order.push_back(id.clone());
+        operations.entries.insert(
+            id.clone(),
+            Entry {
+                operation,
+                call: sent.call,
+                coins: sent.coins,
+                tx,
+            },
+        );
+        prune(&mut operations);
+        OperationHandle { id, rx }
+    }
+
+    /// Send an expired operation again; its new expiry is computed from the current period.
+    pub async fn resubmit(&self, id: &str) -> Result<OperationHandle> {
+        let (call, coins) = {
+            let operations = self.operations.lock().unwrap();
+            let entry = operations
+                .entries
+                .get(id)
+                .ok_or_else(|| anyhow!("unknown operation {}", id))?;
+            if entry.operation.status != OperationStatus::Expired {
+                return Err(anyhow!("operation {} has not expired", id));
+            }
+            (entry.call.clone(), entry.coins)
+        };
+        let sent = self.registry.call(&call, coins).await?;
+        tracing::info!(
+            function = call.function(),
+            expired = %id,
+            operation_id = %sent.operation_id,
+            "expired operation re-sent"
+        );
+        if let Some(entry) = self.operations.lock().unwrap().entries.get_mut(id) {
+            entry.operation.replaced_by = Some(sent.operation_id.clone());
+        }
+        Ok(self.track(sent))
+    }
+
+    /// Track `sent` until it is final, re-sending it up to `resubmits` times if it expires.
+    pub async fn follow(&self, sent: SubmittedCall, resubmits: u32) -> Result<OperationStatus> {
+        let mut handle = self.track(sent);
+        let mut left = resubmits;
+        loop {
+            match handle.outcome().await {
+                OperationStatus::Expired if left > 0 => {
+                    left -= 1;
+                    handle = self.resubmit(&handle.id).await?;
+                }
+                status => return Ok(status),
+            }
+        }
+    }
+
+    /// Tracked operations, most recent first.
+    pub fn list(&self) -> Vec<TrackedOperation> {
+        let operations = self.operations.lock().unwrap();
+        operations
+            .order
+            .iter()
+            .rev()
+            .filter_map(|id| operations.entries.get(id))
+            .map(|entry| entry.operation.clone())
+            .collect()
+    }
+
+    async fn poll(&self) -> Result<()> {
+        let pending: Vec<(String, OperationStatus, u64)> = {
+            let operations = self.operations.lock().unwrap();
+            operations
+                .entries
+                .values()
+                .filter(|e| !e.operation.status.is_terminal())
+                .map(|e| (e.operation.id.clone(), e.operation.status.clone(), e.operation.expire_period))
+                .collect()
+        };
+        if pending.is_empty() {
+            return Ok(());
+        }
+
+        let ids: Vec<String> = pending.iter().map(|(id, _, _)| id.clone()).collect();
+        let infos = self.registry.get_operations(&ids).await?;
+        let period = self.registry.get_current_period().await?;
+
+        for (id, current, expire_period) in pending {
+            let mut status = next_status(&current, infos.get(&id), period, expire_period);
+            if let OperationStatus::Failed { message } = &mut status {
+                *message = match self.registry.get_operation_events(&id).await {
+                    Ok(events) => failure_message(&events),
+                    Err(e) => format!("execution failed (error event unavailable: {})", e),
+                };
+            }
+            if status != current {
+                self.update(&id, status);
+            }
+        }
+        Ok(())
+    }
+
+    fn update(&self, id: &str, status: OperationStatus) {
+        let mut operations = self.operations.lock().unwrap();
+        let Some(entry) = operations.entries.get_mut(id) else {
+            return;
+        };
+        let function = entry.operation.function;
+        match &status {
+            OperationStatus::Executed => {
+                tracing::info!(operation_id = %id, function, "operation executed")
+            }
+            OperationStatus::Failed { message } => {
+                tracing::warn!(operation_id = %id, function, message = %message, "operation failed")
+            }
+            OperationStatus::Expired => {
+                tracing::warn!(operation_id = %id, function, "operation expired before inclusion")
+            }
+            _ => tracing::debug!(operation_id = %id, function, ?status, "operation status"),
+        }
+        entry.operation.status = status.clone();
+        entry.tx.send_replace(status);
+        prune(&mut operations);
+    }
+}
+
+/// Drop the oldest finished operations beyond [`MAX_HISTORY`]; pending ones are kept.
+fn prune(operations: &mut Operations) {
+    let finished = operations
+        .entries
+        .values()
+        .filter(|e| e.operation.status.is_terminal())
+        .count();
+    let mut excess = finished.saturating_sub(MAX_HISTORY);
+    let Operations { entries, order } = operations;
+    order.retain(|id| {
+        if excess > 0 && entries.get(id).is_some_and(|e| e.operation.status.is_terminal()) {
+            entries.remove(id);
+            excess -= 1;
+            false
+        } else {
+            true
+        }
+    });
+}
+
+/// Status of an operation given what the node reports and its current period.
+fn next_status(
+    current: &OperationStatus,
+    info: Option<&OperationInfo>,
+    period: u64,
+    expire_period: u64,
+) -> OperationStatus {
+    match info {
+        Some(OperationInfo { is_final: true, executed: Some(true), .. }) => OperationStatus::Executed,
+        Some(OperationInfo { is_final: true, executed: Some(false), .. }) => {
+            OperationStatus::Failed { message: String::new() }
+        }
+        Some(OperationInfo { in_block: true, .. }) => OperationStatus::Included,
+        // Not in a block: dropped from the pool, or still waiting
+        _ if period > expire_period => OperationStatus::Expired,
+        // A block that held it may have been orphaned; it is pending again
+        _ if *current == OperationStatus::Included => OperationStatus::Pending,
+        _ => current.clone(),
+    }
+}
+
+/// The contract's assertion message from the error event of a failed operation, e.g.
+/// `{"massa_execution_error":"... error: Node not found at assembly/contracts/storage-registry.ts:1052 col: 3"}`.
+fn failure_message(events: &[ScOutputEvent]) -> String {
+    let Some(event) = events.iter().rev().find(|e| e.is_error) else {
+        return "execution failed (no error event)".to_string();
+    };
+    let error = serde_json::from_str::<serde_json::Value>(&event.data)
+        .ok()
+        .and_then(|v| v.get("massa_execution_error")?.as_str().map(str::to_string))
+        .unwrap_or_else(|| event.data.clone());
+    let message = error.rsplit_once("error: ").map_or(error.as_str(), |(_, m)| m);
+    let message = message.split(" at assembly/").next().unwrap_or(message).trim();
+    if message.is_empty() {
+        error
+    } else {
+        message.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn info(in_block: bool, is_final: bool, executed: Option<bool>) -> OperationInfo {
+        OperationInfo {
+            in_pool: !in_block,
+            in_block,
+            is_final,
+            executed,
+        }
+    }
+
+    #[test]
+    fn status_follows_inclusion_finality_and_expiry() {
+        use OperationStatus::*;
+        let pending = Pending;
+        assert_eq!(next_status(&pending, None, 100, 110), Pending);
+        assert_eq!(next_status(&pending, Some(&info(false, false, None)), 110, 110), Pending);
+        assert_eq!(next_status(&pending, Some(&info(false, false, None)), 111, 110), Expired);
+        assert_eq!(next_status(&pending, None, 111, 110), Expired);
+
+        // Once in a block, the expire period no longer matters
+        assert_eq!(next_status(&pending, Some(&info(true, false, Some(true))), 120, 110), Included);
+        assert_eq!(next_status(&Included, Some(&info(true, true, Some(true))), 120, 110), Executed);
+        assert_eq!(
+            next_status(&Included, Some(&info(true, true, Some(false))), 120, 110),
+            Failed { message: String::new() }
+        );
+        // A speculative failure waits for finality
+        assert_eq!(next_status(&Included, Some(&info(true, false, Some(false))), 105, 110), Included);
+        // Orphaned block: back to pending, then expired
+        assert_eq!(next_status(&Included, None, 105, 110), Pending);
+        assert_eq!(next_status(&Included, None, 111, 110), Expired);
+    }
+
+    fn error_event(data: &str) -> ScOutputEvent {
+        ScOutputEvent {
+            period: 1,
+            thread: 0,
+            index_in_slot: 0,
+            emitter: None,
+            operation_id: Some("O1".into()),
+            is_error: true,
+            data: data.into(),
+        }
+    }
+
+    #[test]
+    fn extracts_assertion_message() {
+        let event = error_event(
+            r#"{"massa_execution_error":"Runtime error: runtime error when executing operation O1: VM Error in CallSC context: error: Node not found at assembly/contracts/storage-registry.ts:1052 col: 3"}"#,
+        );
+        assert_eq!(failure_message(&[event]), "Node not found");
+
+        let event = error_event("Insufficient coins for booking");
+        assert_eq!(failure_message(&[event]), "Insufficient coins for booking");
+
+        let mut event = error_event("STORAGE_NODE_REGISTERED:AU1");
+        event.is_error = false;
+        assert_eq!(failure_message(&[event]), "execution failed (no error event)");
+    }
+}
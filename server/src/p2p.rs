@@ -2,30 +2,233 @@
 //!
 //! Features:
 //! - QUIC transport (primary) with TCP fallback
-//! - Peer discovery via smart contract registry
+//! - Peer discovery via smart contract registry, which seeds a Kademlia DHT
+//! - Provider records in the DHT keyed by blob content hash (who stores what)
 //! - Ping for connectivity testing
 //! - Identify protocol for peer info exchange
 //! - Track connected peers
+//! - Peer attestation binding each PeerId to a registered Massa provider address
+//! - Blob transfer protocol (full copies and erasure-coded shards) for replication
+//! - NAT traversal: AutoNAT reachability probes, circuit relay v2 (client and server) and
+//!   DCUtR hole punching; only confirmed external addresses are published
+//! - Optional mDNS discovery of nodes on the local network (development clusters)
+//! - Connection management: inbound/outbound limits, reconnection with exponential backoff,
+//!   temporary bans of misbehaving peers, preference for registered providers
+//! - Peer reputation (latency, transfers, availability, on-chain challenges; see `reputation`)
+//! - Bandwidth metering and rate limits per peer and per blob request kind (see `bandwidth`)
 
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
 use std::sync::Arc;
-use std::sync::RwLock as StdRwLock;
-use std::time::Duration;
+use std::sync::{Mutex as StdMutex, RwLock as StdRwLock};
+use std::time::{Duration, Instant};
 
-use futures::StreamExt;
+use futures::future::BoxFuture;
+use futures::stream::FuturesUnordered;
+use futures::{FutureExt, StreamExt};
 use libp2p::{
-    identify, noise, ping,
+    allow_block_list, autonat, connection_limits, dcutr, identify, kad, mdns, noise, ping, relay,
+    core::transport::ListenerId,
     multiaddr::Protocol,
-    swarm::{NetworkBehaviour, SwarmEvent},
-    tcp, yamux, Multiaddr, PeerId, SwarmBuilder,
+    request_response::{self, ProtocolSupport},
+    swarm::{
+        behaviour::toggle::Toggle,
+        dial_opts::{DialOpts, PeerCondition},
+        NetworkBehaviour, SwarmEvent,
+    },
+    tcp, yamux, Multiaddr, PeerId, StreamProtocol, SwarmBuilder,
 };
-use tokio::sync::{mpsc, RwLock};
+use tokio::sync::{mpsc, oneshot, RwLock};
+
+use crate::attestation::{advertises_peer_id, AttestationError, PeerAttestation};
+use crate::bandwidth::SharedBandwidth;
+use crate::contract::MassaClient;
+use crate::erasure::shard_hash;
+use crate::inventory::{InventoryQuery, InventoryReply, InventorySnapshot};
+use crate::reputation::{PeerStats, Reputation};
+use crate::storage::{write_atomic, BlobMeta, Storage};
+
+/// Protocol name for blob and shard transfers between providers.
+const BLOB_PROTOCOL: &str = "/massa-storage/blob/1.0.0";
+/// Maximum size of a blob transfer message (request or response).
+pub const MAX_BLOB_MESSAGE_BYTES: u64 = 256 * 1024 * 1024;
+/// Protocol name of the providers' Kademlia DHT (kept separate from the public IPFS DHT).
+const KAD_PROTOCOL: &str = "/massa-storage/kad/1.0.0";
+/// Maximum number of blobs this node announces as provider in the DHT.
+const MAX_PROVIDED_KEYS: usize = 1_000_000;
+/// File of the state directory holding the libp2p identity, so the PeerId published in the
+/// registry survives restarts.
+const IDENTITY_FILE: &str = "p2p-identity.key";
+/// Protocol name of the attestation handshake (see `attestation`).
+const ATTEST_PROTOCOL: &str = "/massa-storage/attest/1.0.0";
+/// Registry checks of an attested peer before giving up; a freshly started provider may
+/// not have published its new peer id in the contract yet.
+const REGISTRY_CHECK_ATTEMPTS: u32 = 5;
+const REGISTRY_CHECK_RETRY: Duration = Duration::from_secs(60);
+/// Delay before the handshake is redone with a peer whose registry check could not read the
+/// registry; the peer stays connected but unauthenticated meanwhile.
+const REGISTRY_UNAVAILABLE_RETRY: Duration = Duration::from_secs(300);
+/// Circuit relay v2 hop protocol, advertised via identify by peers that can relay for us.
+const RELAY_HOP_PROTOCOL: &str = "/libp2p/circuit/relay/0.2.0/hop";
+/// Number of relay reservations kept while this node is not publicly reachable.
+const MAX_RELAYS: usize = 2;
+/// Maximum simultaneous connections to a single peer.
+const MAX_CONNECTIONS_PER_PEER: u32 = 2;
+/// First reconnect delay; doubled after each failed dial up to `RECONNECT_MAX_DELAY`.
+const RECONNECT_BASE_DELAY: Duration = Duration::from_secs(5);
+const RECONNECT_MAX_DELAY: Duration = Duration::from_secs(600);
+/// Period of the connection manager (redials, ban expiry, trimming).
+const CONNECTION_TICK: Duration = Duration::from_secs(5);
+/// Share of inbound slots kept free for registered providers.
+const PROVIDER_HEADROOM_PERCENT: usize = 10;
+/// Time an inbound peer has to authenticate before it may be trimmed (covers the
+/// registry check retries).
+const HANDSHAKE_GRACE: Duration = Duration::from_secs(360);
+/// Ban of a peer presenting an invalid attestation.
+const BAN_INVALID_ATTESTATION: Duration = Duration::from_secs(3600);
+/// Ban of a peer whose attested address is not a registered provider.
+const BAN_NOT_REGISTERED: Duration = Duration::from_secs(600);
+/// How often providers' challenge history is re-read from the registry.
+const CHALLENGE_REFRESH: Duration = Duration::from_secs(600);
+/// Statistics of peers not seen for this long are dropped.
+const PEER_STATS_RETENTION: Duration = Duration::from_secs(24 * 3600);
+
+/// Settings of the libp2p node.
+pub struct P2pConfig {
+    /// Listen multiaddrs (TCP and/or QUIC).
+    pub listen_addrs: Vec<String>,
+    /// Operator-declared public addresses, treated as confirmed external addresses.
+    pub external_addrs: Vec<String>,
+    /// Enabled transports; a disabled transport is neither listened on nor dialed.
+    pub tcp: bool,
+    pub quic: bool,
+    /// Massa private key of this provider; signs the peer attestation.
+    pub private_key: String,
+    /// Multiaddrs dialed at startup (bootstrap peers and registry addrs).
+    pub peers_to_dial: Vec<String>,
+    /// Discover nodes on the local network via mDNS. Their attestation is trusted without
+    /// a registry check, so this is meant for development clusters only.
+    pub mdns: bool,
+    /// Maximum inbound and outbound connections.
+    pub max_inbound: u32,
+    pub max_outbound: u32,
+}
 
 /// Combined network behaviour
 #[derive(NetworkBehaviour)]
 struct Behaviour {
     ping: ping::Behaviour,
     identify: identify::Behaviour,
+    blob: request_response::cbor::Behaviour<BlobRequest, BlobResponse>,
+    kad: kad::Behaviour<kad::store::MemoryStore>,
+    attest: request_response::cbor::Behaviour<PeerAttestation, PeerAttestation>,
+    autonat: autonat::v1::Behaviour,
+    relay: relay::Behaviour,
+    relay_client: relay::client::Behaviour,
+    dcutr: dcutr::Behaviour,
+    mdns: Toggle<mdns::tokio::Behaviour>,
+    limits: connection_limits::Behaviour,
+    blocked: allow_block_list::Behaviour<allow_block_list::BlockedPeers>,
+}
+
+/// Reachability of this node from the public internet, as probed by AutoNAT.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, serde::Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Reachability {
+    #[default]
+    Unknown,
+    Public,
+    /// Behind a NAT or firewall; reachable only through relays (and hole punching).
+    Private,
+}
+
+/// Request sent to another provider over the blob protocol.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub enum BlobRequest {
+    /// Store a full copy of a blob (full replication mode).
+    StoreBlob {
+        namespace: String,
+        id: String,
+        #[serde(with = "serde_bytes")]
+        data: Vec<u8>,
+        meta: BlobMeta,
+    },
+    /// Store one shard of an erasure-coded blob. `hash` is the expected Blake3 hash (hex).
+    StoreShard {
+        namespace: String,
+        id: String,
+        index: u8,
+        hash: String,
+        #[serde(with = "serde_bytes")]
+        data: Vec<u8>,
+    },
+    /// Fetch a shard the requesting peer previously stored with `StoreShard`.
+    GetShard {
+        namespace: String,
+        id: String,
+        index: u8,
+    },
+    /// Fetch a full copy of a blob (e.g. to repair a corrupt local copy).
+    GetBlob { namespace: String, id: String },
+    /// Fetch a blob together with its metadata, so the requester can check the data against
+    /// the recorded `content_hash` (read-through retrieval for clients).
+    FetchBlob { namespace: String, id: String },
+    /// Inventory sync query (see `inventory`).
+    Inventory(InventoryQuery),
+}
+
+/// Response to a `BlobRequest`.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub enum BlobResponse {
+    Stored,
+    Shard(#[serde(with = "serde_bytes")] Vec<u8>),
+    Blob(#[serde(with = "serde_bytes")] Vec<u8>),
+    /// Answer to `FetchBlob`.
+    BlobWithMeta {
+        #[serde(with = "serde_bytes")]
+        data: Vec<u8>,
+        meta: BlobMeta,
+    },
+    Inventory(InventoryReply),
+    NotFound,
+    Error(String),
+}
+
+impl BlobRequest {
+    /// Label used for bandwidth accounting.
+    fn kind(&self) -> &'static str {
+        match self {
+            Self::StoreBlob { .. } => "blob/store_blob",
+            Self::StoreShard { .. } => "blob/store_shard",
+            Self::GetShard { .. } => "blob/get_shard",
+            Self::GetBlob { .. } => "blob/get_blob",
+            Self::FetchBlob { .. } => "blob/fetch_blob",
+            Self::Inventory(_) => "blob/inventory",
+        }
+    }
+
+    /// Data bytes carried by the request.
+    fn payload_len(&self) -> u64 {
+        match self {
+            Self::StoreBlob { data, .. } | Self::StoreShard { data, .. } => data.len() as u64,
+            Self::GetShard { .. }
+            | Self::GetBlob { .. }
+            | Self::FetchBlob { .. }
+            | Self::Inventory(_) => 0,
+        }
+    }
+}
+
+impl BlobResponse {
+    /// Data bytes carried by the response.
+    fn payload_len(&self) -> u64 {
+        match self {
+            Self::Shard(data) | Self::Blob(data) | Self::BlobWithMeta { data, .. } => {
+                data.len() as u64
+            }
+            Self::Stored | Self::Inventory(_) | Self::NotFound | Self::Error(_) => 0,
+        }
+    }
 }
 
 /// Connected peer info
@@ -34,27 +237,72 @@ pub struct PeerInfo {
     pub peer_id: String,
     pub addresses: Vec<String>,
     pub agent_version: Option<String>,
+    /// Massa address proven by the peer's attestation.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub massa_address: Option<String>,
+    /// True once the attestation is verified and the registry lists this peer id for
+    /// `massa_address`. Only authenticated peers are treated as providers.
+    pub authenticated: bool,
+    /// Found through mDNS on the local network (authenticated without a registry check).
+    pub locally_discovered: bool,
+    pub direction: ConnectionDirection,
+    /// Filled in by `P2pState::peer_infos`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reputation: Option<Reputation>,
 }
 
 /// Command to send to the P2P task
 pub enum P2pCommand {
     Dial(String), // Multiaddr to dial
+    /// Send a blob protocol request to a peer; the response (or failure) goes to `reply`.
+    BlobRequest {
+        peer: PeerId,
+        request: BlobRequest,
+        reply: oneshot::Sender<Result<BlobResponse, String>>,
+    },
+    /// Announce this node in the DHT as a provider of the blob with this content hash.
+    StartProviding(String),
+    /// Remove this node's provider record of this content hash from the DHT.
+    StopProviding(String),
+    /// Look up the providers of a content hash in the DHT.
+    FindProviders {
+        content_hash: String,
+        reply: oneshot::Sender<Result<Vec<PeerId>, String>>,
+    },
 }
 
 /// Shared state for peer tracking
 pub struct P2pState {
     pub local_peer_id: PeerId,
     pub listen_addrs: Vec<Multiaddr>,
+    /// Confirmed external addresses (AutoNAT probes, relay circuits); the only ones
+    /// registered in the contract.
+    pub external_addrs: Vec<Multiaddr>,
+    pub reachability: Reachability,
     pub connected_peers: HashMap<PeerId, PeerInfo>,
+    pub connections: ConnectionManager,
+    /// Reputation statistics, kept across reconnections.
+    peer_stats: HashMap<PeerId, PeerStats>,
+    bandwidth: SharedBandwidth,
     cmd_tx: mpsc::Sender<P2pCommand>,
 }
 
 impl P2pState {
-    pub fn new(peer_id: PeerId, cmd_tx: mpsc::Sender<P2pCommand>) -> Self {
+    pub fn new(
+        peer_id: PeerId,
+        cmd_tx: mpsc::Sender<P2pCommand>,
+        connections: ConnectionManager,
+        bandwidth: SharedBandwidth,
+    ) -> Self {
         Self {
             local_peer_id: peer_id,
             listen_addrs: Vec::new(),
+            external_addrs: Vec::new(),
+            reachability: Reachability::Unknown,
             connected_peers: HashMap::new(),
+            connections,
+            peer_stats: HashMap::new(),
+            bandwidth,
             cmd_tx,
         }
     }
@@ -63,43 +311,850 @@ impl P2pState {
     pub async fn dial(&self, addr: &str) -> Result<(), mpsc::error::SendError<P2pCommand>> {
         self.cmd_tx.send(P2pCommand::Dial(addr.to_string())).await
     }
+
+    /// True when `peer` is connected and authenticated as a registered provider.
+    pub fn is_authenticated(&self, peer: &PeerId) -> bool {
+        self.connected_peers
+            .get(peer)
+            .is_some_and(|info| info.authenticated)
+    }
+
+    /// Connected peers authenticated as registered providers, best reputation first.
+    pub fn provider_peers(&self) -> Vec<PeerId> {
+        let now = Instant::now();
+        let mut providers: Vec<(PeerId, f64)> = self
+            .connected_peers
+            .iter()
+            .filter(|(_, info)| info.authenticated)
+            .map(|(peer, _)| (*peer, self.reputation_score(peer, now)))
+            .collect();
+        providers.sort_by(|a, b| b.1.total_cmp(&a.1));
+        providers.into_iter().map(|(peer, _)| peer).collect()
+    }
+
+    /// Reputation score of `peer` (0.5 when unknown).
+    pub fn reputation_score(&self, peer: &PeerId, now: Instant) -> f64 {
+        self.peer_stats.get(peer).map_or(0.5, |stats| stats.score(now))
+    }
+
+    /// Connected peers with their current reputation.
+    pub fn peer_infos(&self) -> Vec<PeerInfo> {
+        let now = Instant::now();
+        self.connected_peers
+            .iter()
+            .map(|(peer, info)| PeerInfo {
+                reputation: self.peer_stats.get(peer).map(|stats| stats.reputation(now)),
+                ..info.clone()
+            })
+            .collect()
+    }
+
+    fn stats_mut(&mut self, peer: PeerId) -> &mut PeerStats {
+        self.peer_stats
+            .entry(peer)
+            .or_insert_with(|| PeerStats::new(Instant::now()))
+    }
+
+    /// Count a blob transfer with `peer` that succeeded or failed (e.g. a corrupt copy).
+    pub fn record_transfer(&mut self, peer: PeerId, ok: bool) {
+        self.stats_mut(peer).record_transfer(ok);
+    }
 }
 
 pub type SharedP2pState = Arc<RwLock<P2pState>>;
 
+/// Direction of the first connection to a peer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ConnectionDirection {
+    Inbound,
+    Outbound,
+}
+
+/// A peer the node keeps connected to (bootstrap and registry addrs, authenticated providers).
+struct DialTarget {
+    addrs: Vec<Multiaddr>,
+    /// Consecutive failed dials; reset once connected.
+    failures: u32,
+    /// Next dial attempt while disconnected.
+    next_attempt: Instant,
+    /// Authenticated as a registered provider at least once; dialed first.
+    provider: bool,
+}
+
+struct Ban {
+    until: Instant,
+    reason: String,
+}
+
+/// Connection policy of the node: inbound/outbound limits, reconnection with exponential
+/// backoff, temporary bans of misbehaving peers and preference for registered providers.
+/// Owned by the shared state; the P2P task applies its decisions to the swarm.
+pub struct ConnectionManager {
+    max_inbound: usize,
+    max_outbound: usize,
+    connected: HashMap<PeerId, (ConnectionDirection, Instant)>,
+    targets: HashMap<PeerId, DialTarget>,
+    bans: HashMap<PeerId, Ban>,
+}
+
+/// Connection manager snapshot for `GET /peers`.
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct ConnectionStatus {
+    pub max_inbound: usize,
+    pub max_outbound: usize,
+    pub inbound: usize,
+    pub outbound: usize,
+    /// Dial targets currently disconnected.
+    pub reconnecting: Vec<ReconnectInfo>,
+    pub banned: Vec<BanInfo>,
+}
+
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct ReconnectInfo {
+    pub peer_id: String,
+    pub provider: bool,
+    pub failures: u32,
+    pub next_attempt_secs: u64,
+}
+
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct BanInfo {
+    pub peer_id: String,
+    pub reason: String,
+    pub remaining_secs: u64,
+}
+
+/// Delay before the next dial of a target after `failures` failed attempts.
+fn reconnect_delay(failures: u32) -> Duration {
+    RECONNECT_BASE_DELAY
+        .saturating_mul(1 << failures.min(16))
+        .min(RECONNECT_MAX_DELAY)
+}
+
+impl ConnectionManager {
+    pub fn new(max_inbound: usize, max_outbound: usize) -> Self {
+        Self {
+            max_inbound,
+            max_outbound,
+            connected: HashMap::new(),
+            targets: HashMap::new(),
+            bans: HashMap::new(),
+        }
+    }
+
+    fn count(&self, direction: ConnectionDirection) -> usize {
+        self.connected.values().filter(|(d, _)| *d == direction).count()
+    }
+
+    /// Keep `peer` connected through `addr`. A new target is due immediately; a known one
+    /// keeps its backoff.
+    fn add_target(&mut self, peer: PeerId, addr: Multiaddr, now: Instant) {
+        let target = self.targets.entry(peer).or_insert_with(|| DialTarget {
+            addrs: Vec::new(),
+            failures: 0,
+            next_attempt: now,
+            provider: false,
+        });
+        if !target.addrs.contains(&addr) {
+            target.addrs.push(addr);
+        }
+    }
+
+    /// `peer` authenticated as a registered provider: keep it connected, preferably
+    /// through `addrs` (its identify listen addrs).
+    fn mark_provider(&mut self, peer: PeerId, addrs: Vec<Multiaddr>, now: Instant) {
+        let target = self.targets.entry(peer).or_insert_with(|| DialTarget {
+            addrs: Vec::new(),
+            failures: 0,
+            next_attempt: now,
+            provider: true,
+        });
+        target.provider = true;
+        if !addrs.is_empty() {
+            target.addrs = addrs;
+        }
+    }
+
+    fn connected(&mut self, peer: PeerId, direction: ConnectionDirection, now: Instant) {
+        self.connected.insert(peer, (direction, now));
+        if let Some(target) = self.targets.get_mut(&peer) {
+            target.failures = 0;
+        }
+    }
+
+    fn disconnected(&mut self, peer: &PeerId, now: Instant) {
+        self.connected.remove(peer);
+        if let Some(target) = self.targets.get_mut(peer) {
+            target.next_attempt = now + reconnect_delay(target.failures);
+        }
+    }
+
+    fn dial_failed(&mut self, peer: &PeerId, now: Instant) {
+        if let Some(target) = self.targets.get_mut(peer) {
+            target.failures = target.failures.saturating_add(1);
+            target.next_attempt = now + reconnect_delay(target.failures);
+        }
+    }
+
+    /// Disconnected targets due for a dial, providers first, within the free outbound slots.
+    fn due_dials(&mut self, now: Instant) -> Vec<(PeerId, Vec<Multiaddr>)> {
+        let free = self.max_outbound.saturating_sub(self.count(ConnectionDirection::Outbound));
+        let mut due: Vec<(&PeerId, &DialTarget)> = self
+            .targets
+            .iter()
+            .filter(|(peer, target)| {
+                !self.connected.contains_key(peer)
+                    && !self.bans.contains_key(peer)
+                    && target.next_attempt <= now
+                    && !target.addrs.is_empty()
+            })
+            .collect();
+        due.sort_by_key(|(_, target)| (!target.provider, target.failures));
+        let dials: Vec<(PeerId, Vec<Multiaddr>)> = due
+            .into_iter()
+            .take(free)
+            .map(|(peer, target)| (*peer, target.addrs.clone()))
+            .collect();
+        // Until the dial reports back (connected or failed)
+        for (peer, _) in &dials {
+            if let Some(target) = self.targets.get_mut(peer) {
+                target.next_attempt = now + RECONNECT_MAX_DELAY;
+            }
+        }
+        dials
+    }
+
+    /// Inbound peers to disconnect so that registered providers always find a free slot:
+    /// above the headroom watermark, unauthenticated peers that had time to complete the
+    /// handshake are dropped, oldest first.
+    fn trim_candidates(&self, keep: impl Fn(&PeerId) -> bool, now: Instant) -> Vec<PeerId> {
+        let watermark = self.max_inbound - self.max_inbound * PROVIDER_HEADROOM_PERCENT / 100;
+        let inbound = self.count(ConnectionDirection::Inbound);
+        if inbound <= watermark {
+            return Vec::new();
+        }
+        let mut candidates: Vec<(&PeerId, Instant)> = self
+            .connected
+            .iter()
+            .filter(|(peer, (direction, since))| {
+                *direction == ConnectionDirection::Inbound
+                    && now.saturating_duration_since(*since) >= HANDSHAKE_GRACE
+                    && !keep(peer)
+            })
+            .map(|(peer, (_, since))| (peer, *since))
+            .collect();
+        candidates.sort_by_key(|(_, since)| *since);
+        candidates
+            .into_iter()
+            .take(inbound - watermark)
+            .map(|(peer, _)| *peer)
+            .collect()
+    }
+
+    fn ban(&mut self, peer: PeerId, duration: Duration, reason: String, now: Instant) {
+        self.targets.remove(&peer);
+        self.bans.insert(peer, Ban { until: now + duration, reason });
+    }
+
+    pub fn is_banned(&self, peer: &PeerId) -> bool {
+        self.bans.contains_key(peer)
+    }
+
+    /// Drop bans that have run out and return the peers to unblock.
+    fn expired_bans(&mut self, now: Instant) -> Vec<PeerId> {
+        let expired: Vec<PeerId> = self
+            .bans
+            .iter()
+            .filter(|(_, ban)| ban.until <= now)
+            .map(|(peer, _)| *peer)
+            .collect();
+        for peer in &expired {
+            self.bans.remove(peer);
+        }
+        expired
+    }
+
+    pub fn status(&self) -> ConnectionStatus {
+        let now = Instant::now();
+        ConnectionStatus {
+            max_inbound: self.max_inbound,
+            max_outbound: self.max_outbound,
+            inbound: self.count(ConnectionDirection::Inbound),
+            outbound: self.count(ConnectionDirection::Outbound),
+            reconnecting: self
+                .targets
+                .iter()
+                .filter(|(peer, _)| !self.connected.contains_key(peer))
+                .map(|(peer, target)| ReconnectInfo {
+                    peer_id: peer.to_string(),
+                    provider: target.provider,
+                    failures: target.failures,
+                    next_attempt_secs: target.next_attempt.saturating_duration_since(now).as_secs(),
+                })
+                .collect(),
+            banned: self
+                .bans
+                .iter()
+                .map(|(peer, ban)| BanInfo {
+                    peer_id: peer.to_string(),
+                    reason: ban.reason.clone(),
+                    remaining_secs: ban.until.saturating_duration_since(now).as_secs(),
+                })
+                .collect(),
+        }
+    }
+}
+
+/// Outcome of a blob request for reputation: `None` when it says nothing about the peer
+/// (it simply does not hold the blob).
+fn transfer_outcome(response: &BlobResponse) -> Option<bool> {
+    match response {
+        BlobResponse::Stored
+        | BlobResponse::Shard(_)
+        | BlobResponse::Blob(_)
+        | BlobResponse::BlobWithMeta { .. } => Some(true),
+        BlobResponse::Error(_) => Some(false),
+        BlobResponse::Inventory(_) | BlobResponse::NotFound => None,
+    }
+}
+
+/// Read the challenge history of authenticated providers from the registry.
+async fn refresh_challenge_stats(
+    state: SharedP2pState,
+    registry: Arc<MassaClient>,
+    peers: Vec<(PeerId, String)>,
+) {
+    for (peer, massa_address) in peers {
+        match registry.get_node_info(&massa_address).await {
+            Ok(Some(node)) => state
+                .write()
+                .await
+                .stats_mut(peer)
+                .set_challenges(node.passed_challenges, node.total_challenges),
+            Ok(None) => {}
+            Err(e) => tracing::debug!(%peer, %massa_address, error = %e, "failed to read node info"),
+        }
+    }
+}
+
+/// Send a blob protocol request to `peer` and wait for its response.
+/// The state lock is released before waiting so the P2P task can keep updating it.
+/// Uploaded and downloaded data are metered and wait for the bandwidth limits.
+pub async fn blob_request(
+    state: &SharedP2pState,
+    peer: PeerId,
+    request: BlobRequest,
+) -> Result<BlobResponse, String> {
+    let (cmd_tx, bandwidth) = {
+        let s = state.read().await;
+        (s.cmd_tx.clone(), s.bandwidth.clone())
+    };
+    let peer_key = peer.to_string();
+    let (kind, bytes_out) = (request.kind(), request.payload_len());
+    bandwidth.throttle_peer(&peer_key, bytes_out, 0).await;
+    let response = send_blob_request(&cmd_tx, peer, request).await;
+    let bytes_in = response.as_ref().map_or(0, BlobResponse::payload_len);
+    bandwidth.record_peer(&peer_key, kind, bytes_in, bytes_out);
+    // Downloaded data is paid for after the fact, delaying the caller's next request
+    bandwidth.throttle_peer(&peer_key, 0, bytes_in).await;
+    response
+}
+
+async fn send_blob_request(
+    cmd_tx: &mpsc::Sender<P2pCommand>,
+    peer: PeerId,
+    request: BlobRequest,
+) -> Result<BlobResponse, String> {
+    let (reply, reply_rx) = oneshot::channel();
+    cmd_tx
+        .send(P2pCommand::BlobRequest {
+            peer,
+            request,
+            reply,
+        })
+        .await
+        .map_err(|_| "p2p task is not running".to_string())?;
+    reply_rx
+        .await
+        .map_err(|_| "p2p task dropped the request".to_string())?
+}
+
+/// Announce this node as a provider of a blob (by content hash) in the DHT.
+pub async fn announce_blob(state: &SharedP2pState, content_hash: &str) {
+    let cmd_tx = state.read().await.cmd_tx.clone();
+    if cmd_tx
+        .send(P2pCommand::StartProviding(content_hash.to_string()))
+        .await
+        .is_err()
+    {
+        tracing::warn!(content_hash, "p2p task is not running; blob not announced");
+    }
+}
+
+/// Withdraw this node's DHT provider record of a blob it no longer serves (quarantined, or
+/// overwritten with other content), unless another local blob has the same content.
+pub async fn withdraw_blob(state: &SharedP2pState, storage: &Storage, content_hash: &str) {
+    match storage.list(None) {
+        Ok(entries) if entries.iter().any(|e| e.content_hash.as_deref() == Some(content_hash)) => return,
+        Ok(_) => {}
+        Err(e) => {
+            tracing::warn!(content_hash, error = %e, "failed to list blobs; provider record kept");
+            return;
+        }
+    }
+    let cmd_tx = state.read().await.cmd_tx.clone();
+    if cmd_tx
+        .send(P2pCommand::StopProviding(content_hash.to_string()))
+        .await
+        .is_err()
+    {
+        tracing::warn!(content_hash, "p2p task is not running; provider record kept");
+    }
+}
+
+/// Find the peers that announced a blob (by content hash) in the DHT.
+/// May include the local peer when it stores the blob.
+pub async fn find_providers(
+    state: &SharedP2pState,
+    content_hash: &str,
+) -> Result<Vec<PeerId>, String> {
+    provider_key(content_hash)?;
+    let cmd_tx = state.read().await.cmd_tx.clone();
+    let (reply, reply_rx) = oneshot::channel();
+    cmd_tx
+        .send(P2pCommand::FindProviders {
+            content_hash: content_hash.to_string(),
+            reply,
+        })
+        .await
+        .map_err(|_| "p2p task is not running".to_string())?;
+    reply_rx
+        .await
+        .map_err(|_| "p2p task dropped the request".to_string())?
+}
+
+/// DHT key of a blob's provider records: the raw 32-byte Blake3 content hash.
+pub fn provider_key(content_hash: &str) -> Result<kad::RecordKey, String> {
+    blake3::Hash::from_hex(content_hash)
+        .map(|hash| kad::RecordKey::new(hash.as_bytes()))
+        .map_err(|e| format!("invalid content hash: {}", e))
+}
+
+/// Publish a provider record for `content_hash`.
+fn start_providing(kad: &mut kad::Behaviour<kad::store::MemoryStore>, content_hash: &str) {
+    match provider_key(content_hash) {
+        Ok(key) => {
+            if let Err(e) = kad.start_providing(key) {
+                tracing::warn!(content_hash, error = %e, "failed to announce blob in DHT");
+            }
+        }
+        Err(e) => tracing::warn!(content_hash, error = %e, "not announcing blob"),
+    }
+}
+
+/// Remove the local provider record for `content_hash`. Copies already stored by other DHT
+/// nodes are not republished and expire.
+fn stop_providing(kad: &mut kad::Behaviour<kad::store::MemoryStore>, content_hash: &str) {
+    match provider_key(content_hash) {
+        Ok(key) => kad.stop_providing(&key),
+        Err(e) => tracing::warn!(content_hash, error = %e, "not withdrawing blob"),
+    }
+}
+
+/// Announce every locally stored blob (originals and replicas) in the DHT.
+fn announce_local_blobs(kad: &mut kad::Behaviour<kad::store::MemoryStore>, storage: &Storage) {
+    match storage.list(None) {
+        Ok(entries) => {
+            let mut announced = 0;
+            for hash in entries.iter().filter_map(|e| e.content_hash.as_deref()) {
+                start_providing(kad, hash);
+                announced += 1;
+            }
+            tracing::info!(blobs = announced, "announced local blobs in DHT");
+        }
+        Err(e) => tracing::warn!(error = %e, "failed to list blobs for DHT announcement"),
+    }
+}
+
+/// Why an attested peer was not confirmed as a registered provider.
+#[derive(Debug)]
+enum RegistryCheckError {
+    /// The provider metadata was read and does not advertise the peer id (or is not set).
+    NotAdvertised,
+    /// Every registry lookup failed: nothing is known about the peer yet.
+    Unavailable(String),
+}
+
+impl std::fmt::Display for RegistryCheckError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::NotAdvertised => write!(f, "registered metadata does not advertise this peer id"),
+            Self::Unavailable(e) => write!(f, "registry lookup failed: {}", e),
+        }
+    }
+}
+
+/// Check an attested address against the registry: it must be a registered provider whose
+/// metadata advertises `peer`. Retried since the provider may still be publishing its addrs.
+async fn verify_registered(
+    registry: &MassaClient,
+    attestation: &PeerAttestation,
+    peer: PeerId,
+) -> Result<(), RegistryCheckError> {
+    let mut metadata_read = false;
+    let mut last_error = String::new();
+    for attempt in 0..REGISTRY_CHECK_ATTEMPTS {
+        if attempt > 0 {
+            tokio::time::sleep(REGISTRY_CHECK_RETRY).await;
+        }
+        match registry.find_provider_metadata(&attestation.massa_address).await {
+            Ok(Some(metadata)) if advertises_peer_id(&metadata.p2p_addrs, &peer) => return Ok(()),
+            Ok(_) => metadata_read = true,
+            Err(e) => last_error = e.to_string(),
+        }
+    }
+    if metadata_read {
+        Err(RegistryCheckError::NotAdvertised)
+    } else {
+        Err(RegistryCheckError::Unavailable(last_error))
+    }
+}
+
+/// Result of an attested peer's registry check: peer, attested Massa address, outcome.
+type AuthResult = (PeerId, String, Result<(), RegistryCheckError>);
+
+/// Verify a peer's attestation signature, then check the registry in a background task
+/// that reports on `auth_tx`. Already authenticated or pending peers are skipped.
+/// Peers found via mDNS (`local`) only need a valid signature. Fails on an invalid attestation.
+async fn check_attestation(
+    state: &SharedP2pState,
+    in_flight: &mut HashSet<PeerId>,
+    auth_tx: &mpsc::Sender<AuthResult>,
+    registry: &Arc<MassaClient>,
+    peer: PeerId,
+    attestation: PeerAttestation,
+    local: bool,
+) -> Result<(), AttestationError> {
+    if in_flight.contains(&peer) || state.read().await.is_authenticated(&peer) {
+        return Ok(());
+    }
+    if let Err(e) = attestation.verify(&peer) {
+        tracing::warn!(%peer, massa_address = %attestation.massa_address, error = %e, "rejected peer attestation");
+        return Err(e);
+    }
+    in_flight.insert(peer);
+    let registry = registry.clone();
+    let auth_tx = auth_tx.clone();
+    tokio::spawn(async move {
+        let result = if local {
+            Ok(())
+        } else {
+            verify_registered(&registry, &attestation, peer).await
+        };
+        let _ = auth_tx.send((peer, attestation.massa_address, result)).await;
+    });
+    Ok(())
+}
+
+/// Ban `peer` for `duration`: existing connections are closed and new ones denied.
+async fn ban_peer(
+    swarm: &mut libp2p::Swarm<Behaviour>,
+    state: &SharedP2pState,
+    peer: PeerId,
+    duration: Duration,
+    reason: String,
+) {
+    tracing::warn!(%peer, %reason, secs = duration.as_secs(), "banning peer");
+    state.write().await.connections.ban(peer, duration, reason, Instant::now());
+    swarm.behaviour_mut().blocked.block_peer(peer);
+}
+
+/// Record a confirmed external address (or drop an expired one) in the shared state and in
+/// the address list reported by `/config`.
+async fn set_external_addr(
+    state: &SharedP2pState,
+    discovered_addrs: &StdRwLock<Vec<String>>,
+    addr: &Multiaddr,
+    confirmed: bool,
+) {
+    let addr_str = addr.to_string();
+    {
+        let mut s = state.write().await;
+        s.external_addrs.retain(|a| a != addr);
+        if confirmed {
+            s.external_addrs.push(addr.clone());
+        }
+    }
+    let mut addrs = discovered_addrs.write().unwrap();
+    addrs.retain(|a| *a != addr_str);
+    if confirmed {
+        addrs.push(addr_str);
+    }
+}
+
+/// Listen through relays (up to `MAX_RELAYS`) while this node is not publicly reachable.
+/// Only authenticated providers advertising the relay hop protocol are used.
+fn reserve_relays(
+    swarm: &mut libp2p::Swarm<Behaviour>,
+    candidates: &HashMap<PeerId, Multiaddr>,
+    listeners: &mut HashMap<PeerId, ListenerId>,
+    providers: &[PeerId],
+) {
+    for (relay, addr) in candidates {
+        if listeners.len() >= MAX_RELAYS {
+            break;
+        }
+        if listeners.contains_key(relay) || !providers.contains(relay) {
+            continue;
+        }
+        let circuit = addr
+            .clone()
+            .with(Protocol::P2p(*relay))
+            .with(Protocol::P2pCircuit);
+        match swarm.listen_on(circuit.clone()) {
+            Ok(listener) => {
+                tracing::info!(%relay, %circuit, "requesting relay reservation");
+                listeners.insert(*relay, listener);
+            }
+            Err(e) => tracing::warn!(%relay, error = %e, "failed to listen via relay"),
+        }
+    }
+}
+
+fn is_circuit_addr(addr: &Multiaddr) -> bool {
+    addr.iter().any(|p| matches!(p, Protocol::P2pCircuit))
+}
+
+/// Dial `addr`. When it ends with `/p2p/<peer id>`, the peer is added to the DHT routing
+/// table and handed to the connection manager, which dials it on its next tick and keeps it
+/// connected; banned peers are skipped.
+async fn dial_and_seed(swarm: &mut libp2p::Swarm<Behaviour>, state: &SharedP2pState, addr: Multiaddr) {
+    if let Some(Protocol::P2p(peer_id)) = addr.iter().last() {
+        let mut s = state.write().await;
+        if s.connections.is_banned(&peer_id) {
+            tracing::debug!(%addr, "not dialing banned peer");
+            return;
+        }
+        s.connections.add_target(peer_id, addr.clone(), Instant::now());
+        drop(s);
+        swarm.behaviour_mut().kad.add_address(&peer_id, addr);
+        return;
+    }
+    if let Err(e) = swarm.dial(addr.clone()) {
+        tracing::warn!(%addr, error = %e, "failed to dial peer");
+    }
+}
+
+/// A served inbound blob request: response channel, response, and the content hash of a
+/// stored replica to announce.
+type ServedRequest = (request_response::ResponseChannel<BlobResponse>, BlobResponse, Option<String>);
+
+/// Serve an inbound blob protocol request from local storage. This blocks on disk I/O:
+/// the swarm loop runs it with `spawn_blocking`.
+fn handle_blob_request(
+    storage: &Storage,
+    inventory: &StdMutex<InventorySnapshot>,
+    peer: &PeerId,
+    request: BlobRequest,
+) -> BlobResponse {
+    match request {
+        BlobRequest::StoreBlob {
+            namespace,
+            id,
+            data,
+            meta,
+        } => {
+            match storage.put_replica(&namespace, &id, &data, meta) {
+                Ok(_) => {
+                    tracing::info!(%peer, namespace, id, size = data.len(), "stored replica");
+                    inventory.lock().unwrap_or_else(|e| e.into_inner()).invalidate();
+                    BlobResponse::Stored
+                }
+                Err(e) => BlobResponse::Error(e.to_string()),
+            }
+        }
+        BlobRequest::StoreShard {
+            namespace,
+            id,
+            index,
+            hash,
+            data,
+        } => {
+            if shard_hash(&data) != hash {
+                return BlobResponse::Error("shard hash mismatch".to_string());
+            }
+            match storage.put_shard(&namespace, &id, &peer.to_string(), index, &data) {
+                Ok(()) => {
+                    tracing::info!(%peer, namespace, id, index, size = data.len(), "stored shard");
+                    BlobResponse::Stored
+                }
+                Err(e) => BlobResponse::Error(e.to_string()),
+            }
+        }
+        BlobRequest::GetShard {
+            namespace,
+            id,
+            index,
+        } => match storage.get_shard(&namespace, &id, &peer.to_string(), index) {
+            Ok(data) => BlobResponse::Shard(data),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => BlobResponse::NotFound,
+            Err(e) => BlobResponse::Error(e.to_string()),
+        },
+        BlobRequest::GetBlob { namespace, id } => match storage.get(&namespace, &id) {
+            Ok(data) => BlobResponse::Blob(data),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => BlobResponse::NotFound,
+            Err(e) => BlobResponse::Error(e.to_string()),
+        },
+        BlobRequest::FetchBlob { namespace, id } => {
+            match storage
+                .get(&namespace, &id)
+                .and_then(|data| Ok((data, storage.meta(&namespace, &id)?)))
+            {
+                Ok((data, meta)) => BlobResponse::BlobWithMeta { data, meta },
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => BlobResponse::NotFound,
+                Err(e) => BlobResponse::Error(e.to_string()),
+            }
+        }
+        BlobRequest::Inventory(query) => match inventory.lock().unwrap_or_else(|e| e.into_inner()).get(storage) {
+            Ok(inventory) => BlobResponse::Inventory(inventory.answer(&query)),
+            Err(e) => BlobResponse::Error(e.to_string()),
+        },
+    }
+}
+
 /// Check if a multiaddr contains a localhost address (0.0.0.0 or 127.0.0.1).
 fn is_localhost_multiaddr(addr: &str) -> bool {
     addr.contains("/ip4/0.0.0.0/") || addr.contains("/ip4/127.0.0.1/")
 }
 
+/// Build the combined behaviour (the relay client comes from the swarm builder).
+fn build_behaviour(
+    key: &libp2p::identity::Keypair,
+    relay_client: relay::client::Behaviour,
+    config: &P2pConfig,
+) -> Result<Behaviour, Box<dyn std::error::Error + Send + Sync>> {
+    let peer_id = key.public().to_peer_id();
+    let mut kad_config = kad::Config::new(StreamProtocol::new(KAD_PROTOCOL));
+    kad_config.set_query_timeout(Duration::from_secs(30));
+    let store = kad::store::MemoryStore::with_config(
+        peer_id,
+        kad::store::MemoryStoreConfig {
+            max_provided_keys: MAX_PROVIDED_KEYS,
+            ..Default::default()
+        },
+    );
+    // Kademlia switches to server mode once an external address is confirmed
+    let mut kad = kad::Behaviour::with_config(peer_id, store, kad_config);
+    let mdns = if config.mdns {
+        // LAN peers never get a confirmed public address: serve the DHT right away
+        kad.set_mode(Some(kad::Mode::Server));
+        Some(mdns::tokio::Behaviour::new(mdns::Config::default(), peer_id)?)
+    } else {
+        None
+    };
+
+    Ok(Behaviour {
+        ping: ping::Behaviour::default(),
+        identify: identify::Behaviour::new(identify::Config::new(
+            "/massa-storage/1.0.0".to_string(),
+            key.public(),
+        )),
+        blob: request_response::cbor::Behaviour::with_codec(
+            request_response::cbor::codec::Codec::default()
+                .set_request_size_maximum(MAX_BLOB_MESSAGE_BYTES)
+                .set_response_size_maximum(MAX_BLOB_MESSAGE_BYTES),
+            [(StreamProtocol::new(BLOB_PROTOCOL), ProtocolSupport::Full)],
+            request_response::Config::default()
+                .with_request_timeout(Duration::from_secs(120)),
+        ),
+        kad,
+        attest: request_response::cbor::Behaviour::new(
+            [(StreamProtocol::new(ATTEST_PROTOCOL), ProtocolSupport::Full)],
+            request_response::Config::default(),
+        ),
+        autonat: autonat::v1::Behaviour::new(peer_id, autonat::v1::Config::default()),
+        relay: relay::Behaviour::new(peer_id, relay::Config::default()),
+        relay_client,
+        dcutr: dcutr::Behaviour::new(peer_id),
+        mdns: Toggle::from(mdns),
+        limits: connection_limits::Behaviour::new(
+            connection_limits::ConnectionLimits::default()
+                .with_max_established_incoming(Some(config.max_inbound))
+                .with_max_established_outgoing(Some(config.max_outbound))
+                .with_max_established_per_peer(Some(MAX_CONNECTIONS_PER_PEER)),
+        ),
+        blocked: allow_block_list::Behaviour::default(),
+    })
+}
+
+/// Load the libp2p identity from `state_dir`, generating and saving one on first start.
+fn load_or_create_identity(state_dir: &Path) -> anyhow::Result<libp2p::identity::Keypair> {
+    let path = state_dir.join(IDENTITY_FILE);
+    match std::fs::read(&path) {
+        Ok(encoded) => {
+            return libp2p::identity::Keypair::from_protobuf_encoding(&encoded)
+                .map_err(|e| anyhow::anyhow!("invalid libp2p identity in {}: {}", path.display(), e));
+        }
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+        Err(e) => anyhow::bail!("cannot read libp2p identity {}: {}", path.display(), e),
+    }
+    let keypair = libp2p::identity::Keypair::generate_ed25519();
+    let encoded = keypair.to_protobuf_encoding()?;
+    std::fs::create_dir_all(state_dir)?;
+    write_atomic(&path, &encoded)?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o600))?;
+    }
+    tracing::info!(path = %path.display(), peer_id = %keypair.public().to_peer_id(), "generated libp2p identity");
+    Ok(keypair)
+}
+
+fn swarm_config(cfg: libp2p::swarm::Config) -> libp2p::swarm::Config {
+    cfg.with_idle_connection_timeout(Duration::from_secs(60))
+}
+
 /// Spawn the libp2p node in a background task.
+/// The libp2p identity is kept in the storage state directory, so the PeerId is stable.
+/// Fails when the identity cannot be loaded or the Massa private key cannot sign the peer
+/// attestation.
+/// `registry` is used to check that attested peers are registered providers.
 pub fn spawn(
-    listen_addr: String,
-    massa_address: String,
-    peers_to_dial: Vec<String>,
+    config: P2pConfig,
     discovered_addrs: Arc<StdRwLock<Vec<String>>>,
-) -> SharedP2pState {
+    storage: Storage,
+    registry: Arc<MassaClient>,
+    bandwidth: SharedBandwidth,
+) -> anyhow::Result<SharedP2pState> {
     // Create command channel for dialing new peers
     let (cmd_tx, cmd_rx) = mpsc::channel::<P2pCommand>(32);
 
     let (state, keypair) = {
-        // We need to create identity first to get PeerId for state
-        let keypair = libp2p::identity::Keypair::generate_ed25519();
+        // We need to load the identity first to get PeerId for state
+        let keypair = load_or_create_identity(&storage.state_dir())?;
         let peer_id = keypair.public().to_peer_id();
-        let state = Arc::new(RwLock::new(P2pState::new(peer_id, cmd_tx)));
+        let connections =
+            ConnectionManager::new(config.max_inbound as usize, config.max_outbound as usize);
+        let state = Arc::new(RwLock::new(P2pState::new(peer_id, cmd_tx, connections, bandwidth)));
         (state.clone(), keypair)
     };
 
+    let massa_keypair = crate::massa_grpc::keypair_from_str(&config.private_key)?;
+    let attestation = PeerAttestation::sign(&massa_keypair, &keypair.public().to_peer_id())?;
+
     let state_clone = state.clone();
     tokio::spawn(async move {
         if let Err(e) = run(
-            &listen_addr,
-            &massa_address,
-            peers_to_dial,
+            config,
             keypair,
+            attestation,
             state_clone,
             cmd_rx,
             discovered_addrs,
+            storage,
+            registry,
         )
         .await
         {
@@ -107,98 +1162,136 @@ pub fn spawn(
         }
     });
 
-    state
+    Ok(state)
 }
 
+#[allow(clippy::too_many_arguments)]
 async fn run(
-    listen_addr: &str,
-    massa_address: &str,
-    peers_to_dial: Vec<String>,
+    config: P2pConfig,
     keypair: libp2p::identity::Keypair,
+    attestation: PeerAttestation,
     state: SharedP2pState,
     mut cmd_rx: mpsc::Receiver<P2pCommand>,
     discovered_addrs: Arc<StdRwLock<Vec<String>>>,
+    storage: Storage,
+    registry: Arc<MassaClient>,
 ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
     let local_peer_id = keypair.public().to_peer_id();
 
-    // Build swarm with TCP + QUIC transports + DNS resolution
-    let mut swarm = SwarmBuilder::with_existing_identity(keypair)
-        .with_tokio()
-        .with_tcp(
-            tcp::Config::default(),
-            noise::Config::new,
-            yamux::Config::default,
-        )?
-        .with_quic()
-        .with_dns()?
-        .with_behaviour(|key| Behaviour {
-            ping: ping::Behaviour::default(),
-            identify: identify::Behaviour::new(identify::Config::new(
-                "/massa-storage/1.0.0".to_string(),
-                key.public(),
-            )),
-        })?
-        .with_swarm_config(|cfg| cfg.with_idle_connection_timeout(Duration::from_secs(60)))
-        .build();
+    // Build swarm with the enabled transports (TCP and/or QUIC) + DNS resolution + relay client
+    let builder = SwarmBuilder::with_existing_identity(keypair).with_tokio();
+    let mut swarm = match (config.tcp, config.quic) {
+        (true, true) => builder
+            .with_tcp(tcp::Config::default(), noise::Config::new, yamux::Config::default)?
+            .with_quic()
+            .with_dns()?
+            .with_relay_client(noise::Config::new, yamux::Config::default)?
+            .with_behaviour(|key, relay_client| build_behaviour(key, relay_client, &config))?
+            .with_swarm_config(swarm_config)
+            .build(),
+        (true, false) => builder
+            .with_tcp(tcp::Config::default(), noise::Config::new, yamux::Config::default)?
+            .with_dns()?
+            .with_relay_client(noise::Config::new, yamux::Config::default)?
+            .with_behaviour(|key, relay_client| build_behaviour(key, relay_client, &config))?
+            .with_swarm_config(swarm_config)
+            .build(),
+        (false, true) => builder
+            .with_quic()
+            .with_dns()?
+            .with_relay_client(noise::Config::new, yamux::Config::default)?
+            .with_behaviour(|key, relay_client| build_behaviour(key, relay_client, &config))?
+            .with_swarm_config(swarm_config)
+            .build(),
+        (false, false) => return Err("both TCP and QUIC transports are disabled".into()),
+    };
 
     tracing::info!(
         %local_peer_id,
-        massa_address = %massa_address,
+        massa_address = %attestation.massa_address,
+        tcp = config.tcp,
+        quic = config.quic,
+        mdns = config.mdns,
         "starting libp2p node"
     );
 
-    // Listen on both QUIC and TCP
-    let addr: Multiaddr = listen_addr.parse()?;
-
-    // Try to listen on QUIC (UDP) first, derived from the parsed multiaddr.
-    let mut ip_proto: Option<Protocol> = None;
-    let mut tcp_port: Option<u16> = None;
-    for proto in addr.iter() {
-        match proto {
-            Protocol::Ip4(_) | Protocol::Ip6(_) => {
-                ip_proto = Some(proto);
-            }
-            Protocol::Tcp(port) => {
-                tcp_port = Some(port);
-            }
-            _ => {}
+    let mut listening = 0;
+    for listen_addr in &config.listen_addrs {
+        let result = listen_addr
+            .parse::<Multiaddr>()
+            .map_err(|e| e.to_string())
+            .and_then(|addr| swarm.listen_on(addr).map_err(|e| e.to_string()));
+        match result {
+            Ok(_) => listening += 1,
+            Err(e) => tracing::warn!(%listen_addr, error = %e, "cannot listen on P2P address"),
         }
     }
+    if listening == 0 {
+        return Err("no P2P listen address could be used".into());
+    }
 
-    if let (Some(ip), Some(port)) = (ip_proto, tcp_port) {
-        let mut quic_multiaddr = Multiaddr::empty();
-        quic_multiaddr.push(ip);
-        quic_multiaddr.push(Protocol::Udp(port));
-        quic_multiaddr.push(Protocol::QuicV1);
-        tracing::debug!(%quic_multiaddr, "attempting QUIC listen");
-        match swarm.listen_on(quic_multiaddr.clone()) {
-            Ok(_) => tracing::info!(%quic_multiaddr, "listening on QUIC"),
-            Err(e) => tracing::warn!(error = ?e, "failed to listen on QUIC, using TCP only"),
+    // Operator-declared public addresses: advertised via identify and registered as is
+    let mut configured_external: Vec<Multiaddr> = Vec::new();
+    for external in &config.external_addrs {
+        match external.parse::<Multiaddr>() {
+            Ok(addr) => {
+                tracing::info!(%addr, "using configured external address");
+                swarm.add_external_address(addr.clone());
+                set_external_addr(&state, &discovered_addrs, &addr, true).await;
+                configured_external.push(addr);
+            }
+            Err(e) => tracing::warn!(addr = %external, error = %e, "invalid external multiaddr"),
         }
-    } else {
-        tracing::warn!(
-            %listen_addr,
-            "cannot derive QUIC listen address (missing ip or tcp port)"
-        );
     }
 
-    // Also listen on TCP as fallback
-    swarm.listen_on(addr)?;
-
-    // Dial initial peers
-    for peer_addr in &peers_to_dial {
+    // Dial initial peers (they also seed the DHT routing table)
+    for peer_addr in &config.peers_to_dial {
         match peer_addr.parse::<Multiaddr>() {
             Ok(addr) => {
                 tracing::info!(%addr, "dialing peer");
-                if let Err(e) = swarm.dial(addr.clone()) {
-                    tracing::warn!(%addr, error = %e, "failed to dial peer");
-                }
+                dial_and_seed(&mut swarm, &state, addr).await;
             }
             Err(e) => {
                 tracing::warn!(addr = %peer_addr, error = %e, "invalid multiaddr");
             }
         }
     }
+    if swarm.behaviour_mut().kad.bootstrap().is_err() {
+        tracing::info!("no known peers yet; DHT bootstrap deferred");
+    }
+
+    // Outbound blob requests waiting for a response
+    let mut pending_blob_requests: HashMap<
+        request_response::OutboundRequestId,
+        oneshot::Sender<Result<BlobResponse, String>>,
+    > = HashMap::new();
+    // DHT provider lookups: providers found so far and where to send them
+    let mut pending_provider_queries: HashMap<
+        kad::QueryId,
+        (HashSet<PeerId>, oneshot::Sender<Result<Vec<PeerId>, String>>),
+    > = HashMap::new();
+    // Local blobs are announced once the first DHT bootstrap succeeds
+    let mut local_blobs_announced = false;
+    // Registry checks of attested peers run in background tasks and report here
+    let (auth_tx, mut auth_rx) = mpsc::channel::<AuthResult>(32);
+    let mut auth_in_flight: HashSet<PeerId> = HashSet::new();
+    // Peers whose registry check could not read the registry, due for a new handshake
+    let mut auth_retries: FuturesUnordered<BoxFuture<'static, PeerId>> = FuturesUnordered::new();
+    // Peers able to relay for us (from identify) and our active relay listeners
+    let mut relay_candidates: HashMap<PeerId, Multiaddr> = HashMap::new();
+    let mut relay_listeners: HashMap<PeerId, ListenerId> = HashMap::new();
+    // Peers currently advertised on the local network via mDNS
+    let mut local_peers: HashMap<PeerId, HashSet<Multiaddr>> = HashMap::new();
+    // Local inventory answering peers' sync queries
+    let inventory = Arc::new(StdMutex::new(InventorySnapshot::default()));
+    // Connection manager tick: redials, ban expiry, trimming
+    let mut connection_tick = tokio::time::interval(CONNECTION_TICK);
+    // Reputation: challenge history refresh and pruning of stale statistics
+    let mut stats_tick = tokio::time::interval(CHALLENGE_REFRESH);
+    // Inbound blob requests being served from storage, then waiting for the bandwidth
+    // limits; each yields the response and the content hash of a stored replica to announce
+    let bandwidth = state.read().await.bandwidth.clone();
+    let mut served_requests: FuturesUnordered<BoxFuture<'static, ServedRequest>> = FuturesUnordered::new();
 
     // Event loop - handle both swarm events and dial commands
     loop {
@@ -209,16 +1302,149 @@ async fn run(
                     P2pCommand::Dial(addr_str) => {
                         match addr_str.parse::<Multiaddr>() {
                             Ok(addr) => {
-                                tracing::info!(%addr, "dialing peer (from contract)");
-                                if let Err(e) = swarm.dial(addr.clone()) {
-                                    tracing::warn!(%addr, error = %e, "failed to dial peer");
-                                }
+                                tracing::debug!(%addr, "dial request (from contract)");
+                                dial_and_seed(&mut swarm, &state, addr).await;
                             }
                             Err(e) => {
                                 tracing::warn!(addr = %addr_str, error = %e, "invalid multiaddr");
                             }
                         }
                     }
+                    P2pCommand::BlobRequest { peer, request, reply } => {
+                        let request_id = swarm.behaviour_mut().blob.send_request(&peer, request);
+                        pending_blob_requests.insert(request_id, reply);
+                    }
+                    P2pCommand::StartProviding(content_hash) => {
+                        start_providing(&mut swarm.behaviour_mut().kad, &content_hash);
+                    }
+                    P2pCommand::StopProviding(content_hash) => {
+                        stop_providing(&mut swarm.behaviour_mut().kad, &content_hash);
+                    }
+                    P2pCommand::FindProviders { content_hash, reply } => match provider_key(&content_hash) {
+                        Ok(key) => {
+                            let query_id = swarm.behaviour_mut().kad.get_providers(key);
+                            pending_provider_queries.insert(query_id, (HashSet::new(), reply));
+                        }
+                        Err(e) => {
+                            let _ = reply.send(Err(e));
+                        }
+                    },
+                }
+            }
+
+            _ = connection_tick.tick() => {
+                let now = Instant::now();
+                let mut s = state.write().await;
+                for peer in s.connections.expired_bans(now) {
+                    tracing::info!(%peer, "ban expired");
+                    swarm.behaviour_mut().blocked.unblock_peer(peer);
+                }
+                let trim = s.connections.trim_candidates(
+                    |peer| s.is_authenticated(peer) || local_peers.contains_key(peer),
+                    now,
+                );
+                let dials = s.connections.due_dials(now);
+                drop(s);
+                for peer in trim {
+                    tracing::debug!(%peer, "disconnecting unauthenticated peer to free an inbound slot");
+                    let _ = swarm.disconnect_peer_id(peer);
+                }
+                for (peer, addrs) in dials {
+                    tracing::debug!(%peer, "reconnecting to peer");
+                    let opts = DialOpts::peer_id(peer)
+                        .addresses(addrs)
+                        .condition(PeerCondition::DisconnectedAndNotDialing)
+                        .build();
+                    if let Err(e) = swarm.dial(opts) {
+                        tracing::debug!(%peer, error = %e, "reconnect dial not started");
+                        state.write().await.connections.dial_failed(&peer, Instant::now());
+                    }
+                }
+            }
+
+            Some((channel, response, announce)) = served_requests.next(), if !served_requests.is_empty() => {
+                // Replicas are announced like local uploads
+                if let Some(hash) = announce {
+                    start_providing(&mut swarm.behaviour_mut().kad, &hash);
+                }
+                if swarm.behaviour_mut().blob.send_response(channel, response).is_err() {
+                    tracing::debug!("blob response channel closed");
+                }
+            }
+
+            _ = stats_tick.tick() => {
+                let now = Instant::now();
+                let mut s = state.write().await;
+                s.peer_stats.retain(|_, stats| {
+                    stats.is_connected() || now.saturating_duration_since(stats.last_seen()) < PEER_STATS_RETENTION
+                });
+                let providers: Vec<(PeerId, String)> = s
+                    .connected_peers
+                    .iter()
+                    .filter(|(_, info)| info.authenticated)
+                    .filter_map(|(peer, info)| Some((*peer, info.massa_address.clone()?)))
+                    .collect();
+                drop(s);
+                tokio::spawn(refresh_challenge_stats(state.clone(), registry.clone(), providers));
+            }
+
+            // Registry check of an attested peer finished
+            Some((peer, massa_address, result)) = auth_rx.recv() => {
+                auth_in_flight.remove(&peer);
+                match result {
+                    Ok(()) => {
+                        let mut s = state.write().await;
+                        if let Some(peer_info) = s.connected_peers.get_mut(&peer) {
+                            tracing::info!(%peer, %massa_address, "peer authenticated as registered provider");
+                            tokio::spawn(refresh_challenge_stats(
+                                state.clone(),
+                                registry.clone(),
+                                vec![(peer, massa_address.clone())],
+                            ));
+                            peer_info.massa_address = Some(massa_address);
+                            peer_info.authenticated = true;
+                            let addrs: Vec<Multiaddr> =
+                                peer_info.addresses.iter().filter_map(|a| a.parse().ok()).collect();
+                            // Authenticated providers join the DHT routing table (with their
+                            // identify listen addrs; otherwise this happens on identify)
+                            if peer_info.agent_version.is_some() {
+                                for addr in &addrs {
+                                    swarm.behaviour_mut().kad.add_address(&peer, addr.clone());
+                                }
+                            }
+                            // and are kept connected
+                            s.connections.mark_provider(peer, addrs, Instant::now());
+                        }
+                        // A newly authenticated provider may be able to relay for us
+                        if s.reachability == Reachability::Private && relay_candidates.contains_key(&peer) {
+                            let providers = s.provider_peers();
+                            drop(s);
+                            reserve_relays(&mut swarm, &relay_candidates, &mut relay_listeners, &providers);
+                        }
+                    }
+                    Err(RegistryCheckError::Unavailable(e)) => {
+                        tracing::warn!(%peer, %massa_address, error = %e, "registry unavailable; peer stays unauthenticated until the next check");
+                        auth_retries.push(tokio::time::sleep(REGISTRY_UNAVAILABLE_RETRY).map(move |_| peer).boxed());
+                    }
+                    Err(e) => {
+                        tracing::warn!(%peer, %massa_address, error = %e, "peer is not a registered provider");
+                        let reason = format!("{} is not a registered provider: {}", massa_address, e);
+                        ban_peer(&mut swarm, &state, peer, BAN_NOT_REGISTERED, reason).await;
+                    }
+                }
+            }
+
+            // Redo the handshake with a still connected, unauthenticated peer: its answer
+            // starts a new registry check
+            Some(peer) = auth_retries.next(), if !auth_retries.is_empty() => {
+                let pending = state
+                    .read()
+                    .await
+                    .connected_peers
+                    .get(&peer)
+                    .is_some_and(|info| !info.authenticated);
+                if pending && !auth_in_flight.contains(&peer) {
+                    swarm.behaviour_mut().attest.send_request(&peer, attestation.clone());
                 }
             }
 
@@ -231,12 +1457,11 @@ async fn run(
                             let mut s = state.write().await;
                             s.listen_addrs.push(address.clone());
                         }
-                        let addr_str = address.to_string();
-                        if !is_localhost_multiaddr(&addr_str) {
-                            let mut addrs = discovered_addrs.write().unwrap();
-                            if !addrs.contains(&addr_str) {
-                                addrs.push(addr_str.clone());
-                            }
+                        // A relay circuit address exists only once the relay accepted our
+                        // reservation, so it is reachable by construction.
+                        if is_circuit_addr(&address) {
+                            swarm.add_external_address(address.clone());
+                            set_external_addr(&state, &discovered_addrs, &address, true).await;
                         }
                         tracing::info!(
                             %address,
@@ -245,33 +1470,140 @@ async fn run(
                         );
                     }
 
+                    SwarmEvent::ExpiredListenAddr { address, .. } => {
+                        state.write().await.listen_addrs.retain(|a| *a != address);
+                        if is_circuit_addr(&address) {
+                            swarm.remove_external_address(&address);
+                            set_external_addr(&state, &discovered_addrs, &address, false).await;
+                        }
+                    }
+
+                    SwarmEvent::ListenerClosed { listener_id, reason, .. } => {
+                        if let Some(relay) = relay_listeners
+                            .iter()
+                            .find(|(_, id)| **id == listener_id)
+                            .map(|(relay, _)| *relay)
+                        {
+                            tracing::info!(%relay, reason = ?reason, "relay listener closed");
+                            relay_listeners.remove(&relay);
+                            relay_candidates.remove(&relay);
+                            if state.read().await.reachability == Reachability::Private {
+                                let providers = state.read().await.provider_peers();
+                                reserve_relays(&mut swarm, &relay_candidates, &mut relay_listeners, &providers);
+                            }
+                        }
+                    }
+
+                    SwarmEvent::ExternalAddrConfirmed { address } => {
+                        tracing::info!(%address, "external address confirmed");
+                        set_external_addr(&state, &discovered_addrs, &address, true).await;
+                    }
+
+                    SwarmEvent::ExternalAddrExpired { address } => {
+                        if configured_external.contains(&address) {
+                            // Declared by the operator: AutoNAT probes do not override it
+                            swarm.add_external_address(address);
+                        } else {
+                            tracing::info!(%address, "external address expired");
+                            set_external_addr(&state, &discovered_addrs, &address, false).await;
+                        }
+                    }
+
+                    SwarmEvent::Behaviour(BehaviourEvent::Autonat(autonat::v1::Event::StatusChanged {
+                        old,
+                        new,
+                    })) => {
+                        tracing::info!(?old, ?new, "NAT status changed");
+                        let reachability = match new {
+                            autonat::v1::NatStatus::Public(_) => Reachability::Public,
+                            autonat::v1::NatStatus::Private => Reachability::Private,
+                            autonat::v1::NatStatus::Unknown => Reachability::Unknown,
+                        };
+                        state.write().await.reachability = reachability;
+                        match reachability {
+                            Reachability::Private => {
+                                let providers = state.read().await.provider_peers();
+                                reserve_relays(&mut swarm, &relay_candidates, &mut relay_listeners, &providers);
+                            }
+                            Reachability::Public => {
+                                // Directly reachable: relay circuits are no longer needed
+                                for (_, listener) in relay_listeners.drain() {
+                                    swarm.remove_listener(listener);
+                                }
+                            }
+                            Reachability::Unknown => {}
+                        }
+                    }
+
+                    SwarmEvent::Behaviour(BehaviourEvent::RelayClient(
+                        relay::client::Event::ReservationReqAccepted { relay_peer_id, renewal, .. },
+                    )) => {
+                        if !renewal {
+                            tracing::info!(relay = %relay_peer_id, "relay reservation accepted");
+                        }
+                    }
+
+                    SwarmEvent::Behaviour(BehaviourEvent::Dcutr(dcutr::Event {
+                        remote_peer_id,
+                        result,
+                    })) => match result {
+                        Ok(_) => tracing::info!(peer = %remote_peer_id, "hole punch succeeded; direct connection"),
+                        Err(e) => tracing::debug!(peer = %remote_peer_id, error = %e, "hole punch failed"),
+                    },
+
                     SwarmEvent::ConnectionEstablished {
-                        peer_id, endpoint, ..
+                        peer_id, endpoint, num_established, ..
                     } => {
                         tracing::info!(
                             %peer_id,
                             address = %endpoint.get_remote_address(),
                             "peer connected"
                         );
-                        let mut s = state.write().await;
-                        s.connected_peers.insert(
-                            peer_id,
-                            PeerInfo {
-                                peer_id: peer_id.to_string(),
-                                addresses: vec![endpoint.get_remote_address().to_string()],
-                                agent_version: None,
-                            },
-                        );
+                        if num_established.get() == 1 {
+                            let direction = if endpoint.is_dialer() {
+                                ConnectionDirection::Outbound
+                            } else {
+                                ConnectionDirection::Inbound
+                            };
+                            let mut s = state.write().await;
+                            s.connections.connected(peer_id, direction, Instant::now());
+                            s.stats_mut(peer_id).connected(Instant::now());
+                            s.connected_peers.insert(
+                                peer_id,
+                                PeerInfo {
+                                    peer_id: peer_id.to_string(),
+                                    addresses: vec![endpoint.get_remote_address().to_string()],
+                                    agent_version: None,
+                                    massa_address: None,
+                                    authenticated: false,
+                                    locally_discovered: local_peers.contains_key(&peer_id),
+                                    direction,
+                                    reputation: None,
+                                },
+                            );
+                            drop(s);
+                            // Present our attestation; the peer answers with its own
+                            swarm.behaviour_mut().attest.send_request(&peer_id, attestation.clone());
+                        }
                     }
 
-                    SwarmEvent::ConnectionClosed { peer_id, cause, .. } => {
+                    SwarmEvent::ConnectionClosed { peer_id, cause, num_established, .. } => {
                         tracing::info!(
                             %peer_id,
                             cause = ?cause,
                             "peer disconnected"
                         );
-                        let mut s = state.write().await;
-                        s.connected_peers.remove(&peer_id);
+                        if num_established == 0 {
+                            let mut s = state.write().await;
+                            s.connected_peers.remove(&peer_id);
+                            s.connections.disconnected(&peer_id, Instant::now());
+                            s.stats_mut(peer_id).disconnected(Instant::now());
+                        }
+                    }
+
+                    SwarmEvent::OutgoingConnectionError { peer_id: Some(peer_id), error, .. } => {
+                        tracing::debug!(%peer_id, error = %error, "dial failed");
+                        state.write().await.connections.dial_failed(&peer_id, Instant::now());
                     }
 
                     SwarmEvent::Behaviour(BehaviourEvent::Identify(identify::Event::Received {
@@ -285,17 +1617,223 @@ async fn run(
                             protocols = ?info.protocols,
                             "identified peer"
                         );
+                        // Remember peers that can relay for us (a direct, non-local address)
+                        if info.protocols.iter().any(|p| p.as_ref() == RELAY_HOP_PROTOCOL) {
+                            if let Some(addr) = info.listen_addrs.iter().find(|a| {
+                                !is_circuit_addr(a) && !is_localhost_multiaddr(&a.to_string())
+                            }) {
+                                relay_candidates.insert(peer_id, addr.clone());
+                            }
+                        }
                         let mut s = state.write().await;
+                        if s.is_authenticated(&peer_id) {
+                            // Reconnect to authenticated providers through their listen addrs
+                            s.connections.mark_provider(peer_id, info.listen_addrs.clone(), Instant::now());
+                            // Those speaking our DHT protocol join the routing table
+                            if info.protocols.iter().any(|p| p.as_ref() == KAD_PROTOCOL) {
+                                for addr in &info.listen_addrs {
+                                    swarm.behaviour_mut().kad.add_address(&peer_id, addr.clone());
+                                }
+                            }
+                        }
                         if let Some(peer_info) = s.connected_peers.get_mut(&peer_id) {
                             peer_info.agent_version = Some(info.agent_version);
                             peer_info.addresses = info.listen_addrs.iter().map(|a| a.to_string()).collect();
                         }
+                        if s.reachability == Reachability::Private && relay_listeners.len() < MAX_RELAYS {
+                            let providers = s.provider_peers();
+                            drop(s);
+                            reserve_relays(&mut swarm, &relay_candidates, &mut relay_listeners, &providers);
+                        }
+                    }
+
+                    SwarmEvent::Behaviour(BehaviourEvent::Kad(kad::Event::OutboundQueryProgressed {
+                        id,
+                        result,
+                        step,
+                        ..
+                    })) => match result {
+                        kad::QueryResult::GetProviders(result) => {
+                            match result {
+                                Ok(kad::GetProvidersOk::FoundProviders { providers, .. }) => {
+                                    if let Some((found, _)) = pending_provider_queries.get_mut(&id) {
+                                        found.extend(providers);
+                                    }
+                                }
+                                Ok(kad::GetProvidersOk::FinishedWithNoAdditionalRecord { .. }) => {}
+                                Err(e) => tracing::debug!(error = %e, "DHT provider lookup ended"),
+                            }
+                            if step.last {
+                                if let Some((found, reply)) = pending_provider_queries.remove(&id) {
+                                    let _ = reply.send(Ok(found.into_iter().collect()));
+                                }
+                            }
+                        }
+                        kad::QueryResult::StartProviding(Err(e)) => {
+                            tracing::debug!(error = %e, "DHT provider announcement failed");
+                        }
+                        kad::QueryResult::Bootstrap(Ok(_)) if step.last => {
+                            tracing::debug!("DHT bootstrap completed");
+                            if !local_blobs_announced {
+                                announce_local_blobs(&mut swarm.behaviour_mut().kad, &storage);
+                                local_blobs_announced = true;
+                            }
+                        }
+                        kad::QueryResult::Bootstrap(Err(e)) => {
+                            tracing::debug!(error = %e, "DHT bootstrap failed");
+                        }
+                        _ => {}
+                    },
+
+                    SwarmEvent::Behaviour(BehaviourEvent::Attest(request_response::Event::Message {
+                        peer,
+                        message,
+                        ..
+                    })) => {
+                        let remote = match message {
+                            request_response::Message::Request { request, channel, .. } => {
+                                if swarm.behaviour_mut().attest.send_response(channel, attestation.clone()).is_err() {
+                                    tracing::debug!(%peer, "attestation response channel closed");
+                                }
+                                request
+                            }
+                            request_response::Message::Response { response, .. } => response,
+                        };
+                        let local = local_peers.contains_key(&peer);
+                        let massa_address = remote.massa_address.clone();
+                        if let Err(e) = check_attestation(&state, &mut auth_in_flight, &auth_tx, &registry, peer, remote, local).await {
+                            let reason = format!("invalid attestation for {}: {}", massa_address, e);
+                            ban_peer(&mut swarm, &state, peer, BAN_INVALID_ATTESTATION, reason).await;
+                        }
+                    }
+
+                    SwarmEvent::Behaviour(BehaviourEvent::Attest(request_response::Event::OutboundFailure {
+                        peer,
+                        error,
+                        ..
+                    })) => {
+                        tracing::debug!(%peer, error = %error, "attestation handshake failed");
+                    }
+
+                    SwarmEvent::Behaviour(BehaviourEvent::Blob(request_response::Event::Message {
+                        peer,
+                        message,
+                        ..
+                    })) => match message {
+                        request_response::Message::Request { request, channel, .. } => {
+                            // Only authenticated providers may store or fetch blobs
+                            if !state.read().await.is_authenticated(&peer) {
+                                tracing::debug!(%peer, "refusing blob request from unauthenticated peer");
+                                let response = BlobResponse::Error("peer not authenticated".to_string());
+                                let _ = swarm.behaviour_mut().blob.send_response(channel, response);
+                                continue;
+                            }
+                            let replica = match &request {
+                                BlobRequest::StoreBlob { namespace, id, .. } => Some((namespace.clone(), id.clone())),
+                                _ => None,
+                            };
+                            let (kind, bytes_in) = (request.kind(), request.payload_len());
+                            // Storage I/O runs on the blocking pool and the response waits for the
+                            // bandwidth limits, both without blocking the swarm
+                            let (storage, inventory, bandwidth) = (storage.clone(), inventory.clone(), bandwidth.clone());
+                            served_requests.push(
+                                async move {
+                                    let served = tokio::task::spawn_blocking(move || {
+                                        let response = handle_blob_request(&storage, &inventory, &peer, request);
+                                        let announce = match (&response, replica) {
+                                            (BlobResponse::Stored, Some((namespace, id))) => {
+                                                storage.meta(&namespace, &id).ok().and_then(|meta| meta.content_hash)
+                                            }
+                                            _ => None,
+                                        };
+                                        (response, announce)
+                                    })
+                                    .await;
+                                    let (response, announce) = served.unwrap_or_else(|e| {
+                                        (BlobResponse::Error(format!("blob request failed: {}", e)), None)
+                                    });
+                                    let bytes_out = response.payload_len();
+                                    let peer_key = peer.to_string();
+                                    bandwidth.record_peer(&peer_key, kind, bytes_in, bytes_out);
+                                    bandwidth.throttle_peer(&peer_key, bytes_out, bytes_in).await;
+                                    (channel, response, announce)
+                                }
+                                .boxed(),
+                            );
+                        }
+                        request_response::Message::Response { request_id, response } => {
+                            if let Some(ok) = transfer_outcome(&response) {
+                                state.write().await.record_transfer(peer, ok);
+                            }
+                            if let Some(reply) = pending_blob_requests.remove(&request_id) {
+                                let _ = reply.send(Ok(response));
+                            }
+                        }
+                    },
+
+                    SwarmEvent::Behaviour(BehaviourEvent::Blob(request_response::Event::OutboundFailure {
+                        peer,
+                        request_id,
+                        error,
+                        ..
+                    })) => {
+                        tracing::warn!(%peer, error = %error, "blob request failed");
+                        state.write().await.record_transfer(peer, false);
+                        if let Some(reply) = pending_blob_requests.remove(&request_id) {
+                            let _ = reply.send(Err(error.to_string()));
+                        }
+                    }
+
+                    SwarmEvent::Behaviour(BehaviourEvent::Blob(request_response::Event::InboundFailure {
+                        peer,
+                        error,
+                        ..
+                    })) => {
+                        tracing::debug!(%peer, error = %error, "inbound blob request failed");
+                    }
+
+                    SwarmEvent::Behaviour(BehaviourEvent::Mdns(mdns::Event::Discovered(found))) => {
+                        let mut new_peers = HashSet::new();
+                        for (peer, addr) in found {
+                            if local_peers.entry(peer).or_default().insert(addr) {
+                                new_peers.insert(peer);
+                            }
+                        }
+                        let mut s = state.write().await;
+                        for peer in new_peers {
+                            if let Some(peer_info) = s.connected_peers.get_mut(&peer) {
+                                peer_info.locally_discovered = true;
+                                // Connected before mDNS saw it: redo the handshake without registry check
+                                if !peer_info.authenticated && !auth_in_flight.contains(&peer) {
+                                    swarm.behaviour_mut().attest.send_request(&peer, attestation.clone());
+                                }
+                                continue;
+                            }
+                            let addrs: Vec<Multiaddr> = local_peers[&peer].iter().cloned().collect();
+                            tracing::info!(%peer, addrs = ?addrs, "discovered local peer via mDNS");
+                            if let Err(e) = swarm.dial(DialOpts::peer_id(peer).addresses(addrs).build()) {
+                                tracing::debug!(%peer, error = %e, "failed to dial local peer");
+                            }
+                        }
+                    }
+
+                    SwarmEvent::Behaviour(BehaviourEvent::Mdns(mdns::Event::Expired(expired))) => {
+                        for (peer, addr) in expired {
+                            if let Some(addrs) = local_peers.get_mut(&peer) {
+                                addrs.remove(&addr);
+                                if addrs.is_empty() {
+                                    tracing::debug!(%peer, "local peer no longer advertised via mDNS");
+                                    local_peers.remove(&peer);
+                                }
+                            }
+                        }
                     }
 
                     SwarmEvent::Behaviour(BehaviourEvent::Ping(ping::Event { peer, result, .. })) => {
                         match result {
                             Ok(rtt) => {
                                 tracing::debug!(%peer, rtt_ms = rtt.as_millis(), "ping success");
+                                state.write().await.stats_mut(peer).record_rtt(rtt);
                             }
                             Err(e) => {
                                 tracing::debug!(%peer, error = %e, "ping failed");
@@ -309,3 +1847,79 @@ async fn run(
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn addr(port: u16) -> Multiaddr {
+        format!("/ip4/10.0.0.1/tcp/{}", port).parse().unwrap()
+    }
+
+    #[test]
+    fn identity_survives_restart() {
+        let dir = std::env::temp_dir().join(format!("massa-storage-test-{}", uuid::Uuid::new_v4()));
+        let first = load_or_create_identity(&dir).unwrap();
+        let second = load_or_create_identity(&dir).unwrap();
+        assert_eq!(first.public().to_peer_id(), second.public().to_peer_id());
+
+        std::fs::write(dir.join(IDENTITY_FILE), b"garbage").unwrap();
+        assert!(load_or_create_identity(&dir).is_err());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn reconnect_delay_doubles_up_to_cap() {
+        assert_eq!(reconnect_delay(0), RECONNECT_BASE_DELAY);
+        assert_eq!(reconnect_delay(1), RECONNECT_BASE_DELAY * 2);
+        assert_eq!(reconnect_delay(3), RECONNECT_BASE_DELAY * 8);
+        assert_eq!(reconnect_delay(40), RECONNECT_MAX_DELAY);
+    }
+
+    #[test]
+    fn due_dials_prefer_providers_within_outbound_slots() {
+        let now = Instant::now();
+        let mut manager = ConnectionManager::new(8, 2);
+        let (plain, provider, banned) = (PeerId::random(), PeerId::random(), PeerId::random());
+        manager.add_target(plain, addr(1), now);
+        manager.add_target(banned, addr(2), now);
+        manager.ban(banned, BAN_NOT_REGISTERED, "test".to_string(), now);
+        manager.mark_provider(provider, vec![addr(3)], now);
+        manager.connected(PeerId::random(), ConnectionDirection::Outbound, now);
+
+        // One free outbound slot: the provider goes first, the banned peer never
+        let dials = manager.due_dials(now);
+        assert_eq!(dials, vec![(provider, vec![addr(3)])]);
+        // Not redialed while the attempt is pending
+        assert!(manager.due_dials(now).iter().all(|(p, _)| *p != provider));
+
+        // A failed dial backs off before the next attempt
+        manager.dial_failed(&provider, now);
+        assert!(manager.due_dials(now + reconnect_delay(0)).iter().all(|(p, _)| *p != provider));
+        assert!(manager.due_dials(now + reconnect_delay(1)).iter().any(|(p, _)| *p == provider));
+
+        assert_eq!(manager.expired_bans(now + BAN_NOT_REGISTERED), vec![banned]);
+        assert!(!manager.is_banned(&banned));
+    }
+
+    #[test]
+    fn trims_only_old_unauthenticated_inbound_peers_above_watermark() {
+        let now = Instant::now();
+        let later = now + HANDSHAKE_GRACE;
+        let mut manager = ConnectionManager::new(10, 10);
+        let peers: Vec<PeerId> = (0..10).map(|_| PeerId::random()).collect();
+        for peer in &peers {
+            manager.connected(*peer, ConnectionDirection::Inbound, now);
+        }
+        // 10 inbound peers, watermark 9: the oldest unauthenticated one goes
+        let provider = peers[0];
+        let trimmed = manager.trim_candidates(|p| *p == provider, later);
+        assert_eq!(trimmed.len(), 1);
+        assert_ne!(trimmed[0], provider);
+        // Peers still within the handshake grace are kept
+        assert!(manager.trim_candidates(|_| false, now).is_empty());
+
+        manager.disconnected(&peers[1], later);
+        assert!(manager.trim_candidates(|_| false, later).is_empty());
+    }
+}
@@ -0,0 +1,435 @@
+//! Keeps this node's registry entry in line with the running server.
+//!
+//! At startup (once the first confirmed external P2P addresses are known, or after
+//! `FIRST_ADDRESSES_TIMEOUT`), then every `METADATA_RECONCILE_SECS` and whenever the
+//! endpoint or the confirmed P2P addresses change, the reconciler reads `getNodeInfo` and
+//! `getProviderMetadata` and:
+//!
+//! - registers the node when the registry has no record of it (`registerStorageNode`)
+//! - reports a record that is no longer active (the node was unregistered and cannot
+//!   register again)
+//! - calls `updateStorageAllocation` when `STORAGE_LIMIT_GB` changed
+//! - calls `updateProviderMetadata` when the endpoint or the P2P addresses drifted
+//!
+//! Writes are sent at most once per `METADATA_UPDATE_MIN_SECS`; drift found sooner waits for
+//! the next check. The endpoint is `PUBLIC_ENDPOINT`, or the local network address
+//! discovered again at every check when that endpoint is local. Progress is served by
+//! `GET /admin/metadata`.
+
+use std::collections::HashSet;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use anyhow::{bail, Result};
+use serde::Serialize;
+
+use crate::contract::{MassaClient, SubmittedCall};
+use crate::operations::{OperationStatus, SharedOperations};
+use crate::p2p::SharedP2pState;
+use crate::registry::ProviderMetadata;
+use crate::state::{now_secs, StateCell};
+
+/// Times an expired registry write is re-sent before giving up.
+const WRITE_RESUBMITS: u32 = 2;
+/// How often the endpoint and the confirmed P2P addresses are compared with the last check.
+const LOCAL_CHECK_INTERVAL: Duration = Duration::from_secs(60);
+/// How long the first check waits for confirmed external P2P addresses.
+const FIRST_ADDRESSES_TIMEOUT: Duration = Duration::from_secs(300);
+
+/// Reconciliation interval and write rate limit.
+#[derive(Debug, Clone, Copy)]
+pub struct MetadataConfig {
+    /// Pause between two comparisons with the contract.
+    pub interval: Duration,
+    /// Minimum delay between two registry writes.
+    pub min_update_interval: Duration,
+}
+
+/// Returns true if the endpoint is local/unreachable (0.0.0.0, 127.0.0.1, localhost).
+fn is_local_endpoint(endpoint: &str) -> bool {
+    let lower = endpoint.to_lowercase();
+    lower.contains("0.0.0.0") || lower.contains("127.0.0.1") || lower.contains("localhost")
+}
+
+/// Tries to get a reachable endpoint for the local network (e.g. http://192.168.0.4:4343)
+/// when binding to 0.0.0.0. Uses a UDP "connect" to discover the local IP. Port is taken from bind_address.
+fn try_local_network_endpoint(bind_address: &str) -> Option<String> {
+    let port = bind_address
+        .rsplit(':')
+        .next()
+        .unwrap_or("4343")
+        .trim();
+    let ip = (|| {
+        let socket = std::net::UdpSocket::bind("0.0.0.0:0").ok()?;
+        socket.connect("8.8.8.8:80").ok()?;
+        let addr = socket.local_addr().ok()?;
+        let ip = addr.ip();
+        if ip.is_loopback() {
+            return None;
+        }
+        Some(ip)
+    })()?;
+    Some(format!("http://{}:{}", ip, port))
+}
+
+/// Endpoint and P2P addresses this node wants on the contract. Empty when unknown.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct WantedMetadata {
+    pub endpoint: String,
+    pub p2p_addrs: Vec<String>,
+}
+
+/// What differs between the metadata on the contract and `wanted`, or `None`.
+///
+/// An unknown endpoint or an empty set of confirmed P2P addresses keeps what the contract
+/// holds: losing them for a while (failed discovery, AutoNAT probes) is not drift.
+fn metadata_drift(onchain: Option<&ProviderMetadata>, wanted: &WantedMetadata) -> Option<String> {
+    let (endpoint, p2p_addrs) = match onchain {
+        Some(metadata) => (metadata.endpoint.as_str(), metadata.p2p_addrs.as_slice()),
+        None if wanted.endpoint.is_empty() && wanted.p2p_addrs.is_empty() => return None,
+        None => return Some("no metadata on the contract".to_string()),
+    };
+    let mut drift = Vec::new();
+    if !wanted.endpoint.is_empty() && wanted.endpoint != endpoint {
+        drift.push(format!("endpoint {:?} instead of {:?}", endpoint, wanted.endpoint));
+    }
+    let onchain_addrs: HashSet<&String> = p2p_addrs.iter().collect();
+    let wanted_addrs: HashSet<&String> = wanted.p2p_addrs.iter().collect();
+    if !wanted_addrs.is_empty() && onchain_addrs != wanted_addrs {
+        drift.push(format!(
+            "{} of {} P2P addresses missing, {} stale",
+            wanted_addrs.difference(&onchain_addrs).count(),
+            wanted_addrs.len(),
+            onchain_addrs.difference(&wanted_addrs).count()
+        ));
+    }
+    (!drift.is_empty()).then(|| drift.join("; "))
+}
+
+/// Reconciler state, as returned by `GET /admin/metadata`.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct MetadataStatus {
+    pub address: String,
+    /// Metadata this node wants on the contract.
+    pub wanted: WantedMetadata,
+    /// Whether the registry has a record of this node, as of the last check.
+    pub registered: Option<bool>,
+    /// Whether that record is active, as of the last check.
+    pub active: Option<bool>,
+    pub onchain_endpoint: Option<String>,
+    pub onchain_p2p_addrs: Vec<String>,
+    /// Drift found by the last check and not corrected yet.
+    pub drift: Option<String>,
+    /// Unix time in seconds.
+    pub last_check: Option<u64>,
+    /// Unix time in seconds of the last registry write.
+    pub last_update: Option<u64>,
+    /// Registry writes sent since startup.
+    pub updates: u64,
+    pub last_error: Option<String>,
+}
+
+/// Keeps the registry entry of `address` in line with this server.
+pub struct MetadataReconciler {
+    registry: Arc<MassaClient>,
+    operations: SharedOperations,
+    p2p: SharedP2pState,
+    address: String,
+    storage_limit_gb: u64,
+    public_endpoint: String,
+    bind_address: String,
+    config: MetadataConfig,
+    status: StateCell<MetadataStatus>,
+}
+
+pub type SharedMetadataReconciler = Arc<MetadataReconciler>;
+
+/// Start the reconciler for the node registered as `address`.
+#[allow(clippy::too_many_arguments)]
+pub fn spawn(
+    registry: Arc<MassaClient>,
+    operations: SharedOperations,
+    p2p: SharedP2pState,
+    address: String,
+    storage_limit_gb: u64,
+    public_endpoint: String,
+    bind_address: String,
+    config: MetadataConfig,
+) -> SharedMetadataReconciler {
+    let reconciler = Arc::new(MetadataReconciler {
+        registry,
+        operations,
+        p2p,
+        address: address.clone(),
+        storage_limit_gb,
+        public_endpoint,
+        bind_address,
+        config,
+        status: StateCell::new(
+            MetadataStatus {
+                address,
+                ..MetadataStatus::default()
+            },
+            "metadata status",
+        ),
+    });
+    let worker = reconciler.clone();
+    tokio::spawn(async move { worker.run().await });
+    reconciler
+}
+
+impl MetadataReconciler {
+    pub fn status(&self) -> MetadataStatus {
+        self.status.snapshot()
+    }
+
+    /// Endpoint for the contract: `PUBLIC_ENDPOINT`, or the local network address when that
+    /// endpoint is local (empty when it cannot be discovered).
+    fn endpoint(&self) -> String {
+        if !is_local_endpoint(&self.public_endpoint) {
+            return self.public_endpoint.clone();
+        }
+        try_local_network_endpoint(&self.bind_address).unwrap_or_default()
+    }
+
+    /// Endpoint and confirmed external P2P addresses (AutoNAT, relay reservations), sorted.
+    async fn wanted(&self) -> WantedMetadata {
+        let p2p = self.p2p.read().await;
+        let peer_id = p2p.local_peer_id.to_string();
+        let mut p2p_addrs: Vec<String> = p2p
+            .external_addrs
+            .iter()
+            .map(|a| format!("{}/p2p/{}", a, peer_id))
+            .collect();
+        drop(p2p);
+        p2p_addrs.sort();
+        p2p_addrs.dedup();
+        WantedMetadata {
+            endpoint: self.endpoint(),
+            p2p_addrs,
+        }
+    }
+
+    async fn run(&self) {
+        // Wait for the first confirmed external P2P addresses, so they are in the registration
+        let mut backoff = Duration::from_millis(500);
+        let deadline = Instant::now() + FIRST_ADDRESSES_TIMEOUT;
+        let mut wanted = self.wanted().await;
+        while wanted.p2p_addrs.is_empty() && Instant::now() < deadline {
+            tracing::debug!(?backoff, "waiting for confirmed external P2P addresses");
+            tokio::time::sleep(backoff).await;
+            backoff = std::cmp::min(backoff * 2, Duration::from_secs(8));
+            wanted = self.wanted().await;
+        }
+        if wanted.p2p_addrs.is_empty() {
+            tracing::warn!("no confirmed external P2P address; publishing without P2P addresses");
+        }
+        if is_local_endpoint(&self.public_endpoint) {
+            match wanted.endpoint.as_str() {
+                "" => tracing::info!(
+                    endpoint = %self.public_endpoint,
+                    "endpoint is local (0.0.0.0 / 127.0.0.1 / localhost); could not discover local network IP; not storing endpoint in contract metadata"
+                ),
+                discovered => tracing::info!(
+                    bind = %self.bind_address,
+                    endpoint = %discovered,
+                    "endpoint is local; using discovered local network IP for contract"
+                ),
+            }
+        }
+
+        let mut last_write: Option<Instant> = None;
+        loop {
+            let result = self.check(&wanted, &mut last_write).await;
+            self.status.update(|status| {
+                status.last_check = Some(now_secs());
+                status.last_error = result.as_ref().err().map(|e| format!("{:#}", e));
+            });
+            if let Err(e) = result {
+                tracing::warn!(error = %e, "provider metadata check failed");
+            }
+
+            // Check again after the interval, or as soon as the endpoint or addresses change
+            let checked = Instant::now();
+            loop {
+                tokio::time::sleep(LOCAL_CHECK_INTERVAL).await;
+                let current = self.wanted().await;
+                if current != wanted {
+                    tracing::info!(
+                        endpoint = %current.endpoint,
+                        addrs = ?current.p2p_addrs,
+                        "endpoint or confirmed external P2P addresses changed; checking provider metadata"
+                    );
+                    wanted = current;
+                    break;
+                }
+                if checked.elapsed() >= self.config.interval {
+                    break;
+                }
+            }
+        }
+    }
+
+    /// True when a registry write may be sent now.
+    fn write_allowed(&self, last_write: Option<Instant>) -> bool {
+        last_write.is_none_or(|at| at.elapsed() >= self.config.min_update_interval)
+    }
+
+    /// Compare the registry entry with `wanted` and correct it, within the rate limit.
+    async fn check(&self, wanted: &WantedMetadata, last_write: &mut Option<Instant>) -> Result<()> {
+        self.status.update(|status| status.wanted = wanted.clone());
+        let node = self.registry.get_node_info(&self.address).await?;
+        let onchain = match &node {
+            Some(_) => self.registry.find_provider_metadata(&self.address).await?,
+            None => None,
+        };
+        let drift = match &node {
+            None => Some("node is not registered".to_string()),
+            Some(_) => metadata_drift(onchain.as_ref(), wanted),
+        };
+        self.status.update(|status| {
+            status.registered = Some(node.is_some());
+            status.active = node.as_ref().map(|n| n.active);
+            status.onchain_endpoint = onchain.as_ref().map(|m| m.endpoint.clone());
+            status.onchain_p2p_addrs = onchain.as_ref().map(|m| m.p2p_addrs.clone()).unwrap_or_default();
+            status.drift = drift.clone();
+        });
+
+        let node = match node {
+            Some(node) if !node.active => {
+                tracing::error!("node is inactive in the registry (unregistered); provider metadata not updated");
+                return Ok(());
+            }
+            node => node,
+        };
+        let allocation_drift = node.as_ref().is_some_and(|n| n.allocated_gb != self.storage_limit_gb);
+        if drift.is_none() && !allocation_drift {
+            tracing::debug!("provider metadata matches the contract");
+            return Ok(());
+        }
+        if !self.write_allowed(*last_write) {
+            tracing::info!(
+                drift = drift.as_deref().unwrap_or("storage allocation"),
+                "provider metadata drifted; update rate-limited until the next check"
+            );
+            return Ok(());
+        }
+        *last_write = Some(Instant::now());
+
+        if node.is_none() {
+            tracing::info!(
+                allocated_gb = self.storage_limit_gb,
+                endpoint = %wanted.endpoint,
+                "node not registered; calling registerStorageNode"
+            );
+            let sent = self
+                .registry
+                .register_storage_node(self.storage_limit_gb, &wanted.endpoint, &wanted.p2p_addrs)
+                .await?;
+            self.follow(sent, "registerStorageNode").await?;
+            self.status.update(|status| status.drift = None);
+            return Ok(());
+        }
+
+        if allocation_drift {
+            self.sync_storage_allocation().await?;
+        }
+        if let Some(drift) = drift {
+            tracing::info!(
+                %drift,
+                endpoint = %wanted.endpoint,
+                addrs = ?wanted.p2p_addrs,
+                "provider metadata drifted; calling updateProviderMetadata"
+            );
+            // Keep what the contract holds for whatever this node does not know right now
+            let onchain = onchain.unwrap_or_default();
+            let endpoint = if wanted.endpoint.is_empty() { &onchain.endpoint } else { &wanted.endpoint };
+            let p2p_addrs = if wanted.p2p_addrs.is_empty() { &onchain.p2p_addrs } else { &wanted.p2p_addrs };
+            let sent = self.registry.update_provider_metadata(endpoint, p2p_addrs).await?;
+            self.follow(sent, "updateProviderMetadata").await?;
+            self.status.update(|status| status.drift = None);
+        }
+        Ok(())
+    }
+
+    /// Bring the allocation of the registered node in line with `STORAGE_LIMIT_GB`, which
+    /// may have changed since registration.
+    async fn sync_storage_allocation(&self) -> Result<()> {
+        let config = self.registry.get_config().await?;
+        if !(config.min_allocated_gb..=config.max_allocated_gb).contains(&self.storage_limit_gb) {
+            tracing::error!(
+                storage_limit_gb = self.storage_limit_gb,
+                min_allocated_gb = config.min_allocated_gb,
+                max_allocated_gb = config.max_allocated_gb,
+                "STORAGE_LIMIT_GB is outside the contract's allocation bounds; allocation not updated"
+            );
+            return Ok(());
+        }
+        tracing::info!(
+            allocated_gb = self.storage_limit_gb,
+            "STORAGE_LIMIT_GB changed; calling updateStorageAllocation"
+        );
+        let sent = self.registry.update_storage_allocation(self.storage_limit_gb).await?;
+        self.follow(sent, "updateStorageAllocation").await
+    }
+
+    /// Wait until `sent` is final; an error unless it executed.
+    async fn follow(&self, sent: SubmittedCall, function: &str) -> Result<()> {
+        let operation_id = sent.operation_id.clone();
+        self.status.update(|status| {
+            status.last_update = Some(now_secs());
+            status.updates += 1;
+        });
+        match self.operations.follow(sent, WRITE_RESUBMITS).await? {
+            OperationStatus::Executed => {
+                tracing::info!(%operation_id, function, "contract write executed");
+                Ok(())
+            }
+            status => bail!("{} {} did not execute: {:?}", function, operation_id, status),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn metadata(endpoint: &str, addrs: &[&str]) -> ProviderMetadata {
+        ProviderMetadata {
+            endpoint: endpoint.to_string(),
+            p2p_addrs: addrs.iter().map(|a| a.to_string()).collect(),
+        }
+    }
+
+    fn wanted(endpoint: &str, addrs: &[&str]) -> WantedMetadata {
+        WantedMetadata {
+            endpoint: endpoint.to_string(),
+            p2p_addrs: addrs.iter().map(|a| a.to_string()).collect(),
+        }
+    }
+
+    #[test]
+    fn drift_ignores_order_and_unknown_values() {
+        let onchain = metadata("http://1.2.3.4:4343", &["/ip4/1.2.3.4/tcp/4001", "/ip4/1.2.3.4/udp/4001/quic-v1"]);
+        let same = wanted("http://1.2.3.4:4343", &["/ip4/1.2.3.4/udp/4001/quic-v1", "/ip4/1.2.3.4/tcp/4001"]);
+        assert_eq!(metadata_drift(Some(&onchain), &same), None);
+        // Addresses or endpoint temporarily unknown: keep what the contract holds
+        assert_eq!(metadata_drift(Some(&onchain), &wanted("http://1.2.3.4:4343", &[])), None);
+        assert_eq!(metadata_drift(Some(&onchain), &wanted("", &["/ip4/1.2.3.4/tcp/4001", "/ip4/1.2.3.4/udp/4001/quic-v1"])), None);
+        assert_eq!(metadata_drift(None, &wanted("", &[])), None);
+    }
+
+    #[test]
+    fn drift_reports_changed_endpoint_and_addresses() {
+        let onchain = metadata("http://1.2.3.4:4343", &["/ip4/1.2.3.4/tcp/4001"]);
+        let moved = wanted("http://5.6.7.8:4343", &["/ip4/5.6.7.8/tcp/4001"]);
+        let drift = metadata_drift(Some(&onchain), &moved).unwrap();
+        assert!(drift.contains("endpoint"), "{}", drift);
+        assert!(drift.contains("1 of 1 P2P addresses missing, 1 stale"), "{}", drift);
+        assert!(metadata_drift(Some(&onchain), &wanted("http://1.2.3.4:4343", &["/ip4/1.2.3.4/tcp/4001", "/ip4/1.2.3.4/tcp/4002"])).is_some());
+        assert_eq!(
+            metadata_drift(None, &moved).as_deref(),
+            Some("no metadata on the contract")
+        );
+    }
+}
@@ -0,0 +1,566 @@
+//! Read-through retrieval of blobs held only by other providers.
+//!
+//! When enabled (`READ_THROUGH`), a `GET /data/...` miss asks the connected, authenticated
+//! providers in decreasing reputation order over the blob protocol (`FetchBlob`, answered with
+//! the data and its metadata). Providers whose P2P request fails (e.g. older versions) are then
+//! asked through the HTTP endpoint they registered in the storage registry. A copy is only
+//! served if its Blake3 hash matches the `content_hash` the provider recorded at upload (sent
+//! as `X-Content-Hash` over HTTP); copies that do not match count as failed transfers.
+//!
+//! HTTP copies are streamed to the client as they arrive, up to [`MAX_BLOB_MESSAGE_BYTES`],
+//! and hashed on the way: a copy that fails the check is cut off at its end, so the client
+//! sees a truncated response instead of the last chunk.
+//!
+//! The hash comes from the provider that sends the data, so the check only covers transport
+//! and storage integrity (corruption on the wire or on the provider's disk). It does not
+//! authenticate the content: a provider that lies about both the data and its hash is not
+//! detected. Clients that need that must check the data against a hash obtained elsewhere.
+//!
+//! Fetched blobs can be kept in a cache under `{storage}/.cache/{namespace}/{id}.{hash}`,
+//! bounded by its own quota (`READ_THROUGH_CACHE_MB`) and not counted in the assigned storage.
+//! The least recently used entries are evicted first; hits are re-hashed before being served.
+//! Cache reads and writes (hashing, fsync) run on the blocking thread pool.
+
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use bytes::Bytes;
+use futures::stream::{BoxStream, StreamExt};
+use libp2p::PeerId;
+
+use crate::bandwidth::SharedBandwidth;
+use crate::contract::MassaClient;
+use crate::p2p::{blob_request, BlobRequest, BlobResponse, SharedP2pState, MAX_BLOB_MESSAGE_BYTES};
+use crate::storage::{content_hash, sanitize_segment, write_atomic, Storage, TMP_SUFFIX};
+
+/// Response header carrying the Blake3 hash (hex) recorded for the served blob.
+pub const CONTENT_HASH_HEADER: &str = "x-content-hash";
+/// Request header set on read-through HTTP fetches: the provider answers from what it holds
+/// instead of fetching in turn, so requests never bounce between providers.
+pub const NO_READ_THROUGH_HEADER: &str = "x-no-read-through";
+/// Maximum number of providers asked for one blob.
+const MAX_PROVIDERS: usize = 8;
+/// Timeout of one HTTP fetch.
+const HTTP_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Blob data matching the hash recorded by the provider that sent it.
+pub struct Fetched {
+    pub data: Vec<u8>,
+    pub content_hash: String,
+}
+
+/// Blob streamed from another provider's HTTP endpoint.
+pub struct Streamed {
+    pub content_hash: String,
+    /// Size announced by the provider (`Content-Length`), when known.
+    pub size: Option<u64>,
+    /// Ends with an error when the data outgrows the announced size or the limit, or does
+    /// not match `content_hash`.
+    pub body: BoxStream<'static, io::Result<Bytes>>,
+}
+
+/// Blob retrieved from the cache or another provider.
+pub enum Retrieved {
+    /// Read whole (cache or P2P) and checked against its hash.
+    Whole(Fetched),
+    /// Streamed from an HTTP endpoint; checked once fully read.
+    Streamed(Streamed),
+}
+
+/// `expected` when it is the hash of `data`; `None` for a mismatch or an unknown hash.
+/// Only an integrity check: `expected` comes from the same source as `data`.
+fn matches_hash(data: &[u8], expected: Option<&str>) -> Option<String> {
+    let expected = expected?;
+    (content_hash(data) == expected).then(|| expected.to_string())
+}
+
+struct CacheEntry {
+    hash: String,
+    size: u64,
+    last_used: u64,
+}
+
+#[derive(Default)]
+struct CacheIndex {
+    used: u64,
+    /// Access counter; the entry with the smallest `last_used` is evicted first.
+    clock: u64,
+    entries: HashMap<(String, String), CacheEntry>,
+}
+
+/// Size-bounded LRU cache of fetched blobs.
+pub struct BlobCache {
+    dir: PathBuf,
+    quota: u64,
+    index: Mutex<CacheIndex>,
+}
+
+impl BlobCache {
+    /// Open the cache in `dir`, indexing existing entries by modification time and dropping
+    /// leftover temp files and entries over `quota`.
+    pub fn open(dir: PathBuf, quota: u64) -> io::Result<Self> {
+        fs::create_dir_all(&dir)?;
+        let mut found = Vec::new();
+        for ns_entry in fs::read_dir(&dir)? {
+            let ns_entry = ns_entry?;
+            if !ns_entry.path().is_dir() {
+                continue;
+            }
+            let ns = ns_entry.file_name().to_string_lossy().to_string();
+            for entry in fs::read_dir(ns_entry.path())? {
+                let entry = entry?;
+                let name = entry.file_name().to_string_lossy().to_string();
+                let parsed = name.split_once('.').filter(|_| !name.ends_with(TMP_SUFFIX));
+                let Some((id, hash)) = parsed else {
+                    fs::remove_file(entry.path())?;
+                    continue;
+                };
+                let metadata = entry.metadata()?;
+                let modified = metadata.modified().ok();
+                found.push((modified, ns.clone(), id.to_string(), hash.to_string(), metadata.len()));
+            }
+        }
+        found.sort_by_key(|(modified, ..)| *modified);
+
+        let cache = Self {
+            dir,
+            quota,
+            index: Mutex::new(CacheIndex::default()),
+        };
+        {
+            let mut index = cache.index.lock().unwrap();
+            for (_, ns, id, hash, size) in found {
+                index.clock += 1;
+                index.used += size;
+                let last_used = index.clock;
+                index.entries.insert((ns, id), CacheEntry { hash, size, last_used });
+            }
+            cache.evict(&mut index, 0);
+        }
+        Ok(cache)
+    }
+
+    fn path(&self, key: &(String, String), hash: &str) -> PathBuf {
+        self.dir.join(&key.0).join(format!("{}.{}", key.1, hash))
+    }
+
+    fn remove(&self, index: &mut CacheIndex, key: &(String, String)) {
+        if let Some(entry) = index.entries.remove(key) {
+            index.used -= entry.size;
+            let _ = fs::remove_file(self.path(key, &entry.hash));
+        }
+    }
+
+    /// Evict least recently used entries until `additional` bytes fit in the quota.
+    fn evict(&self, index: &mut CacheIndex, additional: u64) {
+        while index.used + additional > self.quota {
+            let Some(key) = index
+                .entries
+                .iter()
+                .min_by_key(|(_, entry)| entry.last_used)
+                .map(|(key, _)| key.clone())
+            else {
+                break;
+            };
+            self.remove(index, &key);
+        }
+    }
+
+    /// Cached copy of a blob. A copy that no longer matches its hash is dropped.
+    pub fn get(&self, namespace: &str, id: &str) -> Option<Fetched> {
+        let key = (sanitize_segment(namespace), sanitize_segment(id));
+        let hash = {
+            let mut index = self.index.lock().unwrap();
+            index.clock += 1;
+            let clock = index.clock;
+            let entry = index.entries.get_mut(&key)?;
+            entry.last_used = clock;
+            entry.hash.clone()
+        };
+        let data = fs::read(self.path(&key, &hash)).ok();
+        match data.filter(|data| matches_hash(data, Some(&hash)).is_some()) {
+            Some(data) => Some(Fetched {
+                data,
+                content_hash: hash,
+            }),
+            None => {
+                tracing::warn!(namespace, id, "read-through: dropping unreadable or corrupt cache entry");
+                self.remove(&mut self.index.lock().unwrap(), &key);
+                None
+            }
+        }
+    }
+
+    /// Cache a fetched blob, evicting older entries. Blobs larger than the quota are skipped.
+    pub fn put(&self, namespace: &str, id: &str, blob: &Fetched) -> io::Result<()> {
+        let size = blob.data.len() as u64;
+        if size > self.quota {
+            return Ok(());
+        }
+        let key = (sanitize_segment(namespace), sanitize_segment(id));
+        let mut index = self.index.lock().unwrap();
+        self.remove(&mut index, &key);
+        self.evict(&mut index, size);
+        fs::create_dir_all(self.dir.join(&key.0))?;
+        write_atomic(&self.path(&key, &blob.content_hash), &blob.data)?;
+        index.clock += 1;
+        index.used += size;
+        let last_used = index.clock;
+        index.entries.insert(
+            key,
+            CacheEntry {
+                hash: blob.content_hash.clone(),
+                size,
+                last_used,
+            },
+        );
+        Ok(())
+    }
+
+    /// Bytes used by cached blobs.
+    pub fn used(&self) -> u64 {
+        self.index.lock().unwrap().used
+    }
+}
+
+/// Fetches blobs missing locally from other providers.
+pub struct ReadThrough {
+    p2p: SharedP2pState,
+    registry: Arc<MassaClient>,
+    bandwidth: SharedBandwidth,
+    http: reqwest::Client,
+    cache: Option<Arc<BlobCache>>,
+}
+
+pub type SharedReadThrough = Arc<ReadThrough>;
+
+impl ReadThrough {
+    /// `cache_bytes` = 0 disables the cache.
+    pub fn new(
+        storage: &Storage,
+        p2p: SharedP2pState,
+        registry: Arc<MassaClient>,
+        bandwidth: SharedBandwidth,
+        cache_bytes: u64,
+    ) -> Self {
+        let cache = if cache_bytes > 0 {
+            match BlobCache::open(storage.cache_dir(), cache_bytes) {
+                Ok(cache) => {
+                    tracing::info!(quota = cache_bytes, used = cache.used(), "read-through cache opened");
+                    Some(Arc::new(cache))
+                }
+                Err(e) => {
+                    tracing::warn!(error = %e, "failed to open read-through cache; caching disabled");
+                    None
+                }
+            }
+        } else {
+            None
+        };
+        Self {
+            p2p,
+            registry,
+            bandwidth,
+            http: reqwest::Client::builder()
+                .timeout(HTTP_TIMEOUT)
+                .build()
+                .unwrap_or_default(),
+            cache,
+        }
+    }
+
+    /// Cached copy of a blob, if any.
+    pub async fn cached(&self, namespace: &str, id: &str) -> Option<Fetched> {
+        let cache = self.cache.clone()?;
+        let (namespace, id) = (namespace.to_string(), id.to_string());
+        tokio::task::spawn_blocking(move || cache.get(&namespace, &id))
+            .await
+            .ok()
+            .flatten()
+    }
+
+    /// Fetch a copy matching its recorded hash from connected providers, caching it when the cache is enabled.
+    pub async fn fetch(&self, namespace: &str, id: &str) -> Option<Retrieved> {
+        let (namespace, id) = (sanitize_segment(namespace), sanitize_segment(id));
+        if namespace.is_empty() || id.is_empty() {
+            return None;
+        }
+        let fetched = match self.fetch_from_providers(&namespace, &id).await? {
+            Retrieved::Whole(fetched) => fetched,
+            streamed => return Some(streamed),
+        };
+        let Some(cache) = self.cache.clone() else {
+            return Some(Retrieved::Whole(fetched));
+        };
+        // The data is handed back once written, for the response
+        tokio::task::spawn_blocking(move || {
+            if let Err(e) = cache.put(&namespace, &id, &fetched) {
+                tracing::warn!(namespace, id, error = %e, "read-through: failed to cache blob");
+            }
+            Retrieved::Whole(fetched)
+        })
+        .await
+        .ok()
+    }
+
+    async fn fetch_from_providers(&self, namespace: &str, id: &str) -> Option<Retrieved> {
+        let providers: Vec<(PeerId, Option<String>)> = {
+            let s = self.p2p.read().await;
+            s.provider_peers()
+                .into_iter()
+                .take(MAX_PROVIDERS)
+                .map(|peer| {
+                    let massa_address = s
+                        .connected_peers
+                        .get(&peer)
+                        .and_then(|info| info.massa_address.clone());
+                    (peer, massa_address)
+                })
+                .collect()
+        };
+
+        let mut http_fallback = Vec::new();
+        for (peer, massa_address) in providers {
+            let request = BlobRequest::FetchBlob {
+                namespace: namespace.to_string(),
+                id: id.to_string(),
+            };
+            match blob_request(&self.p2p, peer, request).await {
+                Ok(BlobResponse::BlobWithMeta { data, meta }) => {
+                    match matches_hash(&data, meta.content_hash.as_deref()) {
+                        Some(content_hash) => {
+                            tracing::info!(namespace, id, %peer, size = data.len(), "read-through: fetched blob over P2P");
+                            return Some(Retrieved::Whole(Fetched { data, content_hash }));
+                        }
+                        None => {
+                            tracing::warn!(namespace, id, %peer, "read-through: peer copy does not match its recorded hash");
+                            self.p2p.write().await.record_transfer(peer, false);
+                        }
+                    }
+                }
+                Ok(_) => {}
+                Err(e) => {
+                    tracing::debug!(namespace, id, %peer, error = %e, "read-through: P2P fetch failed");
+                    if let Some(massa_address) = massa_address {
+                        http_fallback.push((peer, massa_address));
+                    }
+                }
+            }
+        }
+
+        for (peer, massa_address) in http_fallback {
+            match self.fetch_http(peer, &massa_address, namespace, id).await {
+                Ok(Some(streamed)) => {
+                    tracing::info!(namespace, id, %peer, size = ?streamed.size, "read-through: streaming blob over HTTP");
+                    return Some(Retrieved::Streamed(streamed));
+                }
+                Ok(None) => {}
+                Err(e) => {
+                    tracing::debug!(namespace, id, %peer, error = %e, "read-through: HTTP fetch failed")
+                }
+            }
+        }
+        None
+    }
+
+    /// GET the blob from the provider's registered endpoint. `Ok(None)` when it does not hold it.
+    /// Copies without a recorded hash, or announced larger than the limit, are refused.
+    async fn fetch_http(
+        &self,
+        peer: PeerId,
+        massa_address: &str,
+        namespace: &str,
+        id: &str,
+    ) -> Result<Option<Streamed>, String> {
+        let provider = self
+            .registry
+            .get_provider_metadata(massa_address)
+            .await
+            .map_err(|e| format!("failed to read provider metadata: {}", e))?;
+        if provider.endpoint.is_empty() {
+            return Err("provider has no registered endpoint".to_string());
+        }
+        let url = format!(
+            "{}/data/{}/{}",
+            provider.endpoint.trim_end_matches('/'),
+            namespace,
+            id
+        );
+        let response = self
+            .http
+            .get(&url)
+            .header(NO_READ_THROUGH_HEADER, "1")
+            .send()
+            .await
+            .map_err(|e| e.to_string())?;
+        if response.status() == reqwest::StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
+        if !response.status().is_success() {
+            return Err(format!("{} returned {}", url, response.status()));
+        }
+        let content_hash = response
+            .headers()
+            .get(CONTENT_HASH_HEADER)
+            .and_then(|v| v.to_str().ok())
+            .map(str::to_string)
+            .ok_or_else(|| format!("{} sent no {}", url, CONTENT_HASH_HEADER))?;
+        let size = response.content_length();
+        if size.is_some_and(|size| size > MAX_BLOB_MESSAGE_BYTES) {
+            return Err(format!("{} announced {} bytes, over the {} byte limit", url, size.unwrap_or(0), MAX_BLOB_MESSAGE_BYTES));
+        }
+        let body = HttpBody {
+            response,
+            peer,
+            namespace: namespace.to_string(),
+            id: id.to_string(),
+            expected: content_hash.clone(),
+            limit: size.unwrap_or(MAX_BLOB_MESSAGE_BYTES),
+            received: 0,
+            hasher: blake3::Hasher::new(),
+            copy: self.cache.as_ref().map(|_| Vec::new()),
+            cache: self.cache.clone(),
+            p2p: self.p2p.clone(),
+            bandwidth: self.bandwidth.clone(),
+        };
+        Ok(Some(Streamed {
+            content_hash,
+            size,
+            body: body.into_stream(),
+        }))
+    }
+}
+
+/// Blob being read from an HTTP endpoint: each chunk is throttled, counted and hashed, and
+/// the end of the body is checked against the recorded hash before it is reported.
+struct HttpBody {
+    response: reqwest::Response,
+    peer: PeerId,
+    namespace: String,
+    id: String,
+    expected: String,
+    limit: u64,
+    received: u64,
+    hasher: blake3::Hasher,
+    /// Copy for the cache, dropped once it outgrows the cache quota.
+    copy: Option<Vec<u8>>,
+    cache: Option<Arc<BlobCache>>,
+    p2p: SharedP2pState,
+    bandwidth: SharedBandwidth,
+}
+
+impl HttpBody {
+    fn into_stream(self) -> BoxStream<'static, io::Result<Bytes>> {
+        futures::stream::unfold(Some(self), |body| async move {
+            let mut body = body?;
+            match body.next_chunk().await {
+                Some(Ok(chunk)) => Some((Ok(chunk), Some(body))),
+                Some(Err(e)) => Some((Err(e), None)),
+                None => None,
+            }
+        })
+        .boxed()
+    }
+
+    /// Next chunk of data; `None` once the whole copy matched its hash.
+    async fn next_chunk(&mut self) -> Option<io::Result<Bytes>> {
+        let chunk = match self.response.chunk().await {
+            Ok(Some(chunk)) => chunk,
+            Ok(None) => return self.finish().await.err().map(Err),
+            Err(e) => return Some(Err(io::Error::other(e))),
+        };
+        self.received += chunk.len() as u64;
+        if self.received > self.limit {
+            return Some(Err(self.reject("is larger than announced or allowed").await));
+        }
+        self.bandwidth.throttle(0, chunk.len() as u64).await;
+        self.hasher.update(&chunk);
+        let quota = self.cache.as_ref().map_or(0, |cache| cache.quota);
+        if let Some(copy) = &mut self.copy {
+            if (copy.len() + chunk.len()) as u64 > quota {
+                self.copy = None;
+            } else {
+                copy.extend_from_slice(&chunk);
+            }
+        }
+        Some(Ok(chunk))
+    }
+
+    /// Check the complete copy; a matching one is counted and cached in the background.
+    async fn finish(&mut self) -> io::Result<()> {
+        if self.hasher.finalize().to_hex().as_str() != self.expected {
+            return Err(self.reject("does not match its recorded hash").await);
+        }
+        tracing::info!(namespace = %self.namespace, id = %self.id, peer = %self.peer, size = self.received, "read-through: fetched blob over HTTP");
+        self.p2p.write().await.record_transfer(self.peer, true);
+        if let (Some(cache), Some(data)) = (self.cache.clone(), self.copy.take()) {
+            let (namespace, id) = (self.namespace.clone(), self.id.clone());
+            let blob = Fetched {
+                data,
+                content_hash: self.expected.clone(),
+            };
+            tokio::task::spawn_blocking(move || {
+                if let Err(e) = cache.put(&namespace, &id, &blob) {
+                    tracing::warn!(namespace, id, error = %e, "read-through: failed to cache blob");
+                }
+            });
+        }
+        Ok(())
+    }
+
+    /// Count a bad copy against the provider.
+    async fn reject(&self, reason: &str) -> io::Error {
+        tracing::warn!(namespace = %self.namespace, id = %self.id, peer = %self.peer, "read-through: HTTP copy {}", reason);
+        self.p2p.write().await.record_transfer(self.peer, false);
+        io::Error::new(io::ErrorKind::InvalidData, format!("read-through copy {}", reason))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn blob(data: &[u8]) -> Fetched {
+        Fetched {
+            data: data.to_vec(),
+            content_hash: content_hash(data),
+        }
+    }
+
+    #[test]
+    fn only_matching_hashes_are_accepted() {
+        let hash = content_hash(b"data");
+        assert_eq!(matches_hash(b"data", Some(&hash)), Some(hash.clone()));
+        assert_eq!(matches_hash(b"other", Some(&hash)), None);
+        assert_eq!(matches_hash(b"data", None), None);
+    }
+
+    #[test]
+    fn cache_evicts_least_recently_used() {
+        let dir = std::env::temp_dir().join(format!("massa-cache-test-{}", uuid::Uuid::new_v4()));
+        let cache = BlobCache::open(dir.clone(), 10).unwrap();
+        cache.put("ns", "a", &blob(b"aaaa")).unwrap();
+        cache.put("ns", "b", &blob(b"bbbb")).unwrap();
+        assert_eq!(cache.get("ns", "a").unwrap().data, b"aaaa");
+        // "b" is the least recently used entry
+        cache.put("ns", "c", &blob(b"cccc")).unwrap();
+        assert!(cache.get("ns", "b").is_none());
+        assert_eq!(cache.used(), 8);
+        // Larger than the quota: not cached
+        cache.put("ns", "d", &blob(b"dddddddddddd")).unwrap();
+        assert!(cache.get("ns", "d").is_none());
+
+        // Entries survive a restart; a corrupt entry is dropped on read
+        let reopened = BlobCache::open(dir.clone(), 10).unwrap();
+        assert_eq!(reopened.used(), 8);
+        fs::write(dir.join("ns").join(format!("a.{}", content_hash(b"aaaa"))), b"xxxx").unwrap();
+        assert!(reopened.get("ns", "a").is_none());
+        assert_eq!(reopened.get("ns", "c").unwrap().data, b"cccc");
+        assert_eq!(reopened.used(), 4);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
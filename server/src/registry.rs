@@ -0,0 +1,608 @@
+//! Typed bindings for the storage registry contract (`storage-registry.ts`).
+//!
+//! - Struct codecs matching the contract's `Serializable` layouts
+//!   (`StorageNode`, `Challenge`, `PeriodStats`, `StorageConfig`)
+//! - [`RegistryCall`]: every state-changing entrypoint and its `Args` encoding
+//! - [`RegistryView`]: every read-only entrypoint and its `Args` encoding
+//! - Decoders for view results (`u64ToBytes` values and `Args` payloads)
+//!
+//! This module only encodes and decodes; `MassaClient` sends the calls.
+
+use crate::args::{Args, ArgsError};
+
+/// Contract struct with the same field order as its AssemblyScript `serialize()`.
+pub trait Serializable: Sized {
+    fn encode(&self, args: &mut Args);
+    fn decode(args: &mut Args) -> Result<Self, ArgsError>;
+
+    fn to_bytes(&self) -> Vec<u8> {
+        let mut args = Args::new();
+        self.encode(&mut args);
+        args.into_bytes()
+    }
+
+    fn from_bytes(data: Vec<u8>) -> Result<Self, ArgsError> {
+        Self::decode(&mut Args::from_bytes(data))
+    }
+}
+
+/// Storage node record (`getNodeInfo`).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StorageNode {
+    pub address: String,
+    pub allocated_gb: u64,
+    pub registered_period: u64,
+    pub total_challenges: u64,
+    pub passed_challenges: u64,
+    pub pending_rewards: u64,
+    /// `u64::MAX` when never challenged.
+    pub last_challenged_period: u64,
+    pub last_rewarded_period: u64,
+    pub active: bool,
+}
+
+impl Serializable for StorageNode {
+    fn encode(&self, args: &mut Args) {
+        args.add_string(&self.address)
+            .add_u64(self.allocated_gb)
+            .add_u64(self.registered_period)
+            .add_u64(self.total_challenges)
+            .add_u64(self.passed_challenges)
+            .add_u64(self.pending_rewards)
+            .add_u64(self.last_challenged_period)
+            .add_u64(self.last_rewarded_period)
+            .add_bool(self.active);
+    }
+
+    fn decode(args: &mut Args) -> Result<Self, ArgsError> {
+        Ok(Self {
+            address: args.next_string()?,
+            allocated_gb: args.next_u64()?,
+            registered_period: args.next_u64()?,
+            total_challenges: args.next_u64()?,
+            passed_challenges: args.next_u64()?,
+            pending_rewards: args.next_u64()?,
+            last_challenged_period: args.next_u64()?,
+            last_rewarded_period: args.next_u64()?,
+            active: args.next_bool()?,
+        })
+    }
+}
+
+/// Storage proof challenge (`getChallengeInfo`).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Challenge {
+    pub id: String,
+    pub node_address: String,
+    pub chunk_id: String,
+    pub nonce: u64,
+    pub issued_period: u64,
+    /// Millisecond timestamp after which the challenge can be expired.
+    pub deadline: u64,
+    pub resolved: bool,
+    pub passed: bool,
+}
+
+impl Serializable for Challenge {
+    fn encode(&self, args: &mut Args) {
+        args.add_string(&self.id)
+            .add_string(&self.node_address)
+            .add_string(&self.chunk_id)
+            .add_u64(self.nonce)
+            .add_u64(self.issued_period)
+            .add_u64(self.deadline)
+            .add_bool(self.resolved)
+            .add_bool(self.passed);
+    }
+
+    fn decode(args: &mut Args) -> Result<Self, ArgsError> {
+        Ok(Self {
+            id: args.next_string()?,
+            node_address: args.next_string()?,
+            chunk_id: args.next_string()?,
+            nonce: args.next_u64()?,
+            issued_period: args.next_u64()?,
+            deadline: args.next_u64()?,
+            resolved: args.next_bool()?,
+            passed: args.next_bool()?,
+        })
+    }
+}
+
+/// Per-period reward statistics (`getPeriodStatsView`).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PeriodStats {
+    pub period: u64,
+    pub total_gb_stored: u64,
+    pub total_rewards_distributed: u64,
+    pub active_nodes: u64,
+    pub challenges_issued: u64,
+    pub challenges_passed: u64,
+    pub rewards_distributed: bool,
+}
+
+impl Serializable for PeriodStats {
+    fn encode(&self, args: &mut Args) {
+        args.add_u64(self.period)
+            .add_u64(self.total_gb_stored)
+            .add_u64(self.total_rewards_distributed)
+            .add_u64(self.active_nodes)
+            .add_u64(self.challenges_issued)
+            .add_u64(self.challenges_passed)
+            .add_bool(self.rewards_distributed);
+    }
+
+    fn decode(args: &mut Args) -> Result<Self, ArgsError> {
+        Ok(Self {
+            period: args.next_u64()?,
+            total_gb_stored: args.next_u64()?,
+            total_rewards_distributed: args.next_u64()?,
+            active_nodes: args.next_u64()?,
+            challenges_issued: args.next_u64()?,
+            challenges_passed: args.next_u64()?,
+            rewards_distributed: args.next_bool()?,
+        })
+    }
+}
+
+/// Contract configuration (`getConfigView`, `updateConfig`).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StorageConfig {
+    /// nanoMAS per GB per period.
+    pub reward_per_gb_per_period: u64,
+    pub min_allocated_gb: u64,
+    pub max_allocated_gb: u64,
+    /// Milliseconds a node has to answer a challenge.
+    pub challenge_response_timeout: u64,
+    pub reward_distribution_period: u64,
+}
+
+impl Serializable for StorageConfig {
+    fn encode(&self, args: &mut Args) {
+        args.add_u64(self.reward_per_gb_per_period)
+            .add_u64(self.min_allocated_gb)
+            .add_u64(self.max_allocated_gb)
+            .add_u64(self.challenge_response_timeout)
+            .add_u64(self.reward_distribution_period);
+    }
+
+    fn decode(args: &mut Args) -> Result<Self, ArgsError> {
+        Ok(Self {
+            reward_per_gb_per_period: args.next_u64()?,
+            min_allocated_gb: args.next_u64()?,
+            max_allocated_gb: args.next_u64()?,
+            challenge_response_timeout: args.next_u64()?,
+            reward_distribution_period: args.next_u64()?,
+        })
+    }
+}
+
+/// Network-wide capacity (`getGlobalStorageUsageView`).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GlobalStorageUsage {
+    pub total_allocated_gb: u64,
+    pub total_booked_gb: u64,
+    pub available_gb: u64,
+}
+
+impl Serializable for GlobalStorageUsage {
+    fn encode(&self, args: &mut Args) {
+        args.add_u64(self.total_allocated_gb)
+            .add_u64(self.total_booked_gb)
+            .add_u64(self.available_gb);
+    }
+
+    fn decode(args: &mut Args) -> Result<Self, ArgsError> {
+        Ok(Self {
+            total_allocated_gb: args.next_u64()?,
+            total_booked_gb: args.next_u64()?,
+            available_gb: args.next_u64()?,
+        })
+    }
+}
+
+/// Provider endpoint and P2P multiaddrs (`getProviderMetadataView`).
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ProviderMetadata {
+    /// Empty when not set.
+    pub endpoint: String,
+    pub p2p_addrs: Vec<String>,
+}
+
+impl Serializable for ProviderMetadata {
+    fn encode(&self, args: &mut Args) {
+        args.add_string(&self.endpoint).add_string_array(&self.p2p_addrs);
+    }
+
+    fn decode(args: &mut Args) -> Result<Self, ArgsError> {
+        Ok(Self {
+            endpoint: args.next_string()?,
+            p2p_addrs: args.next_string_array()?,
+        })
+    }
+}
+
+/// State-changing contract entrypoints.
+///
+/// Admin, challenger and deployment entrypoints are listed too so the encodings match the
+/// whole contract, although this server never sends them.
+#[allow(dead_code)]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RegistryCall {
+    /// Deployment arguments; defaults are used when `config` is `None`.
+    Constructor { admin: String, config: Option<StorageConfig> },
+    RegisterStorageNode { allocated_gb: u64, endpoint: String, p2p_addrs: Vec<String> },
+    UpdateStorageAllocation { allocated_gb: u64 },
+    UnregisterStorageNode,
+    IssueChallenge { challenge_id: String, node_address: String, chunk_id: String, nonce: u64 },
+    SubmitProof { challenge_id: String, proof: Vec<u8> },
+    ResolveExpiredChallenges { challenge_ids: Vec<String> },
+    DistributeRewards { period: u64, node_addresses: Vec<String> },
+    ClaimRewards,
+    UpdateProviderMetadata { endpoint: String, p2p_addrs: Vec<String> },
+    /// Payable: `amount_gb * getUploaderPricePerGbView()` coins.
+    RegisterAsUploader { amount_gb: u64 },
+    RecordFileUpload { uploader_address: String, file_size_bytes: u64 },
+    RemoveFileUpload { uploader_address: String, file_size_bytes: u64 },
+    /// Batched `recordFileUpload`; both lists have the same length.
+    RecordFileUploads { uploader_addresses: Vec<String>, file_sizes_bytes: Vec<u64> },
+    UpdateConfig(StorageConfig),
+    AddChallenger { address: String },
+    RemoveChallenger { address: String },
+    AddStorageAdmin { address: String },
+    RemoveStorageAdmin { address: String },
+    SetUploaderPricePerGb { price_per_gb: u64 },
+    SetPaused { paused: bool },
+    TransferAdmin { new_admin: String },
+    /// Payable: the transferred coins fund rewards.
+    FundContract,
+}
+
+impl RegistryCall {
+    /// Exported function name.
+    pub fn function(&self) -> &'static str {
+        match self {
+            Self::Constructor { .. } => "constructor",
+            Self::RegisterStorageNode { .. } => "registerStorageNode",
+            Self::UpdateStorageAllocation { .. } => "updateStorageAllocation",
+            Self::UnregisterStorageNode => "unregisterStorageNode",
+            Self::IssueChallenge { .. } => "issueChallenge",
+            Self::SubmitProof { .. } => "submitProof",
+            Self::ResolveExpiredChallenges { .. } => "resolveExpiredChallenges",
+            Self::DistributeRewards { .. } => "distributeRewards",
+            Self::ClaimRewards => "claimRewards",
+            Self::UpdateProviderMetadata { .. } => "updateProviderMetadata",
+            Self::RegisterAsUploader { .. } => "registerAsUploader",
+            Self::RecordFileUpload { .. } => "recordFileUpload",
+            Self::RemoveFileUpload { .. } => "removeFileUpload",
+            Self::RecordFileUploads { .. } => "recordFileUploads",
+            Self::UpdateConfig(_) => "updateConfig",
+            Self::AddChallenger { .. } => "addChallenger",
+            Self::RemoveChallenger { .. } => "removeChallenger",
+            Self::AddStorageAdmin { .. } => "addStorageAdmin",
+            Self::RemoveStorageAdmin { .. } => "removeStorageAdmin",
+            Self::SetUploaderPricePerGb { .. } => "setUploaderPricePerGbAdmin",
+            Self::SetPaused { .. } => "setPaused",
+            Self::TransferAdmin { .. } => "transferAdmin",
+            Self::FundContract => "fundContract",
+        }
+    }
+
+    /// Serialized call parameter.
+    pub fn args(&self) -> Vec<u8> {
+        let mut args = Args::new();
+        match self {
+            Self::Constructor { admin, config } => {
+                args.add_string(admin);
+                if let Some(config) = config {
+                    config.encode(&mut args);
+                }
+            }
+            Self::RegisterStorageNode { allocated_gb, endpoint, p2p_addrs } => {
+                args.add_u64(*allocated_gb).add_string(endpoint).add_string_array(p2p_addrs);
+            }
+            Self::UpdateStorageAllocation { allocated_gb } => {
+                args.add_u64(*allocated_gb);
+            }
+            Self::UnregisterStorageNode | Self::ClaimRewards | Self::FundContract => {}
+            Self::IssueChallenge { challenge_id, node_address, chunk_id, nonce } => {
+                args.add_string(challenge_id)
+                    .add_string(node_address)
+                    .add_string(chunk_id)
+                    .add_u64(*nonce);
+            }
+            Self::SubmitProof { challenge_id, proof } => {
+                args.add_string(challenge_id).add_bytes(proof);
+            }
+            Self::ResolveExpiredChallenges { challenge_ids } => {
+                args.add_string_array(challenge_ids);
+            }
+            Self::DistributeRewards { period, node_addresses } => {
+                args.add_u64(*period).add_string_array(node_addresses);
+            }
+            Self::UpdateProviderMetadata { endpoint, p2p_addrs } => {
+                args.add_string(endpoint).add_string_array(p2p_addrs);
+            }
+            Self::RegisterAsUploader { amount_gb } => {
+                args.add_u64(*amount_gb);
+            }
+            Self::RecordFileUpload { uploader_address, file_size_bytes }
+            | Self::RemoveFileUpload { uploader_address, file_size_bytes } => {
+                args.add_string(uploader_address).add_u64(*file_size_bytes);
+            }
+            Self::RecordFileUploads { uploader_addresses, file_sizes_bytes } => {
+                args.add_string_array(uploader_addresses).add_u64_array(file_sizes_bytes);
+            }
+            Self::UpdateConfig(config) => config.encode(&mut args),
+            Self::AddChallenger { address }
+            | Self::RemoveChallenger { address }
+            | Self::AddStorageAdmin { address }
+            | Self::RemoveStorageAdmin { address } => {
+                args.add_string(address);
+            }
+            Self::SetUploaderPricePerGb { price_per_gb } => {
+                args.add_u64(*price_per_gb);
+            }
+            Self::SetPaused { paused } => {
+                args.add_bool(*paused);
+            }
+            Self::TransferAdmin { new_admin } => {
+                args.add_string(new_admin);
+            }
+        }
+        args.into_bytes()
+    }
+}
+
+/// Read-only contract entrypoints.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RegistryView {
+    /// Fails with "Node not found" when unregistered.
+    NodeInfo { address: String },
+    ProviderMetadata { address: String },
+    RegisteredAddresses,
+    /// Fails with "Challenge not found" when unknown.
+    ChallengeInfo { challenge_id: String },
+    PeriodStats { period: u64 },
+    Config,
+    TotalNodesCount,
+    NodeAddressAt { index: u64 },
+    GlobalStorageUsage,
+    PendingRewards { address: String },
+    IsStorageAdmin { address: String },
+    IsAllowedUploader { address: String },
+    BookedUploaderGb { address: String },
+    UploaderPricePerGb,
+    UploaderUsage { address: String },
+}
+
+impl RegistryView {
+    /// Exported function name.
+    pub fn function(&self) -> &'static str {
+        match self {
+            Self::NodeInfo { .. } => "getNodeInfo",
+            Self::ProviderMetadata { .. } => "getProviderMetadataView",
+            Self::RegisteredAddresses => "getRegisteredAddressesView",
+            Self::ChallengeInfo { .. } => "getChallengeInfo",
+            Self::PeriodStats { .. } => "getPeriodStatsView",
+            Self::Config => "getConfigView",
+            Self::TotalNodesCount => "getTotalNodesCount",
+            Self::NodeAddressAt { .. } => "getNodeAddressAt",
+            Self::GlobalStorageUsage => "getGlobalStorageUsageView",
+            Self::PendingRewards { .. } => "calculatePendingRewards",
+            Self::IsStorageAdmin { .. } => "getIsStorageAdmin",
+            Self::IsAllowedUploader { .. } => "getIsAllowedUploader",
+            Self::BookedUploaderGb { .. } => "getBookedUploaderGbView",
+            Self::UploaderPricePerGb => "getUploaderPricePerGbView",
+            Self::UploaderUsage { .. } => "getUploaderUsageView",
+        }
+    }
+
+    /// Serialized call parameter.
+    pub fn args(&self) -> Vec<u8> {
+        let mut args = Args::new();
+        match self {
+            Self::NodeInfo { address }
+            | Self::ProviderMetadata { address }
+            | Self::PendingRewards { address }
+            | Self::IsStorageAdmin { address }
+            | Self::IsAllowedUploader { address }
+            | Self::BookedUploaderGb { address }
+            | Self::UploaderUsage { address } => {
+                args.add_string(address);
+            }
+            Self::ChallengeInfo { challenge_id } => {
+                args.add_string(challenge_id);
+            }
+            Self::PeriodStats { period } => {
+                args.add_u64(*period);
+            }
+            Self::NodeAddressAt { index } => {
+                args.add_u64(*index);
+            }
+            Self::RegisteredAddresses
+            | Self::Config
+            | Self::TotalNodesCount
+            | Self::GlobalStorageUsage
+            | Self::UploaderPricePerGb => {}
+        }
+        args.into_bytes()
+    }
+}
+
+/// Decode a `u64ToBytes` result (8 bytes little-endian, no prefix).
+pub fn decode_u64(data: Vec<u8>) -> Result<u64, ArgsError> {
+    Args::from_bytes(data).next_u64()
+}
+
+/// Decode a `u64ToBytes(flag ? 1 : 0)` result.
+pub fn decode_flag(data: Vec<u8>) -> Result<bool, ArgsError> {
+    Ok(decode_u64(data)? == 1)
+}
+
+/// Decode an `Args` string array result; an empty result is an empty list.
+pub fn decode_string_array(data: Vec<u8>) -> Result<Vec<String>, ArgsError> {
+    if data.is_empty() {
+        return Ok(Vec::new());
+    }
+    Args::from_bytes(data).next_string_array()
+}
+
+/// Decode an `Args` string result (`getNodeAddressAt`); empty when out of range.
+pub fn decode_string(data: Vec<u8>) -> Result<String, ArgsError> {
+    Args::from_bytes(data).next_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Little-endian helpers to spell fixtures out byte by byte, the way
+    /// `@massalabs/as-types` lays them out.
+    fn str_bytes(s: &str) -> Vec<u8> {
+        let mut out = (s.len() as u32).to_le_bytes().to_vec();
+        out.extend_from_slice(s.as_bytes());
+        out
+    }
+
+    fn u64_bytes(v: u64) -> Vec<u8> {
+        v.to_le_bytes().to_vec()
+    }
+
+    fn node_fixture() -> Vec<u8> {
+        let mut out = str_bytes("AU12node");
+        for v in [50u64, 1200, 7, 6, 3_000_000_000, u64::MAX, 1190] {
+            out.extend(u64_bytes(v));
+        }
+        out.push(1);
+        out
+    }
+
+    #[test]
+    fn storage_node_round_trips_fixture() {
+        let node = StorageNode::from_bytes(node_fixture()).unwrap();
+        assert_eq!(
+            node,
+            StorageNode {
+                address: "AU12node".into(),
+                allocated_gb: 50,
+                registered_period: 1200,
+                total_challenges: 7,
+                passed_challenges: 6,
+                pending_rewards: 3_000_000_000,
+                last_challenged_period: u64::MAX,
+                last_rewarded_period: 1190,
+                active: true,
+            }
+        );
+        assert_eq!(node.to_bytes(), node_fixture());
+    }
+
+    #[test]
+    fn challenge_and_stats_round_trip_fixtures() {
+        let mut fixture = str_bytes("ch-1");
+        fixture.extend(str_bytes("AU12node"));
+        fixture.extend(str_bytes("default/abc"));
+        for v in [42u64, 1300, 1_700_000_060_000] {
+            fixture.extend(u64_bytes(v));
+        }
+        fixture.extend([1, 0]);
+        let challenge = Challenge::from_bytes(fixture.clone()).unwrap();
+        assert_eq!(challenge.chunk_id, "default/abc");
+        assert_eq!(challenge.deadline, 1_700_000_060_000);
+        assert!(challenge.resolved && !challenge.passed);
+        assert_eq!(challenge.to_bytes(), fixture);
+
+        let mut fixture = Vec::new();
+        for v in [1300u64, 500, 9_000, 4, 4, 3] {
+            fixture.extend(u64_bytes(v));
+        }
+        fixture.push(0);
+        let stats = PeriodStats::from_bytes(fixture.clone()).unwrap();
+        assert_eq!((stats.active_nodes, stats.challenges_passed), (4, 3));
+        assert!(!stats.rewards_distributed);
+        assert_eq!(stats.to_bytes(), fixture);
+
+        let mut fixture = Vec::new();
+        for v in [1_000_000u64, 1, 1000, 60_000, 100] {
+            fixture.extend(u64_bytes(v));
+        }
+        let config = StorageConfig::from_bytes(fixture.clone()).unwrap();
+        assert_eq!(config.challenge_response_timeout, 60_000);
+        assert_eq!(config.to_bytes(), fixture);
+
+        // Truncated structs are rejected rather than zero-filled.
+        assert!(StorageConfig::from_bytes(fixture[..39].to_vec()).is_err());
+    }
+
+    #[test]
+    fn call_and_view_args_match_contract_layout() {
+        let call = RegistryCall::IssueChallenge {
+            challenge_id: "ch-1".into(),
+            node_address: "AU12node".into(),
+            chunk_id: "ns/id".into(),
+            nonce: 9,
+        };
+        let mut expected = str_bytes("ch-1");
+        expected.extend(str_bytes("AU12node"));
+        expected.extend(str_bytes("ns/id"));
+        expected.extend(u64_bytes(9));
+        assert_eq!(call.function(), "issueChallenge");
+        assert_eq!(call.args(), expected);
+
+        let call = RegistryCall::SubmitProof { challenge_id: "ch-1".into(), proof: vec![0xaa, 0xbb] };
+        let mut expected = str_bytes("ch-1");
+        expected.extend(2u32.to_le_bytes());
+        expected.extend([0xaa, 0xbb]);
+        assert_eq!(call.args(), expected);
+
+        let call = RegistryCall::RecordFileUploads {
+            uploader_addresses: vec!["AU1".into(), "AU2".into()],
+            file_sizes_bytes: vec![10, 20],
+        };
+        let mut expected = 14u32.to_le_bytes().to_vec();
+        expected.extend(str_bytes("AU1"));
+        expected.extend(str_bytes("AU2"));
+        expected.extend(16u32.to_le_bytes());
+        expected.extend(u64_bytes(10));
+        expected.extend(u64_bytes(20));
+        assert_eq!(call.args(), expected);
+
+        assert_eq!(RegistryCall::SetPaused { paused: true }.args(), vec![1]);
+        assert!(RegistryCall::ClaimRewards.args().is_empty());
+        assert_eq!(
+            RegistryCall::UpdateStorageAllocation { allocated_gb: 80 }.args(),
+            u64_bytes(80)
+        );
+        assert_eq!(
+            RegistryView::PendingRewards { address: "AU1".into() }.args(),
+            str_bytes("AU1")
+        );
+        assert_eq!(RegistryView::PeriodStats { period: 7 }.args(), u64_bytes(7));
+    }
+
+    #[test]
+    fn decodes_scalar_and_list_results() {
+        assert_eq!(decode_u64(u64_bytes(1234)).unwrap(), 1234);
+        assert!(decode_flag(u64_bytes(1)).unwrap());
+        assert!(!decode_flag(u64_bytes(0)).unwrap());
+        assert!(decode_u64(vec![1, 2, 3]).is_err());
+        assert!(decode_string_array(Vec::new()).unwrap().is_empty());
+        assert_eq!(decode_string(str_bytes("AU12node")).unwrap(), "AU12node");
+
+        let mut addrs = Args::new();
+        addrs.add_string_array(&["AU1".to_string(), "AU2".to_string()]);
+        assert_eq!(
+            decode_string_array(addrs.into_bytes()).unwrap(),
+            vec!["AU1".to_string(), "AU2".to_string()]
+        );
+
+        let metadata = ProviderMetadata {
+            endpoint: "https://node.example".into(),
+            p2p_addrs: vec!["/ip4/1.2.3.4/tcp/4001".into()],
+        };
+        assert_eq!(ProviderMetadata::from_bytes(metadata.to_bytes()).unwrap(), metadata);
+        let usage = GlobalStorageUsage { total_allocated_gb: 100, total_booked_gb: 40, available_gb: 60 };
+        assert_eq!(GlobalStorageUsage::from_bytes(usage.to_bytes()).unwrap(), usage);
+    }
+}
@@ -0,0 +1,620 @@
+//! Replication manager: enforces each blob's redundancy choice over P2P.
+//!
+//! - Full replication: push full copies to `min_replication - 1` other providers.
+//! - Erasure coding: encode the blob into `k + m` shards and place each shard on a
+//!   different provider (see `erasure`).
+//!
+//! Placement (replica peers or shard holders) is recorded in the blob's `.meta` so it
+//! survives restarts. A periodic sweep retries blobs whose placement is incomplete and
+//! rebuilds erasure-coded blobs whose local data was lost from any `k` shards.
+//!
+//! The sweep also reconciles inventories with each provider (see `inventory`): replicas a
+//! peer lost are sent again, and blobs whose local data was lost are fetched back from a peer
+//! holding the same content hash.
+//!
+//! When the node drains (see `drain`), [`ReplicationHandle::hand_off_all`] places every local
+//! blob so that it no longer depends on this node's copy.
+
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use libp2p::PeerId;
+use tokio::sync::mpsc;
+
+use crate::erasure::{self, shard_hash, ErasureMeta};
+use crate::inventory::{reconcile, Inventory, InventoryDiff};
+use crate::p2p::{announce_blob, blob_request, BlobRequest, BlobResponse, SharedP2pState};
+use crate::storage::{content_hash, BlobMeta, Storage};
+
+/// How often the manager re-checks all blobs for incomplete placement.
+const SWEEP_INTERVAL: Duration = Duration::from_secs(120);
+/// How often inventories are reconciled with a connected provider.
+const INVENTORY_SYNC_INTERVAL: Duration = Duration::from_secs(1800);
+
+/// Handle used by the HTTP API to queue freshly uploaded blobs for replication.
+#[derive(Clone)]
+pub struct ReplicationHandle {
+    tx: mpsc::Sender<(String, String)>,
+    manager: Arc<ReplicationManager>,
+}
+
+/// Outcome of one hand-off pass over all local blobs.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct HandOff {
+    /// Blobs that no longer depend on this node's copy.
+    pub handed_off: usize,
+    /// Blobs still waiting for a provider to take them.
+    pub pending: usize,
+}
+
+impl ReplicationHandle {
+    /// Queue a blob for replication. If the queue is full the periodic sweep picks it up later.
+    pub fn enqueue(&self, namespace: &str, id: &str) {
+        if let Err(e) = self.tx.try_send((namespace.to_string(), id.to_string())) {
+            tracing::debug!(namespace, id, error = %e, "replication queue full; left to sweep");
+        }
+    }
+
+    /// Hand every local blob off to other providers, so the node can leave the network:
+    /// full copies go to enough providers to meet the redundancy requirement without this
+    /// node, unplaced shards are placed, and replicas held for others are copied to a provider
+    /// whose inventory lacks them. Blobs in `done` are skipped and those handed off are added
+    /// to it, so the pass can be repeated until `pending` is zero.
+    pub async fn hand_off_all(&self, done: &mut HashSet<(String, String)>) -> std::io::Result<HandOff> {
+        let entries = self.manager.storage.list(None)?;
+        let mut replicas = HashSet::new();
+        for entry in &entries {
+            let key = (entry.namespace.clone(), entry.id.clone());
+            if done.contains(&key) {
+                continue;
+            }
+            if entry.replica {
+                replicas.insert(key);
+            } else if self.manager.hand_off(&key.0, &key.1).await {
+                done.insert(key);
+            }
+        }
+        if !replicas.is_empty() {
+            let local = Inventory::from_index(&entries);
+            self.manager.hand_off_replicas(&local, &replicas, done).await;
+        }
+
+        let pending = entries
+            .iter()
+            .filter(|e| !done.contains(&(e.namespace.clone(), e.id.clone())))
+            .count();
+        Ok(HandOff {
+            handed_off: entries.len() - pending,
+            pending,
+        })
+    }
+}
+
+struct ReplicationManager {
+    storage: Storage,
+    p2p: SharedP2pState,
+}
+
+/// Spawn the replication manager in a background task.
+pub fn spawn(storage: Storage, p2p: SharedP2pState) -> ReplicationHandle {
+    let (tx, mut rx) = mpsc::channel::<(String, String)>(256);
+    let manager = Arc::new(ReplicationManager { storage, p2p });
+    let handle = ReplicationHandle { tx, manager: manager.clone() };
+
+    tokio::spawn(async move {
+        let mut sweep = tokio::time::interval(SWEEP_INTERVAL);
+        // Last inventory sync per connected provider
+        let mut last_sync: HashMap<PeerId, Instant> = HashMap::new();
+        loop {
+            tokio::select! {
+                Some((namespace, id)) = rx.recv() => {
+                    manager.replicate(&namespace, &id).await;
+                }
+                _ = sweep.tick() => {
+                    manager.sweep().await;
+                    manager.sync_inventories(&mut last_sync).await;
+                }
+            }
+        }
+    });
+
+    handle
+}
+
+/// Returns true when the blob's redundancy requirement is already satisfied.
+fn placement_complete(meta: &BlobMeta) -> bool {
+    match &meta.erasure {
+        Some(erasure) => erasure.unplaced_shards().is_empty(),
+        None => meta.replicas.len() + 1 >= meta.min_replication as usize,
+    }
+}
+
+/// Returns true when the blob's redundancy requirement is satisfied without this node's copy
+/// (at least one copy elsewhere, even when the uploader asked for none).
+fn placement_complete_without_self(meta: &BlobMeta) -> bool {
+    match &meta.erasure {
+        Some(erasure) => erasure.unplaced_shards().is_empty(),
+        None => meta.replicas.len() >= (meta.min_replication as usize).max(1),
+    }
+}
+
+/// Request storing a full copy on a peer. The peer checks `meta.content_hash` against the
+/// data, so it is filled in for legacy blobs uploaded without one.
+fn store_blob(namespace: &str, id: &str, data: Vec<u8>, meta: BlobMeta) -> BlobRequest {
+    let meta = BlobMeta {
+        content_hash: Some(content_hash(&data)),
+        ..meta
+    };
+    BlobRequest::StoreBlob {
+        namespace: namespace.to_string(),
+        id: id.to_string(),
+        data,
+        meta,
+    }
+}
+
+impl ReplicationManager {
+    /// Connected providers (authenticated peers) that can receive replicas or shards,
+    /// best reputation first.
+    async fn candidate_peers(&self) -> Vec<PeerId> {
+        self.p2p.read().await.provider_peers()
+    }
+
+    /// Bring one blob's placement up to its redundancy requirement.
+    async fn replicate(&self, namespace: &str, id: &str) {
+        let mut meta = match self.storage.meta(namespace, id) {
+            Ok(meta) => meta,
+            Err(e) => {
+                tracing::debug!(namespace, id, error = %e, "blob metadata unavailable; skipping replication");
+                return;
+            }
+        };
+        if meta.replica || placement_complete(&meta) {
+            return;
+        }
+
+        let data = match self.storage.get(namespace, id) {
+            Ok(data) => data,
+            Err(e) => {
+                tracing::warn!(namespace, id, error = %e, "cannot read blob for replication");
+                return;
+            }
+        };
+
+        let candidates = self.candidate_peers().await;
+        if candidates.is_empty() {
+            tracing::debug!(namespace, id, "no connected peers; replication deferred");
+            return;
+        }
+
+        let mut erasure = meta.erasure.take();
+        let changed = match erasure.as_mut() {
+            Some(erasure) => self.place_shards(namespace, id, &data, erasure, &candidates).await,
+            None => self.place_replicas(namespace, id, &data, &mut meta, &candidates).await,
+        };
+        meta.erasure = erasure;
+
+        if changed {
+            self.record_placement(namespace, id, &data, &meta);
+        }
+        if !placement_complete(&meta) {
+            tracing::info!(namespace, id, "placement incomplete; will retry on next sweep");
+        }
+    }
+
+    /// Place one blob uploaded to this node so it no longer depends on this node's copy.
+    /// Returns true once done.
+    async fn hand_off(&self, namespace: &str, id: &str) -> bool {
+        let mut meta = match self.storage.meta(namespace, id) {
+            Ok(meta) => meta,
+            Err(e) => {
+                tracing::debug!(namespace, id, error = %e, "blob metadata unavailable; skipping hand-off");
+                return false;
+            }
+        };
+        if placement_complete_without_self(&meta) {
+            return true;
+        }
+        let candidates = self.candidate_peers().await;
+        if candidates.is_empty() {
+            return false;
+        }
+        let data = match self.storage.get(namespace, id) {
+            Ok(data) => data,
+            Err(e) => {
+                tracing::warn!(namespace, id, error = %e, "cannot read blob for hand-off");
+                return false;
+            }
+        };
+
+        let mut erasure = meta.erasure.take();
+        let changed = match erasure.as_mut() {
+            Some(erasure) => self.place_shards(namespace, id, &data, erasure, &candidates).await,
+            None => {
+                let wanted = (meta.min_replication as usize).max(1);
+                self.place_replicas_up_to(namespace, id, &data, &mut meta, &candidates, wanted)
+                    .await
+            }
+        };
+        meta.erasure = erasure;
+        if changed {
+            self.record_placement(namespace, id, &data, &meta);
+        }
+        placement_complete_without_self(&meta)
+    }
+
+    /// Record where `data` was placed (`meta.replicas` / `meta.erasure`) in the blob's current
+    /// metadata, unless the blob was re-uploaded with other content meanwhile.
+    fn record_placement(&self, namespace: &str, id: &str, data: &[u8], meta: &BlobMeta) {
+        let placed_hash = content_hash(data);
+        let recorded = self.storage.modify_meta(namespace, id, |current| {
+            if current.content_hash.as_ref().is_some_and(|hash| *hash != placed_hash) {
+                return false;
+            }
+            current.replicas = meta.replicas.clone();
+            current.erasure = meta.erasure.clone();
+            true
+        });
+        match recorded {
+            Ok(true) => {}
+            Ok(false) => tracing::info!(namespace, id, "blob changed during placement; placement not recorded"),
+            Err(e) => tracing::warn!(namespace, id, error = %e, "failed to record replica placement"),
+        }
+    }
+
+    /// Send full copies to peers not already holding one. Returns true if placement changed.
+    async fn place_replicas(
+        &self,
+        namespace: &str,
+        id: &str,
+        data: &[u8],
+        meta: &mut BlobMeta,
+        candidates: &[PeerId],
+    ) -> bool {
+        // This node's copy counts towards the requirement
+        let wanted = (meta.min_replication as usize).saturating_sub(1);
+        self.place_replicas_up_to(namespace, id, data, meta, candidates, wanted)
+            .await
+    }
+
+    /// Send full copies until `wanted` peers hold one. Returns true if placement changed.
+    async fn place_replicas_up_to(
+        &self,
+        namespace: &str,
+        id: &str,
+        data: &[u8],
+        meta: &mut BlobMeta,
+        candidates: &[PeerId],
+        wanted: usize,
+    ) -> bool {
+        let needed = wanted.saturating_sub(meta.replicas.len());
+        let targets: Vec<PeerId> = candidates
+            .iter()
+            .filter(|p| !meta.replicas.contains(&p.to_string()))
+            .take(needed)
+            .cloned()
+            .collect();
+        let mut changed = false;
+        for peer in &targets {
+            let request = store_blob(namespace, id, data.to_vec(), meta.clone());
+            match blob_request(&self.p2p, *peer, request).await {
+                Ok(BlobResponse::Stored) => {
+                    tracing::info!(namespace, id, %peer, "replica placed");
+                    meta.replicas.push(peer.to_string());
+                    changed = true;
+                }
+                Ok(other) => tracing::warn!(namespace, id, %peer, response = ?other, "peer refused replica"),
+                Err(e) => tracing::warn!(namespace, id, %peer, error = %e, "replica transfer failed"),
+            }
+        }
+        changed
+    }
+
+    /// Send unplaced shards, each to a different peer. Returns true if placement changed.
+    async fn place_shards(
+        &self,
+        namespace: &str,
+        id: &str,
+        data: &[u8],
+        erasure: &mut ErasureMeta,
+        candidates: &[PeerId],
+    ) -> bool {
+        let shards = match erasure::encode(data, erasure.scheme()) {
+            Ok(shards) => shards,
+            Err(e) => {
+                tracing::warn!(namespace, id, error = %e, "erasure encoding failed");
+                return false;
+            }
+        };
+        let holders: HashSet<String> = erasure.shard_holders.iter().flatten().cloned().collect();
+        let mut free_peers = candidates.iter().filter(|p| !holders.contains(&p.to_string()));
+        let mut changed = false;
+
+        for index in erasure.unplaced_shards() {
+            let Some(peer) = free_peers.next() else {
+                break;
+            };
+            let shard = &shards[index];
+            if shard_hash(shard) != erasure.shard_hashes[index] {
+                tracing::error!(namespace, id, index, "shard hash differs from upload; blob data changed");
+                return changed;
+            }
+            let request = BlobRequest::StoreShard {
+                namespace: namespace.to_string(),
+                id: id.to_string(),
+                index: index as u8,
+                hash: erasure.shard_hashes[index].clone(),
+                data: shard.clone(),
+            };
+            match blob_request(&self.p2p, *peer, request).await {
+                Ok(BlobResponse::Stored) => {
+                    tracing::info!(namespace, id, index, %peer, "shard placed");
+                    erasure.shard_holders[index] = Some(peer.to_string());
+                    changed = true;
+                }
+                Ok(other) => tracing::warn!(namespace, id, index, %peer, response = ?other, "peer refused shard"),
+                Err(e) => tracing::warn!(namespace, id, index, %peer, error = %e, "shard transfer failed"),
+            }
+        }
+        changed
+    }
+
+    /// Rebuild a lost erasure-coded blob from any `k` shards held by peers, asking the
+    /// holders with the best reputation first.
+    async fn rebuild(&self, namespace: &str, id: &str, erasure: &ErasureMeta) {
+        let scheme = erasure.scheme();
+        let mut shards: Vec<Option<Vec<u8>>> = vec![None; scheme.total_shards()];
+        let mut available = 0;
+
+        let mut holders: Vec<(usize, PeerId, f64)> = {
+            let p2p = self.p2p.read().await;
+            let now = Instant::now();
+            erasure
+                .shard_holders
+                .iter()
+                .enumerate()
+                .filter_map(|(index, holder)| {
+                    let peer = holder.as_ref()?.parse::<PeerId>().ok()?;
+                    Some((index, peer, p2p.reputation_score(&peer, now)))
+                })
+                .collect()
+        };
+        holders.sort_by(|a, b| b.2.total_cmp(&a.2));
+
+        for (index, peer, _) in holders {
+            if available >= scheme.data_shards as usize {
+                break;
+            }
+            let request = BlobRequest::GetShard {
+                namespace: namespace.to_string(),
+                id: id.to_string(),
+                index: index as u8,
+            };
+            match blob_request(&self.p2p, peer, request).await {
+                Ok(BlobResponse::Shard(data)) if shard_hash(&data) == erasure.shard_hashes[index] => {
+                    shards[index] = Some(data);
+                    available += 1;
+                }
+                Ok(BlobResponse::Shard(_)) => {
+                    tracing::warn!(namespace, id, index, %peer, "peer returned a corrupt shard");
+                    self.p2p.write().await.record_transfer(peer, false);
+                }
+                Ok(other) => tracing::debug!(namespace, id, index, %peer, response = ?other, "shard unavailable"),
+                Err(e) => tracing::debug!(namespace, id, index, %peer, error = %e, "shard fetch failed"),
+            }
+        }
+
+        match erasure::reconstruct(shards, scheme, erasure.original_size as usize) {
+            Ok(data) => match self.storage.restore_data(namespace, id, &data) {
+                Ok(()) => {
+                    tracing::info!(namespace, id, size = data.len(), "blob rebuilt from shards");
+                    announce_blob(&self.p2p, &content_hash(&data)).await;
+                }
+                Err(e) => tracing::warn!(namespace, id, error = %e, "failed to store rebuilt blob"),
+            },
+            Err(e) => tracing::warn!(namespace, id, error = %e, "cannot rebuild blob from shards"),
+        }
+    }
+
+    /// Retry incomplete placements and rebuild erasure-coded blobs whose data was lost.
+    async fn sweep(&self) {
+        match self.storage.list(None) {
+            Ok(entries) => {
+                for entry in entries {
+                    if entry.replica || (entry.erasure.is_none() && entry.min_replication <= 1) {
+                        continue;
+                    }
+                    self.replicate(&entry.namespace, &entry.id).await;
+                }
+            }
+            Err(e) => tracing::warn!(error = %e, "replication sweep: failed to list blobs"),
+        }
+
+        match self.storage.missing_data() {
+            Ok(missing) => {
+                for (namespace, id) in missing {
+                    if let Ok(BlobMeta {
+                        erasure: Some(erasure),
+                        ..
+                    }) = self.storage.meta(&namespace, &id)
+                    {
+                        self.rebuild(&namespace, &id, &erasure).await;
+                    }
+                }
+            }
+            Err(e) => tracing::warn!(error = %e, "replication sweep: failed to scan metadata"),
+        }
+    }
+
+    /// Reconcile inventories with providers not synced within `INVENTORY_SYNC_INTERVAL`.
+    /// Providers that disconnected are forgotten, so they are synced again once back.
+    async fn sync_inventories(&self, last_sync: &mut HashMap<PeerId, Instant>) {
+        let providers = self.candidate_peers().await;
+        last_sync.retain(|peer, _| providers.contains(peer));
+        let due: Vec<PeerId> = providers
+            .into_iter()
+            .filter(|peer| {
+                last_sync
+                    .get(peer)
+                    .is_none_or(|at| at.elapsed() >= INVENTORY_SYNC_INTERVAL)
+            })
+            .collect();
+        if due.is_empty() {
+            return;
+        }
+        let local = match self.storage.list(None) {
+            Ok(index) => Inventory::from_index(&index),
+            Err(e) => {
+                tracing::warn!(error = %e, "inventory sync: failed to list blobs");
+                return;
+            }
+        };
+
+        for peer in due {
+            last_sync.insert(peer, Instant::now());
+            match self.inventory_diff(&local, peer).await {
+                Ok(diff) => {
+                    tracing::info!(
+                        %peer,
+                        missing_locally = diff.missing_locally.len(),
+                        missing_remotely = diff.missing_remotely.len(),
+                        queries = diff.queries,
+                        "inventory reconciled"
+                    );
+                    self.apply_inventory_diff(peer, diff).await;
+                }
+                Err(e) => tracing::debug!(%peer, error = %e, "inventory sync failed"),
+            }
+        }
+    }
+
+    /// Reconcile the `local` inventory with `peer`'s.
+    async fn inventory_diff(&self, local: &Inventory, peer: PeerId) -> Result<InventoryDiff, String> {
+        let p2p = &self.p2p;
+        reconcile(local, |query| async move {
+            match blob_request(p2p, peer, BlobRequest::Inventory(query)).await? {
+                BlobResponse::Inventory(reply) => Ok(reply),
+                other => Err(format!("unexpected response: {:?}", other)),
+            }
+        })
+        .await
+    }
+
+    /// Copy the `replicas` this node holds for other providers to providers whose inventory
+    /// lacks them, so the copy does not leave with this node. Sending a replica to a provider
+    /// already holding the blob would overwrite its copy (possibly the original), hence the
+    /// inventory check.
+    async fn hand_off_replicas(
+        &self,
+        local: &Inventory,
+        replicas: &HashSet<(String, String)>,
+        done: &mut HashSet<(String, String)>,
+    ) {
+        for peer in self.candidate_peers().await {
+            if replicas.iter().all(|key| done.contains(key)) {
+                return;
+            }
+            let diff = match self.inventory_diff(local, peer).await {
+                Ok(diff) => diff,
+                Err(e) => {
+                    tracing::debug!(%peer, error = %e, "inventory sync failed; replicas not handed off to peer");
+                    continue;
+                }
+            };
+            for (namespace, entry) in diff.missing_remotely {
+                let key = (namespace, entry.id);
+                if !replicas.contains(&key) || done.contains(&key) {
+                    continue;
+                }
+                let (namespace, id) = (&key.0, &key.1);
+                let (Ok(meta), Ok(data)) = (self.storage.meta(namespace, id), self.storage.get(namespace, id)) else {
+                    continue;
+                };
+                let request = store_blob(namespace, id, data, meta);
+                match blob_request(&self.p2p, peer, request).await {
+                    Ok(BlobResponse::Stored) => {
+                        tracing::info!(namespace, id, %peer, "replica handed off");
+                        done.insert(key);
+                    }
+                    Ok(other) => tracing::warn!(namespace, id, %peer, response = ?other, "peer refused replica"),
+                    Err(e) => tracing::warn!(namespace, id, %peer, error = %e, "replica transfer failed"),
+                }
+            }
+        }
+    }
+
+    /// Resend replicas `peer` lost, and restore local blobs whose data was lost from its copy.
+    async fn apply_inventory_diff(&self, peer: PeerId, diff: InventoryDiff) {
+        for (namespace, entry) in diff.missing_remotely {
+            let Ok(meta) = self.storage.meta(&namespace, &entry.id) else {
+                continue;
+            };
+            let assigned = !meta.replica
+                && meta.erasure.is_none()
+                && meta.replicas.contains(&peer.to_string())
+                && meta.content_hash.as_ref() == Some(&entry.content_hash);
+            if !assigned {
+                continue;
+            }
+            let Ok(data) = self.storage.get(&namespace, &entry.id) else {
+                continue;
+            };
+            let request = BlobRequest::StoreBlob {
+                namespace: namespace.clone(),
+                id: entry.id.clone(),
+                data,
+                meta,
+            };
+            match blob_request(&self.p2p, peer, request).await {
+                Ok(BlobResponse::Stored) => {
+                    tracing::info!(namespace, id = entry.id, %peer, "replica lost by peer sent again")
+                }
+                Ok(other) => tracing::warn!(namespace, id = entry.id, %peer, response = ?other, "peer refused replica"),
+                Err(e) => tracing::warn!(namespace, id = entry.id, %peer, error = %e, "replica transfer failed"),
+            }
+        }
+
+        if diff.missing_locally.is_empty() {
+            return;
+        }
+        let missing: HashSet<(String, String)> = match self.storage.missing_data() {
+            Ok(missing) => missing.into_iter().collect(),
+            Err(e) => {
+                tracing::warn!(error = %e, "inventory sync: failed to scan metadata");
+                return;
+            }
+        };
+        for (namespace, entry) in diff.missing_locally {
+            let lost = missing.contains(&(namespace.clone(), entry.id.clone()))
+                && matches!(
+                    self.storage.meta(&namespace, &entry.id),
+                    Ok(BlobMeta { content_hash: Some(hash), .. }) if hash == entry.content_hash
+                );
+            if !lost {
+                continue;
+            }
+            let request = BlobRequest::GetBlob {
+                namespace: namespace.clone(),
+                id: entry.id.clone(),
+            };
+            match blob_request(&self.p2p, peer, request).await {
+                Ok(BlobResponse::Blob(data)) if content_hash(&data) == entry.content_hash => {
+                    match self.storage.restore_data(&namespace, &entry.id, &data) {
+                        Ok(()) => {
+                            tracing::info!(namespace, id = entry.id, %peer, "lost blob restored from peer");
+                            announce_blob(&self.p2p, &entry.content_hash).await;
+                        }
+                        Err(e) => tracing::warn!(namespace, id = entry.id, error = %e, "failed to restore blob"),
+                    }
+                }
+                Ok(BlobResponse::Blob(_)) => {
+                    tracing::warn!(namespace, id = entry.id, %peer, "peer returned a corrupt blob");
+                    self.p2p.write().await.record_transfer(peer, false);
+                }
+                Ok(other) => tracing::debug!(namespace, id = entry.id, %peer, response = ?other, "blob unavailable"),
+                Err(e) => tracing::debug!(namespace, id = entry.id, %peer, error = %e, "blob fetch failed"),
+            }
+        }
+    }
+}
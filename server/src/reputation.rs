@@ -0,0 +1,199 @@
+//! Peer reputation: per-peer statistics folded into a score in `[0, 1]`.
+//!
+//! Inputs:
+//! - Ping round-trip time (moving average)
+//! - Blob transfers that succeeded or failed (replicas, shards, repair fetches)
+//! - Availability: share of the observation time the peer was connected
+//! - On-chain challenge history (`passedChallenges / totalChallenges` from `getNodeInfo`)
+//!
+//! Unknown inputs count as neutral (0.5), so a new peer ranks between good and bad ones.
+//! Replication and retrieval try providers in decreasing score order.
+
+use std::time::{Duration, Instant};
+
+/// Weights of the score components (sum to 1).
+const WEIGHT_LATENCY: f64 = 0.2;
+const WEIGHT_TRANSFERS: f64 = 0.3;
+const WEIGHT_AVAILABILITY: f64 = 0.2;
+const WEIGHT_CHALLENGES: f64 = 0.3;
+/// RTT at which the latency component is 0.5.
+const REFERENCE_RTT_MS: f64 = 200.0;
+/// Smoothing factor of the RTT moving average.
+const RTT_ALPHA: f64 = 0.2;
+/// Observation time below which availability is not yet meaningful.
+const MIN_OBSERVATION: Duration = Duration::from_secs(60);
+
+/// Statistics of one peer, kept across reconnections.
+#[derive(Debug, Clone)]
+pub struct PeerStats {
+    first_seen: Instant,
+    last_seen: Instant,
+    connected_since: Option<Instant>,
+    /// Connected time of past connections.
+    connected_total: Duration,
+    rtt_ms: Option<f64>,
+    transfers_ok: u64,
+    transfers_failed: u64,
+    /// `(passed, total)` challenges from the storage registry.
+    challenges: Option<(u64, u64)>,
+}
+
+/// Reputation snapshot reported in `PeerInfo`.
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct Reputation {
+    pub score: f64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rtt_ms: Option<u64>,
+    pub transfers_ok: u64,
+    pub transfers_failed: u64,
+    /// Share of the time since first seen the peer was connected.
+    pub availability: f64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub challenges_passed: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub challenges_total: Option<u64>,
+}
+
+/// Laplace-smoothed success ratio (0.5 without observations).
+fn success_ratio(ok: u64, total: u64) -> f64 {
+    (ok as f64 + 1.0) / (total as f64 + 2.0)
+}
+
+impl PeerStats {
+    pub fn new(now: Instant) -> Self {
+        Self {
+            first_seen: now,
+            last_seen: now,
+            connected_since: None,
+            connected_total: Duration::ZERO,
+            rtt_ms: None,
+            transfers_ok: 0,
+            transfers_failed: 0,
+            challenges: None,
+        }
+    }
+
+    pub fn connected(&mut self, now: Instant) {
+        self.last_seen = now;
+        self.connected_since.get_or_insert(now);
+    }
+
+    pub fn disconnected(&mut self, now: Instant) {
+        if let Some(since) = self.connected_since.take() {
+            self.connected_total += now.saturating_duration_since(since);
+        }
+        self.last_seen = now;
+    }
+
+    pub fn is_connected(&self) -> bool {
+        self.connected_since.is_some()
+    }
+
+    /// Last connection or disconnection.
+    pub fn last_seen(&self) -> Instant {
+        self.last_seen
+    }
+
+    pub fn record_rtt(&mut self, rtt: Duration) {
+        let sample = rtt.as_secs_f64() * 1000.0;
+        self.rtt_ms = Some(match self.rtt_ms {
+            Some(avg) => avg + RTT_ALPHA * (sample - avg),
+            None => sample,
+        });
+    }
+
+    pub fn record_transfer(&mut self, ok: bool) {
+        if ok {
+            self.transfers_ok += 1;
+        } else {
+            self.transfers_failed += 1;
+        }
+    }
+
+    pub fn set_challenges(&mut self, passed: u64, total: u64) {
+        self.challenges = Some((passed.min(total), total));
+    }
+
+    fn availability(&self, now: Instant) -> Option<f64> {
+        let observed = now.saturating_duration_since(self.first_seen);
+        if observed < MIN_OBSERVATION {
+            return None;
+        }
+        let current = self
+            .connected_since
+            .map(|since| now.saturating_duration_since(since))
+            .unwrap_or_default();
+        let connected = (self.connected_total + current).as_secs_f64();
+        Some((connected / observed.as_secs_f64()).min(1.0))
+    }
+
+    /// Weighted score in `[0, 1]`; higher is better.
+    pub fn score(&self, now: Instant) -> f64 {
+        let latency = self
+            .rtt_ms
+            .map(|rtt| 1.0 / (1.0 + rtt / REFERENCE_RTT_MS))
+            .unwrap_or(0.5);
+        let transfers = success_ratio(self.transfers_ok, self.transfers_ok + self.transfers_failed);
+        let availability = self.availability(now).unwrap_or(0.5);
+        let challenges = self
+            .challenges
+            .map(|(passed, total)| success_ratio(passed, total))
+            .unwrap_or(0.5);
+        WEIGHT_LATENCY * latency
+            + WEIGHT_TRANSFERS * transfers
+            + WEIGHT_AVAILABILITY * availability
+            + WEIGHT_CHALLENGES * challenges
+    }
+
+    pub fn reputation(&self, now: Instant) -> Reputation {
+        Reputation {
+            score: self.score(now),
+            rtt_ms: self.rtt_ms.map(|rtt| rtt.round() as u64),
+            transfers_ok: self.transfers_ok,
+            transfers_failed: self.transfers_failed,
+            availability: self.availability(now).unwrap_or(1.0),
+            challenges_passed: self.challenges.map(|(passed, _)| passed),
+            challenges_total: self.challenges.map(|(_, total)| total),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn new_peer_is_neutral() {
+        let now = Instant::now();
+        let stats = PeerStats::new(now);
+        assert!((stats.score(now) - 0.5).abs() < 1e-9);
+    }
+
+    #[test]
+    fn failures_and_failed_challenges_lower_the_score() {
+        let now = Instant::now();
+        let mut good = PeerStats::new(now);
+        let mut bad = PeerStats::new(now);
+        for _ in 0..10 {
+            good.record_transfer(true);
+            bad.record_transfer(false);
+        }
+        good.set_challenges(20, 20);
+        bad.set_challenges(2, 20);
+        good.record_rtt(Duration::from_millis(20));
+        bad.record_rtt(Duration::from_millis(900));
+        assert!(good.score(now) > 0.8);
+        assert!(bad.score(now) < 0.3);
+    }
+
+    #[test]
+    fn availability_counts_connected_time() {
+        let start = Instant::now();
+        let mut stats = PeerStats::new(start);
+        stats.connected(start);
+        stats.disconnected(start + Duration::from_secs(90));
+        let reputation = stats.reputation(start + Duration::from_secs(180));
+        assert!((reputation.availability - 0.5).abs() < 1e-9);
+        assert!(!stats.is_connected());
+    }
+}
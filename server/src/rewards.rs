@@ -0,0 +1,326 @@
+//! Reward claiming and wallet balance monitoring.
+//!
+//! Every `REWARDS_INTERVAL_SECS` the task:
+//!
+//! - Reads the wallet balance (gRPC) and the node's `calculatePendingRewards`, and keeps a
+//!   bounded history of both.
+//! - Pauses contract writes that pay a fee while the balance is below `MASSA_MIN_BALANCE`,
+//!   and resumes them once it is back above. `claimRewards` is still sent while paused,
+//!   since it is how the balance recovers.
+//! - Sends `claimRewards` once the pending rewards reach `REWARDS_CLAIM_THRESHOLD`, and
+//!   follows the operation until it is final.
+//!
+//! Claims and balance samples are saved in `{STORAGE_PATH}/.state/rewards.json` and served
+//! by `GET /admin/rewards`. A claim still pending at shutdown is followed again on startup.
+
+use std::collections::VecDeque;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+
+use crate::contract::{MassaClient, SubmittedCall};
+use crate::operations::{OperationStatus, SharedOperations};
+use crate::registry::RegistryCall;
+use crate::state::{now_secs, StateCell};
+use massa_models::amount::Amount;
+
+/// Claims kept in the history.
+const MAX_CLAIMS: usize = 500;
+/// Balance samples kept in the history (a week at the default interval).
+const MAX_SAMPLES: usize = 168;
+/// Delay before the first check, so startup writes (registration) go first.
+const STARTUP_DELAY: Duration = Duration::from_secs(30);
+
+/// Claim threshold, balance floor and check interval.
+#[derive(Debug, Clone, Copy)]
+pub struct RewardsConfig {
+    /// Pending rewards that trigger a claim.
+    pub claim_threshold: Amount,
+    /// Balance below which fee-paying writes are paused.
+    pub min_balance: Amount,
+    pub interval: Duration,
+}
+
+/// One `claimRewards` operation.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Claim {
+    pub operation_id: String,
+    pub expire_period: u64,
+    /// Pending rewards when the claim was sent, in nanoMAS.
+    pub amount: u64,
+    /// Unix time in seconds.
+    pub sent_at: u64,
+    #[serde(flatten)]
+    pub status: OperationStatus,
+}
+
+/// Balance and pending rewards at one check.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BalanceSample {
+    /// Unix time in seconds.
+    pub at: u64,
+    /// nanoMAS.
+    pub balance: u64,
+    /// nanoMAS.
+    pub pending_rewards: u64,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct RewardsState {
+    claims: VecDeque<Claim>,
+    samples: VecDeque<BalanceSample>,
+}
+
+impl RewardsState {
+    fn add_claim(&mut self, claim: Claim) {
+        self.claims.push_back(claim);
+        while self.claims.len() > MAX_CLAIMS {
+            self.claims.pop_front();
+        }
+    }
+
+    fn add_sample(&mut self, sample: BalanceSample) {
+        self.samples.push_back(sample);
+        while self.samples.len() > MAX_SAMPLES {
+            self.samples.pop_front();
+        }
+    }
+
+    fn set_status(&mut self, operation_id: &str, status: OperationStatus) {
+        if let Some(claim) = self.claims.iter_mut().find(|c| c.operation_id == operation_id) {
+            claim.status = status;
+        }
+    }
+
+    /// nanoMAS claimed by executed claims still in the history.
+    fn total_claimed(&self) -> u64 {
+        self.claims
+            .iter()
+            .filter(|c| c.status == OperationStatus::Executed)
+            .fold(0u64, |sum, c| sum.saturating_add(c.amount))
+    }
+}
+
+/// True when `pending` rewards (nanoMAS) are worth a claim.
+fn should_claim(pending: u64, threshold: Amount) -> bool {
+    pending > 0 && pending >= threshold.to_raw()
+}
+
+/// Earnings report, as returned by `GET /admin/rewards`. Amounts are in nanoMAS.
+#[derive(Debug, Clone, Serialize)]
+pub struct RewardsReport {
+    pub address: String,
+    pub balance: Option<u64>,
+    pub pending_rewards: Option<u64>,
+    pub claim_threshold: u64,
+    pub min_balance: u64,
+    /// Why fee-paying writes are paused, when they are.
+    pub writes_paused: Option<String>,
+    /// Sum of the executed claims in `claims`.
+    pub total_claimed: u64,
+    /// Most recent first.
+    pub claims: Vec<Claim>,
+    /// Most recent first.
+    pub balances: Vec<BalanceSample>,
+    pub last_error: Option<String>,
+}
+
+/// Claims this node's rewards and watches its balance.
+pub struct Rewards {
+    registry: Arc<MassaClient>,
+    operations: SharedOperations,
+    address: String,
+    config: RewardsConfig,
+    state: StateCell<RewardsState>,
+    last_error: Mutex<Option<String>>,
+}
+
+pub type SharedRewards = Arc<Rewards>;
+
+/// Load the history from `state_dir` and start the rewards task for `address`.
+pub fn spawn(
+    registry: Arc<MassaClient>,
+    operations: SharedOperations,
+    address: String,
+    config: RewardsConfig,
+    state_dir: &Path,
+) -> SharedRewards {
+    let state = StateCell::load(state_dir.join("rewards.json"), "rewards history");
+    let rewards = Arc::new(Rewards {
+        registry,
+        operations,
+        address,
+        config,
+        state,
+        last_error: Mutex::new(None),
+    });
+    let worker = rewards.clone();
+    tokio::spawn(async move { worker.run().await });
+    rewards
+}
+
+impl Rewards {
+    pub fn report(&self) -> RewardsReport {
+        self.state.with(|state| {
+            let latest = state.samples.back();
+            RewardsReport {
+                address: self.address.clone(),
+                balance: latest.map(|s| s.balance),
+                pending_rewards: latest.map(|s| s.pending_rewards),
+                claim_threshold: self.config.claim_threshold.to_raw(),
+                min_balance: self.config.min_balance.to_raw(),
+                writes_paused: self.registry.writes_paused(),
+                total_claimed: state.total_claimed(),
+                claims: state.claims.iter().rev().cloned().collect(),
+                balances: state.samples.iter().rev().cloned().collect(),
+                last_error: self.last_error.lock().unwrap().clone(),
+            }
+        })
+    }
+
+    async fn run(&self) {
+        tokio::time::sleep(STARTUP_DELAY).await;
+        self.resume_pending_claims().await;
+        loop {
+            let result = self.check().await;
+            *self.last_error.lock().unwrap() = result.as_ref().err().map(|e| format!("{:#}", e));
+            if let Err(e) = result {
+                tracing::warn!(error = %e, "rewards check failed");
+            }
+            tokio::time::sleep(self.config.interval).await;
+        }
+    }
+
+    /// Follow claims that were still pending when the server stopped.
+    async fn resume_pending_claims(&self) {
+        let pending: Vec<Claim> = self.state.with(|state| {
+            state
+                .claims
+                .iter()
+                .filter(|c| !c.status.is_terminal())
+                .cloned()
+                .collect()
+        });
+        for claim in pending {
+            let sent = SubmittedCall {
+                call: RegistryCall::ClaimRewards,
+                coins: Amount::from_raw(0),
+                operation_id: claim.operation_id.clone(),
+                expire_period: claim.expire_period,
+            };
+            let status = self.operations.track(sent).outcome().await;
+            self.state.update(|state| state.set_status(&claim.operation_id, status));
+        }
+    }
+
+    async fn check(&self) -> Result<()> {
+        let balance = self.registry.get_balance().await?;
+        let pending = self.registry.calculate_pending_rewards(&self.address).await?;
+        self.state.update(|state| {
+            state.add_sample(BalanceSample {
+                at: now_secs(),
+                balance: balance.to_raw(),
+                pending_rewards: pending,
+            })
+        });
+        self.apply_balance_floor(balance);
+
+        if !should_claim(pending, self.config.claim_threshold) {
+            return Ok(());
+        }
+        let sent = self.registry.claim_rewards().await?;
+        let operation_id = sent.operation_id.clone();
+        self.state.update(|state| {
+            state.add_claim(Claim {
+                operation_id: operation_id.clone(),
+                expire_period: sent.expire_period,
+                amount: pending,
+                sent_at: now_secs(),
+                status: OperationStatus::Pending,
+            })
+        });
+
+        let status = self.operations.follow(sent, 0).await?;
+        match &status {
+            OperationStatus::Executed => {
+                tracing::info!(%operation_id, amount = %Amount::from_raw(pending), "rewards claimed");
+                // The claim may lift the pause right away
+                if let Ok(balance) = self.registry.get_balance().await {
+                    self.apply_balance_floor(balance);
+                }
+            }
+            status => tracing::warn!(%operation_id, ?status, "reward claim did not execute"),
+        }
+        self.state.update(|state| state.set_status(&operation_id, status));
+        Ok(())
+    }
+
+    /// Pause fee-paying writes below the balance floor, resume them above it.
+    fn apply_balance_floor(&self, balance: Amount) {
+        let low = balance < self.config.min_balance;
+        let paused = self.registry.writes_paused().is_some();
+        if low && !paused {
+            tracing::warn!(
+                %balance,
+                min_balance = %self.config.min_balance,
+                "wallet balance too low to pay fees; pausing contract writes"
+            );
+            self.registry.pause_writes(Some(format!(
+                "balance {} MAS is below MASSA_MIN_BALANCE ({} MAS)",
+                balance, self.config.min_balance
+            )));
+        } else if !low && paused {
+            tracing::info!(%balance, "wallet balance restored; resuming contract writes");
+            self.registry.pause_writes(None);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn claim(id: &str, amount: u64, status: OperationStatus) -> Claim {
+        Claim {
+            operation_id: id.to_string(),
+            expire_period: 10,
+            amount,
+            sent_at: 0,
+            status,
+        }
+    }
+
+    #[test]
+    fn claims_above_threshold() {
+        let threshold = Amount::from_raw(1_000);
+        assert!(!should_claim(0, Amount::from_raw(0)));
+        assert!(!should_claim(999, threshold));
+        assert!(should_claim(1_000, threshold));
+        assert!(should_claim(1, Amount::from_raw(0)));
+    }
+
+    #[test]
+    fn history_is_bounded_and_totals_executed_claims() {
+        let mut state = RewardsState::default();
+        state.add_claim(claim("op1", 500, OperationStatus::Pending));
+        state.add_claim(claim("op2", 700, OperationStatus::Executed));
+        state.set_status("op1", OperationStatus::Failed { message: "No rewards to claim".into() });
+        assert_eq!(state.total_claimed(), 700);
+        state.set_status("op1", OperationStatus::Executed);
+        assert_eq!(state.total_claimed(), 1_200);
+
+        for i in 0..MAX_SAMPLES + 5 {
+            state.add_sample(BalanceSample { at: i as u64, balance: 0, pending_rewards: 0 });
+        }
+        assert_eq!(state.samples.len(), MAX_SAMPLES);
+        assert_eq!(state.samples.front().map(|s| s.at), Some(5));
+
+        // The history survives a restart, statuses included
+        let restored: RewardsState = serde_json::from_slice(&serde_json::to_vec(&state).unwrap()).unwrap();
+        assert_eq!(restored.total_claimed(), 1_200);
+        assert_eq!(restored.claims.len(), 2);
+    }
+}
@@ -0,0 +1,511 @@
+//! Massa node access with endpoint failover.
+//!
+//! - [`Endpoints`]: configured URLs with health tracking. Healthy endpoints are tried first,
+//!   in configured order; an endpoint that fails is parked for a cooldown that doubles with
+//!   each consecutive failure.
+//! - [`JsonRpc`]: JSON-RPC requests with a per-request timeout, failed over to the next
+//!   endpoint and retried with jittered backoff on transport errors.
+//! - [`Grpc`]: signing gRPC client, connected lazily to each endpoint and reconnected when it
+//!   fails. Writes are sent concurrently and only retried when they cannot have reached a node.
+
+use std::collections::hash_map::RandomState;
+use std::collections::HashMap;
+use std::hash::{BuildHasher, Hasher};
+use std::time::{Duration, Instant};
+
+use anyhow::{anyhow, Result};
+use massa_models::amount::Amount;
+use serde::{Deserialize, Serialize};
+use tokio::sync::Mutex;
+
+use crate::massa_grpc::{self, ChainId, EventStream, GrpcClient, ScCall, SentOperation, StreamError};
+
+/// Default per-request timeout (`MASSA_RPC_TIMEOUT_SECS`).
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(15);
+/// Passes over all endpoints before a request fails.
+const MAX_ROUNDS: u32 = 3;
+const BACKOFF_BASE: Duration = Duration::from_millis(250);
+const BACKOFF_MAX: Duration = Duration::from_secs(4);
+const COOLDOWN_BASE: Duration = Duration::from_secs(5);
+const COOLDOWN_MAX: Duration = Duration::from_secs(300);
+
+/// Health of one endpoint, as reported by `GET /admin/rpc`.
+#[derive(Debug, Clone, Serialize)]
+pub struct EndpointHealth {
+    pub url: String,
+    /// False while the endpoint is parked after a failure.
+    pub healthy: bool,
+    pub consecutive_failures: u32,
+    pub requests: u64,
+    pub failures: u64,
+    pub last_latency_ms: Option<u64>,
+    pub last_error: Option<String>,
+}
+
+#[derive(Debug, Default)]
+struct EndpointState {
+    consecutive_failures: u32,
+    down_until: Option<Instant>,
+    requests: u64,
+    failures: u64,
+    last_latency: Option<Duration>,
+    last_error: Option<String>,
+}
+
+/// Configured endpoints of one protocol and their health.
+pub struct Endpoints {
+    urls: Vec<String>,
+    state: std::sync::Mutex<Vec<EndpointState>>,
+}
+
+impl Endpoints {
+    pub fn new(urls: Vec<String>) -> Self {
+        let state = urls.iter().map(|_| EndpointState::default()).collect();
+        Self {
+            urls,
+            state: std::sync::Mutex::new(state),
+        }
+    }
+
+    pub fn url(&self, index: usize) -> &str {
+        &self.urls[index]
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.urls.is_empty()
+    }
+
+    /// Indices in the order to try: healthy endpoints in configured order, then the parked
+    /// ones, soonest back first. Every endpoint is included so a request never gives up
+    /// while one might answer.
+    pub fn order(&self, now: Instant) -> Vec<usize> {
+        let state = self.state.lock().unwrap();
+        let (mut healthy, mut parked): (Vec<usize>, Vec<usize>) = (0..state.len())
+            .partition(|&i| state[i].down_until.is_none_or(|until| until <= now));
+        healthy.sort_by_key(|&i| state[i].consecutive_failures);
+        parked.sort_by_key(|&i| state[i].down_until);
+        healthy.extend(parked);
+        healthy
+    }
+
+    pub fn record_success(&self, index: usize, latency: Duration) {
+        let mut state = self.state.lock().unwrap();
+        let entry = &mut state[index];
+        entry.requests += 1;
+        entry.consecutive_failures = 0;
+        entry.down_until = None;
+        entry.last_latency = Some(latency);
+    }
+
+    pub fn record_failure(&self, index: usize, error: &str, now: Instant) {
+        let mut state = self.state.lock().unwrap();
+        let entry = &mut state[index];
+        entry.requests += 1;
+        entry.failures += 1;
+        entry.consecutive_failures = entry.consecutive_failures.saturating_add(1);
+        let cooldown = COOLDOWN_BASE
+            .saturating_mul(1 << (entry.consecutive_failures - 1).min(16))
+            .min(COOLDOWN_MAX);
+        entry.down_until = Some(now + cooldown);
+        entry.last_error = Some(error.to_string());
+        tracing::warn!(
+            endpoint = %self.urls[index],
+            failures = entry.consecutive_failures,
+            ?cooldown,
+            error = %error,
+            "Massa endpoint failed"
+        );
+    }
+
+    pub fn health(&self) -> Vec<EndpointHealth> {
+        let now = Instant::now();
+        let state = self.state.lock().unwrap();
+        self.urls
+            .iter()
+            .zip(state.iter())
+            .map(|(url, s)| EndpointHealth {
+                url: url.clone(),
+                healthy: s.down_until.is_none_or(|until| until <= now),
+                consecutive_failures: s.consecutive_failures,
+                requests: s.requests,
+                failures: s.failures,
+                last_latency_ms: s.last_latency.map(|d| d.as_millis() as u64),
+                last_error: s.last_error.clone(),
+            })
+            .collect()
+    }
+}
+
+/// Delay before retry round `round` (0-based): exponential, capped, with jitter in `[d/2, d]`.
+fn backoff(round: u32) -> Duration {
+    let max = BACKOFF_BASE.saturating_mul(1 << round.min(16)).min(BACKOFF_MAX);
+    let mut hasher = RandomState::new().build_hasher();
+    hasher.write_u32(round);
+    let jitter = hasher.finish() % (max.as_millis() as u64 / 2 + 1);
+    max / 2 + Duration::from_millis(jitter)
+}
+
+#[derive(Serialize)]
+struct JsonRpcRequest<'a> {
+    jsonrpc: &'static str,
+    id: u64,
+    method: &'a str,
+    params: &'a serde_json::Value,
+}
+
+#[derive(Deserialize)]
+struct JsonRpcResponse {
+    result: Option<serde_json::Value>,
+    error: Option<serde_json::Value>,
+}
+
+/// JSON-RPC client over a list of Massa node endpoints.
+pub struct JsonRpc {
+    http: reqwest::Client,
+    endpoints: Endpoints,
+}
+
+impl JsonRpc {
+    pub fn new(urls: Vec<String>, timeout: Duration) -> Self {
+        let http = reqwest::Client::builder()
+            .timeout(timeout)
+            .build()
+            .expect("failed to build HTTP client");
+        Self {
+            http,
+            endpoints: Endpoints::new(urls),
+        }
+    }
+
+    /// Send `method` and return its `result`. Transport errors (connection, timeout, HTTP
+    /// 5xx or 429, unreadable body) move on to the next endpoint; an error answered by the
+    /// node is returned as is.
+    pub async fn request(&self, method: &str, params: serde_json::Value) -> Result<serde_json::Value> {
+        if self.endpoints.is_empty() {
+            return Err(anyhow!("no JSON-RPC endpoint configured"));
+        }
+        let req = JsonRpcRequest {
+            jsonrpc: "2.0",
+            id: 1,
+            method,
+            params: &params,
+        };
+        let mut last_error = None;
+        for round in 0..MAX_ROUNDS {
+            if round > 0 {
+                tokio::time::sleep(backoff(round - 1)).await;
+            }
+            for index in self.endpoints.order(Instant::now()) {
+                let started = Instant::now();
+                match self.send(self.endpoints.url(index), &req).await {
+                    Ok(resp) => {
+                        self.endpoints.record_success(index, started.elapsed());
+                        if let Some(err) = resp.error {
+                            return Err(anyhow!("RPC error: {:?}", err));
+                        }
+                        return resp.result.ok_or_else(|| anyhow!("No result"));
+                    }
+                    Err(e) => {
+                        self.endpoints.record_failure(index, &e.to_string(), Instant::now());
+                        last_error = Some(e);
+                    }
+                }
+            }
+        }
+        Err(last_error
+            .unwrap_or_else(|| anyhow!("no JSON-RPC endpoint answered"))
+            .context(format!("{} failed on every JSON-RPC endpoint", method)))
+    }
+
+    async fn send(&self, url: &str, req: &JsonRpcRequest<'_>) -> Result<JsonRpcResponse> {
+        let resp = self.http.post(url).json(req).send().await?;
+        let status = resp.status();
+        if status.is_server_error() || status == reqwest::StatusCode::TOO_MANY_REQUESTS {
+            return Err(anyhow!("HTTP {}", status));
+        }
+        Ok(resp.json().await?)
+    }
+
+    pub fn health(&self) -> Vec<EndpointHealth> {
+        self.endpoints.health()
+    }
+}
+
+/// Failure of [`Grpc::send_calls`] that leaves the fate of the operations unknown.
+#[derive(Debug)]
+pub enum SendError {
+    /// The call timed out or the stream broke after the operations were written. They may
+    /// still be included, so they are not resent; `operations` are their ids, in call order.
+    MaybeSent {
+        functions: String,
+        endpoint: String,
+        reason: String,
+        operations: Vec<SentOperation>,
+    },
+}
+
+impl std::fmt::Display for SendError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::MaybeSent {
+                functions,
+                endpoint,
+                reason,
+                ..
+            } => write!(f, "{} {} on {}; it may still be included", functions, reason, endpoint),
+        }
+    }
+}
+
+impl std::error::Error for SendError {}
+
+/// True when a failed send cannot have delivered the operations: the connection failed,
+/// or the node was unavailable before they were written to the stream.
+fn unsent(e: &anyhow::Error) -> bool {
+    // The only failure after the operations were sent (see `GrpcClient::send_calls`)
+    if e.is::<StreamError>() {
+        return false;
+    }
+    e.chain().any(|cause| {
+        cause.downcast_ref::<tonic::transport::Error>().is_some()
+            || cause
+                .downcast_ref::<tonic::Status>()
+                .is_some_and(|s| s.code() == tonic::Code::Unavailable)
+    })
+}
+
+/// Signing gRPC client over a list of Massa node endpoints.
+pub struct Grpc {
+    endpoints: Endpoints,
+    private_key: String,
+    chain_id: ChainId,
+    timeout: Duration,
+    /// Connected client of each endpoint used so far. Clones share the operation stream.
+    clients: Mutex<HashMap<usize, GrpcClient>>,
+}
+
+impl Grpc {
+    /// Fails only on an invalid key; endpoints are connected on first use.
+    pub fn new(urls: Vec<String>, private_key: &str, chain_id: ChainId, timeout: Duration) -> Result<Self> {
+        massa_grpc::keypair_from_str(private_key)?;
+        Ok(Self {
+            endpoints: Endpoints::new(urls),
+            private_key: private_key.to_string(),
+            chain_id,
+            timeout,
+            clients: Mutex::new(HashMap::new()),
+        })
+    }
+
+    /// Client of endpoint `index`, connected on first use. The lock is only held while
+    /// connecting, not while sending.
+    async fn client(&self, index: usize) -> Result<GrpcClient> {
+        let mut clients = self.clients.lock().await;
+        if let Some(client) = clients.get(&index) {
+            return Ok(client.clone());
+        }
+        let url = self.endpoints.url(index);
+        let client = tokio::time::timeout(self.timeout, GrpcClient::new(url, &self.private_key, self.chain_id))
+            .await
+            .map_err(|_| anyhow!("connection to {} timed out", url))??;
+        clients.insert(index, client.clone());
+        Ok(client)
+    }
+
+    /// Drop the client of endpoint `index` so the next call reconnects.
+    async fn disconnect(&self, index: usize) {
+        self.clients.lock().await.remove(&index);
+    }
+
+    /// Send `calls` in one submission and return their operations, in order. Endpoints are
+    /// tried in health order; the submission moves on only when it was not sent, so it is
+    /// never submitted twice. Concurrent submissions share each endpoint's stream.
+    ///
+    /// A submission that may have reached a node fails with [`SendError::MaybeSent`].
+    pub async fn send_calls(&self, calls: &[ScCall]) -> Result<Vec<SentOperation>> {
+        if self.endpoints.is_empty() {
+            return Err(anyhow!("no gRPC endpoint configured"));
+        }
+        let functions = calls
+            .iter()
+            .map(|c| c.function_name.as_str())
+            .collect::<Vec<_>>()
+            .join(", ");
+        let mut last_error = None;
+        for round in 0..MAX_ROUNDS {
+            if round > 0 {
+                tokio::time::sleep(backoff(round - 1)).await;
+            }
+            for index in self.endpoints.order(Instant::now()) {
+                let started = Instant::now();
+                let client = match self.client(index).await {
+                    Ok(client) => client,
+                    Err(e) => {
+                        self.endpoints.record_failure(index, &format!("{:#}", e), Instant::now());
+                        last_error = Some(e);
+                        continue;
+                    }
+                };
+                let signed = match client.sign_calls(calls).await {
+                    Ok(signed) => signed,
+                    Err(e) => {
+                        self.endpoints.record_failure(index, &format!("{:#}", e), Instant::now());
+                        self.disconnect(index).await;
+                        last_error = Some(e);
+                        continue;
+                    }
+                };
+                let operations = signed.sent.clone();
+                match tokio::time::timeout(self.timeout, client.send_signed(signed)).await {
+                    Ok(Ok(sent)) => {
+                        self.endpoints.record_success(index, started.elapsed());
+                        return Ok(sent);
+                    }
+                    Ok(Err(e)) if unsent(&e) => {
+                        self.endpoints.record_failure(index, &format!("{:#}", e), Instant::now());
+                        self.disconnect(index).await;
+                        last_error = Some(e);
+                    }
+                    Ok(Err(e)) if e.is::<StreamError>() => {
+                        // The stream broke after the operations were written.
+                        self.endpoints.record_failure(index, &format!("{:#}", e), Instant::now());
+                        self.disconnect(index).await;
+                        return Err(SendError::MaybeSent {
+                            functions,
+                            endpoint: self.endpoints.url(index).to_string(),
+                            reason: format!("lost ({:#})", e),
+                            operations,
+                        }
+                        .into());
+                    }
+                    Ok(Err(e)) => {
+                        // The node answered (e.g. rejected the operation): not an endpoint failure.
+                        self.endpoints.record_success(index, started.elapsed());
+                        return Err(e);
+                    }
+                    Err(_) => {
+                        // The operations may have been sent; retrying could submit them twice.
+                        self.endpoints.record_failure(index, "call timed out", Instant::now());
+                        self.disconnect(index).await;
+                        return Err(SendError::MaybeSent {
+                            functions,
+                            endpoint: self.endpoints.url(index).to_string(),
+                            reason: "timed out".to_string(),
+                            operations,
+                        }
+                        .into());
+                    }
+                }
+            }
+        }
+        Err(last_error
+            .unwrap_or_else(|| anyhow!("no gRPC endpoint answered"))
+            .context(format!("{} failed on every gRPC endpoint", functions)))
+    }
+
+    /// Candidate balance of `address`, from the healthiest endpoint that answers.
+    pub async fn get_balance(&self, address: &str) -> Result<Amount> {
+        let mut last_error = None;
+        for index in self.endpoints.order(Instant::now()) {
+            let started = Instant::now();
+            let result = match self.client(index).await {
+                Ok(client) => tokio::time::timeout(self.timeout, client.get_balance(address))
+                    .await
+                    .unwrap_or_else(|_| Err(anyhow!("balance query timed out"))),
+                Err(e) => Err(e),
+            };
+            match result {
+                Ok(balance) => {
+                    self.endpoints.record_success(index, started.elapsed());
+                    return Ok(balance);
+                }
+                Err(e) => {
+                    self.endpoints.record_failure(index, &format!("{:#}", e), Instant::now());
+                    last_error = Some(e);
+                }
+            }
+        }
+        Err(last_error.unwrap_or_else(|| anyhow!("no gRPC endpoint configured")))
+    }
+
+    /// Subscribe to finalized events of `emitter` on the healthiest endpoint that accepts.
+    pub async fn subscribe_final_events(&self, emitter: &str) -> Result<EventStream> {
+        let mut last_error = None;
+        for index in self.endpoints.order(Instant::now()) {
+            let url = self.endpoints.url(index);
+            let started = Instant::now();
+            let subscribe = massa_grpc::subscribe_final_events(url, emitter);
+            match tokio::time::timeout(self.timeout, subscribe).await {
+                Ok(Ok(stream)) => {
+                    self.endpoints.record_success(index, started.elapsed());
+                    return Ok(stream);
+                }
+                Ok(Err(e)) => {
+                    self.endpoints.record_failure(index, &format!("{:#}", e), Instant::now());
+                    last_error = Some(e);
+                }
+                Err(_) => {
+                    self.endpoints.record_failure(index, "subscription timed out", Instant::now());
+                    last_error = Some(anyhow!("subscription to {} timed out", url));
+                }
+            }
+        }
+        Err(last_error.unwrap_or_else(|| anyhow!("no gRPC endpoint configured")))
+    }
+
+    pub fn health(&self) -> Vec<EndpointHealth> {
+        self.endpoints.health()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn endpoints() -> Endpoints {
+        Endpoints::new(vec!["a".into(), "b".into(), "c".into()])
+    }
+
+    #[test]
+    fn failed_endpoints_are_tried_last_until_cooldown() {
+        let endpoints = endpoints();
+        let now = Instant::now();
+        assert_eq!(endpoints.order(now), vec![0, 1, 2]);
+
+        endpoints.record_failure(0, "connection refused", now);
+        assert_eq!(endpoints.order(now), vec![1, 2, 0]);
+        assert!(!endpoints.health()[0].healthy);
+
+        // Parked endpoints are ordered by when they come back
+        endpoints.record_failure(0, "connection refused", now);
+        endpoints.record_failure(1, "timeout", now);
+        assert_eq!(endpoints.order(now), vec![2, 1, 0]);
+
+        // Back in the healthy set after the cooldown, behind endpoints without failures
+        assert_eq!(endpoints.order(now + COOLDOWN_BASE * 2), vec![2, 1, 0]);
+        assert_eq!(endpoints.order(now + COOLDOWN_BASE), vec![2, 1, 0]);
+
+        endpoints.record_success(0, Duration::from_millis(40));
+        assert_eq!(endpoints.order(now), vec![0, 2, 1]);
+        let health = &endpoints.health()[0];
+        assert_eq!((health.requests, health.failures, health.consecutive_failures), (3, 2, 0));
+        assert_eq!(health.last_latency_ms, Some(40));
+    }
+
+    #[test]
+    fn cooldown_and_backoff_are_bounded() {
+        let endpoints = endpoints();
+        let now = Instant::now();
+        for _ in 0..40 {
+            endpoints.record_failure(2, "down", now);
+        }
+        assert!(endpoints.order(now + COOLDOWN_MAX).contains(&2));
+        assert_eq!(endpoints.order(now + COOLDOWN_MAX)[..2], [0, 1]);
+
+        for round in 0..40 {
+            let delay = backoff(round);
+            let max = BACKOFF_BASE.saturating_mul(1 << round.min(16)).min(BACKOFF_MAX);
+            assert!(delay >= max / 2 && delay <= max, "round {}: {:?}", round, delay);
+        }
+    }
+}
@@ -0,0 +1,266 @@
+//! Background integrity scrubber.
+//!
+//! Walks every blob in `Storage` at a bounded read rate and recomputes its Blake3 hash
+//! against the `content_hash` recorded at upload. Corrupt blobs are quarantined; the
+//! scrubber then tries to fetch a healthy copy from peers (replica holders first, then
+//! any connected provider). Erasure-coded blobs left without data are rebuilt from shards by
+//! the replication sweep. Progress and findings are exposed via `GET /admin/scrub`.
+
+use std::collections::VecDeque;
+use std::sync::Arc;
+use std::time::Duration;
+
+use libp2p::PeerId;
+use tokio::sync::RwLock;
+
+use crate::p2p::{announce_blob, blob_request, withdraw_blob, BlobRequest, BlobResponse, SharedP2pState};
+use crate::state::now_secs;
+use crate::storage::{content_hash, BlobMeta, Storage};
+
+/// Maximum number of findings kept in memory for the admin endpoint.
+const MAX_FINDINGS: usize = 100;
+
+/// What the scrubber found wrong with a blob.
+#[derive(Debug, Clone, serde::Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FindingKind {
+    /// Data hash differs from the hash recorded at upload.
+    HashMismatch,
+    /// Data could not be read.
+    ReadError,
+}
+
+/// One corrupt blob detected by the scrubber.
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct ScrubFinding {
+    pub namespace: String,
+    pub id: String,
+    pub kind: FindingKind,
+    pub detail: String,
+    pub detected_at: u64,
+    /// Path the corrupt data was moved to, if it was quarantined.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub quarantined_to: Option<String>,
+    /// True when a healthy copy was fetched from a peer and restored.
+    pub repaired: bool,
+}
+
+/// Scrubber progress and findings.
+#[derive(Debug, Clone, Default, serde::Serialize)]
+pub struct ScrubStatus {
+    /// Configured read rate in bytes per second.
+    pub rate_bytes_per_sec: u64,
+    /// True while a pass is in progress.
+    pub running: bool,
+    /// Number of passes started since the server started.
+    pub passes: u64,
+    pub pass_started_at: Option<u64>,
+    pub last_pass_completed_at: Option<u64>,
+    /// Blobs in the current (or last) pass.
+    pub pass_blobs_total: u64,
+    pub pass_blobs_checked: u64,
+    pub pass_bytes_checked: u64,
+    /// Blobs skipped because no hash was recorded at upload (legacy uploads).
+    pub pass_blobs_unverified: u64,
+    /// Totals since the server started.
+    pub corrupt_total: u64,
+    pub repaired_total: u64,
+    /// Most recent findings (newest last, at most 100).
+    pub findings: VecDeque<ScrubFinding>,
+}
+
+pub type SharedScrubStatus = Arc<RwLock<ScrubStatus>>;
+
+/// Spawn the scrubber. `rate_bytes_per_sec` bounds disk reads; `interval` is the pause
+/// between the end of one pass and the start of the next.
+pub fn spawn(
+    storage: Storage,
+    p2p: SharedP2pState,
+    rate_bytes_per_sec: u64,
+    interval: Duration,
+) -> SharedScrubStatus {
+    let status = Arc::new(RwLock::new(ScrubStatus {
+        rate_bytes_per_sec,
+        ..ScrubStatus::default()
+    }));
+    let scrubber = Scrubber {
+        storage,
+        p2p,
+        rate_bytes_per_sec,
+        status: status.clone(),
+    };
+    tokio::spawn(async move {
+        loop {
+            scrubber.run_pass().await;
+            tokio::time::sleep(interval).await;
+        }
+    });
+    status
+}
+
+struct Scrubber {
+    storage: Storage,
+    p2p: SharedP2pState,
+    rate_bytes_per_sec: u64,
+    status: SharedScrubStatus,
+}
+
+impl Scrubber {
+    async fn run_pass(&self) {
+        let entries = match self.storage.list(None) {
+            Ok(entries) => entries,
+            Err(e) => {
+                tracing::warn!(error = %e, "scrub: failed to list blobs");
+                return;
+            }
+        };
+        {
+            let mut s = self.status.write().await;
+            s.running = true;
+            s.passes += 1;
+            s.pass_started_at = Some(now_secs());
+            s.pass_blobs_total = entries.len() as u64;
+            s.pass_blobs_checked = 0;
+            s.pass_bytes_checked = 0;
+            s.pass_blobs_unverified = 0;
+        }
+        tracing::info!(blobs = entries.len(), "scrub pass started");
+
+        for entry in entries {
+            let size = self.check_blob(&entry.namespace, &entry.id).await;
+            {
+                let mut s = self.status.write().await;
+                s.pass_blobs_checked += 1;
+                s.pass_bytes_checked += size;
+            }
+            // Throttle: spend at least size / rate seconds per blob.
+            if self.rate_bytes_per_sec > 0 && size > 0 {
+                let secs = size as f64 / self.rate_bytes_per_sec as f64;
+                tokio::time::sleep(Duration::from_secs_f64(secs)).await;
+            }
+        }
+
+        let mut s = self.status.write().await;
+        s.running = false;
+        s.last_pass_completed_at = Some(now_secs());
+        tracing::info!(
+            checked = s.pass_blobs_checked,
+            bytes = s.pass_bytes_checked,
+            corrupt_total = s.corrupt_total,
+            "scrub pass completed"
+        );
+    }
+
+    /// Verify one blob; returns the number of bytes read.
+    async fn check_blob(&self, namespace: &str, id: &str) -> u64 {
+        let meta = match self.storage.meta(namespace, id) {
+            Ok(meta) => meta,
+            Err(_) => return 0, // deleted since listing
+        };
+        let (kind, detail, size) = match self.storage.get(namespace, id) {
+            Ok(data) => {
+                let size = data.len() as u64;
+                let Some(expected) = meta.content_hash.as_ref() else {
+                    self.status.write().await.pass_blobs_unverified += 1;
+                    return size;
+                };
+                let actual = content_hash(&data);
+                if &actual == expected {
+                    return size;
+                }
+                (
+                    FindingKind::HashMismatch,
+                    format!("expected {}, got {}", expected, actual),
+                    size,
+                )
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return 0,
+            Err(e) => (FindingKind::ReadError, e.to_string(), 0),
+        };
+
+        // Checked without the storage lock: a concurrent upload may have rewritten the blob
+        let quarantined_to = match self.storage.quarantine_if_corrupt(namespace, id) {
+            Ok(Some(path)) => Some(path.display().to_string()),
+            Ok(None) => {
+                tracing::debug!(namespace, id, "scrub: blob rewritten during the check; skipped");
+                return size;
+            }
+            Err(e) => {
+                tracing::warn!(namespace, id, error = %e, "scrub: failed to quarantine blob");
+                None
+            }
+        };
+        tracing::error!(namespace, id, kind = ?kind, detail = %detail, "scrub: corrupt blob");
+        // The metadata may also have been rewritten since it was read
+        let meta = self.storage.meta(namespace, id).unwrap_or(meta);
+        if let (Some(_), Some(hash)) = (&quarantined_to, &meta.content_hash) {
+            withdraw_blob(&self.p2p, &self.storage, hash).await;
+        }
+        let repaired = quarantined_to.is_some() && self.repair(namespace, id, &meta).await;
+
+        let mut s = self.status.write().await;
+        s.corrupt_total += 1;
+        if repaired {
+            s.repaired_total += 1;
+        }
+        if s.findings.len() >= MAX_FINDINGS {
+            s.findings.pop_front();
+        }
+        s.findings.push_back(ScrubFinding {
+            namespace: namespace.to_string(),
+            id: id.to_string(),
+            kind,
+            detail,
+            detected_at: now_secs(),
+            quarantined_to,
+            repaired,
+        });
+        size
+    }
+
+    /// Fetch a healthy full copy from peers and restore it. Replica holders are asked first,
+    /// then the other providers by reputation.
+    async fn repair(&self, namespace: &str, id: &str, meta: &BlobMeta) -> bool {
+        let Some(expected) = meta.content_hash.as_ref() else {
+            return false;
+        };
+        let mut peers: Vec<PeerId> = meta.replicas.iter().filter_map(|p| p.parse().ok()).collect();
+        for peer in self.p2p.read().await.provider_peers() {
+            if !peers.contains(&peer) {
+                peers.push(peer);
+            }
+        }
+
+        for peer in peers {
+            let request = BlobRequest::GetBlob {
+                namespace: namespace.to_string(),
+                id: id.to_string(),
+            };
+            match blob_request(&self.p2p, peer, request).await {
+                Ok(BlobResponse::Blob(data)) if &content_hash(&data) == expected => {
+                    match self.storage.restore_data(namespace, id, &data) {
+                        Ok(()) => {
+                            tracing::info!(namespace, id, %peer, "scrub: blob repaired from peer");
+                            announce_blob(&self.p2p, expected).await;
+                            return true;
+                        }
+                        Err(e) => {
+                            tracing::warn!(namespace, id, error = %e, "scrub: failed to restore blob");
+                            return false;
+                        }
+                    }
+                }
+                Ok(BlobResponse::Blob(_)) => {
+                    tracing::warn!(namespace, id, %peer, "scrub: peer copy is also corrupt");
+                    self.p2p.write().await.record_transfer(peer, false);
+                }
+                Ok(_) => {}
+                Err(e) => tracing::debug!(namespace, id, %peer, error = %e, "scrub: fetch failed"),
+            }
+        }
+        if meta.erasure.is_some() {
+            tracing::info!(namespace, id, "scrub: no full copy found; left to shard rebuild");
+        }
+        false
+    }
+}
@@ -0,0 +1,133 @@
+//! State kept by the background tasks (usage outbox, rewards, drain, metadata reconciler).
+//!
+//! A [`StateCell`] holds the task's state behind a lock. Cells created with
+//! [`StateCell::load`] are also saved as JSON (usually under `{STORAGE_PATH}/.state/`)
+//! after every [`StateCell::update`], so the task can resume after a restart.
+
+use std::io;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+use crate::storage::write_atomic;
+
+/// Current Unix time in seconds (0 if the clock is before the epoch).
+pub fn now_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+/// A task's state behind a lock, optionally saved to disk on every update.
+pub struct StateCell<T> {
+    value: Mutex<T>,
+    /// Where the state is saved; `None` keeps it in memory only.
+    path: Option<PathBuf>,
+    /// What the state is, for log messages.
+    what: &'static str,
+}
+
+impl<T: Serialize> StateCell<T> {
+    /// A state that is only kept in memory.
+    pub fn new(value: T, what: &'static str) -> Self {
+        Self {
+            value: Mutex::new(value),
+            path: None,
+            what,
+        }
+    }
+
+    /// Load the state saved at `path`. A missing file starts from the default; so does an
+    /// unreadable one, after logging it.
+    pub fn load(path: PathBuf, what: &'static str) -> Self
+    where
+        T: DeserializeOwned + Default,
+    {
+        let value = match std::fs::read(&path) {
+            Ok(data) => serde_json::from_slice(&data).unwrap_or_else(|e| {
+                tracing::error!(error = %e, path = %path.display(), "unreadable {}; starting empty", what);
+                T::default()
+            }),
+            Err(_) => T::default(),
+        };
+        Self {
+            value: Mutex::new(value),
+            path: Some(path),
+            what,
+        }
+    }
+
+    /// Run `read` on the current state.
+    pub fn with<R>(&self, read: impl FnOnce(&T) -> R) -> R {
+        read(&self.value.lock().unwrap())
+    }
+
+    pub fn snapshot(&self) -> T
+    where
+        T: Clone,
+    {
+        self.with(T::clone)
+    }
+
+    /// Apply `change` and save the state; a failed save is logged, the memory state stays
+    /// authoritative until the next successful save.
+    pub fn update<R>(&self, change: impl FnOnce(&mut T) -> R) -> R {
+        let mut value = self.value.lock().unwrap();
+        let result = change(&mut value);
+        if let Err(e) = self.save(&value) {
+            tracing::error!(error = %e, "failed to save {}", self.what);
+        }
+        result
+    }
+
+    /// Like [`StateCell::update`], but returns the save error to the caller.
+    pub fn try_update<R>(&self, change: impl FnOnce(&mut T) -> R) -> io::Result<R> {
+        let mut value = self.value.lock().unwrap();
+        let result = change(&mut value);
+        self.save(&value)?;
+        Ok(result)
+    }
+
+    fn save(&self, value: &T) -> io::Result<()> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        let data = serde_json::to_vec(value).map_err(io::Error::other)?;
+        write_atomic(path, &data)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn updates_are_saved_and_reloaded() {
+        let dir = std::env::temp_dir().join(format!("massa-state-test-{}", uuid::Uuid::new_v4()));
+        let path = dir.join("counts.json");
+
+        let cell: StateCell<Vec<u64>> = StateCell::load(path.clone(), "counts");
+        assert!(cell.snapshot().is_empty());
+        let len = cell.update(|v| {
+            v.push(3);
+            v.len()
+        });
+        assert_eq!(len, 1);
+        cell.try_update(|v| v.push(4)).unwrap();
+
+        let reloaded: StateCell<Vec<u64>> = StateCell::load(path.clone(), "counts");
+        assert_eq!(reloaded.snapshot(), vec![3, 4]);
+
+        std::fs::write(&path, b"not json").unwrap();
+        let corrupt: StateCell<Vec<u64>> = StateCell::load(path, "counts");
+        assert!(corrupt.snapshot().is_empty());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -1,20 +1,49 @@
 //! Simple filesystem storage backend with indexing by namespace and id.
 //! Data is stored under `{storage_path}/{namespace}/{id}`; listing reads directory metadata.
 //! Optional per-blob metadata (e.g. min_replication) is stored in `{id}.meta` (JSON).
+//! Erasure-coded shards held for other providers are stored in `{id}.shards/{origin}-{index}`,
+//! `origin` being the peer id of the provider that placed them.
+//!
+//! Writes are crash-safe: every file is written to a `.tmp` sibling, fsynced, then renamed
+//! into place. For blobs, renaming the `.meta` file is the commit point: it records the Blake3
+//! hash of the data, so `recover()` at startup can roll a committed data temp file forward
+//! and discard anything that was not committed.
 
 use std::fs;
-use std::io;
+use std::io::{self, Write};
 use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
 use std::time::UNIX_EPOCH;
 
 use uuid::Uuid;
 
+use crate::envelope::EnvelopeMeta;
+use crate::erasure::ErasureMeta;
+use crate::state::now_secs;
+
 /// Allowed range for uploader-requested minimum replication (1 = single copy only).
 pub const MIN_REPLICATION_MIN: u8 = 1;
 pub const MIN_REPLICATION_MAX: u8 = 32;
 
-/// Per-blob metadata stored in `{id}.meta`. Used so that when P2P replication is
-/// implemented, the system can enforce the uploader's minimum replication requirement.
+/// Suffix of temporary files written before being renamed into place.
+pub const TMP_SUFFIX: &str = ".tmp";
+/// Directory under the storage base where corrupt blob data is moved by the scrubber.
+/// Starts with a dot so it can never collide with a sanitized namespace.
+const QUARANTINE_DIR: &str = ".quarantine";
+/// Directory under the storage base holding the read-through cache (see `read_through`).
+/// Bounded by its own quota and not counted in the storage usage.
+pub const CACHE_DIR: &str = ".cache";
+/// Directory under the storage base for server state (e.g. the libp2p identity and the contract
+/// event cursor).
+const STATE_DIR: &str = ".state";
+
+/// Returns true for directories under the base that are not namespaces (e.g. quarantine).
+pub fn is_reserved_dir(name: &str) -> bool {
+    name.starts_with('.')
+}
+
+/// Per-blob metadata stored in `{id}.meta`. The replication manager uses it to enforce the
+/// uploader's redundancy choice (full copies or erasure-coded shards) and to track placement.
 #[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
 pub struct BlobMeta {
     /// Minimum number of replicas the uploader requested (1 = no requirement beyond single copy).
@@ -22,38 +51,115 @@ pub struct BlobMeta {
     /// Massa address of the uploader (when upload auth was used). Omitted for legacy uploads.
     #[serde(skip_serializing_if = "Option::is_none")]
     pub uploader_address: Option<String>,
+    /// Blake3 hash (hex) of the blob data, set by `Storage::put`. Omitted for legacy uploads.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub content_hash: Option<String>,
+    /// Encryption envelope header info when the blob is client-side encrypted (see `envelope`).
+    /// The provider stores and serves the envelope bytes as-is.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub envelope: Option<EnvelopeMeta>,
+    /// Erasure coding scheme and shard placement when the uploader chose erasure coding
+    /// instead of full replication.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub erasure: Option<ErasureMeta>,
+    /// Peer ids of providers holding a full copy (full replication mode, origin side).
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub replicas: Vec<String>,
+    /// True when this blob is a copy received from another provider over P2P.
+    /// Replicas are not replicated further.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub replica: bool,
+}
+
+impl Default for BlobMeta {
+    fn default() -> Self {
+        Self {
+            min_replication: MIN_REPLICATION_MIN,
+            uploader_address: None,
+            content_hash: None,
+            envelope: None,
+            erasure: None,
+            replicas: Vec::new(),
+            replica: false,
+        }
+    }
 }
 
 fn meta_path_for_id(ns_path: &Path, id: &str) -> PathBuf {
     ns_path.join(format!("{}.meta", id))
 }
 
+fn shards_dir_for_id(ns_path: &Path, id: &str) -> PathBuf {
+    ns_path.join(format!("{}.shards", id))
+}
+
+/// File name of shard `index` placed by `origin` (a peer id) in a shard directory.
+fn shard_file_name(origin: &str, index: u8) -> String {
+    format!("{}-{}", sanitize_segment(origin), index)
+}
+
+/// Temporary sibling of `path` (`{name}.tmp`).
+fn tmp_path(path: &Path) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(TMP_SUFFIX);
+    path.with_file_name(name)
+}
+
+/// Create `path` with `data` and fsync it.
+fn write_synced(path: &Path, data: &[u8]) -> io::Result<()> {
+    let mut file = fs::File::create(path)?;
+    file.write_all(data)?;
+    file.sync_all()
+}
+
+/// Fsync a directory so renames inside it are durable.
+#[cfg(unix)]
+fn sync_dir(dir: &Path) -> io::Result<()> {
+    fs::File::open(dir)?.sync_all()
+}
+
+#[cfg(not(unix))]
+fn sync_dir(_dir: &Path) -> io::Result<()> {
+    Ok(())
+}
+
+/// Atomically replace `path` with `data` (temp file + fsync + rename + directory fsync).
+pub fn write_atomic(path: &Path, data: &[u8]) -> io::Result<()> {
+    let tmp = tmp_path(path);
+    write_synced(&tmp, data)?;
+    fs::rename(&tmp, path)?;
+    if let Some(dir) = path.parent() {
+        sync_dir(dir)?;
+    }
+    Ok(())
+}
+
+/// Blake3 hash (hex) of blob data, as recorded in `BlobMeta::content_hash`.
+pub fn content_hash(data: &[u8]) -> String {
+    blake3::hash(data).to_hex().to_string()
+}
+
+/// Outcome of the startup recovery pass.
+#[derive(Debug, Default)]
+pub struct RecoveryReport {
+    /// Committed blob writes whose data file was renamed into place by recovery.
+    pub rolled_forward: usize,
+    /// Uncommitted or partial temp files removed.
+    pub discarded: usize,
+}
+
 /// Read BlobMeta from `{id}.meta`; returns default (min_replication=1, no uploader) if missing/invalid.
 fn read_blob_meta(ns_path: &Path, id: &str) -> BlobMeta {
     let meta_path = meta_path_for_id(ns_path, id);
     let contents = match fs::read_to_string(&meta_path) {
         Ok(c) => c,
-        Err(_) => {
-            return BlobMeta {
-                min_replication: MIN_REPLICATION_MIN,
-                uploader_address: None,
-            }
-        }
-    };
-    let meta: BlobMeta = match serde_json::from_str(&contents) {
-        Ok(m) => m,
-        Err(_) => {
-            return BlobMeta {
-                min_replication: MIN_REPLICATION_MIN,
-                uploader_address: None,
-            }
-        }
+        Err(_) => return BlobMeta::default(),
     };
-    meta
+    serde_json::from_str(&contents).unwrap_or_default()
 }
 
 /// Sanitize a segment for use in paths (namespace or id): only alphanumeric, dash, underscore.
-fn sanitize_segment(s: &str) -> String {
+pub fn sanitize_segment(s: &str) -> String {
     s.chars()
         .map(|c| if c.is_ascii_alphanumeric() || c == '-' || c == '_' {
             c
@@ -69,6 +175,8 @@ pub struct Storage {
     base: PathBuf,
     /// put() rejects uploads that would exceed this total size (bytes).
     storage_limit_bytes: u64,
+    /// Serializes writes so the limit check and temp file names cannot race.
+    write_lock: Arc<Mutex<()>>,
 }
 
 #[derive(Debug, serde::Serialize)]
@@ -82,6 +190,17 @@ pub struct IndexEntry {
     pub created_at: u64,
     /// Minimum replication requested by the uploader (1 if no metadata or not set).
     pub min_replication: u8,
+    /// Envelope info for client-side encrypted blobs.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub envelope: Option<EnvelopeMeta>,
+    /// Erasure scheme (`k+m`) when the blob is erasure-coded.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub erasure: Option<String>,
+    /// Blake3 hash (hex) of the data; key of the blob's provider records in the DHT.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub content_hash: Option<String>,
+    /// True when this node holds the blob as a replica for another provider.
+    pub replica: bool,
 }
 
 impl Storage {
@@ -89,24 +208,42 @@ impl Storage {
         Self {
             base,
             storage_limit_bytes,
+            write_lock: Arc::new(Mutex::new(())),
         }
     }
 
+    fn lock_writes(&self) -> std::sync::MutexGuard<'_, ()> {
+        self.write_lock.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
     /// Storage limit in bytes (set from STORAGE_LIMIT_GB at startup).
     pub fn storage_limit_bytes(&self) -> u64 {
         self.storage_limit_bytes
     }
 
-    /// Total size in bytes of all files under the storage base directory.
+    /// Directory of the read-through cache.
+    pub fn cache_dir(&self) -> PathBuf {
+        self.base.join(CACHE_DIR)
+    }
+
+    /// Directory for server state files.
+    pub fn state_dir(&self) -> PathBuf {
+        self.base.join(STATE_DIR)
+    }
+
+    /// Total size in bytes of all files under the storage base directory, excluding the
+    /// read-through cache and quarantined data.
     pub fn total_size(&self) -> io::Result<u64> {
-        fn dir_size(path: &Path) -> io::Result<u64> {
+        fn dir_size(path: &Path, skip: &[PathBuf]) -> io::Result<u64> {
             let mut total: u64 = 0;
             if path.is_dir() {
                 for entry in fs::read_dir(path)? {
                     let entry = entry?;
                     let path = entry.path();
-                    if path.is_dir() {
-                        total += dir_size(&path)?;
+                    if skip.contains(&path) {
+                        continue;
+                    } else if path.is_dir() {
+                        total += dir_size(&path, skip)?;
                     } else {
                         total += entry.metadata()?.len();
                     }
@@ -114,7 +251,36 @@ impl Storage {
             }
             Ok(total)
         }
-        dir_size(&self.base)
+        dir_size(&self.base, &[self.cache_dir(), self.base.join(QUARANTINE_DIR)])
+    }
+
+    /// Returns an error if current usage + `additional` bytes would exceed the storage limit.
+    fn check_limit(&self, additional: usize) -> io::Result<()> {
+        let current = self.total_size()?;
+        let new_total = current.saturating_add(additional as u64);
+        if new_total > self.storage_limit_bytes {
+            return Err(io::Error::new(
+                io::ErrorKind::Other,
+                format!(
+                    "storage limit exceeded: current {} bytes, limit {} bytes, upload {} bytes",
+                    current, self.storage_limit_bytes, additional
+                ),
+            ));
+        }
+        Ok(())
+    }
+
+    /// Sanitized namespace directory and id for an existing blob.
+    fn blob_location(&self, namespace: &str, id: &str) -> io::Result<(PathBuf, String)> {
+        let ns = sanitize_segment(namespace);
+        let id = sanitize_segment(id);
+        if ns.is_empty() || id.is_empty() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "namespace and id must be non-empty",
+            ));
+        }
+        Ok((self.base.join(&ns), id))
     }
 
     /// Ensure base and namespace dirs exist.
@@ -133,57 +299,235 @@ impl Storage {
 
     /// Store raw bytes under namespace with optional id; returns the id used.
     /// Returns an error if current usage + data would exceed the storage limit.
-    /// `min_replication` and optional `uploader_address` are stored in `{id}.meta`.
+    /// `meta` (min_replication, uploader, envelope info) is stored in `{id}.meta` together
+    /// with the data's Blake3 hash; data and metadata are committed together (see module docs).
     pub fn put(
         &self,
         namespace: &str,
         id_hint: Option<&str>,
         data: &[u8],
-        min_replication: u8,
-        uploader_address: Option<String>,
+        meta: BlobMeta,
     ) -> io::Result<String> {
-        let current = self.total_size()?;
-        let new_total = current.saturating_add(data.len() as u64);
-        if new_total > self.storage_limit_bytes {
-            return Err(io::Error::new(
-                io::ErrorKind::Other,
-                format!(
-                    "storage limit exceeded: current {} bytes, limit {} bytes, upload {} bytes",
-                    current, self.storage_limit_bytes, data.len()
-                ),
-            ));
+        let _guard = self.lock_writes();
+        self.write_blob(namespace, id_hint, data, meta)
+    }
+
+    /// Store a full copy of a blob sent by another provider. `meta.content_hash` must match
+    /// `data`, and an existing blob is only overwritten by a replica of the same content:
+    /// a provider can neither replace a blob uploaded here nor change a replica's data.
+    pub fn put_replica(&self, namespace: &str, id: &str, data: &[u8], meta: BlobMeta) -> io::Result<()> {
+        let hash = content_hash(data);
+        if meta.content_hash.as_deref() != Some(hash.as_str()) {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "content hash mismatch"));
+        }
+        let _guard = self.lock_writes();
+        match self.meta(namespace, id) {
+            Ok(existing) if !existing.replica => {
+                return Err(io::Error::new(
+                    io::ErrorKind::AlreadyExists,
+                    "blob exists locally and is not a replica",
+                ));
+            }
+            Ok(existing) if existing.content_hash.as_ref().is_some_and(|h| *h != hash) => {
+                return Err(io::Error::new(
+                    io::ErrorKind::AlreadyExists,
+                    "replica exists with different content",
+                ));
+            }
+            Ok(_) => {}
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+            Err(e) => return Err(e),
         }
+        let meta = BlobMeta {
+            erasure: None,
+            replicas: Vec::new(),
+            replica: true,
+            ..meta
+        };
+        self.write_blob(namespace, Some(id), data, meta).map(|_| ())
+    }
+
+    /// Write data and metadata (see `put`); the caller holds the write lock.
+    fn write_blob(
+        &self,
+        namespace: &str,
+        id_hint: Option<&str>,
+        data: &[u8],
+        meta: BlobMeta,
+    ) -> io::Result<String> {
+        self.check_limit(data.len())?;
         let ns_path = self.ensure_namespace(namespace)?;
         let id = id_hint
             .map(|s| sanitize_segment(s))
             .filter(|s| !s.is_empty())
             .unwrap_or_else(|| Uuid::new_v4().to_string());
         let path = ns_path.join(&id);
-        fs::write(&path, data)?;
+        let meta_path = meta_path_for_id(&ns_path, &id);
         let meta = BlobMeta {
-            min_replication,
-            uploader_address,
+            content_hash: Some(content_hash(data)),
+            ..meta
         };
-        let meta_path = meta_path_for_id(&ns_path, &id);
-        fs::write(
-            meta_path,
-            serde_json::to_string(&meta).expect("BlobMeta serialization is infallible"),
+
+        let data_tmp = tmp_path(&path);
+        let meta_tmp = tmp_path(&meta_path);
+        write_synced(&data_tmp, data)?;
+        write_synced(
+            &meta_tmp,
+            serde_json::to_string(&meta)
+                .expect("BlobMeta serialization is infallible")
+                .as_bytes(),
         )?;
+        sync_dir(&ns_path)?;
+        // Commit point: once the metadata is in place, recovery rolls the data forward.
+        fs::rename(&meta_tmp, &meta_path)?;
+        fs::rename(&data_tmp, &path)?;
+        sync_dir(&ns_path)?;
         Ok(id)
     }
 
     /// Get raw bytes by namespace and id.
     pub fn get(&self, namespace: &str, id: &str) -> io::Result<Vec<u8>> {
-        let ns = sanitize_segment(namespace);
+        let (ns_path, id) = self.blob_location(namespace, id)?;
+        fs::read(ns_path.join(&id))
+    }
+
+    /// Read the metadata of a blob. Returns NotFound when neither the blob nor its
+    /// `.meta` file exists (metadata alone is enough, e.g. for a blob lost locally).
+    pub fn meta(&self, namespace: &str, id: &str) -> io::Result<BlobMeta> {
+        let (ns_path, id) = self.blob_location(namespace, id)?;
+        if !ns_path.join(&id).is_file() && !meta_path_for_id(&ns_path, &id).is_file() {
+            return Err(io::Error::new(io::ErrorKind::NotFound, "blob not found"));
+        }
+        Ok(read_blob_meta(&ns_path, &id))
+    }
+
+    /// Overwrite the metadata of an existing blob (e.g. after replicas or shards were placed).
+    pub fn update_meta(&self, namespace: &str, id: &str, meta: &BlobMeta) -> io::Result<()> {
+        let _guard = self.lock_writes();
+        let (ns_path, id) = self.blob_location(namespace, id)?;
+        write_atomic(
+            &meta_path_for_id(&ns_path, &id),
+            serde_json::to_string(meta)
+                .expect("BlobMeta serialization is infallible")
+                .as_bytes(),
+        )
+    }
+
+    /// Read-modify-write of a blob's metadata under the write lock, so a concurrent upload
+    /// is not overwritten with stale fields. Nothing is written when `change` returns false.
+    /// Returns whether the metadata was written.
+    pub fn modify_meta(
+        &self,
+        namespace: &str,
+        id: &str,
+        change: impl FnOnce(&mut BlobMeta) -> bool,
+    ) -> io::Result<bool> {
+        let _guard = self.lock_writes();
+        let mut meta = self.meta(namespace, id)?;
+        if !change(&mut meta) {
+            return Ok(false);
+        }
+        let (ns_path, id) = self.blob_location(namespace, id)?;
+        write_atomic(
+            &meta_path_for_id(&ns_path, &id),
+            serde_json::to_string(&meta)
+                .expect("BlobMeta serialization is infallible")
+                .as_bytes(),
+        )?;
+        Ok(true)
+    }
+
+    /// Restore the data of a blob whose metadata is kept (e.g. after erasure reconstruction).
+    pub fn restore_data(&self, namespace: &str, id: &str, data: &[u8]) -> io::Result<()> {
+        let _guard = self.lock_writes();
+        self.check_limit(data.len())?;
+        let (ns_path, id) = self.blob_location(namespace, id)?;
+        write_atomic(&ns_path.join(&id), data)
+    }
+
+    /// Move a blob's data to `{base}/.quarantine/{namespace}/{id}-{unix_secs}`, keeping its
+    /// metadata in place so the blob shows up as missing and can be repaired or rebuilt.
+    pub fn quarantine(&self, namespace: &str, id: &str) -> io::Result<PathBuf> {
+        let _guard = self.lock_writes();
+        self.move_to_quarantine(namespace, id)
+    }
+
+    /// Quarantine a blob found corrupt unless it changed since it was checked: under the
+    /// write lock, its data must still be unreadable or differ from the `content_hash` of its
+    /// current metadata. Returns `None` when the blob is now healthy or gone.
+    pub fn quarantine_if_corrupt(&self, namespace: &str, id: &str) -> io::Result<Option<PathBuf>> {
+        let _guard = self.lock_writes();
+        let expected = self.meta(namespace, id)?.content_hash;
+        match self.get(namespace, id) {
+            Ok(data) if expected.is_none() || expected == Some(content_hash(&data)) => return Ok(None),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+            _ => {}
+        }
+        self.move_to_quarantine(namespace, id).map(Some)
+    }
+
+    /// True when data of blob `id` was moved to the quarantine directory.
+    pub fn is_quarantined(&self, namespace: &str, id: &str) -> bool {
+        let prefix = format!("{}-", sanitize_segment(id));
+        let Ok(entries) = fs::read_dir(self.base.join(QUARANTINE_DIR).join(sanitize_segment(namespace))) else {
+            return false;
+        };
+        entries.flatten().any(|entry| {
+            let name = entry.file_name().to_string_lossy().to_string();
+            name.strip_prefix(&prefix)
+                .is_some_and(|secs| !secs.is_empty() && secs.bytes().all(|b| b.is_ascii_digit()))
+        })
+    }
+
+    /// Move a blob's data to the quarantine directory; the caller holds the write lock.
+    fn move_to_quarantine(&self, namespace: &str, id: &str) -> io::Result<PathBuf> {
+        let (ns_path, id) = self.blob_location(namespace, id)?;
+        let ns = ns_path.file_name().unwrap_or_default().to_os_string();
+        let dir = self.base.join(QUARANTINE_DIR).join(ns);
+        fs::create_dir_all(&dir)?;
+        let target = dir.join(format!("{}-{}", id, now_secs()));
+        fs::rename(ns_path.join(&id), &target)?;
+        sync_dir(&ns_path)?;
+        Ok(target)
+    }
+
+    /// Store shard `index` of an erasure-coded blob held on behalf of provider `origin`.
+    /// Shards are kept per origin, so a provider cannot replace another one's shards, and an
+    /// existing shard is only rewritten with the same data.
+    pub fn put_shard(&self, namespace: &str, id: &str, origin: &str, index: u8, data: &[u8]) -> io::Result<()> {
+        let _guard = self.lock_writes();
+        let ns_path = self.ensure_namespace(namespace)?;
         let id = sanitize_segment(id);
-        if ns.is_empty() || id.is_empty() {
-            return Err(io::Error::new(
-                io::ErrorKind::InvalidInput,
-                "namespace and id must be non-empty",
-            ));
+        if id.is_empty() || sanitize_segment(origin).is_empty() {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "id and origin must be non-empty"));
+        }
+        let dir = shards_dir_for_id(&ns_path, &id);
+        let path = dir.join(shard_file_name(origin, index));
+        match fs::read(&path) {
+            Ok(existing) if existing == data => return Ok(()),
+            Ok(_) => {
+                return Err(io::Error::new(
+                    io::ErrorKind::AlreadyExists,
+                    "shard exists with different content",
+                ));
+            }
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+            Err(e) => return Err(e),
+        }
+        self.check_limit(data.len())?;
+        fs::create_dir_all(&dir)?;
+        write_atomic(&path, data)
+    }
+
+    /// Get shard `index` of an erasure-coded blob held for provider `origin`. Shards stored
+    /// before origins were recorded (`{id}.shards/{index}`) are served to any provider.
+    pub fn get_shard(&self, namespace: &str, id: &str, origin: &str, index: u8) -> io::Result<Vec<u8>> {
+        let (ns_path, id) = self.blob_location(namespace, id)?;
+        let dir = shards_dir_for_id(&ns_path, &id);
+        match fs::read(dir.join(shard_file_name(origin, index))) {
+            Err(e) if e.kind() == io::ErrorKind::NotFound => fs::read(dir.join(index.to_string())),
+            result => result,
         }
-        let path = self.base.join(&ns).join(&id);
-        fs::read(&path)
     }
 
     /// List entries in a namespace (optional). If namespace is None, list all namespaces' entries.
@@ -213,6 +557,9 @@ impl Storage {
                         .file_name()
                         .into_string()
                         .unwrap_or_default();
+                    if is_reserved_dir(&ns) {
+                        continue;
+                    }
                     self.list_in_dir(&path, &ns, &mut entries)?;
                 }
             }
@@ -221,6 +568,109 @@ impl Storage {
         Ok(entries)
     }
 
+    /// Startup recovery pass: finish or discard writes interrupted by a crash.
+    /// - `{id}.meta.tmp` present: the blob write never committed; both temp files are removed.
+    /// - `{id}.tmp` next to a `.meta` whose `content_hash` matches: committed; renamed into place.
+    /// - any other `.tmp` file (including in shard directories): removed.
+    pub fn recover(&self) -> io::Result<RecoveryReport> {
+        let _guard = self.lock_writes();
+        let mut report = RecoveryReport::default();
+        if !self.base.is_dir() {
+            return Ok(report);
+        }
+        for ns_entry in fs::read_dir(&self.base)? {
+            let ns_entry = ns_entry?;
+            let reserved = is_reserved_dir(&ns_entry.file_name().to_string_lossy());
+            if ns_entry.path().is_dir() && !reserved {
+                self.recover_dir(&ns_entry.path(), &mut report)?;
+            }
+        }
+        Ok(report)
+    }
+
+    fn recover_dir(&self, dir: &Path, report: &mut RecoveryReport) -> io::Result<()> {
+        let mut tmp_files = Vec::new();
+        for entry in fs::read_dir(dir)? {
+            let path = entry?.path();
+            let name = path
+                .file_name()
+                .and_then(|n| n.to_str())
+                .unwrap_or_default()
+                .to_string();
+            if path.is_dir() {
+                // Shard directories only ever hold standalone atomic writes.
+                if name.ends_with(".shards") {
+                    for shard in fs::read_dir(&path)? {
+                        let shard = shard?.path();
+                        if shard.to_string_lossy().ends_with(TMP_SUFFIX) {
+                            fs::remove_file(&shard)?;
+                            report.discarded += 1;
+                        }
+                    }
+                }
+            } else if let Some(stem) = name.strip_suffix(TMP_SUFFIX) {
+                tmp_files.push(stem.to_string());
+            }
+        }
+
+        for stem in &tmp_files {
+            let tmp = dir.join(format!("{}{}", stem, TMP_SUFFIX));
+            if stem.ends_with(".meta") {
+                fs::remove_file(&tmp)?;
+                report.discarded += 1;
+                continue;
+            }
+            let id = stem.as_str();
+            let meta_uncommitted = tmp_files.iter().any(|t| *t == format!("{}.meta", id));
+            let committed_hash = if meta_uncommitted {
+                None
+            } else {
+                read_blob_meta(dir, id).content_hash
+            };
+            let tmp_matches = match committed_hash {
+                Some(hash) => fs::read(&tmp).map(|d| content_hash(&d) == hash).unwrap_or(false),
+                None => false,
+            };
+            if tmp_matches {
+                fs::rename(&tmp, dir.join(id))?;
+                report.rolled_forward += 1;
+                tracing::info!(dir = %dir.display(), id, "recovered committed blob write");
+            } else {
+                fs::remove_file(&tmp)?;
+                report.discarded += 1;
+                tracing::info!(dir = %dir.display(), id, "discarded uncommitted blob write");
+            }
+        }
+        sync_dir(dir)
+    }
+
+    /// Blobs whose `.meta` file exists but whose data file is missing, as (namespace, id).
+    pub fn missing_data(&self) -> io::Result<Vec<(String, String)>> {
+        let mut missing = Vec::new();
+        if !self.base.is_dir() {
+            return Ok(missing);
+        }
+        for ns_entry in fs::read_dir(&self.base)? {
+            let ns_entry = ns_entry?;
+            if !ns_entry.path().is_dir() {
+                continue;
+            }
+            let ns = ns_entry.file_name().into_string().unwrap_or_default();
+            if is_reserved_dir(&ns) {
+                continue;
+            }
+            for entry in fs::read_dir(ns_entry.path())? {
+                let name = entry?.file_name().into_string().unwrap_or_default();
+                if let Some(id) = name.strip_suffix(".meta") {
+                    if !ns_entry.path().join(id).is_file() {
+                        missing.push((ns.clone(), id.to_string()));
+                    }
+                }
+            }
+        }
+        Ok(missing)
+    }
+
     fn list_in_dir(
         &self,
         dir: &Path,
@@ -235,8 +685,8 @@ impl Storage {
                     .file_name()
                     .into_string()
                     .unwrap_or_default();
-                // Skip metadata sidecar files
-                if name.ends_with(".meta") {
+                // Skip sidecar files (.meta, .tmp); sanitized ids never contain dots
+                if name.contains('.') {
                     continue;
                 }
                 let id = name;
@@ -256,9 +706,155 @@ impl Storage {
                     size,
                     created_at,
                     min_replication: meta.min_replication,
+                    envelope: meta.envelope,
+                    erasure: meta.erasure.map(|e| e.scheme().to_string()),
+                    content_hash: meta.content_hash,
+                    replica: meta.replica,
                 });
             }
         }
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_storage() -> Storage {
+        let base = std::env::temp_dir().join(format!("massa-storage-test-{}", Uuid::new_v4()));
+        fs::create_dir_all(&base).unwrap();
+        Storage::new(base, 1024 * 1024)
+    }
+
+    #[test]
+    fn put_records_content_hash() {
+        let storage = temp_storage();
+        let id = storage.put("ns", Some("blob"), b"data", BlobMeta::default()).unwrap();
+
+        assert_eq!(storage.get("ns", &id).unwrap(), b"data");
+        assert_eq!(storage.meta("ns", &id).unwrap().content_hash, Some(content_hash(b"data")));
+        assert!(!storage.base.join("ns").join("blob.tmp").exists());
+        fs::remove_dir_all(&storage.base).unwrap();
+    }
+
+    #[test]
+    fn put_replica_refuses_to_overwrite() {
+        let storage = temp_storage();
+        let replica_meta = |data: &[u8]| BlobMeta {
+            content_hash: Some(content_hash(data)),
+            ..BlobMeta::default()
+        };
+        storage.put("ns", Some("own"), b"data", BlobMeta::default()).unwrap();
+        assert!(storage.put_replica("ns", "own", b"data", replica_meta(b"data")).is_err());
+
+        assert!(storage.put_replica("ns", "copy", b"data", replica_meta(b"other")).is_err());
+        storage.put_replica("ns", "copy", b"data", replica_meta(b"data")).unwrap();
+        assert!(storage.meta("ns", "copy").unwrap().replica);
+        // Sending the same replica again is fine; different content is not.
+        storage.put_replica("ns", "copy", b"data", replica_meta(b"data")).unwrap();
+        assert!(storage.put_replica("ns", "copy", b"evil", replica_meta(b"evil")).is_err());
+        assert_eq!(storage.get("ns", "copy").unwrap(), b"data");
+        fs::remove_dir_all(&storage.base).unwrap();
+    }
+
+    #[test]
+    fn modify_meta_reads_current_metadata() {
+        let storage = temp_storage();
+        storage.put("ns", Some("blob"), b"data", BlobMeta::default()).unwrap();
+        assert!(!storage.modify_meta("ns", "blob", |_| false).unwrap());
+        assert!(storage
+            .modify_meta("ns", "blob", |meta| {
+                meta.replicas.push("peer".to_string());
+                true
+            })
+            .unwrap());
+        let meta = storage.meta("ns", "blob").unwrap();
+        assert_eq!(meta.replicas, vec!["peer".to_string()]);
+        assert_eq!(meta.content_hash, Some(content_hash(b"data")));
+        assert!(storage.modify_meta("ns", "missing", |_| true).is_err());
+        fs::remove_dir_all(&storage.base).unwrap();
+    }
+
+    #[test]
+    fn recover_rolls_forward_committed_write() {
+        let storage = temp_storage();
+        let ns_path = storage.base.join("ns");
+        fs::create_dir_all(&ns_path).unwrap();
+        // Crash after the metadata commit, before the data rename.
+        let meta = BlobMeta {
+            content_hash: Some(content_hash(b"new")),
+            ..BlobMeta::default()
+        };
+        fs::write(ns_path.join("blob.meta"), serde_json::to_string(&meta).unwrap()).unwrap();
+        fs::write(ns_path.join("blob.tmp"), b"new").unwrap();
+
+        let report = storage.recover().unwrap();
+        assert_eq!(report.rolled_forward, 1);
+        assert_eq!(storage.get("ns", "blob").unwrap(), b"new");
+        fs::remove_dir_all(&storage.base).unwrap();
+    }
+
+    #[test]
+    fn recover_discards_uncommitted_write() {
+        let storage = temp_storage();
+        storage.put("ns", Some("blob"), b"old", BlobMeta::default()).unwrap();
+        let ns_path = storage.base.join("ns");
+        // Crash before the metadata commit: old blob must survive untouched.
+        fs::write(ns_path.join("blob.tmp"), b"partial").unwrap();
+        fs::write(ns_path.join("blob.meta.tmp"), b"{").unwrap();
+
+        let report = storage.recover().unwrap();
+        assert_eq!(report.discarded, 2);
+        assert_eq!(storage.get("ns", "blob").unwrap(), b"old");
+        assert_eq!(storage.list(Some("ns")).unwrap().len(), 1);
+        fs::remove_dir_all(&storage.base).unwrap();
+    }
+
+    #[test]
+    fn quarantine_keeps_meta_and_hides_blob() {
+        let storage = temp_storage();
+        storage.put("ns", Some("blob"), b"data", BlobMeta::default()).unwrap();
+
+        let target = storage.quarantine("ns", "blob").unwrap();
+        assert_eq!(fs::read(target).unwrap(), b"data");
+        assert!(storage.list(None).unwrap().is_empty());
+        assert_eq!(
+            storage.missing_data().unwrap(),
+            vec![("ns".to_string(), "blob".to_string())]
+        );
+        // Quarantined data does not count towards the storage limit
+        assert_eq!(storage.total_size().unwrap(), fs::metadata(storage.base.join("ns/blob.meta")).unwrap().len());
+        fs::remove_dir_all(&storage.base).unwrap();
+    }
+
+    #[test]
+    fn quarantine_if_corrupt_rechecks_current_data() {
+        let storage = temp_storage();
+        // Rewritten since the scrubber read it: healthy now, left in place
+        storage.put("ns", Some("blob"), b"data", BlobMeta::default()).unwrap();
+        assert_eq!(storage.quarantine_if_corrupt("ns", "blob").unwrap(), None);
+        assert_eq!(storage.get("ns", "blob").unwrap(), b"data");
+
+        fs::write(storage.base.join("ns").join("blob"), b"rot!").unwrap();
+        let target = storage.quarantine_if_corrupt("ns", "blob").unwrap().unwrap();
+        assert_eq!(fs::read(target).unwrap(), b"rot!");
+        fs::remove_dir_all(&storage.base).unwrap();
+    }
+
+    #[test]
+    fn put_shard_is_kept_per_origin() {
+        let storage = temp_storage();
+        storage.put_shard("ns", "blob", "peer-a", 0, b"mine").unwrap();
+        storage.put_shard("ns", "blob", "peer-b", 0, b"theirs").unwrap();
+        assert_eq!(storage.get_shard("ns", "blob", "peer-a", 0).unwrap(), b"mine");
+        assert_eq!(storage.get_shard("ns", "blob", "peer-b", 0).unwrap(), b"theirs");
+
+        // Re-sending the same shard is a no-op; different data is refused
+        storage.put_shard("ns", "blob", "peer-a", 0, b"mine").unwrap();
+        let err = storage.put_shard("ns", "blob", "peer-a", 0, b"evil").unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::AlreadyExists);
+        assert_eq!(storage.get_shard("ns", "blob", "peer-a", 0).unwrap(), b"mine");
+        fs::remove_dir_all(&storage.base).unwrap();
+    }
+}
@@ -0,0 +1,469 @@
+//! Durable outbox of uploader usage for the storage registry.
+//!
+//! Uploads no longer send one `recordFileUpload` each. The size is added to a per-uploader
+//! delta that is saved in `{STORAGE_PATH}/.state/usage-outbox.json` before the upload
+//! returns. A background task sends the deltas as one `recordFileUploads` call:
+//!
+//! - Every minute, or sooner when [`MAX_BATCH_UPLOADERS`] uploaders are waiting.
+//! - One batch is in flight at a time. It stays in the file until its operation is final,
+//!   so a restart resumes it instead of losing or re-sending it.
+//! - A failed or expired batch goes back to the queue and is retried with backoff.
+//!
+//! Settlement from the events of the batch operation (matched by its operation id):
+//!
+//! - An executed batch records every uploader except those with a `FILE_UPLOAD_SKIPPED`
+//!   event; the contract refused them, so they are dropped rather than queued again.
+//! - When the status of a batch cannot be known anymore (expired, or a send that may have
+//!   reached the node), an uploader with a `FILE_UPLOAD_RECORDED` event for its delta is
+//!   recorded, a skipped one is dropped, and the others are queued again.
+//!
+//! Every hour, the on-chain usage (`getUploaderUsageView`) of each uploader is compared with
+//! the bytes this server recorded. A lower value is reported as drift (e.g. after
+//! `removeFileUpload`).
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+use tokio::sync::Notify;
+
+use crate::contract::{MassaClient, SubmittedCall};
+use crate::events::ContractEvent;
+use crate::operations::{OperationStatus, SharedOperations};
+use crate::registry::RegistryCall;
+use crate::rpc::SendError;
+use crate::state::StateCell;
+use massa_models::amount::Amount;
+
+/// Uploaders per `recordFileUploads` call.
+pub const MAX_BATCH_UPLOADERS: usize = 16;
+const FLUSH_INTERVAL: Duration = Duration::from_secs(60);
+const RECONCILE_INTERVAL: Duration = Duration::from_secs(3600);
+const RETRY_BASE: Duration = Duration::from_secs(30);
+const RETRY_MAX: Duration = Duration::from_secs(1800);
+
+/// A batch sent (or about to be sent) whose outcome is not known yet.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+struct Batch {
+    /// Uploader and bytes to add.
+    entries: Vec<(String, u64)>,
+    operation_id: Option<String>,
+    expire_period: Option<u64>,
+}
+
+impl Batch {
+    fn call(&self) -> RegistryCall {
+        RegistryCall::RecordFileUploads {
+            uploader_addresses: self.entries.iter().map(|(u, _)| u.clone()).collect(),
+            file_sizes_bytes: self.entries.iter().map(|(_, b)| *b).collect(),
+        }
+    }
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct OutboxState {
+    /// Bytes not sent yet, per uploader.
+    pending: BTreeMap<String, u64>,
+    in_flight: Option<Batch>,
+    /// Bytes this server recorded on-chain, per uploader.
+    recorded: BTreeMap<String, u64>,
+}
+
+impl OutboxState {
+    fn add_pending(&mut self, uploader: &str, bytes: u64) {
+        let entry = self.pending.entry(uploader.to_string()).or_default();
+        *entry = entry.saturating_add(bytes);
+    }
+
+    /// Move up to [`MAX_BATCH_UPLOADERS`] pending deltas into a new in-flight batch.
+    fn start_batch(&mut self) -> Option<Batch> {
+        if self.in_flight.is_some() || self.pending.is_empty() {
+            return None;
+        }
+        let uploaders: Vec<String> = self.pending.keys().take(MAX_BATCH_UPLOADERS).cloned().collect();
+        let entries = uploaders
+            .into_iter()
+            .filter_map(|u| self.pending.remove(&u).map(|b| (u, b)))
+            .collect();
+        let batch = Batch { entries, ..Batch::default() };
+        self.in_flight = Some(batch.clone());
+        Some(batch)
+    }
+
+    fn add_recorded(&mut self, uploader: String, bytes: u64) {
+        let entry = self.recorded.entry(uploader).or_default();
+        *entry = entry.saturating_add(bytes);
+    }
+
+    /// The in-flight batch executed: its bytes are recorded, except for the uploaders the
+    /// contract skipped.
+    fn complete(&mut self, events: &[ContractEvent]) {
+        let skipped = skipped_uploaders(events);
+        for (uploader, bytes) in self.in_flight.take().map(|b| b.entries).unwrap_or_default() {
+            if skipped.contains(uploader.as_str()) {
+                tracing::warn!(uploader = %uploader, bytes, "contract skipped uploader usage; dropping it");
+            } else {
+                self.add_recorded(uploader, bytes);
+            }
+        }
+    }
+
+    /// The in-flight batch did not execute: its deltas are queued again.
+    fn requeue(&mut self) {
+        for (uploader, bytes) in self.in_flight.take().map(|b| b.entries).unwrap_or_default() {
+            self.add_pending(&uploader, bytes);
+        }
+    }
+
+    /// Settle a batch whose status is unknown from the events of its operation.
+    fn settle_from_events(&mut self, events: &[ContractEvent]) {
+        let Some(batch) = self.in_flight.take() else {
+            return;
+        };
+        let skipped = skipped_uploaders(events);
+        for (uploader, bytes) in batch.entries {
+            let recorded = events.iter().any(|event| {
+                matches!(event, ContractEvent::FileUploadRecorded { uploader: u, size_bytes, .. }
+                    if *u == uploader && *size_bytes == bytes)
+            });
+            if recorded {
+                self.add_recorded(uploader, bytes);
+            } else if skipped.contains(uploader.as_str()) {
+                tracing::warn!(uploader = %uploader, bytes, "contract skipped uploader usage; dropping it");
+            } else {
+                self.add_pending(&uploader, bytes);
+            }
+        }
+    }
+}
+
+fn skipped_uploaders(events: &[ContractEvent]) -> BTreeSet<&str> {
+    events
+        .iter()
+        .filter_map(|event| match event {
+            ContractEvent::FileUploadSkipped { uploader, .. } => Some(uploader.as_str()),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Outbox status, as reported by `GET /admin/usage-outbox`.
+#[derive(Debug, Clone, Serialize)]
+pub struct OutboxStatus {
+    pub pending_uploaders: usize,
+    pub pending_bytes: u64,
+    pub in_flight_uploaders: usize,
+    pub in_flight_operation: Option<String>,
+    pub last_error: Option<String>,
+    /// Uploaders whose on-chain usage is below what this server recorded, and by how much.
+    pub drift: BTreeMap<String, u64>,
+}
+
+/// Queues uploader usage and records it on the contract in batches.
+pub struct UsageOutbox {
+    registry: Arc<MassaClient>,
+    operations: SharedOperations,
+    state: StateCell<OutboxState>,
+    last_error: Mutex<Option<String>>,
+    drift: Mutex<BTreeMap<String, u64>>,
+    flush_now: Notify,
+}
+
+pub type SharedUsageOutbox = Arc<UsageOutbox>;
+
+/// Load the outbox from `state_dir` and start sending it.
+pub fn spawn(registry: Arc<MassaClient>, operations: SharedOperations, state_dir: &Path) -> SharedUsageOutbox {
+    let state = StateCell::<OutboxState>::load(state_dir.join("usage-outbox.json"), "usage outbox");
+    state.with(|state| {
+        tracing::info!(
+            pending = state.pending.len(),
+            in_flight = state.in_flight.is_some(),
+            "usage outbox loaded"
+        )
+    });
+    let outbox = Arc::new(UsageOutbox {
+        registry,
+        operations,
+        state,
+        last_error: Mutex::new(None),
+        drift: Mutex::new(BTreeMap::new()),
+        flush_now: Notify::new(),
+    });
+    let worker = outbox.clone();
+    tokio::spawn(async move { worker.run().await });
+    outbox
+}
+
+impl UsageOutbox {
+    /// Queue `bytes` uploaded by `uploader`. Returns once the delta is saved.
+    pub fn record(&self, uploader: &str, bytes: u64) -> std::io::Result<()> {
+        if bytes == 0 {
+            return Ok(());
+        }
+        let pending = self.state.try_update(|state| {
+            state.add_pending(uploader, bytes);
+            state.pending.len()
+        })?;
+        if pending >= MAX_BATCH_UPLOADERS {
+            self.flush_now.notify_one();
+        }
+        Ok(())
+    }
+
+    pub fn status(&self) -> OutboxStatus {
+        self.state.with(|state| OutboxStatus {
+            pending_uploaders: state.pending.len(),
+            pending_bytes: state.pending.values().fold(0u64, |sum, b| sum.saturating_add(*b)),
+            in_flight_uploaders: state.in_flight.as_ref().map_or(0, |b| b.entries.len()),
+            in_flight_operation: state.in_flight.as_ref().and_then(|b| b.operation_id.clone()),
+            last_error: self.last_error.lock().unwrap().clone(),
+            drift: self.drift.lock().unwrap().clone(),
+        })
+    }
+
+    async fn run(&self) {
+        if let Err(e) = self.resume().await {
+            tracing::warn!(error = %e, "failed to settle the usage batch left in flight");
+        }
+        let mut failures = 0u32;
+        let mut last_reconcile = tokio::time::Instant::now();
+        loop {
+            let wait = if failures == 0 {
+                FLUSH_INTERVAL
+            } else {
+                RETRY_BASE.saturating_mul(1 << (failures - 1).min(10)).min(RETRY_MAX)
+            };
+            tokio::select! {
+                _ = tokio::time::sleep(wait) => {}
+                _ = self.flush_now.notified(), if failures == 0 => {}
+            }
+
+            let in_flight = self.state.with(|state| state.in_flight.is_some());
+            let result = if in_flight { self.resume().await } else { self.flush().await };
+            match result {
+                Ok(()) => {
+                    failures = 0;
+                    *self.last_error.lock().unwrap() = None;
+                }
+                Err(e) => {
+                    failures = failures.saturating_add(1);
+                    tracing::warn!(error = %e, failures, "failed to record uploader usage on contract");
+                    *self.last_error.lock().unwrap() = Some(format!("{:#}", e));
+                }
+            }
+
+            if last_reconcile.elapsed() >= RECONCILE_INTERVAL {
+                last_reconcile = tokio::time::Instant::now();
+                if let Err(e) = self.reconcile().await {
+                    tracing::warn!(error = %e, "failed to reconcile uploader usage");
+                }
+            }
+        }
+    }
+
+    /// Send the next batch and wait until it is final.
+    async fn flush(&self) -> Result<()> {
+        let Some(batch) = self.state.update(OutboxState::start_batch) else {
+            return Ok(());
+        };
+
+        let sent = match self.registry.call(&batch.call(), Amount::from_raw(0)).await {
+            Ok(sent) => sent,
+            Err(e) => {
+                // A send that may have reached the node stays in flight and is settled on the
+                // next attempt; any other error means it was not sent.
+                if let Some(SendError::MaybeSent { operations, .. }) = e.downcast_ref::<SendError>() {
+                    if let Some(sent) = operations.first() {
+                        self.state.update(|state| {
+                            if let Some(in_flight) = state.in_flight.as_mut() {
+                                in_flight.operation_id = Some(sent.id.clone());
+                                in_flight.expire_period = Some(sent.expire_period);
+                            }
+                        });
+                    }
+                    return Err(e);
+                }
+                self.state.update(OutboxState::requeue);
+                return Err(e);
+            }
+        };
+        tracing::info!(
+            operation_id = %sent.operation_id,
+            uploaders = batch.entries.len(),
+            bytes = batch.entries.iter().map(|(_, b)| *b).sum::<u64>(),
+            "uploader usage batch sent"
+        );
+        self.state.update(|state| {
+            if let Some(in_flight) = state.in_flight.as_mut() {
+                in_flight.operation_id = Some(sent.operation_id.clone());
+                in_flight.expire_period = Some(sent.expire_period);
+            }
+        });
+
+        let operation_id = sent.operation_id.clone();
+        match self.operations.follow(sent, 0).await? {
+            OperationStatus::Executed => {
+                // Kept in flight until the events are read; `resume` tries again.
+                let events = self.batch_events(&operation_id).await?;
+                self.state.update(|state| state.complete(&events));
+                Ok(())
+            }
+            OperationStatus::Failed { message } => {
+                self.state.update(OutboxState::requeue);
+                Err(anyhow!("recordFileUploads failed: {}", message))
+            }
+            status => {
+                self.state.update(OutboxState::requeue);
+                Err(anyhow!("recordFileUploads did not execute: {:?}", status))
+            }
+        }
+    }
+
+    /// Settle the batch left in flight by a previous run or an ambiguous send.
+    async fn resume(&self) -> Result<()> {
+        let Some(batch) = self.state.with(|state| state.in_flight.clone()) else {
+            return Ok(());
+        };
+        let (Some(operation_id), Some(expire_period)) = (batch.operation_id.clone(), batch.expire_period) else {
+            // Saved before the node answered: not sent, or the server stopped right after
+            self.state.update(OutboxState::requeue);
+            return Ok(());
+        };
+        let sent = SubmittedCall {
+            call: batch.call(),
+            coins: Amount::from_raw(0),
+            operation_id: operation_id.clone(),
+            expire_period,
+        };
+        match self.operations.track(sent).outcome().await {
+            OperationStatus::Executed => {
+                let events = self.batch_events(&operation_id).await?;
+                self.state.update(|state| state.complete(&events));
+            }
+            OperationStatus::Failed { .. } => self.state.update(OutboxState::requeue),
+            // Expired or unknown: the node may have forgotten an executed operation, so
+            // the events it emitted decide.
+            _ => {
+                let events = self.batch_events(&operation_id).await?;
+                self.state.update(|state| state.settle_from_events(&events));
+                tracing::info!(uploaders = batch.entries.len(), "usage batch settled from its events");
+            }
+        }
+        Ok(())
+    }
+
+    /// Registry events emitted by the batch operation `operation_id`.
+    async fn batch_events(&self, operation_id: &str) -> Result<Vec<ContractEvent>> {
+        Ok(self
+            .registry
+            .get_operation_events(operation_id)
+            .await?
+            .into_iter()
+            .filter(|event| !event.is_error)
+            .map(|event| ContractEvent::parse(&event.data))
+            .collect())
+    }
+
+    /// Compare the on-chain usage of every uploader with what this server recorded.
+    async fn reconcile(&self) -> Result<()> {
+        let recorded = self.state.with(|state| state.recorded.clone());
+        let mut drift = BTreeMap::new();
+        for (uploader, bytes) in recorded {
+            let on_chain = self.registry.get_uploader_usage(&uploader).await?;
+            if on_chain < bytes {
+                tracing::warn!(
+                    uploader = %uploader,
+                    recorded = bytes,
+                    on_chain,
+                    "on-chain uploader usage is below what this server recorded"
+                );
+                drift.insert(uploader, bytes - on_chain);
+            }
+        }
+        *self.drift.lock().unwrap() = drift;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn batches_aggregate_and_settle() {
+        let mut state = OutboxState::default();
+        state.add_pending("AU1", 100);
+        state.add_pending("AU2", 50);
+        state.add_pending("AU1", 25);
+
+        let batch = state.start_batch().unwrap();
+        assert_eq!(batch.entries, vec![("AU1".to_string(), 125), ("AU2".to_string(), 50)]);
+        assert!(state.pending.is_empty());
+        // One batch at a time
+        state.add_pending("AU3", 10);
+        assert!(state.start_batch().is_none());
+
+        // A failed batch is queued again, merged with newer deltas
+        state.add_pending("AU1", 5);
+        state.requeue();
+        assert_eq!(state.pending.get("AU1"), Some(&130));
+        assert_eq!(state.pending.len(), 3);
+
+        state.start_batch().unwrap();
+        state.complete(&[]);
+        assert!(state.in_flight.is_none());
+        assert_eq!(state.recorded.get("AU1"), Some(&130));
+        assert_eq!(state.recorded.get("AU3"), Some(&10));
+
+        // Uploaders the contract skipped are dropped, not recorded
+        state.add_pending("AU1", 7);
+        state.add_pending("AU4", 3);
+        state.start_batch().unwrap();
+        state.complete(&[ContractEvent::FileUploadSkipped { uploader: "AU4".to_string(), size_bytes: 3 }]);
+        assert_eq!(state.recorded.get("AU1"), Some(&137));
+        assert!(!state.recorded.contains_key("AU4"));
+        assert!(state.pending.is_empty());
+    }
+
+    #[test]
+    fn batches_are_bounded() {
+        let mut state = OutboxState::default();
+        for i in 0..MAX_BATCH_UPLOADERS + 3 {
+            state.add_pending(&format!("AU{:02}", i), 1);
+        }
+        assert_eq!(state.start_batch().unwrap().entries.len(), MAX_BATCH_UPLOADERS);
+        assert_eq!(state.pending.len(), 3);
+    }
+
+    #[test]
+    fn unknown_outcome_is_settled_from_events() {
+        let mut state = OutboxState::default();
+        state.add_pending("AU1", 100);
+        state.add_pending("AU2", 50);
+        state.add_pending("AU3", 20);
+        state.start_batch().unwrap();
+
+        // AU1 was recorded, AU2 skipped, AU3 has no event
+        state.settle_from_events(&[
+            ContractEvent::FileUploadRecorded {
+                uploader: "AU1".to_string(),
+                size_bytes: 100,
+                usage_bytes: 1_100,
+            },
+            ContractEvent::FileUploadSkipped { uploader: "AU2".to_string(), size_bytes: 50 },
+        ]);
+        assert!(state.in_flight.is_none());
+        assert_eq!(state.recorded.get("AU1"), Some(&100));
+        assert!(!state.recorded.contains_key("AU2"));
+        assert!(!state.pending.contains_key("AU2"));
+        assert_eq!(state.pending.get("AU3"), Some(&20));
+
+        // The state survives a restart
+        let restored: OutboxState = serde_json::from_slice(&serde_json::to_vec(&state).unwrap()).unwrap();
+        assert_eq!(restored.pending, state.pending);
+        assert_eq!(restored.recorded, state.recorded);
+    }
+}
@@ -28,6 +28,7 @@ import {
   unregisterStorageNode,
   updateProviderMetadata,
   recordFileUpload,
+  recordFileUploads,
   removeFileUpload,
   getUploaderUsageView,
 } from '../contracts/storage-registry';
@@ -681,6 +682,61 @@ describe('Storage Registry - File Upload Tracking', () => {
     expect(bytesToU64(bookedB)).toBe(5);
   });
 
+  it('should record batched uploads and skip stale entries', () => {
+    const UPLOADER_A = UPLOADER_ADDRESS;
+    const UPLOADER_B = 'AU1mARGo8BjjFLbUTd3Fihs95EL8wwjPgcoHGzJTdQhQ14KPa3ab';
+    const NOT_ALLOWED_ADDRESS =
+      'AU1mARGo8BjjFLbUTd3Fihs95EL8wwjPgcoHGzJTdQhQ14KPa3xx';
+
+    switchUser(UPLOADER_A);
+    mockBalance(UPLOADER_A, 10_000_000_000);
+    mockTransferredCoins(3 * DEFAULT_PRICE_PER_GB);
+    registerAsUploader(new Args().add<u64>(3).serialize());
+
+    switchUser(UPLOADER_B);
+    mockBalance(UPLOADER_B, 10_000_000_000);
+    mockTransferredCoins(5 * DEFAULT_PRICE_PER_GB);
+    registerAsUploader(new Args().add<u64>(5).serialize());
+
+    switchUser(STORAGE_ADMIN_ADDRESS);
+    recordFileUpload(new Args().add(UPLOADER_A).add<u64>(100).serialize());
+    recordFileUploads(
+      new Args()
+        .add<Array<string>>([UPLOADER_A, NOT_ALLOWED_ADDRESS, UPLOADER_B])
+        .add<Array<u64>>([400, 1_000, 2_000])
+        .serialize(),
+    );
+
+    const usageA = getUploaderUsageView(new Args().add(UPLOADER_A).serialize());
+    const usageB = getUploaderUsageView(new Args().add(UPLOADER_B).serialize());
+    const usageNotAllowed = getUploaderUsageView(
+      new Args().add(NOT_ALLOWED_ADDRESS).serialize(),
+    );
+    expect(bytesToU64(usageA)).toBe(500);
+    expect(bytesToU64(usageB)).toBe(2_000);
+    expect(bytesToU64(usageNotAllowed)).toBe(0);
+  });
+
+  throws('should fail to record batched uploads when caller is not storage admin', () => {
+    switchUser(UPLOADER_ADDRESS);
+    recordFileUploads(
+      new Args()
+        .add<Array<string>>([UPLOADER_ADDRESS])
+        .add<Array<u64>>([1_000])
+        .serialize(),
+    );
+  });
+
+  throws('should fail to record batched uploads with mismatched lengths', () => {
+    switchUser(STORAGE_ADMIN_ADDRESS);
+    recordFileUploads(
+      new Args()
+        .add<Array<string>>([UPLOADER_ADDRESS])
+        .add<Array<u64>>([1_000, 2_000])
+        .serialize(),
+    );
+  });
+
   it('should allow storage admin to remove file upload', () => {
     // Register uploader and record uploads
     switchUser(UPLOADER_ADDRESS);
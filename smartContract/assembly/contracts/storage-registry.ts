@@ -992,6 +992,69 @@ export function recordFileUpload(binaryArgs: StaticArray<u8>): void {
   );
 }
 
+/**
+ * Record several file uploads in one operation (batched usage deltas from a server).
+ * Entries with a zero size or an uploader that is no longer allowed are skipped with a
+ * FILE_UPLOAD_SKIPPED event, so one stale entry does not revert the whole batch.
+ * Callable by storage admins (servers).
+ * @param binaryArgs - Serialized Args containing:
+ *   - uploaderAddresses: Array<string>
+ *   - fileSizesBytes: Array<u64> (same length; bytes to add for each uploader)
+ */
+export function recordFileUploads(binaryArgs: StaticArray<u8>): void {
+  assertNotPaused();
+  assert(
+    isStorageAdmin(Context.caller().toString()),
+    'Caller is not a storage admin',
+  );
+
+  const args = new Args(binaryArgs);
+  const uploaderAddresses = args
+    .nextStringArray()
+    .expect('uploaderAddresses argument is missing or invalid');
+  const fileSizesBytes = args
+    .nextFixedSizeArray<u64>()
+    .expect('fileSizesBytes argument is missing or invalid');
+
+  assert(
+    uploaderAddresses.length == fileSizesBytes.length,
+    'uploaderAddresses and fileSizesBytes must have the same length',
+  );
+
+  for (let i = 0; i < uploaderAddresses.length; i++) {
+    const uploaderAddress = uploaderAddresses[i];
+    const fileSizeBytes = fileSizesBytes[i];
+
+    if (
+      fileSizeBytes == 0 ||
+      !(
+        isStorageAdmin(uploaderAddress) ||
+        getBookedUploaderGb(uploaderAddress) > 0
+      )
+    ) {
+      generateEvent(
+        'FILE_UPLOAD_SKIPPED:' +
+          uploaderAddress +
+          ',' +
+          fileSizeBytes.toString(),
+      );
+      continue;
+    }
+
+    const newUsage = getUploaderUsage(uploaderAddress) + fileSizeBytes;
+    setUploaderUsage(uploaderAddress, newUsage);
+
+    generateEvent(
+      'FILE_UPLOAD_RECORDED:' +
+        uploaderAddress +
+        ',' +
+        fileSizeBytes.toString() +
+        ',' +
+        newUsage.toString(),
+    );
+  }
+}
+
 /**
  * Remove file upload record to update storage usage when a file is deleted.
  * Callable by storage admins (servers) to update usage when files are removed.